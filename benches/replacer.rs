@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base::page::PageID;
+use base::page_cache::CACHE_SIZE;
+use base::replacer::{AccessType, Clock, Replacer, TwoQ, ARC, LRU};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ACCESSES: usize = 20_000;
+
+/// Replays the accesses against a cache of `CACHE_SIZE` frames the way the page cache drives its
+/// replacer, pinning each page only whilst it's accessed, and returns how many were hits
+fn replay(replacer: &dyn Replacer, accesses: &[PageID]) -> usize {
+    let mut frames = HashMap::new();
+    let mut pages = vec![None; CACHE_SIZE];
+    let mut free: Vec<_> = (0..CACHE_SIZE).collect();
+    let mut hits = 0;
+
+    for &page_id in accesses {
+        let i = match frames.get(&page_id) {
+            Some(&i) => {
+                hits += 1;
+                replacer.record_access(i, page_id, AccessType::Get);
+                i
+            }
+            None => {
+                let i = free.pop().or_else(|| replacer.evict()).expect("nothing is left pinned");
+                if let Some(evicted) = pages[i].replace(page_id) {
+                    frames.remove(&evicted);
+                }
+                frames.insert(page_id, i);
+
+                replacer.remove(i);
+                replacer.record_access(i, page_id, AccessType::Get);
+                i
+            }
+        };
+
+        replacer.pin(i);
+        replacer.unpin(i);
+    }
+
+    hits
+}
+
+/// Lookups that mostly hit a few hundred pages out of a thousand
+fn point_lookups() -> Vec<PageID> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..ACCESSES).map(|_| (rng.gen::<f64>().powi(3) * 1000.0) as PageID).collect()
+}
+
+/// Lookups into a hot set that nearly fills the cache, interleaved with scans over pages that are
+/// each read once
+fn scan_heavy() -> Vec<PageID> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut accesses = Vec::with_capacity(ACCESSES);
+    let mut cold = 1000;
+    while accesses.len() < ACCESSES {
+        for _ in 0..256 {
+            accesses.push(rng.gen_range(0..48));
+        }
+        for _ in 0..256 {
+            accesses.push(cold);
+            cold += 1;
+        }
+    }
+
+    accesses
+}
+
+type NewReplacer = fn() -> Arc<dyn Replacer>;
+
+fn policies() -> Vec<(&'static str, NewReplacer)> {
+    vec![
+        ("LRU-2", || -> Arc<dyn Replacer> { LRU::new(2) }),
+        ("CLOCK", || -> Arc<dyn Replacer> { Clock::new() }),
+        ("2Q", || -> Arc<dyn Replacer> { TwoQ::new(CACHE_SIZE) }),
+        ("ARC", || -> Arc<dyn Replacer> { ARC::new(CACHE_SIZE) }),
+    ]
+}
+
+fn bench_workload(c: &mut Criterion, name: &str, accesses: &[PageID]) {
+    for (policy, new) in policies() {
+        let hits = replay(new().as_ref(), accesses);
+        println!("{name}/{policy}: {:.1}% hit rate", hits as f64 * 100.0 / accesses.len() as f64);
+    }
+
+    let mut group = c.benchmark_group(name);
+    for (policy, new) in policies() {
+        group.bench_function(policy, |b| {
+            b.iter_batched(
+                new,
+                |replacer| replay(replacer.as_ref(), accesses),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_replacers(c: &mut Criterion) {
+    bench_workload(c, "point_lookups", &point_lookups());
+    bench_workload(c, "scan_heavy", &scan_heavy());
+}
+
+criterion_group!(benches, bench_replacers);
+criterion_main!(benches);
@@ -7,16 +7,26 @@ use std::{
 use base::{
     catalog::Catalog, disk::FileSystem, execution::execute, optimiser::Optimiser,
     page_cache::PageCache, physical_plan::PhysicalOperator, planner::Planner, replacer::LRU,
-    sql::Parser,
+    sql::Parser, wal::FileLog,
 };
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 fn main() -> Result<()> {
     let disk = FileSystem::new("db.base")?;
+    let log = FileLog::new("db.base.wal")?;
     let replacer = LRU::new(2);
-    let pc = PageCache::new(disk, replacer, 0);
-    let catalog = Arc::new(Mutex::new(Catalog::new(pc)));
+    let pc = PageCache::open(disk, log, replacer)?;
+    let catalog = match pc.catalog_root()? {
+        Some(root) => Catalog::open(pc.clone(), root)?,
+        None => {
+            let catalog = Catalog::new(pc.clone())?;
+            pc.set_catalog_root(catalog.root())?;
+            pc.checkpoint()?;
+            catalog
+        }
+    };
+    let catalog = Arc::new(Mutex::new(catalog));
     let planner = Planner::new(Arc::clone(&catalog));
     let optimiser = Optimiser::new(Arc::clone(&catalog));
 
@@ -38,6 +48,8 @@ fn main() -> Result<()> {
         if let Err(e) = run_query(&input, &planner, &optimiser) {
             writeln!(stdout, "{e}")?;
         };
+
+        pc.checkpoint()?;
     }
 }
 
@@ -70,13 +82,12 @@ fn run_query(input: &str, planner: &Planner, optimiser: &Optimiser) -> Result<()
 
         for row in result.iter().take(10) {
             for (i, column) in schema.iter().enumerate() {
-                let value = row.get_value(column.offset, column.ty);
+                let value = row.get_value(column);
                 column_widths[i] = max(column_widths[i], value.display_len() + CELL_PADDING);
             }
         }
 
-        for i in 0..schema.len() {
-            let width = column_widths[i];
+        for width in &column_widths {
             write!(stdout, "+{:-^width$}", "")?;
         }
         writeln!(stdout, "+")?;
@@ -89,22 +100,20 @@ fn run_query(input: &str, planner: &Planner, optimiser: &Optimiser) -> Result<()
         writeln!(stdout, "|")?;
 
         for row in &result {
-            for i in 0..schema.len() {
-                let width = column_widths[i];
+            for width in &column_widths {
                 write!(stdout, "+{:-^width$}", "")?;
             }
             writeln!(stdout, "+")?;
 
             for (i, column) in schema.iter().enumerate() {
-                let value = row.get_value(column.offset, column.ty);
+                let value = row.get_value(column);
                 let width = column_widths[i];
                 write!(stdout, "|{value:^width$}")?;
             }
             writeln!(stdout, "|")?;
         }
 
-        for i in 0..schema.len() {
-            let width = column_widths[i];
+        for width in &column_widths {
             write!(stdout, "+{:-^width$}", "")?;
         }
         writeln!(stdout, "+")?;
@@ -33,6 +33,13 @@ impl<const SIZE: usize> BitMap<SIZE> {
         bit_index & b > 0
     }
 
+    /// Returns the index of the first set bit
+    pub fn first_set(&self) -> Option<usize> {
+        let index = self.inner.iter().position(|b| *b != 0)?;
+
+        Some((index << 3) + self.inner[index].trailing_zeros() as usize)
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         &self.inner
     }
@@ -91,4 +98,17 @@ mod test {
         assert!(!bm.check(200));
         assert!(!bm.check(512));
     }
+
+    #[test]
+    fn test_first_set() {
+        let mut bm = BitMap::<128>::new();
+        assert_eq!(None, bm.first_set());
+
+        bm.set(512, true);
+        bm.set(177, true);
+        assert_eq!(Some(177), bm.first_set());
+
+        bm.set(177, false);
+        assert_eq!(Some(512), bm.first_set());
+    }
 }
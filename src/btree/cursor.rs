@@ -0,0 +1,500 @@
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+use crate::btree::node::{is_leaf, Node, NodeType};
+use crate::btree::slot::{Either, Slot};
+use crate::catalog::schema::Schema;
+use crate::get_ptr;
+use crate::page::{DiskObject, PageBuf, PageID};
+use crate::page_cache::{OwnedPin, SharedPageCache};
+use crate::storable::Storable;
+use crate::table::tuple::{Comparand, Data as TupleData};
+
+/// A position between two keys in the leaves of a B-tree. The cursor holds a pin on its current
+/// leaf along with a copy of its slots, so no latches are held between calls. It walks the sibling
+/// pointers of the leaves to move between them, and since the tree may have changed since the
+/// current leaf was copied, any keys it has already passed are skipped.
+pub struct Cursor<V> {
+    pc: SharedPageCache,
+    schema: Schema,
+    /// The page that points to the root of the tree
+    id: PageID,
+    leaf: Option<(OwnedPin, Node<V>)>,
+    /// The number of slots in the leaf that are before the cursor
+    pos: usize,
+}
+
+impl<V> Cursor<V>
+where
+    V: Storable + Clone + Eq,
+{
+    pub fn new(pc: SharedPageCache, schema: Schema, id: PageID) -> Self {
+        Self { pc, schema, id, leaf: None, pos: 0 }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Moves the cursor to the start of a range, so that `next` returns its first key
+    pub fn seek(&mut self, from: Bound<&TupleData>) -> crate::Result<()> {
+        let key = match from {
+            Bound::Included(key) | Bound::Excluded(key) => Some(key),
+            Bound::Unbounded => None,
+        };
+        self.descend(key, false)?;
+
+        self.pos = match from {
+            Bound::Included(key) => self.count(|k| self.cmp(k, key).is_lt()),
+            Bound::Excluded(key) => self.count(|k| self.cmp(k, key).is_le()),
+            Bound::Unbounded => 0,
+        };
+
+        Ok(())
+    }
+
+    /// Moves the cursor to the end of a range, so that `prev` returns its last key
+    pub fn seek_back(&mut self, to: Bound<&TupleData>) -> crate::Result<()> {
+        let key = match to {
+            Bound::Included(key) | Bound::Excluded(key) => Some(key),
+            Bound::Unbounded => None,
+        };
+        self.descend(key, true)?;
+
+        self.pos = match to {
+            Bound::Included(key) => self.count(|k| self.cmp(k, key).is_le()),
+            Bound::Excluded(key) => self.count(|k| self.cmp(k, key).is_lt()),
+            Bound::Unbounded => self.count(|_| true),
+        };
+
+        Ok(())
+    }
+
+    /// Returns the entry after the cursor and moves the cursor past it
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> crate::Result<Option<(TupleData, V)>> {
+        loop {
+            let Some((pin, node)) = &self.leaf else { return Ok(None) };
+            if let Some(Slot(key, value)) = node.iter().nth(self.pos) {
+                self.pos += 1;
+                return Ok(Some((key.clone(), self::value(value))));
+            }
+
+            let last = node.last_key().cloned();
+            let passed =
+                |k: &TupleData| last.as_ref().is_some_and(|last| self.cmp(k, last).is_le());
+
+            // The leaf is read again in case keys were moved into it, and otherwise the next leaf
+            // is latched before it's released, which is the order writers latch siblings in
+            let guard = pin.read();
+            let Some(live) = read_leaf::<V>(&guard.data, node.id, &self.schema) else {
+                drop(guard);
+                self.reseek(last.as_ref().map_or(Bound::Unbounded, Bound::Excluded), false)?;
+                continue;
+            };
+
+            if live.iter().any(|Slot(k, _)| !passed(k)) {
+                drop(guard);
+                self.pos = live.iter().take_while(|Slot(k, _)| passed(k)).count();
+                self.leaf.as_mut().unwrap().1 = live;
+                continue;
+            }
+
+            if live.next == -1 {
+                return Ok(None);
+            }
+
+            let next_pin = self.pc.fetch_page_owned(live.next)?;
+            let next_node: Node<V> = Node::deserialise(next_pin.read().data, &self.schema);
+            drop(guard);
+
+            self.pos = next_node.iter().take_while(|Slot(k, _)| passed(k)).count();
+            self.leaf = Some((next_pin, next_node));
+        }
+    }
+
+    /// Returns the entry before the cursor and moves the cursor back past it
+    pub fn prev(&mut self) -> crate::Result<Option<(TupleData, V)>> {
+        loop {
+            let Some((pin, node)) = &self.leaf else { return Ok(None) };
+            if self.pos > 0 {
+                self.pos -= 1;
+                let Slot(key, value) = node.iter().nth(self.pos).unwrap();
+                return Ok(Some((key.clone(), self::value(value))));
+            }
+
+            let first = node.first().map(|Slot(key, _)| key.clone());
+            let before =
+                |k: &TupleData| first.as_ref().is_none_or(|first| self.cmp(k, first).is_lt());
+            let bound = first.as_ref().map_or(Bound::Unbounded, Bound::Excluded);
+            let id = node.id;
+
+            // The previous leaf can't be latched whilst this one is, since siblings are latched
+            // from left to right. So once its ID is known, both are latched again in order, and
+            // checked to still be next to each other.
+            let prev = {
+                let guard = pin.read();
+                read_leaf::<V>(&guard.data, id, &self.schema).map(|live| live.prev)
+            };
+            let leaves = match prev {
+                Some(-1) => {
+                    let guard = pin.read();
+                    read_leaf::<V>(&guard.data, id, &self.schema).map(|live| (live, None))
+                }
+                Some(prev) => {
+                    let prev_pin = self.pc.fetch_page_owned(prev)?;
+                    let prev_guard = prev_pin.read();
+                    let guard = pin.read();
+                    let prev_node = read_leaf::<V>(&prev_guard.data, prev, &self.schema)
+                        .filter(|prev_node| prev_node.next == id);
+                    let live = read_leaf::<V>(&guard.data, id, &self.schema);
+                    drop(guard);
+                    drop(prev_guard);
+
+                    live.zip(prev_node.map(|prev_node| (prev_pin, prev_node)))
+                        .map(|(live, prev)| (live, Some(prev)))
+                }
+                None => None,
+            };
+
+            let Some((live, prev)) = leaves else {
+                self.reseek(bound, true)?;
+                continue;
+            };
+
+            if live.iter().any(|Slot(k, _)| before(k)) {
+                self.pos = live.iter().take_while(|Slot(k, _)| before(k)).count();
+                self.leaf.as_mut().unwrap().1 = live;
+                continue;
+            }
+
+            let Some((prev_pin, prev_node)) = prev else { return Ok(None) };
+            self.pos = prev_node.iter().take_while(|Slot(k, _)| before(k)).count();
+            self.leaf = Some((prev_pin, prev_node));
+        }
+    }
+
+    /// Searches the tree again for the cursor's position after its leaf has changed
+    fn reseek(&mut self, bound: Bound<&TupleData>, back: bool) -> crate::Result<()> {
+        match back {
+            true => self.seek_back(bound),
+            false => self.seek(bound),
+        }
+    }
+
+    /// Loads the leaf that would hold `key`. Without a key, the first or the last leaf is loaded.
+    fn descend(&mut self, key: Option<&TupleData>, last: bool) -> crate::Result<()> {
+        self.leaf = None;
+
+        let pc = self.pc.clone();
+        let Some(mut page) = super::read_root(&pc, self.id)? else { return Ok(()) };
+        loop {
+            let node: Node<V> = Node::deserialise(page.data, &self.schema);
+            if node.t == NodeType::Leaf {
+                let pin = pc.fetch_page_owned(node.id)?;
+                self.leaf = Some((pin, node));
+                return Ok(());
+            }
+
+            // Keys after the last separator can only be in the last child
+            let last_child = || match node.next {
+                -1 => node.iter().last().map(|slot| get_ptr!(slot)),
+                next => Some(next),
+            };
+            let ptr = match key {
+                Some(key) => node.find_child(key, &self.schema).or_else(last_child),
+                None if last => last_child(),
+                None => node.first_ptr(),
+            }
+            .expect("internal nodes should have children");
+
+            // The child is latched before its parent is released
+            page = pc.fetch_page(ptr)?.into_read();
+        }
+    }
+
+    pub fn cmp(&self, lhs: &TupleData, rhs: &TupleData) -> Ordering {
+        Comparand(&self.schema, lhs).cmp(&Comparand(&self.schema, rhs))
+    }
+
+    /// The number of keys at the start of the current leaf that match `f`
+    fn count(&self, f: impl Fn(&TupleData) -> bool) -> usize {
+        let Some((_, node)) = &self.leaf else { return 0 };
+        node.iter().take_while(|Slot(k, _)| f(k)).count()
+    }
+}
+
+fn value<V: Clone>(either: &Either<V>) -> V {
+    match either {
+        Either::Value(value) => value.clone(),
+        Either::Pointer(_) => unreachable!(),
+    }
+}
+
+/// Reads the leaf on the page, as long as the page still holds the leaf with the given ID
+fn read_leaf<V: Storable>(buf: &PageBuf, id: PageID, schema: &Schema) -> Option<Node<V>> {
+    is_leaf(buf, id).then(|| Node::deserialise(*buf, schema))
+}
+
+/// The entries of a B-tree between two bounds, which can be iterated from either end. Each end
+/// has its own cursor, which is only positioned once the first entry is taken from that end.
+pub struct Range<V> {
+    front: Cursor<V>,
+    back: Cursor<V>,
+    from: Bound<TupleData>,
+    to: Bound<TupleData>,
+    /// The last key taken from each end, which is used to tell when the ends meet
+    front_key: Option<TupleData>,
+    back_key: Option<TupleData>,
+    front_started: bool,
+    back_started: bool,
+    done: bool,
+}
+
+impl<V> Range<V>
+where
+    V: Storable + Clone + Eq,
+{
+    pub fn new(
+        pc: SharedPageCache,
+        schema: Schema,
+        id: PageID,
+        from: Bound<TupleData>,
+        to: Bound<TupleData>,
+    ) -> Self {
+        Self {
+            front: Cursor::new(pc.clone(), schema.clone(), id),
+            back: Cursor::new(pc, schema, id),
+            from,
+            to,
+            front_key: None,
+            back_key: None,
+            front_started: false,
+            back_started: false,
+            done: false,
+        }
+    }
+
+    fn front(&mut self) -> crate::Result<Option<(TupleData, V)>> {
+        if !self.front_started {
+            self.front.seek(self.from.as_ref())?;
+            self.front_started = true;
+        }
+
+        let Some((key, value)) = self.front.next()? else { return Ok(None) };
+        let cmp = |other: &TupleData| self.front.cmp(&key, other);
+        let in_range = match &self.to {
+            Bound::Included(to) => cmp(to).is_le(),
+            Bound::Excluded(to) => cmp(to).is_lt(),
+            Bound::Unbounded => true,
+        };
+        if !in_range || self.back_key.as_ref().is_some_and(|back| cmp(back).is_ge()) {
+            return Ok(None);
+        }
+
+        self.front_key = Some(key.clone());
+        Ok(Some((key, value)))
+    }
+
+    fn back(&mut self) -> crate::Result<Option<(TupleData, V)>> {
+        if !self.back_started {
+            self.back.seek_back(self.to.as_ref())?;
+            self.back_started = true;
+        }
+
+        let Some((key, value)) = self.back.prev()? else { return Ok(None) };
+        let cmp = |other: &TupleData| self.back.cmp(&key, other);
+        let in_range = match &self.from {
+            Bound::Included(from) => cmp(from).is_ge(),
+            Bound::Excluded(from) => cmp(from).is_gt(),
+            Bound::Unbounded => true,
+        };
+        if !in_range || self.front_key.as_ref().is_some_and(|front| cmp(front).is_le()) {
+            return Ok(None);
+        }
+
+        self.back_key = Some(key.clone());
+        Ok(Some((key, value)))
+    }
+
+    /// Turns an entry from one of the ends into an item, ending the iterator after the last
+    /// entry or an error
+    fn item(
+        &mut self,
+        result: crate::Result<Option<(TupleData, V)>>,
+    ) -> Option<crate::Result<(TupleData, V)>> {
+        let item = result.transpose();
+        self.done = !matches!(item, Some(Ok(_)));
+
+        item
+    }
+}
+
+impl<V> Iterator for Range<V>
+where
+    V: Storable + Clone + Eq,
+{
+    type Item = crate::Result<(TupleData, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.front();
+        self.item(result)
+    }
+}
+
+impl<V> DoubleEndedIterator for Range<V>
+where
+    V: Storable + Clone + Eq,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.back();
+        self.item(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound::{self, *};
+
+    use rand::{seq::SliceRandom, thread_rng};
+
+    use crate::btree::BTree;
+    use crate::disk::Memory;
+    use crate::page::PAGE_SIZE;
+    use crate::page_cache::PageCache;
+    use crate::replacer::LRU;
+    use crate::schema;
+    use crate::table::tuple::Data as TupleData;
+
+    #[test]
+    fn test_range() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 32;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let lru = LRU::new(K);
+        let pc = PageCache::new(disk, lru, 0);
+
+        let schema = schema! {c1 Int};
+        let btree = BTree::new(pc.clone(), &schema)?;
+        assert_eq!(None, btree.iter(Unbounded, Unbounded).next_back().transpose()?);
+
+        // Only even keys, so bounds can fall between them
+        let mut keys = (0..1000).map(|i| i * 2).collect::<Vec<i32>>();
+        keys.shuffle(&mut thread_rng());
+        for k in &keys {
+            btree.insert(&(*k).into(), k)?;
+        }
+        keys.sort();
+
+        let within = |k: &i32, from: Bound<i32>, to: Bound<i32>| {
+            let above = match from {
+                Included(from) => *k >= from,
+                Excluded(from) => *k > from,
+                Unbounded => true,
+            };
+            let below = match to {
+                Included(to) => *k <= to,
+                Excluded(to) => *k < to,
+                Unbounded => true,
+            };
+            above && below
+        };
+        let key = |bound: Bound<i32>| bound.map(TupleData::from);
+
+        let bounds = [
+            (Unbounded, Unbounded),
+            (Included(500), Included(900)),
+            (Excluded(500), Excluded(900)),
+            (Included(501), Excluded(901)),
+            (Excluded(-1), Included(0)),
+            (Unbounded, Excluded(1500)),
+            (Included(1998), Unbounded),
+            (Excluded(1998), Unbounded),
+            (Included(700), Excluded(700)),
+            (Included(5000), Unbounded),
+        ];
+        for (from, to) in bounds {
+            let want = keys.iter().copied().filter(|k| within(k, from, to)).collect::<Vec<_>>();
+
+            let have = btree
+                .iter(key(from), key(to))
+                .map(|entry| entry.map(|(_, v)| v))
+                .collect::<crate::Result<Vec<_>>>()?;
+            assert_eq!(want, have, "{from:?} {to:?}");
+
+            let have = btree
+                .iter(key(from), key(to))
+                .rev()
+                .map(|entry| entry.map(|(_, v)| v))
+                .collect::<crate::Result<Vec<_>>>()?;
+            assert_eq!(want.iter().rev().copied().collect::<Vec<_>>(), have, "{from:?} {to:?}");
+
+            // Taking from both ends stops once they meet
+            let mut range = btree.iter(key(from), key(to));
+            let (mut front, mut back) = (Vec::new(), Vec::new());
+            loop {
+                match range.next().transpose()? {
+                    Some((_, v)) => front.push(v),
+                    None => break,
+                }
+                match range.next_back().transpose()? {
+                    Some((_, v)) => back.push(v),
+                    None => break,
+                }
+            }
+            back.reverse();
+            assert_eq!(want, [front, back].concat(), "{from:?} {to:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cursor() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 32;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let lru = LRU::new(K);
+        let pc = PageCache::new(disk, lru, 0);
+
+        let schema = schema! {c1 Int};
+        let btree = BTree::new(pc.clone(), &schema)?;
+        for k in 0..1000 {
+            btree.insert(&k.into(), &k)?;
+        }
+
+        // The cursor can change direction, crossing between leaves both ways
+        let mut cursor = btree.cursor();
+        cursor.seek(Excluded(&500.into()))?;
+        let mut have = Vec::new();
+        for _ in 0..300 {
+            have.push(cursor.next()?.unwrap().1);
+        }
+        for _ in 0..600 {
+            have.push(cursor.prev()?.unwrap().1);
+        }
+        let want = (501..801).chain((201..801).rev()).collect::<Vec<_>>();
+        assert_eq!(want, have);
+
+        cursor.seek_back(Included(&0.into()))?;
+        assert_eq!(Some(0), cursor.prev()?.map(|(_, v)| v));
+        assert_eq!(None, cursor.prev()?);
+
+        cursor.seek(Unbounded)?;
+        assert_eq!(Some(0), cursor.next()?.map(|(_, v)| v));
+        cursor.seek_back(Unbounded)?;
+        assert_eq!(Some(999), cursor.prev()?.map(|(_, v)| v));
+        assert_eq!(Some(999), cursor.next()?.map(|(_, v)| v));
+        assert_eq!(None, cursor.next()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use crate::btree::node::{Node, NodeType};
+use crate::btree::root::RootPointer;
+use crate::btree::slot::{Either, Slot};
+use crate::btree::BTree;
+use crate::page::PageID;
+use crate::storable::Storable;
+use crate::table::tuple::{Comparand, Data as TupleData};
+use crate::wal::autocommit;
+
+/// The share of each node that bulk loading fills by default, which leaves room for a few inserts
+/// before the nodes have to be split
+pub const FILL_FACTOR: f64 = 0.9;
+
+/// A level of a tree that's being bulk loaded. Nodes are filled from left to right, and a full
+/// node is held back until the one after it is full too, so that the last two nodes on the level
+/// can be evened out if the last one ends up underfull.
+struct Level<V> {
+    /// The full node before `current`, which hasn't been written yet
+    pending: Option<Node<V>>,
+    current: Option<Node<V>>,
+    /// The pages of the nodes that have been started on the level
+    pages: Vec<PageID>,
+}
+
+impl<V> BTree<'_, V>
+where
+    V: Storable + Clone + Eq,
+{
+    /// Fills an empty tree with entries in ascending order of key. The tree is built from the
+    /// leaves up, filling each node to `fill` of its capacity, except for the last node on each
+    /// level, which can hold less but is never underfull. This is much quicker than inserting the
+    /// entries one at a time, which leaves most nodes half full after they're split. If loading
+    /// fails, the nodes written so far are given back and the tree is left empty.
+    ///
+    /// Panics if the tree isn't empty, if the keys aren't strictly ascending, or if `fill` is
+    /// outside of 0.5 to 1, since below a half the last two nodes on a level couldn't be evened out
+    /// without leaving one of them underfull.
+    pub fn load(
+        &self,
+        entries: impl IntoIterator<Item = (TupleData, V)>,
+        fill: f64,
+    ) -> crate::Result<()> {
+        assert!((0.5..=1.0).contains(&fill), "fill factor should be between 0.5 and 1");
+        assert_eq!(-1, self.root()?, "only an empty tree can be bulk loaded");
+
+        // Nothing can reach the nodes until the root is set, so they're written outside of any
+        // transaction, which would otherwise hold on to every node until it finished. If the tree
+        // can't be finished, the nodes are given back.
+        let mut levels = Vec::new();
+        let root = autocommit(self.pc.log(), || {
+            let built = self.build(&mut levels, entries, fill);
+            if built.is_err() {
+                for page_id in levels.iter().flat_map(|level| &level.pages) {
+                    let _ = self.pc.deallocate_page(*page_id);
+                }
+            }
+
+            built
+        })?;
+
+        let txn = self.pc.begin();
+        self.pc.fetch_page(self.id)?.write().put(&RootPointer { root });
+        txn.commit()
+    }
+
+    /// Builds the levels of the tree from the entries, returning the root
+    fn build(
+        &self,
+        levels: &mut Vec<Level<V>>,
+        entries: impl IntoIterator<Item = (TupleData, V)>,
+        fill: f64,
+    ) -> crate::Result<PageID> {
+        let mut last: Option<TupleData> = None;
+        for (key, value) in entries {
+            if let Some(last) = &last {
+                assert!(
+                    Comparand(self.schema, last) < Comparand(self.schema, &key),
+                    "bulk loaded keys should be strictly ascending"
+                );
+            }
+            last = Some(key.clone());
+
+            self.push(levels, 0, Slot(key, Either::Value(value)), fill)?;
+        }
+
+        // Finishing a level adds its last nodes to the level above, so it can grow a new level
+        let mut root = -1;
+        let mut height = 0;
+        while height < levels.len() {
+            if let Some(id) = self.finish(levels, height, fill)? {
+                root = id;
+            }
+            height += 1;
+        }
+
+        Ok(root)
+    }
+
+    /// Appends a slot to the node being filled on a level, starting a new node if there isn't one.
+    /// Once the node is full, the node held back before it is written.
+    fn push(
+        &self,
+        levels: &mut Vec<Level<V>>,
+        height: usize,
+        slot: Slot<V>,
+        fill: f64,
+    ) -> crate::Result<()> {
+        if levels.len() == height {
+            levels.push(Level { pending: None, current: None, pages: Vec::new() });
+        }
+
+        let level = &mut levels[height];
+        let node = match &mut level.current {
+            Some(node) => node,
+            None => {
+                let t = if height == 0 { NodeType::Leaf } else { NodeType::Internal };
+                let mut node = Node::new(self.pc.new_page()?.id, t, false);
+                if let Some(prev) = level.pending.as_mut().filter(|_| t == NodeType::Leaf) {
+                    prev.next = node.id;
+                    node.prev = prev.id;
+                }
+                level.pages.push(node.id);
+                level.current.insert(node)
+            }
+        };
+
+        node.push(slot);
+        if node.filled(fill, self.schema) {
+            let full = level.current.take();
+            if let Some(pending) = std::mem::replace(&mut level.pending, full) {
+                self.write(levels, height, pending, fill)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a finished node and adds its separator to the level above
+    fn write(
+        &self,
+        levels: &mut Vec<Level<V>>,
+        height: usize,
+        node: Node<V>,
+        fill: f64,
+    ) -> crate::Result<()> {
+        self.pc.fetch_page(node.id)?.write().put(&node);
+        self.push(levels, height + 1, node.get_separator(self.schema), fill)
+    }
+
+    /// Writes the nodes left on a level once every level below it is finished. If the level only
+    /// has one node, it's the root, and its ID is returned.
+    fn finish(
+        &self,
+        levels: &mut Vec<Level<V>>,
+        height: usize,
+        fill: f64,
+    ) -> crate::Result<Option<PageID>> {
+        let level = &mut levels[height];
+        let (pending, current) = (level.pending.take(), level.current.take());
+        if level.pages.len() == 1 {
+            let mut root = pending.or(current).expect("a started level should have a node");
+            root.is_root = true;
+            self.pc.fetch_page(root.id)?.write().put(&root);
+
+            return Ok(Some(root.id));
+        }
+
+        // Every node but the first is started after the one before it is held back
+        let mut pending = pending.expect("a level with several nodes should have a full node");
+        if let Some(mut current) = current {
+            if current.underfull(self.schema) {
+                let Slot(separator, _) = pending.get_separator(self.schema);
+                pending.redistribute(&mut current, &separator, self.schema);
+            }
+            self.write(levels, height, pending, fill)?;
+            self.write(levels, height, current, fill)?;
+        } else {
+            self.write(levels, height, pending, fill)?;
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::btree::load::FILL_FACTOR;
+    use crate::btree::BTree;
+    use crate::disk::Memory;
+    use crate::page::PAGE_SIZE;
+    use crate::page_cache::PageCache;
+    use crate::replacer::LRU;
+    use crate::schema;
+    use crate::table::tuple::{Builder, Data as TupleData};
+
+    #[test]
+    fn test_btree_load() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 1024;
+        const K: usize = 2;
+
+        // Wide keys keep the nodes small, so the tree is three levels deep
+        let schema = schema! {c1 Int, c2 BigInt, c3 BigInt, c4 BigInt, c5 BigInt};
+        let key = |i: i32| {
+            let b = Builder::new().int(i).big_int(i.into());
+            b.big_int(0).big_int(0).big_int(0).build()
+        };
+        let entries = |n: i32| (0..n).map(|i| (key(i * 2), i)).collect::<Vec<_>>();
+
+        let mut nodes = Vec::new();
+        for (n, fill) in [(0, FILL_FACTOR), (1, 1.0), (2000, 0.5), (2000, FILL_FACTOR), (2000, 1.0)]
+        {
+            let pc = PageCache::new(Memory::new::<MEMORY>(), LRU::new(K), 0);
+            let btree = BTree::new(pc.clone(), &schema)?;
+            btree.load(entries(n), fill)?;
+
+            nodes.push(btree.check()?);
+            assert_eq!(entries(n), btree.scan()?);
+
+            // The loaded tree can be written to like any other
+            let mut want = BTreeMap::from_iter((0..n).map(|i| (i * 2, i)));
+            for i in 0..n {
+                btree.insert(&key(i * 2 + 1), &i)?;
+                want.insert(i * 2 + 1, i);
+            }
+            for i in (0..n).step_by(3) {
+                btree.delete(&key(i * 2))?;
+                want.remove(&(i * 2));
+            }
+            btree.check()?;
+            let want = want.into_iter().map(|(k, value)| (key(k), value)).collect::<Vec<_>>();
+            assert_eq!(want, btree.scan()?);
+        }
+
+        // Fuller nodes need fewer of them
+        assert_eq!([0, 1], nodes[..2]);
+        assert!(nodes[2] > nodes[3] && nodes[3] > nodes[4], "{nodes:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "strictly ascending")]
+    fn test_btree_load_unsorted() {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        let pc = PageCache::new(Memory::new::<MEMORY>(), LRU::new(2), 0);
+        let schema = schema! { c1 Int };
+        let btree = BTree::new(pc, &schema).unwrap();
+        btree.load([(TupleData::from(2), 0), (TupleData::from(1), 0)], FILL_FACTOR).unwrap();
+    }
+}
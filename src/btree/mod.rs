@@ -1,18 +1,33 @@
+pub mod cursor;
+pub mod load;
 pub mod node;
+pub mod root;
 pub mod slot;
 
 use std::marker::PhantomData;
+use std::ops::Bound;
 
+use crate::btree::cursor::{Cursor, Range};
 use crate::btree::node::{Node, NodeType};
+use crate::btree::root::RootPointer;
 use crate::btree::slot::{Either, Slot};
 use crate::catalog::schema::Schema;
-use crate::page::{DiskObject, ObjectReadGuard, PageID, PageWriteGuard};
-use crate::page_cache::SharedPageCache;
+use crate::page::{DiskObject, PageID, PageReadGuard, PageWriteGuard, PAGE_SIZE};
+use crate::page_cache::{Latch, PageCache, SharedPageCache};
 use crate::storable::Storable;
 use crate::table::tuple::{Comparand, Data as TupleData};
+use crate::wal::{IndexOp, LogicalUndo};
 
+type WriteLatch<'a> = Latch<'a, PageWriteGuard<'a>>;
+
+/// A B-tree which can be used from several threads at once. Every operation starts at the page
+/// that points to the root and works its way down by latch crabbing: a node is only latched whilst
+/// its parent is, and the parent is released once nothing below it can change it. Readers only
+/// hold two read latches at a time. Writers hold write latches on the nodes that a split or merge
+/// further down could still change, and siblings are always latched from left to right.
 pub struct BTree<'s, V> {
-    root: PageID,
+    /// The page holding the ID of the root node
+    id: PageID,
     pc: SharedPageCache,
     schema: &'s Schema,
     _data: PhantomData<V>,
@@ -22,326 +37,334 @@ impl<'s, V> BTree<'s, V>
 where
     V: Storable + Clone + Eq,
 {
-    pub fn new(pc: SharedPageCache, schema: &'s Schema) -> Self {
-        Self { root: -1, pc, schema, _data: PhantomData }
-    }
+    /// Creates an empty tree, allocating the page that points to its root
+    pub fn new(pc: SharedPageCache, schema: &'s Schema) -> crate::Result<Self> {
+        let id = {
+            let pin = pc.new_page()?;
+            pin.write().put(&RootPointer { root: -1 });
+            pin.id
+        };
 
-    pub fn new_with_root(pc: SharedPageCache, root: PageID, schema: &'s Schema) -> Self {
-        Self { root, pc, schema, _data: PhantomData }
+        Ok(Self::open(pc, id, schema))
     }
 
-    pub fn root(&self) -> PageID {
-        self.root
+    /// Opens the tree whose root is pointed to by page `id`
+    pub fn open(pc: SharedPageCache, id: PageID, schema: &'s Schema) -> Self {
+        Self { id, pc, schema, _data: PhantomData }
     }
 
-    // TODO: One thread could split the root whilst another holds a pin to the root. Should double
-    // check is_root
-    pub fn insert(&mut self, key: &TupleData, value: &V) -> crate::Result<()> {
-        let pin;
-        let rpage = match self.root {
-            -1 => {
-                pin = self.pc.new_page()?;
-                let node: Node<V> = Node::new(pin.id, NodeType::Leaf, true);
-                let mut page = pin.write();
-                page.put(&node);
-                page
-            }
-            id => {
-                pin = self.pc.fetch_page(id)?;
-                pin.write()
-            }
-        };
-        self.root = rpage.id;
+    /// Returns the ID of the page that points to the root, which the tree is opened from
+    pub fn id(&self) -> PageID {
+        self.id
+    }
 
-        if let Some((s, os)) = self._insert(None, rpage, key, value)? {
-            let new_root_page = self.pc.new_page()?;
-            let mut new_root = Node::new(new_root_page.id, NodeType::Internal, true);
-            self.root = new_root.id;
+    /// Returns the ID of the root node, which is -1 if the tree is empty
+    pub fn root(&self) -> crate::Result<PageID> {
+        let pin = self.pc.fetch_page(self.id)?;
+        let root = pin.read_object::<RootPointer>(self.schema).root;
 
-            new_root.insert(s, self.schema);
-            new_root.insert(os, self.schema);
+        Ok(root)
+    }
 
-            let mut w = new_root_page.write();
-            w.put(&new_root);
+    /// Inserts the key, replacing its value if it already exists. Full nodes are split on the way
+    /// down, so the parent of a node is released as soon as the node is known to have room.
+    pub fn insert(&self, key: &TupleData, value: &V) -> crate::Result<()> {
+        let txn = self.pc.begin();
+
+        let mut pointer = self.pc.fetch_page(self.id)?.into_write();
+        let mut root = RootPointer::deserialise(pointer.data, self.schema).root;
+        if root == -1 {
+            let pin = self.pc.new_page()?;
+            pin.write().put(&Node::<V>::new(pin.id, NodeType::Leaf, true));
+            root = pin.id;
+            pointer.put(&RootPointer { root });
         }
 
-        Ok(())
-    }
-
-    fn _insert<'a>(
-        &'a self,
-        mut prev_page: Option<&'a PageWriteGuard<'a>>,
-        mut page: PageWriteGuard<'a>,
-        key: &TupleData,
-        value: &V,
-    ) -> crate::Result<Option<(Slot<V>, Slot<V>)>> {
+        let mut page = self.pc.fetch_page(root)?.into_write();
         let mut node: Node<V> = Node::deserialise(page.data, self.schema);
-
-        let mut split = None;
         if node.almost_full(self.schema) {
-            let new_page = self.pc.new_page()?;
-            let mut npage = new_page.write();
-            let mut nnode = node.split(new_page.id);
+            // The root is split in two below a new root
+            let (right, rnode, (s, os)) = self.split(&mut page, &mut node)?;
+            let go_right = Comparand(self.schema, key) >= Comparand(self.schema, &s.0);
 
-            if Comparand(self.schema, key) >= Comparand(self.schema, node.last_key().unwrap()) {
-                // Write the node
-                page.put(&node);
-
-                // We don't need to keep a lock on this side of the tree
-                drop(page);
-
-                // Find and insert
-                {
-                    // Find the child node
-                    let ptr = match nnode.find_child(key, self.schema) {
-                        Some(ptr) => ptr,
-                        None if nnode.t == NodeType::Internal => {
-                            // Bump the last node if no pointer found
-                            let Slot(_, v) = nnode.pop_last().unwrap();
-                            nnode.insert(Slot(key.next(self.schema), v), self.schema);
-
-                            match nnode.find_child(key, self.schema) {
-                                Some(ptr) => ptr,
-                                None => unreachable!(),
-                            }
-                        }
-                        None => {
-                            // Reached leaf node
-                            nnode.replace(
-                                Slot(key.clone(), Either::Value(value.clone())),
-                                self.schema,
-                            );
-                            npage.put(&nnode);
-
-                            return Ok(node.get_separators(Some(nnode), self.schema));
-                        }
-                    };
-
-                    let child_page = self.pc.fetch_page(ptr)?;
-                    let cpage = child_page.write();
-
-                    prev_page.take();
-                    if let Some((s, os)) = self._insert(Some(&npage), cpage, key, value)? {
-                        nnode.replace(s, self.schema);
-                        nnode.replace(os, self.schema);
-                    }
-
-                    // Write the new node
-                    npage.put(&nnode);
+            let new_root_page = self.pc.new_page()?;
+            let mut new_root = Node::new(new_root_page.id, NodeType::Internal, true);
+            new_root.insert(s, self.schema);
+            new_root.insert(os, self.schema);
+            new_root_page.write().put(&new_root);
+            pointer.put(&RootPointer { root: new_root.id });
 
-                    return Ok(node.get_separators(Some(nnode), self.schema));
-                }
+            if go_right {
+                (page, node) = (right, rnode);
             }
-
-            // Write the new node
-            // Original node is written below
-            npage.put(&nnode);
-
-            split = Some(nnode)
         }
+        drop(pointer);
 
-        // Find and insert
-        {
-            // Find the child node
-            let ptr = match node.find_child(key, self.schema) {
-                Some(ptr) => ptr,
-                None if node.t == NodeType::Internal => {
-                    // Bump the last node if no pointer found
+        while node.t == NodeType::Internal {
+            // Keys past the last separator belong in the last child, so its separator is bumped
+            let i = match node.child_index(key, self.schema) {
+                Some(i) => i,
+                None => {
                     let Slot(_, v) = node.pop_last().unwrap();
                     node.insert(Slot(key.next(self.schema), v), self.schema);
-
-                    match node.find_child(key, self.schema) {
-                        Some(ptr) => ptr,
-                        None => unreachable!(),
-                    }
-                }
-                None => {
-                    // Reached leaf node
-                    node.replace(Slot(key.clone(), Either::Value(value.clone())), self.schema);
                     page.put(&node);
-
-                    return Ok(node.get_separators(split, self.schema));
+                    node.len() - 1
                 }
             };
 
-            let child_page = self.pc.fetch_page(ptr)?;
-            let cpage = child_page.write();
+            let mut child = self.pc.fetch_page(node.child(i))?.into_write();
+            let mut cnode: Node<V> = Node::deserialise(child.data, self.schema);
+            if cnode.almost_full(self.schema) {
+                let (right, rnode, (s, os)) = self.split(&mut child, &mut cnode)?;
+                let go_right = Comparand(self.schema, key) >= Comparand(self.schema, &s.0);
+                node.split_child(s, os, self.schema);
+                page.put(&node);
 
-            prev_page.take();
-            if let Some((s, os)) = self._insert(Some(&page), cpage, key, value)? {
-                node.replace(s, self.schema);
-                node.replace(os, self.schema);
+                if go_right {
+                    (child, cnode) = (right, rnode);
+                }
             }
 
-            // Write the original node
-            page.put(&node);
-
-            Ok(node.get_separators(split, self.schema))
-        }
-    }
-
-    pub fn scan(&self) -> crate::Result<Vec<(TupleData, V)>> {
-        let mut ret = Vec::new();
-        if self.root == -1 {
-            return Ok(ret);
+            // The child has room, so this insert can't change its parent any more
+            (page, node) = (child, cnode);
         }
 
-        let pin = self.pc.fetch_page(self.root)?;
-        let r = pin.read_object::<Node<V>>(self.schema);
-
-        self._scan(None, r, &mut ret)?;
+        let old = node.replace(Slot(key.clone(), Either::Value(value.clone())), self.schema);
+        page.put(&node);
+        drop(page);
 
-        Ok(ret)
+        let undo = match old {
+            Some(Slot(_, Either::Value(old))) => {
+                LogicalUndo::new(IndexOp::BTreeInsert, self.id, self.schema, key, &old)
+            }
+            _ => LogicalUndo::new(IndexOp::BTreeDelete, self.id, self.schema, key, value),
+        };
+        txn.commit_logical(undo)
     }
 
-    fn _scan<'a>(
+    /// Splits the latched node, moving the upper half of its slots to a new node. Both halves are
+    /// written, and the new node is returned latched along with the separators of both halves.
+    #[allow(clippy::type_complexity)]
+    fn split<'a>(
         &'a self,
-        mut prev_node: Option<ObjectReadGuard<'a, Node<V>>>,
-        node: ObjectReadGuard<'a, Node<V>>,
-        acc: &mut Vec<(TupleData, V)>,
-    ) -> crate::Result<()> {
-        // Find first leaf
-        if node.t != NodeType::Leaf {
-            let Slot(_, v) = node.first().unwrap();
-            match v {
-                Either::Pointer(ptr) => {
-                    let pin = self.pc.fetch_page(*ptr)?;
-                    let r = pin.read_object::<Node<V>>(self.schema);
-
-                    prev_node.take();
-                    return self._scan(Some(node), r, acc);
-                }
-                Either::Value(_) => unreachable!(),
-            };
+        page: &mut WriteLatch<'a>,
+        node: &mut Node<V>,
+    ) -> crate::Result<(WriteLatch<'a>, Node<V>, (Slot<V>, Slot<V>))> {
+        let mut new_page = self.pc.new_page()?.into_write();
+        let new = node.split(new_page.id);
+        if new.t == NodeType::Leaf && new.next != -1 {
+            let next_page = self.pc.fetch_page(new.next)?;
+            next_page.write_object::<Node<V>>(self.schema).prev = new.id;
         }
 
-        acc.extend(node.iter().map(|Slot(k, v)| match v {
-            Either::Value(v) => (k.clone(), v.clone()),
-            Either::Pointer(_) => unreachable!(),
-        }));
-
-        if node.next == -1 {
-            return Ok(());
-        }
+        page.put(node);
+        new_page.put(&new);
+        let separators = node.get_separators(Some(&new), self.schema).unwrap();
 
-        let pin = self.pc.fetch_page(node.next)?;
-        let r = pin.read_object::<Node<V>>(self.schema);
+        Ok((new_page, new, separators))
+    }
 
-        prev_node.take();
-        self._scan(Some(node), r, acc)
+    /// Opens a cursor on the tree, which has to be positioned with `seek` or `seek_back`
+    pub fn cursor(&self) -> Cursor<V> {
+        Cursor::new(self.pc.clone(), self.schema.clone(), self.id)
     }
 
-    pub fn range(&self, from: &TupleData, to: &TupleData) -> crate::Result<Vec<(TupleData, V)>> {
-        let mut ret = Vec::new();
+    /// Returns the entries between two bounds lazily, in ascending order or in descending order
+    /// when reversed
+    pub fn iter(&self, from: Bound<TupleData>, to: Bound<TupleData>) -> Range<V> {
+        Range::new(self.pc.clone(), self.schema.clone(), self.id, from, to)
+    }
 
-        let cur = match self.get_ptr(from, self.root)? {
-            Some(c) => c,
-            None => return Ok(ret),
-        };
+    pub fn scan(&self) -> crate::Result<Vec<(TupleData, V)>> {
+        self.iter(Bound::Unbounded, Bound::Unbounded).collect()
+    }
 
-        let page = self.pc.fetch_page(cur)?;
-        let r = page.read_object::<Node<V>>(self.schema);
+    pub fn range(&self, from: &TupleData, to: &TupleData) -> crate::Result<Vec<(TupleData, V)>> {
+        self.iter(Bound::Included(from.clone()), Bound::Included(to.clone())).collect()
+    }
 
-        self._range(None, r, &mut ret, from, to)?;
+    pub fn get(&self, key: &TupleData) -> crate::Result<Option<Slot<V>>> {
+        let Some(mut page) = read_root(&self.pc, self.id)? else { return Ok(None) };
+
+        loop {
+            let node: Node<V> = Node::deserialise(page.data, self.schema);
+            match node.find_child(key, self.schema) {
+                Some(ptr) => page = self.pc.fetch_page(ptr)?.into_read(),
+                None if node.t == NodeType::Leaf => return Ok(node.get(key, self.schema).cloned()),
+                None => return Ok(None),
+            }
+        }
+    }
 
-        Ok(ret)
+    /// Deletes the key, returning false if it doesn't exist. Nodes that are left underfull take
+    /// slots from or are merged with a sibling, and the root is collapsed once it only has one
+    /// child, giving back the pages that are no longer used.
+    pub fn delete(&self, key: &TupleData) -> crate::Result<bool> {
+        let txn = self.pc.begin();
+        let Some(value) = self._delete(key)? else {
+            txn.commit()?;
+            return Ok(false);
+        };
+        txn.commit_logical(LogicalUndo::new(
+            IndexOp::BTreeInsert,
+            self.id,
+            self.schema,
+            key,
+            &value,
+        ))?;
+
+        Ok(true)
     }
 
-    fn _range<'a>(
-        &'a self,
-        mut prev_node: Option<ObjectReadGuard<'a, Node<V>>>,
-        node: ObjectReadGuard<'a, Node<V>>,
-        acc: &mut Vec<(TupleData, V)>,
-        from: &TupleData,
-        to: &TupleData,
-    ) -> crate::Result<()> {
-        let next = node.next;
-        let len = acc.len();
-        acc.extend(
-            node.iter()
-                .skip_while(|Slot(k, _)| Comparand(self.schema, k) < Comparand(self.schema, from))
-                .take_while(|Slot(k, _)| Comparand(self.schema, k) <= Comparand(self.schema, to))
-                .cloned()
-                .map(|Slot(k, v)| {
-                    let v = match v {
-                        Either::Value(v) => v,
-                        _ => unreachable!(),
-                    };
-                    (k, v)
-                }),
-        );
-        if len == acc.len() {
-            return Ok(());
+    /// Deletes the key, returning the value it had
+    fn _delete(&self, key: &TupleData) -> crate::Result<Option<V>> {
+        let mut pointer = Some(self.pc.fetch_page(self.id)?.into_write());
+        let root = RootPointer::deserialise(pointer.as_ref().unwrap().data, self.schema).root;
+        if root == -1 {
+            return Ok(None);
         }
 
-        if next == -1 {
-            return Ok(());
+        let mut page = self.pc.fetch_page(root)?.into_write();
+        let mut node: Node<V> = Node::deserialise(page.data, self.schema);
+
+        // The root is only replaced once it's an empty leaf or it's left with one child
+        let root_safe = match node.t {
+            NodeType::Leaf => node.len() > 1,
+            NodeType::Internal => node.len() > 2,
+        };
+        if root_safe {
+            pointer = None;
         }
 
-        let next_page = self.pc.fetch_page(next)?;
-        let r = next_page.read_object::<Node<V>>(self.schema);
+        // The latched nodes above the current one, with the position of the child that was taken
+        let mut path = Vec::new();
+        while node.t == NodeType::Internal {
+            let Some(i) = node.child_index(key, self.schema) else { return Ok(None) };
+            let child = self.pc.fetch_page(node.child(i))?.into_write();
+            let cnode: Node<V> = Node::deserialise(child.data, self.schema);
+
+            if cnode.at_minimum(self.schema) {
+                path.push((page, node, i));
+            } else {
+                // The child won't be left underfull, so nothing above it can change
+                path.clear();
+                pointer = None;
+            }
+            (page, node) = (child, cnode);
+        }
 
-        prev_node.take();
+        let Some(Slot(_, Either::Value(value))) = node.get(key, self.schema).cloned() else {
+            return Ok(None);
+        };
+        node.remove(key, self.schema);
+        page.put(&node);
+
+        let mut underfull = node.underfull(self.schema);
+        let mut top = (page, node);
+        while let Some((mut ppage, mut pnode, i)) = path.pop() {
+            // The child is released so that it can be latched again in order with its sibling.
+            // Other writers can't reach it whilst its parent is latched.
+            drop(top);
+            if underfull && pnode.len() > 1 {
+                self.rebalance(&mut pnode, i)?;
+                ppage.put(&pnode);
+            }
 
-        self._range(Some(node), r, acc, from, to)
-    }
+            underfull = pnode.underfull(self.schema);
+            top = (ppage, pnode);
+        }
 
-    fn get_ptr(&self, key: &TupleData, ptr: PageID) -> crate::Result<Option<PageID>> {
-        assert!(ptr != -1);
+        if let Some(mut pointer) = pointer {
+            let (mut page, node) = top;
+            let root = match node.t {
+                NodeType::Internal if node.len() == 1 => node.child(0),
+                NodeType::Leaf if node.is_empty() => -1,
+                _ => return Ok(Some(value)),
+            };
 
-        let page = self.pc.fetch_page(ptr)?;
-        let node = page.read_object::<Node<V>>(self.schema);
+            if root != -1 {
+                let pin = self.pc.fetch_page(root)?;
+                pin.write_object::<Node<V>>(self.schema).is_root = true;
+            }
+            pointer.put(&RootPointer { root });
 
-        match node.find_child(key, self.schema) {
-            Some(ptr) => self.get_ptr(key, ptr),
-            None if node.t == NodeType::Leaf => Ok(Some(ptr)),
-            None => Ok(None),
+            page.put_range(&[0; PAGE_SIZE], 0..PAGE_SIZE);
+            drop(page);
+            self.pc.deallocate_page(node.id)?;
         }
+
+        Ok(Some(value))
     }
 
-    pub fn get(&self, key: &TupleData) -> crate::Result<Option<Slot<V>>> {
-        if self.root == -1 {
-            return Ok(None);
+    /// Fixes the underfull child at position `i` of `parent` by merging it with a sibling, or by
+    /// moving slots over from the sibling if they wouldn't fit in one node
+    fn rebalance(&self, parent: &mut Node<V>, i: usize) -> crate::Result<()> {
+        // The child is paired with its left sibling, unless it's the first child
+        let i = i.saturating_sub(1);
+        let separator = parent.key(i).clone();
+
+        let left_page = self.pc.fetch_page(parent.child(i))?;
+        let right_page = self.pc.fetch_page(parent.child(i + 1))?;
+        let mut left_w = left_page.write();
+        let mut right_w = right_page.write();
+        let mut left: Node<V> = Node::deserialise(left_w.data, self.schema);
+        let mut right: Node<V> = Node::deserialise(right_w.data, self.schema);
+
+        if !left.can_merge(&right, self.schema) {
+            let separator = left.redistribute(&mut right, &separator, self.schema);
+            parent.set_key(i, separator);
+            left_w.put(&left);
+            right_w.put(&right);
+
+            return Ok(());
         }
 
-        self._get(key, self.root)
-    }
+        left.merge(right, &separator);
+        parent.merge_children(i);
+        if left.t == NodeType::Leaf && left.next != -1 {
+            let next_page = self.pc.fetch_page(left.next)?;
+            next_page.write_object::<Node<V>>(self.schema).prev = left.id;
+        }
+        left_w.put(&left);
 
-    fn _get(&self, key: &TupleData, ptr: PageID) -> crate::Result<Option<Slot<V>>> {
-        let page = self.pc.fetch_page(ptr)?;
-        let node = page.read_object::<Node<V>>(self.schema);
+        // A cursor may still have the page pinned, clearing it tells the cursor to search again
+        right_w.put_range(&[0; PAGE_SIZE], 0..PAGE_SIZE);
+        drop(right_w);
+        self.pc.deallocate_page(right_page.id)?;
 
-        match node.find_child(key, self.schema) {
-            Some(ptr) => self._get(key, ptr),
-            None if node.t == NodeType::Leaf => Ok(node.get(key, self.schema).cloned()),
-            None => Ok(None),
-        }
+        Ok(())
     }
 
-    pub fn delete(&self, key: &TupleData) -> crate::Result<bool> {
-        if self.root == -1 {
-            return Ok(false);
+    /// Gives back every page of the tree, including the page that points to its root
+    pub fn deallocate(self) -> crate::Result<()> {
+        let txn = self.pc.begin();
+
+        let root = self.root()?;
+        if root != -1 {
+            self._deallocate(root)?;
         }
+        self.pc.deallocate_page(self.id)?;
 
-        self._delete(key, self.root)
+        txn.commit()
     }
 
-    fn _delete(&self, key: &TupleData, ptr: PageID) -> crate::Result<bool> {
+    fn _deallocate(&self, ptr: PageID) -> crate::Result<()> {
         let page = self.pc.fetch_page(ptr)?;
-        let mut w = page.write();
-        let mut node: Node<V> = Node::deserialise(w.data, self.schema);
-
-        match node.find_child(key, self.schema) {
-            Some(ptr) => self._delete(key, ptr),
-            None if node.t == NodeType::Leaf => {
-                let rem = node.remove(key, self.schema);
-                if rem {
-                    w.put(&node);
+        let node = page.read_object::<Node<V>>(self.schema);
+
+        if node.t != NodeType::Leaf {
+            for Slot(_, v) in node.iter() {
+                if let Either::Pointer(ptr) = v {
+                    self._deallocate(*ptr)?;
                 }
-                Ok(rem)
             }
-            None => Ok(false),
+
+            if node.next != -1 {
+                self._deallocate(node.next)?;
+            }
         }
+
+        self.pc.deallocate_page(ptr)
     }
 
     #[cfg(test)]
@@ -364,12 +387,13 @@ where
     #[cfg(test)]
     #[allow(dead_code)]
     fn leaf_count(&self) -> crate::Result<usize> {
-        if self.root == -1 {
+        let root = self.root()?;
+        if root == -1 {
             return Ok(0);
         }
 
         let mut ret = 1;
-        let mut cur = self.first(self.root)?;
+        let mut cur = self.first(root)?;
 
         while cur != -1 {
             let pin = self.pc.fetch_page(cur)?;
@@ -381,6 +405,102 @@ where
 
         Ok(ret)
     }
+
+    /// Checks the structure of the tree, returning the number of nodes in it. Every key must lie
+    /// within the bounds set by its parents, nodes other than the root can't be underfull, all of
+    /// the leaves must be at the same depth and linked in order in both directions.
+    #[cfg(test)]
+    fn check(&self) -> crate::Result<usize> {
+        let root = self.root()?;
+        if root == -1 {
+            return Ok(0);
+        }
+
+        let mut leaves = Vec::new();
+        let nodes = self._check(root, None, None, 0, &mut leaves)?;
+
+        let depths = leaves.iter().map(|(_, depth)| *depth).collect::<Vec<_>>();
+        assert!(depths.windows(2).all(|w| w[0] == w[1]), "leaves at different depths");
+
+        let mut prev = -1;
+        let mut cur = self.first(root)?;
+        for (id, _) in &leaves {
+            assert_eq!(*id, cur, "leaf out of order in the chain");
+            let pin = self.pc.fetch_page(cur)?;
+            let node = pin.read_object::<Node<V>>(self.schema);
+            assert_eq!(prev, node.prev, "leaf {cur} has the wrong previous leaf");
+            prev = cur;
+            cur = node.next;
+        }
+        assert_eq!(-1, cur, "leaf chain doesn't end at the last leaf");
+
+        Ok(nodes)
+    }
+
+    #[cfg(test)]
+    fn _check(
+        &self,
+        ptr: PageID,
+        lower: Option<&TupleData>,
+        upper: Option<&TupleData>,
+        depth: usize,
+        leaves: &mut Vec<(PageID, usize)>,
+    ) -> crate::Result<usize> {
+        let page = self.pc.fetch_page(ptr)?;
+        let node = page.read_object::<Node<V>>(self.schema).clone();
+        drop(page);
+
+        let cmp = |lhs: &TupleData, rhs: &TupleData| {
+            Comparand(self.schema, lhs).cmp(&Comparand(self.schema, rhs))
+        };
+
+        assert_eq!(depth == 0, node.is_root, "node {ptr} has the wrong root flag");
+        if !node.is_root {
+            assert!(!node.underfull(self.schema), "node {ptr} is underfull");
+        }
+
+        let keys = node.iter().map(|Slot(k, _)| k).collect::<Vec<_>>();
+        assert!(keys.windows(2).all(|w| cmp(w[0], w[1]).is_lt()), "node {ptr} is out of order");
+
+        if node.t == NodeType::Leaf {
+            for key in keys {
+                assert!(lower.is_none_or(|lower| cmp(lower, key).is_le()), "key below bound");
+                assert!(upper.is_none_or(|upper| cmp(key, upper).is_lt()), "key above bound");
+            }
+            leaves.push((ptr, depth));
+
+            return Ok(1);
+        }
+
+        assert!(!node.is_root || node.len() > 1, "root {ptr} only has one child");
+
+        let mut nodes = 1;
+        let mut lower = lower;
+        for (i, key) in keys.into_iter().enumerate() {
+            assert!(lower.is_none_or(|lower| cmp(lower, key).is_lt()), "separator below bound");
+            assert!(upper.is_none_or(|upper| cmp(key, upper).is_le()), "separator above bound");
+
+            nodes += self._check(node.child(i), lower, Some(key), depth + 1, leaves)?;
+            lower = Some(key);
+        }
+
+        Ok(nodes)
+    }
+}
+
+/// Read latches the root of the tree whose root pointer is on page `id`, returning `None` if the
+/// tree is empty. The pointer is released once the root is latched, so the root can't be replaced
+/// in between.
+fn read_root(pc: &PageCache, id: PageID) -> crate::Result<Option<Latch<'_, PageReadGuard<'_>>>> {
+    let pointer = pc.fetch_page(id)?.into_read();
+    let root = RootPointer::deserialise(pointer.data, &Schema::default()).root;
+    if root == -1 {
+        return Ok(None);
+    }
+
+    let page = pc.fetch_page(root)?.into_read();
+
+    Ok(Some(page))
 }
 
 #[cfg(test)]
@@ -390,6 +510,10 @@ mod test {
     use crate::page_cache::PageCache;
     use crate::replacer::LRU;
     use crate::schema;
+    use crate::table::tuple::{Builder, Value};
+    use crate::wal::MemoryLog;
+
+    use std::sync::Arc;
 
     use rand::{seq::SliceRandom, thread_rng, Rng};
 
@@ -421,7 +545,7 @@ mod test {
         let pc = PageCache::new(disk, lru, 0);
 
         let schema = schema! {c1 Int};
-        let mut btree = BTree::new(pc.clone(), &schema);
+        let btree = BTree::new(pc.clone(), &schema)?;
 
         // Insert and get
         let range = -230..230;
@@ -497,7 +621,7 @@ mod test {
         let pc2 = pc.clone();
 
         let schema = schema! {c1 Int};
-        let mut btree = BTree::new(pc.clone(), &schema);
+        let btree = BTree::new(pc.clone(), &schema)?;
 
         let range = -50..50;
         let mut want = inserts!(range, i32);
@@ -533,7 +657,7 @@ mod test {
 
                 let schema = schema! {c1 Int};
 
-                let mut btree = BTree::new(pc.clone(), &schema);
+                let btree = BTree::new(pc.clone(), &schema)?;
 
                 let mut inserts = inserts!($range, i32);
                 for (k, v) in &inserts {
@@ -584,4 +708,457 @@ mod test {
         from: -100,
         to: -50
     );
+
+    #[test]
+    fn test_btree_composite_keys() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 32;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let lru = LRU::new(K);
+        let pc = PageCache::new(disk, lru, 0);
+
+        let schema = schema! {c1 Int, c2 Varchar};
+        let key = |c1: i32, c2: &str| Builder::new().int(c1).varchar(c2).build();
+
+        let btree = BTree::new(pc.clone(), &schema)?;
+
+        // Every leaf split falls between keys that share their first column
+        let mut inserts = (0..300)
+            .map(|i| (key(i / 20, &"z".repeat(i as usize % 20)), i))
+            .collect::<Vec<(TupleData, i32)>>();
+        inserts.shuffle(&mut thread_rng());
+        for (k, v) in &inserts {
+            btree.insert(k, v)?;
+        }
+
+        for (k, v) in &inserts {
+            let have = btree.get(k)?;
+            let want = Some(Slot(k.clone(), Either::Value(*v)));
+            assert_eq!(want, have);
+        }
+
+        inserts.sort_by(|(k, _), (k0, _)| Comparand(&schema, k).cmp(&Comparand(&schema, k0)));
+        assert_eq!(inserts, btree.scan()?);
+
+        let c1 = &schema.columns[0];
+        let want = inserts
+            .into_iter()
+            .filter(|(k, _)| k.get_value(c1) == Value::Int(7))
+            .collect::<Vec<_>>();
+        let have = btree
+            .iter(Bound::Included(key(7, "")), Bound::Excluded(key(8, "")))
+            .collect::<crate::Result<Vec<_>>>()?;
+        assert_eq!(want, have);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_btree_delete_rebalance() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 1024;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let lru = LRU::new(K);
+        let pc = PageCache::new(disk, lru, 0);
+
+        // Wide keys keep the nodes small, so the tree is three levels deep
+        let schema = schema! {c1 Int, c2 BigInt, c3 BigInt, c4 BigInt, c5 BigInt};
+        let key = |i: i32| {
+            let b = Builder::new().int(i).big_int(i.into());
+            b.big_int(0).big_int(0).big_int(0).build()
+        };
+        let btree = BTree::new(pc.clone(), &schema)?;
+
+        let mut inserts = (0..3000).map(|i| (key(i), i)).collect::<Vec<(TupleData, i32)>>();
+        inserts.shuffle(&mut thread_rng());
+        for (k, v) in &inserts {
+            btree.insert(k, v)?;
+        }
+        let nodes = btree.check()?;
+
+        // Deleting in a random order merges and redistributes nodes at every level
+        inserts.shuffle(&mut thread_rng());
+        let (deletes, rest) = inserts.split_at(inserts.len() * 9 / 10);
+        for (i, (k, _)) in deletes.iter().enumerate() {
+            assert!(btree.delete(k)?);
+            assert!(!btree.delete(k)?);
+            assert_eq!(None, btree.get(k)?);
+
+            if i % 100 == 0 {
+                btree.check()?;
+            }
+        }
+        assert!(btree.check()? < nodes / 2);
+
+        for (k, v) in rest {
+            assert_eq!(Some(Slot(k.clone(), Either::Value(*v))), btree.get(k)?);
+        }
+
+        let mut want = rest.to_vec();
+        want.sort_by(|(k, _), (k0, _)| Comparand(&schema, k).cmp(&Comparand(&schema, k0)));
+        assert_eq!(want, btree.scan()?);
+        assert_eq!(want.iter().rev().cloned().collect::<Vec<_>>(), {
+            btree
+                .iter(Bound::Unbounded, Bound::Unbounded)
+                .rev()
+                .collect::<crate::Result<Vec<_>>>()?
+        });
+
+        // The root collapses as the tree empties, until there is no root at all
+        for (i, (k, _)) in rest.iter().enumerate() {
+            assert!(btree.delete(k)?);
+            if i % 10 == 0 {
+                btree.check()?;
+            }
+        }
+        assert_eq!(-1, btree.root()?);
+        assert!(btree.scan()?.is_empty());
+
+        // The tree can be built again from empty
+        for (k, v) in &inserts {
+            btree.insert(k, v)?;
+        }
+        btree.check()?;
+        assert_eq!(inserts.len(), btree.scan()?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_btree_delete_frees_pages() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 256;
+        const K: usize = 2;
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+        let pc = PageCache::open(disk, log, LRU::new(K))?;
+
+        let schema = schema! {c1 Int};
+        let btree = BTree::new(pc.clone(), &schema)?;
+
+        let inserts = inserts!(0..2000, i32);
+        for (k, v) in &inserts {
+            btree.insert(k, v)?;
+        }
+        let nodes = btree.check()?;
+        let end = pc.new_page()?.id;
+
+        for (k, _) in &inserts {
+            btree.delete(k)?;
+        }
+        assert_eq!(-1, btree.root()?);
+
+        // Every node was given back, so building the same tree again reuses the same pages rather
+        // than growing the file. Extending the free map may take a few pages from the end.
+        for (k, v) in &inserts {
+            btree.insert(k, v)?;
+        }
+        assert_eq!(nodes, btree.check()?);
+        assert!(pc.new_page()?.id < end + 4);
+
+        Ok(())
+    }
+
+    /// Checks that a scan of the tree, in either direction, is in order and includes every key in
+    /// `keep`, which are never deleted
+    fn check_scan(btree: &BTree<i32>, keep: &[i32], rev: bool) -> crate::Result<()> {
+        let iter = btree.iter(Bound::Unbounded, Bound::Unbounded).map(|entry| entry.map(|e| e.1));
+        let mut keys = match rev {
+            true => iter.rev().collect::<crate::Result<Vec<_>>>()?,
+            false => iter.collect::<crate::Result<Vec<_>>>()?,
+        };
+        if rev {
+            keys.reverse();
+        }
+
+        assert!(keys.windows(2).all(|w| w[0] < w[1]), "scan out of order");
+        assert!(keep.iter().all(|k| keys.binary_search(k).is_ok()), "scan is missing keys");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_btree_concurrent_inserts() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 512;
+        const K: usize = 2;
+        const WRITERS: i32 = 8;
+        const READERS: usize = 4;
+        const KEYS: i32 = 1000;
+        let disk = Memory::new::<MEMORY>();
+        let lru = LRU::new(K);
+        let pc = PageCache::new(disk, lru, 0);
+
+        let schema = schema! {c1 Int};
+        let btree = BTree::new(pc.clone(), &schema)?;
+
+        // The first keys are inserted up front, so readers always have something to find
+        let keep = (0..KEYS * WRITERS).step_by(37).collect::<Vec<_>>();
+        for k in &keep {
+            btree.insert(&(*k).into(), k)?;
+        }
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|s| -> crate::Result<()> {
+            let readers = (0..READERS)
+                .map(|r| {
+                    let (btree, keep, done) = (&btree, &keep, &done);
+                    s.spawn(move || -> crate::Result<()> {
+                        while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                            for k in keep {
+                                assert_eq!(Some(*k), btree.get(&(*k).into())?.map(|s| value(&s.1)));
+                            }
+                            check_scan(btree, keep, r % 2 == 1)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let btree = &btree;
+                    s.spawn(move || -> crate::Result<()> {
+                        let mut keys = (0..KEYS).map(|i| i * WRITERS + w).collect::<Vec<_>>();
+                        keys.shuffle(&mut thread_rng());
+                        for k in keys {
+                            btree.insert(&k.into(), &k)?;
+                            assert_eq!(Some(k), btree.get(&k.into())?.map(|s| value(&s.1)));
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+            for reader in readers {
+                reader.join().unwrap()?;
+            }
+
+            Ok(())
+        })?;
+
+        btree.check()?;
+        let want = (0..KEYS * WRITERS).collect::<Vec<_>>();
+        let have = btree.scan()?.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        assert_eq!(want, have);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_btree_concurrent_deletes() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 1024;
+        const K: usize = 2;
+        const WRITERS: i32 = 8;
+        const READERS: usize = 4;
+        const KEYS: i32 = 1000;
+        let disk = Memory::new::<MEMORY>();
+        let pc = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+
+        let schema = schema! {c1 Int};
+        let btree = BTree::new(pc.clone(), &schema)?;
+
+        let mut keys = (0..KEYS * WRITERS).collect::<Vec<_>>();
+        keys.shuffle(&mut thread_rng());
+        for k in &keys {
+            btree.insert(&(*k).into(), k)?;
+        }
+
+        // Every writer deletes its own keys and inserts some of them back, whilst the readers
+        // look for the keys that are never touched
+        let keep = (0..KEYS * WRITERS).step_by(37).collect::<Vec<_>>();
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|s| -> crate::Result<()> {
+            let readers = (0..READERS)
+                .map(|r| {
+                    let (btree, keep, done) = (&btree, &keep, &done);
+                    s.spawn(move || -> crate::Result<()> {
+                        while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                            for k in keep {
+                                assert_eq!(Some(*k), btree.get(&(*k).into())?.map(|s| value(&s.1)));
+                            }
+                            check_scan(btree, keep, r % 2 == 1)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let (btree, keep) = (&btree, &keep);
+                    s.spawn(move || -> crate::Result<()> {
+                        let mut keys = (0..KEYS)
+                            .map(|i| i * WRITERS + w)
+                            .filter(|k| keep.binary_search(k).is_err())
+                            .collect::<Vec<_>>();
+                        keys.shuffle(&mut thread_rng());
+                        for k in &keys {
+                            assert!(btree.delete(&(*k).into())?);
+                            assert_eq!(None, btree.get(&(*k).into())?);
+                        }
+                        for k in keys.iter().filter(|k| *k % 3 == 0) {
+                            btree.insert(&(*k).into(), k)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+            for reader in readers {
+                reader.join().unwrap()?;
+            }
+
+            Ok(())
+        })?;
+
+        btree.check()?;
+        let want = (0..KEYS * WRITERS)
+            .filter(|k| k % 3 == 0 || keep.binary_search(k).is_ok())
+            .collect::<Vec<_>>();
+        let have = btree.scan()?.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        assert_eq!(want, have);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_btree_concurrent_rollback() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 1024;
+        const K: usize = 2;
+        const WRITERS: i32 = 4;
+        const KEYS: i32 = 1000;
+        let disk = Memory::new::<MEMORY>();
+        let pc = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+
+        let schema = schema! {c1 Int};
+        let btree = BTree::new(pc.clone(), &schema)?;
+
+        // Every key past the writers' is left to the transactions that roll back, so they share
+        // pages with the keys that are committed
+        let stride = WRITERS + 1;
+        let base = (0..KEYS).map(|i| -(i + 1)).collect::<Vec<_>>();
+        for k in &base {
+            btree.insert(&(*k).into(), k)?;
+        }
+
+        std::thread::scope(|s| -> crate::Result<()> {
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let btree = &btree;
+                    s.spawn(move || -> crate::Result<()> {
+                        let mut keys = (0..KEYS).map(|i| i * stride + w).collect::<Vec<_>>();
+                        keys.shuffle(&mut thread_rng());
+                        for k in keys {
+                            btree.insert(&k.into(), &k)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let (pc, btree, base) = (&pc, &btree, &base);
+            let aborter = s.spawn(move || -> crate::Result<()> {
+                for chunk in (0..KEYS).collect::<Vec<_>>().chunks(50) {
+                    let _txn = pc.begin();
+                    for i in chunk {
+                        let k = i * stride + WRITERS;
+                        btree.insert(&k.into(), &k)?;
+                        assert!(btree.delete(&base[*i as usize].into())?);
+                        btree.insert(&(-k - KEYS * stride).into(), &k)?;
+                    }
+                }
+
+                Ok(())
+            });
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+            aborter.join().unwrap()?;
+
+            Ok(())
+        })?;
+
+        btree.check()?;
+        let mut want = (0..KEYS * stride).filter(|k| k % stride != WRITERS).collect::<Vec<_>>();
+        want.extend(&base);
+        want.sort();
+        let have = btree.scan()?.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        assert_eq!(want, have);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_btree_recover_rollback() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 256;
+        const K: usize = 2;
+        const KEYS: i32 = 1000;
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+        let schema = schema! {c1 Int};
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let btree = BTree::new(pc.clone(), &schema)?;
+            id = btree.id();
+            for k in (0..KEYS).step_by(3) {
+                btree.insert(&k.into(), &k)?;
+            }
+
+            // The transaction never finishes, and the keys committed after it land on the pages
+            // it changed
+            let txn = pc.begin();
+            for k in (1..KEYS).step_by(3) {
+                btree.insert(&k.into(), &k)?;
+            }
+            for k in (0..KEYS).step_by(6) {
+                assert!(btree.delete(&k.into())?);
+            }
+
+            std::thread::scope(|s| {
+                s.spawn(|| -> crate::Result<()> {
+                    for k in (2..KEYS).step_by(3) {
+                        btree.insert(&k.into(), &k)?;
+                    }
+
+                    Ok(())
+                })
+                .join()
+                .unwrap()
+            })?;
+
+            pc.flush_all_pages()?;
+            std::mem::forget(txn);
+        }
+
+        let pc = PageCache::open(disk, log, LRU::new(K))?;
+        let btree = BTree::<i32>::open(pc.clone(), id, &schema);
+        btree.check()?;
+        let want = (0..KEYS).filter(|k| k % 3 != 1).collect::<Vec<_>>();
+        let have = btree.scan()?.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        assert_eq!(want, have);
+
+        Ok(())
+    }
+
+    fn value(either: &Either<i32>) -> i32 {
+        match either {
+            Either::Value(v) => *v,
+            Either::Pointer(_) => unreachable!(),
+        }
+    }
 }
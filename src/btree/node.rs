@@ -1,7 +1,7 @@
 use crate::btree::slot::Either;
 use crate::catalog::schema::Schema;
 use crate::get_ptr;
-use crate::page::{DiskObject, PageBuf, PageID, PAGE_SIZE};
+use crate::page::{DiskObject, PageBuf, PageID, PAGE_LSN, PAGE_SIZE};
 use crate::storable::Storable;
 use crate::table::tuple::{bytes_to_tuple, Comparand, Data as TupleData};
 
@@ -49,15 +49,27 @@ const NODE_IS_ROOT: usize = 1;
 const NODE_LEN: Range<usize> = 2..6;
 const NODE_NEXT: Range<usize> = 6..10;
 const NODE_ID: Range<usize> = 10..14;
-const NODE_VALUES_START: usize = 14;
+const NODE_PREV: Range<usize> = 14..18;
+const NODE_VALUES_START: usize = 18;
 
-// | NodeType (1) | Root (1) | Len (4) | Max (4) | Next (4) | PageID (4) | Values
+/// The size of the slots at which a node is split
+const NODE_CAPACITY: usize = (PAGE_LSN.start - NODE_VALUES_START) / 4;
+
+/// Whether the page holds the leaf with the given ID, without reading the rest of the node. A leaf
+/// that's been merged away is freed, and its page can be reused by anything.
+pub fn is_leaf(buf: &PageBuf, id: PageID) -> bool {
+    buf[NODE_TYPE] == u8::from(NodeType::Leaf) && buf[NODE_ID] == id.to_be_bytes()
+}
+
+// | NodeType (1) | Root (1) | Len (4) | Next (4) | PageID (4) | Prev (4) | Values
 #[derive(Clone, Debug, PartialEq)]
 pub struct Node<V> {
     pub t: NodeType,
     pub is_root: bool,
     pub next: PageID,
     pub id: PageID,
+    /// The leaf before this one, which is only set for leaves
+    pub prev: PageID,
     values: Vec<Slot<V>>,
 }
 
@@ -73,6 +85,7 @@ where
         ret[NODE_LEN].copy_from_slice(&(self.values.len() as u32).to_be_bytes());
         ret[NODE_NEXT].copy_from_slice(&self.next.to_be_bytes());
         ret[NODE_ID].copy_from_slice(&self.id.to_be_bytes());
+        ret[NODE_PREV].copy_from_slice(&self.prev.to_be_bytes());
 
         let mut from = NODE_VALUES_START;
         for value in &self.values {
@@ -82,7 +95,7 @@ where
             from += size;
         }
 
-        if ret == [0; 4096] {
+        if ret == [0; PAGE_SIZE] {
             panic!("PageBuf::from(Node) produced an empty buffer");
         }
 
@@ -95,6 +108,7 @@ where
         let len = u32::from_be_bytes(buf[NODE_LEN].try_into().unwrap());
         let next = PageID::from_be_bytes(buf[NODE_NEXT].try_into().unwrap());
         let id = PageID::from_be_bytes(buf[NODE_ID].try_into().unwrap());
+        let prev = PageID::from_be_bytes(buf[NODE_PREV].try_into().unwrap());
 
         let mut values = Vec::new();
         let mut left = &buf[NODE_VALUES_START..];
@@ -106,7 +120,7 @@ where
             left = &left[slot_size..];
         }
 
-        Self { t, is_root, next, id, values }
+        Self { t, is_root, next, id, prev, values }
     }
 }
 
@@ -125,19 +139,21 @@ where
     V: Storable,
 {
     pub fn new(id: PageID, t: NodeType, is_root: bool) -> Self {
-        Self { t, is_root, next: -1, id, values: Vec::new() }
+        Self { t, is_root, next: -1, id, prev: -1, values: Vec::new() }
     }
 
-    /// Split out half of self's values into a new node.
+    /// Split out half of self's values into a new node. For leaves, the caller has to point the
+    /// `prev` of the leaf that followed self at the new node.
     pub fn split(&mut self, id: PageID) -> Node<V> {
         // All values in the greater half end up in `rest`
         let rest = self.values.split_off(self.values.len() / 2);
         self.is_root = false;
 
-        let mut new = Node { t: self.t, is_root: false, next: -1, id, values: rest };
+        let mut new = Node { t: self.t, is_root: false, next: -1, id, prev: -1, values: rest };
 
         if self.t == NodeType::Leaf {
             new.next = self.next;
+            new.prev = self.id;
             self.next = new.id;
         }
 
@@ -146,15 +162,15 @@ where
 
     /// Using last values for separators
     pub fn get_separators(
-        self,
-        other: Option<Node<V>>,
+        &self,
+        other: Option<&Node<V>>,
         schema: &Schema,
     ) -> Option<(Slot<V>, Slot<V>)> {
         other.map(|other| (self.get_separator(schema), other.get_separator(schema)))
     }
 
     /// Using last values for separators
-    fn get_separator(self, schema: &Schema) -> Slot<V> {
+    pub fn get_separator(&self, schema: &Schema) -> Slot<V> {
         let Slot(k, _) = self.values.last().expect("there should be a last slot");
         let k = if self.t == NodeType::Leaf { k.next(schema) } else { k.clone() };
         Slot(k, Either::Pointer(self.id))
@@ -192,10 +208,121 @@ where
     }
 
     pub fn almost_full(&self, schema: &Schema) -> bool {
+        self.size(schema) >= NODE_CAPACITY
+    }
+
+    /// Whether the node has reached `fill` of its capacity, at which point bulk loading moves on to
+    /// the next node
+    pub fn filled(&self, fill: f64, schema: &Schema) -> bool {
+        self.size(schema) as f64 >= NODE_CAPACITY as f64 * fill
+    }
+
+    /// Whether the node has fallen below a quarter of its capacity, at which point it should be
+    /// merged with or take slots from a sibling
+    pub fn underfull(&self, schema: &Schema) -> bool {
+        self.size(schema) < NODE_CAPACITY / 4
+    }
+
+    /// Whether the node would be left underfull if it lost a slot. Until a node is found that
+    /// wouldn't be, a delete has to hold on to the nodes above it, since they may be rebalanced.
+    pub fn at_minimum(&self, schema: &Schema) -> bool {
+        let slot = self.size(schema) / self.values.len().max(1);
+        self.size(schema).saturating_sub(slot) < NODE_CAPACITY / 4
+    }
+
+    /// Whether the slots of both nodes would fit in one node that's at most three quarters full,
+    /// which leaves room for inserts before it has to be split again
+    pub fn can_merge(&self, other: &Node<V>, schema: &Schema) -> bool {
+        self.size(schema) + other.size(schema) < NODE_CAPACITY * 3 / 4
+    }
+
+    fn size(&self, schema: &Schema) -> usize {
         // TODO: Needs to take into account varchar
         // schema.size() = key, either size = value size + flag
         self.values.len() * (schema.tuple_size() + Either::<V>::SIZE)
-            >= (PAGE_SIZE - NODE_VALUES_START) / 4
+    }
+
+    /// Returns the position of the child that would hold `key`, or `None` if it's past the last
+    /// separator
+    pub fn child_index(&self, key: &TupleData, schema: &Schema) -> Option<usize> {
+        self.values.iter().position(|s| Comparand(schema, key) < Comparand(schema, &s.0))
+    }
+
+    /// Returns the pointer of the child at position `i`
+    pub fn child(&self, i: usize) -> PageID {
+        let slot = &self.values[i];
+        get_ptr!(slot)
+    }
+
+    pub fn key(&self, i: usize) -> &TupleData {
+        &self.values[i].0
+    }
+
+    pub fn set_key(&mut self, i: usize, key: TupleData) {
+        self.values[i].0 = key;
+    }
+
+    /// Replaces the pointer to a child that was split with its two halves. The separator of the
+    /// child still bounds the right half, and the separator of the left half goes before it.
+    pub fn split_child(&mut self, left: Slot<V>, right: Slot<V>, schema: &Schema) {
+        let ptr = get_ptr!(left);
+        match self.values.iter_mut().find(|s| matches!(s.1, Either::Pointer(p) if p == ptr)) {
+            Some(slot) => slot.1 = right.1,
+            None => {
+                self.replace(right, schema);
+            }
+        }
+        self.replace(left, schema);
+    }
+
+    /// Removes the child at position `i` once it has been merged into its right sibling's place.
+    /// The right sibling's separator is kept, and it points to the merged child.
+    pub fn merge_children(&mut self, i: usize) {
+        let Slot(_, left) = self.values.remove(i);
+        self.values[i].1 = left;
+    }
+
+    /// Moves all of `other`'s slots to the end of this node, where `other` is the next node at the
+    /// same level and `separator` is the separator between them
+    pub fn merge(&mut self, other: Node<V>, separator: &TupleData) {
+        match self.t {
+            // The last child's separator may be below the node's separator, which would leave a
+            // gap between the children once they're merged
+            NodeType::Internal => self.set_last_key(separator),
+            NodeType::Leaf => self.next = other.next,
+        }
+
+        self.values.extend(other.values);
+    }
+
+    /// Evens out the slots of this node and `other`, where `other` is the next node at the same
+    /// level and `separator` is the separator between them. Returns the new separator.
+    pub fn redistribute(
+        &mut self,
+        other: &mut Node<V>,
+        separator: &TupleData,
+        schema: &Schema,
+    ) -> TupleData {
+        if self.t == NodeType::Internal {
+            self.set_last_key(separator);
+        }
+
+        let mut values = std::mem::take(&mut self.values);
+        values.append(&mut other.values);
+        other.values = values.split_off(values.len() / 2);
+        self.values = values;
+
+        let last = self.last_key().expect("there should be a last slot");
+        match self.t {
+            NodeType::Internal => last.clone(),
+            NodeType::Leaf => last.next(schema),
+        }
+    }
+
+    fn set_last_key(&mut self, key: &TupleData) {
+        if let Some(Slot(k, _)) = self.values.last_mut() {
+            *k = key.clone();
+        }
     }
 
     pub fn insert(&mut self, slot: Slot<V>, schema: &Schema) -> bool {
@@ -235,6 +362,11 @@ where
         None
     }
 
+    /// Appends a slot, which has to sort after all of the node's slots
+    pub fn push(&mut self, slot: Slot<V>) {
+        self.values.push(slot);
+    }
+
     pub fn pop_last(&mut self) -> Option<Slot<V>> {
         self.values.pop()
     }
@@ -291,6 +423,7 @@ mod test {
             is_root: true,
             next: -1,
             id: 0,
+            prev: -1,
             values: vec![
                 Slot(10.into(), Either::Value(20)),
                 Slot(0.into(), Either::Pointer(1)),
@@ -317,6 +450,7 @@ mod test {
             is_root: true,
             next: -1,
             id: 0,
+            prev: -1,
             values: vec![
                 Slot(10.into(), Either::Value(1)),
                 Slot(20.into(), Either::Value(2)),
@@ -339,6 +473,7 @@ mod test {
             is_root: false,
             next: 1,
             id: 0,
+            prev: -1,
             values: vec![
                 Slot(10.into(), Either::Value(1)),
                 Slot(20.into(), Either::Value(2)),
@@ -355,6 +490,7 @@ mod test {
             is_root: false,
             next: -1,
             id: 1,
+            prev: 0,
             values: vec![
                 Slot(60.into(), Either::Value(6)),
                 Slot(70.into(), Either::Value(7)),
@@ -377,6 +513,7 @@ mod test {
             is_root: false,
             next: 1,
             id: 0,
+            prev: -1,
             values: vec![
                 Slot(10.into(), Either::Value(1)),
                 Slot(20.into(), Either::Value(2)),
@@ -391,6 +528,7 @@ mod test {
             is_root: false,
             next: -1,
             id: 1,
+            prev: -1,
             values: vec![
                 Slot(60.into(), Either::Value(6)),
                 Slot(70.into(), Either::Value(7)),
@@ -401,7 +539,7 @@ mod test {
             ],
         };
 
-        let Some(slots) = node.get_separators(Some(other), &schema) else {
+        let Some(slots) = node.get_separators(Some(&other), &schema) else {
             panic!("expected separators")
         };
         let expected = (Slot(51.into(), Either::Pointer(0)), Slot(111.into(), Either::Pointer(1)));
@@ -417,6 +555,7 @@ mod test {
             is_root: false,
             next: 1,
             id: 0,
+            prev: -1,
             values: vec![
                 Slot(10.into(), Either::Pointer(1)),
                 Slot(20.into(), Either::Pointer(2)),
@@ -431,6 +570,7 @@ mod test {
             is_root: false,
             next: -1,
             id: 1,
+            prev: -1,
             values: vec![
                 Slot(60.into(), Either::Pointer(6)),
                 Slot(70.into(), Either::Pointer(7)),
@@ -441,7 +581,7 @@ mod test {
             ],
         };
 
-        let Some(slots) = node.get_separators(Some(other), &schema) else {
+        let Some(slots) = node.get_separators(Some(&other), &schema) else {
             panic!("expected separators")
         };
         let expected = (Slot(50.into(), Either::Pointer(0)), Slot(110.into(), Either::Pointer(1)));
@@ -457,6 +597,7 @@ mod test {
             is_root: false,
             next: 1,
             id: 0,
+            prev: -1,
             values: vec![
                 Slot(10.into(), Either::Pointer(1)),
                 Slot(20.into(), Either::Pointer(2)),
@@ -495,8 +636,14 @@ mod test {
     fn test_values() {
         let schema = schema! {c1 Int};
 
-        let mut node: Node<i32> =
-            Node { t: NodeType::Internal, is_root: false, next: 1, id: 0, values: vec![] };
+        let mut node: Node<i32> = Node {
+            t: NodeType::Internal,
+            is_root: false,
+            next: 1,
+            id: 0,
+            prev: -1,
+            values: vec![],
+        };
 
         // Insert
         let range = -50..50;
@@ -528,7 +675,7 @@ mod test {
         assert_eq!(node.values.len(), second_half.len());
 
         for Slot(k, _) in first_half {
-            if let Some(_) = node.get(k, &schema) {
+            if node.get(k, &schema).is_some() {
                 panic!("unexpected deleted slot: {k:?}")
             }
         }
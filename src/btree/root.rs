@@ -0,0 +1,28 @@
+use std::ops::Range;
+
+use crate::catalog::schema::Schema;
+use crate::page::{DiskObject, PageBuf, PageID, PAGE_SIZE};
+
+// RootPointer:
+// | Root (4) |
+//
+// The root node of a B-tree moves whenever it's split or collapsed, so the tree is found through a
+// page that points to it instead. The page never moves, and a root of -1 means the tree is empty.
+const ROOT: Range<usize> = 0..4;
+
+pub struct RootPointer {
+    pub root: PageID,
+}
+
+impl DiskObject for RootPointer {
+    fn serialise(&self) -> PageBuf {
+        let mut ret = [0; PAGE_SIZE];
+        ret[ROOT].copy_from_slice(&self.root.to_be_bytes());
+
+        ret
+    }
+
+    fn deserialise(buf: PageBuf, _: &Schema) -> Self {
+        Self { root: PageID::from_be_bytes(buf[ROOT].try_into().unwrap()) }
+    }
+}
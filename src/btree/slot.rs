@@ -51,7 +51,7 @@ where
                 Either::Value(value)
             }
             1 => {
-                let b: [u8; 4] = value.try_into().unwrap();
+                let b: [u8; 4] = value[..4].try_into().unwrap();
                 let ptr = i32::from_be_bytes(b);
                 Either::Pointer(ptr)
             }
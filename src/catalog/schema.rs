@@ -38,7 +38,12 @@ pub struct Column {
     pub name: String,
     pub ty: Type,
     pub offset: usize,
+    /// Index of the column's bit in the tuple's null bitmap
+    pub position: usize,
     pub table: Option<String>,
+    /// Hidden columns are left out of `*` and can only be referenced by their qualified name. The
+    /// key columns of a USING join are hidden, since they're merged into a single column.
+    pub hidden: bool,
 }
 
 impl std::fmt::Display for Column {
@@ -55,32 +60,34 @@ pub struct Schema {
 
 impl Schema {
     pub fn new(columns: Vec<Column>) -> Self {
-        Self { tuple_size: columns.iter().fold(0, |acc, column| acc + column.ty.size()), columns }
+        let tuple_size = columns
+            .iter()
+            .fold(null_bitmap_size(columns.len()), |acc, column| acc + column.ty.size());
+
+        Self { tuple_size, columns }
     }
 
-    /// Returns a new `Schema` which has been filtered to include the specified columns
+    /// Returns a new `Schema` which has been filtered to include the specified columns, in the
+    /// order they're given
     pub fn filter(&self, columns: &[&str]) -> Self {
-        let mut tuple_size = 0;
-        let columns = self
+        let columns = columns
             .iter()
-            .filter(|Column { name, .. }| columns.contains(&name.as_str()))
-            .map(|col| {
-                tuple_size += col.ty.size();
-                col.clone()
-            })
+            .filter_map(|name| self.iter().find(|column| column.name == *name))
+            .cloned()
             .collect();
 
-        Self { columns, tuple_size }
+        Self::new(columns)
     }
 
-    /// Returns a new `Schema` where the offsets have been adjusted so that all columns are packed
-    /// together
+    /// Returns a new `Schema` where the offsets and positions have been adjusted so that all
+    /// columns are packed together after the null bitmap
     pub fn compact(&self) -> Self {
         let mut schema = self.clone();
-        let mut current = 0;
+        let mut current = null_bitmap_size(schema.len());
 
-        for Column { ty, offset, .. } in &mut schema.columns {
+        for (i, Column { ty, offset, position, .. }) in schema.columns.iter_mut().enumerate() {
             *offset = current;
+            *position = i;
             current += ty.size();
         }
 
@@ -89,19 +96,19 @@ impl Schema {
 
     /// Returns a new `Schema` where `other` is appended
     pub fn join(&self, other: &Schema) -> Self {
-        let mut schema = self.clone();
-        schema.columns.extend(other.columns.iter().cloned());
-        schema.tuple_size += other.tuple_size;
+        let mut columns = self.columns.clone();
+        columns.extend(other.columns.iter().cloned());
 
-        schema.compact()
+        Schema::new(columns).compact()
     }
 
     pub fn qualify(&mut self, table: &str) {
         self.columns.iter_mut().for_each(|column| column.table = Some(table.to_string()));
     }
 
+    /// Finds the first column with the unqualified name, skipping hidden columns
     pub fn find_column_by_name(&self, column_name: &str) -> Option<&Column> {
-        self.columns.iter().find(|Column { name, .. }| name == column_name)
+        self.columns.iter().find(|Column { name, hidden, .. }| !hidden && name == column_name)
     }
 
     pub fn find_column_by_name_and_table(
@@ -122,6 +129,10 @@ impl Schema {
         self.columns.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
     pub fn iter(&self) -> std::slice::Iter<'_, Column> {
         self.columns.iter()
     }
@@ -137,6 +148,11 @@ impl Default for SchemaBuilder {
     }
 }
 
+/// Tuples start with a bitmap with one bit for each column, set if the column is null
+pub fn null_bitmap_size(columns: usize) -> usize {
+    columns.div_ceil(8)
+}
+
 impl SchemaBuilder {
     pub fn new() -> Self {
         Self { columns: vec![] }
@@ -164,7 +180,9 @@ macro_rules! column {
             name: $name.into(),
             ty: $crate::catalog::schema::Type::$ty,
             offset: 0,
+            position: 0,
             table: Some($table.into()),
+            hidden: false,
         }
     };
     ($name:expr, $ty:tt) => {
@@ -172,11 +190,20 @@ macro_rules! column {
             name: $name.into(),
             ty: $crate::catalog::schema::Type::$ty,
             offset: 0,
+            position: 0,
             table: None,
+            hidden: false,
         }
     };
     ($name:expr => $ty:expr) => {
-        $crate::catalog::schema::Column { name: $name.into(), ty: $ty, offset: 0, table: None }
+        $crate::catalog::schema::Column {
+            name: $name.into(),
+            ty: $ty,
+            offset: 0,
+            position: 0,
+            table: None,
+            hidden: false,
+        }
     };
 }
 
@@ -194,7 +221,9 @@ macro_rules! schema {
                 name: stringify!($name).into(),
                 ty: $crate::catalog::schema::Type::$ty,
                 offset: 0,
+                position: 0,
                 table: None,
+                hidden: false,
             },)*];
             $crate::catalog::schema::Schema::new(columns).compact()
         }
@@ -0,0 +1,206 @@
+use crate::catalog::schema::{Column, Schema, Type};
+use crate::catalog::{IndexType, OID};
+use crate::page::PageID;
+use crate::schema;
+use crate::table::tuple::{Builder as TupleBuilder, Data as TupleData, Value};
+
+// The catalog is stored in three system tables which start on consecutive pages. The first of
+// these is the catalog root, which is recorded in the database header:
+//
+// tables:  | OID | Name | FirstPageID | LastPageID |
+// columns: | TableOID | Position | Name | Type | Offset |
+// indexes: | OID | Name | TableOID | Type | Unique | RootPageID | Key |
+//
+// The last page ID of each table is the last page when it was created. Tables are walked forwards
+// from there to find the actual last page when the catalog is opened.
+pub const TABLES_OFFSET: PageID = 0;
+pub const COLUMNS_OFFSET: PageID = 1;
+pub const INDEXES_OFFSET: PageID = 2;
+
+/// Key column names are joined with this to fit into a single varchar
+const KEY_SEPARATOR: char = ',';
+
+pub fn tables_schema() -> Schema {
+    schema! { oid Int, name Varchar, first_page_id Int, last_page_id Int }
+}
+
+pub fn columns_schema() -> Schema {
+    schema! { table_oid Int, position Int, name Varchar, ty TinyInt, offset Int }
+}
+
+pub fn indexes_schema() -> Schema {
+    schema! {
+        oid Int, name Varchar, table_oid Int, ty TinyInt, unique Bool, root_page_id Int, key Varchar
+    }
+}
+
+pub struct TableRow {
+    pub oid: OID,
+    pub name: String,
+    pub first_page_id: PageID,
+    pub last_page_id: PageID,
+}
+
+pub struct ColumnRow {
+    pub table_oid: OID,
+    pub position: u32,
+    pub column: Column,
+}
+
+pub struct IndexRow {
+    pub oid: OID,
+    pub name: String,
+    pub table_oid: OID,
+    pub index_ty: IndexType,
+    pub unique: bool,
+    pub root_page_id: PageID,
+    pub key: Vec<String>,
+}
+
+fn get_values(tuple: &TupleData, schema: &Schema) -> Vec<Value> {
+    schema.iter().map(|column| tuple.get_value(column)).collect()
+}
+
+fn tiny_int(value: &Value) -> i8 {
+    match value {
+        Value::TinyInt(v) => *v,
+        _ => unreachable!(),
+    }
+}
+
+fn bool(value: &Value) -> bool {
+    match value {
+        Value::Bool(v) => *v,
+        _ => unreachable!(),
+    }
+}
+
+fn int(value: &Value) -> i32 {
+    match value {
+        Value::Int(v) => *v,
+        _ => unreachable!(),
+    }
+}
+
+fn varchar(value: &Value) -> String {
+    match value {
+        Value::Varchar(v) => v.clone(),
+        _ => unreachable!(),
+    }
+}
+
+pub(crate) fn type_to_i8(ty: Type) -> i8 {
+    match ty {
+        Type::TinyInt => 0,
+        Type::Bool => 1,
+        Type::Int => 2,
+        Type::BigInt => 3,
+        Type::Varchar => 4,
+    }
+}
+
+pub(crate) fn type_from_i8(ty: i8) -> Type {
+    match ty {
+        0 => Type::TinyInt,
+        1 => Type::Bool,
+        2 => Type::Int,
+        3 => Type::BigInt,
+        4 => Type::Varchar,
+        _ => unreachable!("invalid column type {ty} in catalog"),
+    }
+}
+
+fn index_type_to_i8(ty: IndexType) -> i8 {
+    match ty {
+        IndexType::HashTable => 0,
+        IndexType::BTree => 1,
+    }
+}
+
+fn index_type_from_i8(ty: i8) -> IndexType {
+    match ty {
+        0 => IndexType::HashTable,
+        1 => IndexType::BTree,
+        _ => unreachable!("invalid index type {ty} in catalog"),
+    }
+}
+
+impl TableRow {
+    pub fn to_tuple(&self) -> TupleData {
+        TupleBuilder::new()
+            .int(self.oid as i32)
+            .varchar(&self.name)
+            .int(self.first_page_id)
+            .int(self.last_page_id)
+            .build()
+    }
+
+    pub fn from_tuple(tuple: &TupleData) -> Self {
+        let values = get_values(tuple, &tables_schema());
+
+        Self {
+            oid: int(&values[0]) as OID,
+            name: varchar(&values[1]),
+            first_page_id: int(&values[2]),
+            last_page_id: int(&values[3]),
+        }
+    }
+}
+
+impl ColumnRow {
+    pub fn to_tuple(&self) -> TupleData {
+        TupleBuilder::new()
+            .int(self.table_oid as i32)
+            .int(self.position as i32)
+            .varchar(&self.column.name)
+            .tiny_int(type_to_i8(self.column.ty))
+            .int(self.column.offset as i32)
+            .build()
+    }
+
+    pub fn from_tuple(tuple: &TupleData) -> Self {
+        let values = get_values(tuple, &columns_schema());
+        let position = int(&values[1]) as u32;
+
+        Self {
+            table_oid: int(&values[0]) as OID,
+            position,
+            column: Column {
+                name: varchar(&values[2]),
+                ty: type_from_i8(tiny_int(&values[3])),
+                offset: int(&values[4]) as usize,
+                position: position as usize,
+                table: None,
+                hidden: false,
+            },
+        }
+    }
+}
+
+impl IndexRow {
+    pub fn to_tuple(&self) -> TupleData {
+        TupleBuilder::new()
+            .int(self.oid as i32)
+            .varchar(&self.name)
+            .int(self.table_oid as i32)
+            .tiny_int(index_type_to_i8(self.index_ty))
+            .bool(self.unique)
+            .int(self.root_page_id)
+            .varchar(&self.key.join(&KEY_SEPARATOR.to_string()))
+            .build()
+    }
+
+    pub fn from_tuple(tuple: &TupleData) -> Self {
+        let values = get_values(tuple, &indexes_schema());
+
+        Self {
+            oid: int(&values[0]) as OID,
+            name: varchar(&values[1]),
+            table_oid: int(&values[2]) as OID,
+            index_ty: index_type_from_i8(tiny_int(&values[3])),
+            unique: bool(&values[4]),
+            root_page_id: int(&values[5]),
+            key: varchar(&values[6]).split(KEY_SEPARATOR).map(String::from).collect(),
+        }
+    }
+}
@@ -1,13 +1,32 @@
-use std::{cell::UnsafeCell, io, os::fd::AsRawFd, path::Path};
+use std::{cell::UnsafeCell, io, os::fd::AsRawFd, path::Path, sync::Arc};
 
 use nix::sys::uio;
 use std::fs::{File, OpenOptions};
 
 use crate::page::{PageBuf, PageID, PAGE_SIZE};
 
-pub trait Disk {
+pub trait Disk: Send + Sync {
     fn read_page(&self, page_id: PageID) -> io::Result<PageBuf>;
     fn write_page(&self, page_id: PageID, data: &PageBuf) -> io::Result<()>;
+
+    /// Makes sure previous writes have reached stable storage
+    fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<D: Disk + ?Sized> Disk for Arc<D> {
+    fn read_page(&self, page_id: PageID) -> io::Result<PageBuf> {
+        (**self).read_page(page_id)
+    }
+
+    fn write_page(&self, page_id: PageID, data: &PageBuf) -> io::Result<()> {
+        (**self).write_page(page_id, data)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        (**self).sync()
+    }
 }
 
 pub struct FileSystem {
@@ -32,6 +51,10 @@ impl Disk for FileSystem {
 
         Ok(())
     }
+
+    fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
 }
 
 impl FileSystem {
@@ -78,6 +101,6 @@ impl Memory {
     pub fn new<const SIZE: usize>() -> Self {
         assert!(SIZE.is_multiple_of(PAGE_SIZE));
 
-        Self { buf: UnsafeCell::new(Box::new([0; SIZE])), size: SIZE }
+        Self { buf: UnsafeCell::new(vec![0; SIZE].into_boxed_slice()), size: SIZE }
     }
 }
@@ -1,6 +1,5 @@
 use crate::{
     catalog::schema::{Column, Schema, Type},
-    get_value,
     sql::{Expr, Function, FunctionName, Ident, Literal, Op},
     table::tuple::{Data as TupleData, Value},
 };
@@ -59,16 +58,20 @@ pub fn eval(expr: &Expr, schema: &Schema, tuple: &TupleData) -> Result<Value, Ev
     }
 }
 
+/// Finds the column an identifier refers to. Compound identifiers are qualified by table.
+pub fn find_column<'a>(ident: &Ident, schema: &'a Schema) -> Option<&'a Column> {
+    match ident {
+        Ident::Single(name) => schema.find_column_by_name(name),
+        Ident::Compound(idents) => schema.find_column_by_name_and_table(&idents[0], &idents[1]),
+    }
+}
+
 fn eval_ident(ident: &Ident, schema: &Schema, tuple: &TupleData) -> Result<Value, EvalError> {
-    let Some(column) = schema
-        .columns
-        .iter()
-        .find(|Column { name, .. }| name.as_str() == ident.to_string().as_str())
-    else {
+    let Some(column) = find_column(ident, schema) else {
         Err(format!("unknown identifier: {}", ident))?
     };
 
-    Ok(tuple.get_value(column.offset, column.ty))
+    Ok(tuple.get_value(column))
 }
 
 fn eval_literal(literal: &Literal) -> Result<Value, EvalError> {
@@ -82,7 +85,7 @@ fn eval_literal(literal: &Literal) -> Result<Value, EvalError> {
         }
         Literal::String(string) => Value::Varchar(string.to_owned()),
         Literal::Bool(bool) => Value::Bool(*bool),
-        Literal::Null => todo!(),
+        Literal::Null => Value::Null,
     };
 
     Ok(value)
@@ -90,12 +93,13 @@ fn eval_literal(literal: &Literal) -> Result<Value, EvalError> {
 
 fn eval_is_null(
     expr: &Expr,
-    _negated: bool,
+    negated: bool,
     schema: &Schema,
     tuple: &TupleData,
 ) -> Result<Value, EvalError> {
-    let _value = eval(expr, schema, tuple)?;
-    todo!("value == null?")
+    let value = eval(expr, schema, tuple)?;
+
+    Ok(Value::Bool((value == Value::Null) != negated))
 }
 
 fn eval_in_list(
@@ -106,16 +110,27 @@ fn eval_in_list(
     tuple: &TupleData,
 ) -> Result<Value, EvalError> {
     let search = eval(expr, schema, tuple)?;
-    let mut found = false;
+    if search == Value::Null {
+        return Ok(Value::Null);
+    }
+
+    // If the value isn't found but the list contains a null then the result is unknown
+    let mut result = Value::Bool(false);
     for expr in list {
         let value = eval(expr, schema, tuple)?;
-        if value == search {
-            found = true;
+        if value == Value::Null {
+            result = Value::Null;
+        } else if value == search {
+            result = Value::Bool(true);
             break;
         }
     }
 
-    Ok(Value::Bool(found && !negated))
+    if negated {
+        return Ok(not(result));
+    }
+
+    Ok(result)
 }
 
 fn eval_between(
@@ -130,13 +145,14 @@ fn eval_between(
     let low = eval(low, schema, tuple)?;
     let high = eval(high, schema, tuple)?;
 
-    if Value::Bool(true) != value_op(&value, Op::Ge, &low)?
-        && Value::Bool(true) != value_op(&value, Op::Le, &high)?
-    {
-        return Ok(Value::Bool(false));
+    let result =
+        value_op(&value_op(&value, Op::Ge, &low)?, Op::And, &value_op(&value, Op::Le, &high)?)?;
+
+    if negated {
+        return Ok(not(result));
     }
 
-    Ok(Value::Bool(!negated))
+    Ok(result)
 }
 
 fn eval_binary_op(
@@ -173,6 +189,8 @@ fn eval_concat(args: &[Expr], schema: &Schema, tuple: &TupleData) -> Result<Valu
     for arg in args {
         let value = eval(arg, schema, tuple)?;
         match value {
+            // Nulls are ignored, like in Postgres
+            Value::Null => {}
             Value::TinyInt(v) => result.push_str(&v.to_string()),
             Value::Bool(v) => result.push_str(&v.to_string()),
             Value::Int(v) => result.push_str(&v.to_string()),
@@ -192,6 +210,10 @@ fn eval_contains(args: &[Expr], schema: &Schema, tuple: &TupleData) -> Result<Va
     let arg0 = eval(&args[0], schema, tuple)?;
     let arg1 = eval(&args[1], schema, tuple)?;
 
+    if arg0 == Value::Null || arg1 == Value::Null {
+        return Ok(Value::Null);
+    }
+
     if arg0.ty() != Type::Varchar && arg1.ty() != Type::Varchar {
         return Ok(Value::Bool(false));
     }
@@ -199,12 +221,34 @@ fn eval_contains(args: &[Expr], schema: &Schema, tuple: &TupleData) -> Result<Va
     Ok(Value::Bool(get_value!(arg0, Varchar).contains(get_value!(arg1, Varchar).as_str())))
 }
 
+/// Comparisons with null are unknown, so also null. AND and OR follow three-valued logic: the
+/// result is only null if it would depend on the unknown value.
 fn value_op(lhs: &Value, op: Op, rhs: &Value) -> Result<Value, EvalError> {
+    match (lhs, rhs) {
+        (Value::Null, Value::Null) => return Ok(Value::Null),
+        (Value::Null, value) | (value, Value::Null) => {
+            let result = match (op, value) {
+                (Op::And, Value::Bool(false)) => Value::Bool(false),
+                (Op::Or, Value::Bool(true)) => Value::Bool(true),
+                _ => Value::Null,
+            };
+
+            return Ok(result);
+        }
+        _ => {}
+    }
+
+    // Integers of different widths are compared as BIGINT, e.g. SUM(c1) > 5
+    if let (Some(lhs), Some(rhs)) = (widen(lhs), widen(rhs)) {
+        return Ok(Value::Bool(numeric_op(lhs, op, rhs)?));
+    }
+
     if lhs.ty() != rhs.ty() {
         Err(format!("cannot perform {} {op} {}", lhs.ty(), rhs.ty()))?
     }
 
     let result = match lhs {
+        Value::Null => unreachable!(),
         Value::TinyInt(lhs) => numeric_op(*lhs, op, *get_value!(rhs, TinyInt)),
         Value::Bool(lhs) => bool_op(*lhs, op, *get_value!(rhs, Bool)),
         Value::Int(lhs) => numeric_op(*lhs, op, *get_value!(rhs, Int)),
@@ -215,6 +259,22 @@ fn value_op(lhs: &Value, op: Op, rhs: &Value) -> Result<Value, EvalError> {
     Ok(Value::Bool(result))
 }
 
+fn widen(value: &Value) -> Option<i64> {
+    match value {
+        Value::TinyInt(value) => Some(*value as i64),
+        Value::Int(value) => Some(*value as i64),
+        Value::BigInt(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn not(value: Value) -> Value {
+    match value {
+        Value::Bool(value) => Value::Bool(!value),
+        value => value,
+    }
+}
+
 fn numeric_op<T: PartialEq + PartialOrd>(lhs: T, op: Op, rhs: T) -> Result<bool, EvalError> {
     let result = match op {
         Op::Eq => lhs == rhs,
@@ -261,8 +321,9 @@ fn varchar_op(lhs: &str, op: Op, rhs: &str) -> Result<bool, EvalError> {
 #[cfg(test)]
 mod test {
     use super::eval;
+    use crate::catalog::schema::Type;
     use crate::schema;
-    use crate::sql::expr_builder::{concat, contains, ident, lit};
+    use crate::sql::expr_builder::{concat, contains, ident, lit, null};
     use crate::table::tuple::{Builder as TupleBuilder, Value};
 
     macro_rules! test_eval {
@@ -343,4 +404,35 @@ mod test {
         TupleBuilder::new().varchar("a").varchar("b").build(),
         Ok(Value::Varchar("abc9".to_string()))
     );
+
+    test_eval!(t13, null().is_null(), Ok(Value::Bool(true)));
+    test_eval!(t14, lit(1).is_not_null(), Ok(Value::Bool(true)));
+    test_eval!(t15, null().eq(null()), Ok(Value::Null));
+    test_eval!(t16, lit(1).lt(null()), Ok(Value::Null));
+    test_eval!(t17, null().and(lit(false)), Ok(Value::Bool(false)));
+    test_eval!(t18, null().and(lit(true)), Ok(Value::Null));
+    test_eval!(t19, lit(true).or(null()), Ok(Value::Bool(true)));
+    test_eval!(t20, lit(false).or(null()), Ok(Value::Null));
+    test_eval!(t21, lit(1).in_list(vec![null(), lit(1)]), Ok(Value::Bool(true)));
+    test_eval!(t22, lit(1).in_list(vec![null(), lit(2)]), Ok(Value::Null));
+    test_eval!(t23, lit(1).not_in_list(vec![lit(2)]), Ok(Value::Bool(true)));
+    test_eval!(t24, lit(1).not_between(lit(2), lit(3)), Ok(Value::Bool(true)));
+    test_eval!(t25, lit(1).between(null(), lit(0)), Ok(Value::Bool(false)));
+    test_eval!(t26, lit(1).between(null(), lit(3)), Ok(Value::Null));
+
+    test_eval!(
+        t27,
+        ident("c1").is_null().and(ident("c2").eq(lit(2))),
+        schema! {c1 Varchar, c2 Int},
+        TupleBuilder::new().null(Type::Varchar).int(2).build(),
+        Ok(Value::Bool(true))
+    );
+
+    test_eval!(
+        t28,
+        concat(vec![ident("c1"), lit("a"), null()]),
+        schema! {c1 Varchar},
+        TupleBuilder::new().null(Type::Varchar).build(),
+        Ok(Value::Varchar("a".to_string()))
+    );
 }
@@ -10,3 +10,992 @@ pub fn execute(plan: &mut dyn PhysicalOperator) -> Result<Vec<TupleData>> {
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicI32, Ordering::Relaxed},
+        Arc, Mutex,
+    };
+
+    use crate::{
+        btree::BTree,
+        catalog::{schema::Type, Catalog, IndexType},
+        disk::{Disk, Memory},
+        execution::execute,
+        hash_table::extendible::ExtendibleHashTable,
+        optimiser::Optimiser,
+        page::{PageBuf, PageID, PAGE_SIZE},
+        page_cache::{PageCache, CACHE_SIZE},
+        planner::Planner,
+        replacer::LRU,
+        schema,
+        sql::Parser,
+        table::{
+            node::RID,
+            tuple::{Builder as TupleBuilder, Data as TupleData, Value},
+        },
+        wal::MemoryLog,
+    };
+
+    fn run(planner: &Planner, optimiser: &Optimiser, statement: &str) -> Vec<TupleData> {
+        let mut parser = Parser::new(statement).unwrap();
+        let statement = parser.parse_statements().unwrap().pop().unwrap();
+        let plan = optimiser.transform(planner.plan(statement).unwrap());
+
+        execute(optimiser.implement(plan).as_mut()).unwrap()
+    }
+
+    #[test]
+    fn test_update_delete() {
+        const MEMORY: usize = PAGE_SIZE * 8;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let row = |c1: i32, c2: &str| TupleBuilder::new().int(c1).varchar(c2).build();
+        let count = |n: i32| vec![TupleBuilder::new().int(n).build()];
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(&planner, &optimiser, "INSERT INTO t1 VALUES (1, 'a'), (2, 'b'), (3, 'c')");
+
+        // 'b' grows and is relocated, so shouldn't be visited twice
+        let have = run(&planner, &optimiser, "UPDATE t1 SET c2 = 'bbbbbbbb' WHERE c1 = 2");
+        assert_eq!(count(1), have);
+        let have = run(&planner, &optimiser, "UPDATE t1 SET c2 = 'z'");
+        assert_eq!(count(3), have);
+
+        let have = run(&planner, &optimiser, "DELETE FROM t1 WHERE c1 = 1");
+        assert_eq!(count(1), have);
+
+        let have = run(&planner, &optimiser, "SELECT * FROM t1");
+        assert_eq!(vec![row(3, "z"), row(2, "z")], have);
+
+        let have = run(&planner, &optimiser, "DELETE FROM t1");
+        assert_eq!(count(2), have);
+        assert!(run(&planner, &optimiser, "SELECT * FROM t1").is_empty());
+    }
+
+    #[test]
+    fn test_nulls() {
+        const MEMORY: usize = PAGE_SIZE * 8;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(&planner, &optimiser, "INSERT INTO t1 VALUES (NULL, 'a'), (2, NULL), (NULL, NULL)");
+        run(&planner, &optimiser, "UPDATE t1 SET c2 = NULL WHERE c2 = 'a'");
+
+        let have =
+            run(&planner, &optimiser, "SELECT c1 FROM t1 WHERE c2 IS NULL AND c1 IS NOT NULL");
+        assert_eq!(vec![TupleBuilder::new().int(2).build()], have);
+
+        // Comparisons with null are never true
+        let have = run(&planner, &optimiser, "SELECT * FROM t1 WHERE c1 = NULL OR c1 != 2");
+        assert!(have.is_empty());
+
+        let have = run(&planner, &optimiser, "SELECT * FROM t1 WHERE c1 IS NULL");
+        let want = TupleBuilder::new().null(Type::Int).null(Type::Varchar).build();
+        assert_eq!(vec![want.clone(), want], have);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        const MEMORY: usize = PAGE_SIZE * 8;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(
+            &planner,
+            &optimiser,
+            "INSERT INTO t1 VALUES (1, 'a'), (2, 'b'), (3, 'a'), (NULL, 'b'), (3, 'a'), (4, NULL)",
+        );
+
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT c2, COUNT(*), COUNT(c1), COUNT(DISTINCT c1), SUM(c1), MIN(c1), MAX(c1), AVG(c1)
+            FROM t1 GROUP BY c2",
+        );
+        let row = |c2: Option<&str>, count: (i32, i32, i32), sum: i64, min, max, avg| {
+            let builder = match c2 {
+                Some(c2) => TupleBuilder::new().varchar(c2),
+                None => TupleBuilder::new().null(Type::Varchar),
+            };
+            builder.int(count.0).int(count.1).int(count.2).big_int(sum).int(min).int(max).int(avg)
+        };
+        let want = vec![
+            row(Some("a"), (3, 3, 2), 7, 1, 3, 2).build(),
+            row(Some("b"), (2, 1, 1), 2, 2, 2, 2).build(),
+            row(None, (1, 1, 1), 4, 4, 4, 4).build(),
+        ];
+        assert_eq!(want, have);
+
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT c2 FROM t1 WHERE c2 IS NOT NULL GROUP BY c2 HAVING SUM(c1) > 5",
+        );
+        assert_eq!(vec![TupleBuilder::new().varchar("a").build()], have);
+
+        // Aggregates without a GROUP BY always return a row
+        let have = run(&planner, &optimiser, "SELECT COUNT(*), SUM(c1) FROM t1 WHERE c1 > 10");
+        let want = TupleBuilder::new().int(0).null(Type::BigInt).build();
+        assert_eq!(vec![want], have);
+
+        // COUNT(*) counts rows whose only column is null
+        run(&planner, &optimiser, "CREATE TABLE t2 (c1 INT)");
+        run(&planner, &optimiser, "INSERT INTO t2 VALUES (NULL), (1), (NULL)");
+        let have = run(&planner, &optimiser, "SELECT COUNT(*), COUNT(c1) FROM t2");
+        assert_eq!(vec![TupleBuilder::new().int(3).int(1).build()], have);
+
+        // AVG is an integer, the fractional part is dropped
+        run(&planner, &optimiser, "CREATE TABLE t3 (c1 INT, c2 INT)");
+        run(&planner, &optimiser, "INSERT INTO t3 VALUES (1, 1), (2, 1), (5, 2), (6, 2), (6, 2)");
+        let have = run(&planner, &optimiser, "SELECT AVG(c1) FROM t3 GROUP BY c2");
+        let want = vec![TupleBuilder::new().int(1).build(), TupleBuilder::new().int(5).build()];
+        assert_eq!(want, have);
+    }
+
+    #[test]
+    fn test_sort() {
+        const MEMORY: usize = PAGE_SIZE * 8;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let row = |c1: i32, c2: &str| TupleBuilder::new().int(c1).varchar(c2).build();
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(
+            &planner,
+            &optimiser,
+            "INSERT INTO t1 VALUES (2, 'b'), (1, 'c'), (NULL, 'a'), (2, 'a'), (1, 'd')",
+        );
+
+        let have = run(&planner, &optimiser, "SELECT c1, c2 FROM t1 ORDER BY c1, c2");
+        let null = TupleBuilder::new().null(Type::Int).varchar("a").build();
+        let want = vec![row(1, "c"), row(1, "d"), row(2, "a"), row(2, "b"), null.clone()];
+        assert_eq!(want, have);
+
+        let have = run(&planner, &optimiser, "SELECT c1, c2 FROM t1 ORDER BY c1 DESC, c2 ASC");
+        let want = vec![null.clone(), row(2, "a"), row(2, "b"), row(1, "c"), row(1, "d")];
+        assert_eq!(want, have);
+
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT c1, c2 FROM t1 ORDER BY c1 NULLS FIRST, c2 DESC NULLS LAST",
+        );
+        let want = vec![null, row(1, "d"), row(1, "c"), row(2, "b"), row(2, "a")];
+        assert_eq!(want, have);
+
+        let have = run(&planner, &optimiser, "SELECT c2 FROM t1 ORDER BY c2 DESC");
+        let want: Vec<_> =
+            ["d", "c", "b", "a", "a"].map(|c2| TupleBuilder::new().varchar(c2).build()).into();
+        assert_eq!(want, have);
+
+        // The order can use columns that aren't selected, and aliases that are
+        let have = run(&planner, &optimiser, "SELECT c2 AS name FROM t1 ORDER BY c1 DESC, name");
+        let want: Vec<_> =
+            ["a", "a", "b", "c", "d"].map(|c2| TupleBuilder::new().varchar(c2).build()).into();
+        assert_eq!(want, have);
+    }
+
+    #[test]
+    fn test_external_sort() {
+        const MEMORY: usize = PAGE_SIZE * 512;
+        const K: usize = 2;
+        const ROWS: i32 = 8000;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let padding = "x".repeat(32);
+
+        // The table is larger than the page cache, so the sort has to spill runs
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        for chunk in (0..ROWS).collect::<Vec<_>>().chunks(500) {
+            let values = chunk
+                .iter()
+                .map(|i| format!("({}, '{padding}{i}')", i * 7919 % ROWS))
+                .collect::<Vec<_>>()
+                .join(", ");
+            run(&planner, &optimiser, &format!("INSERT INTO t1 VALUES {values}"));
+        }
+        let pages = catalog.lock().unwrap().get_table_by_name("t1").unwrap().table.meta();
+        assert!(pages.last_page_id - pages.first_page_id > CACHE_SIZE as i32);
+
+        let row = |c1: i32| {
+            let c2 = format!("{padding}{}", (0..ROWS).find(|i| i * 7919 % ROWS == c1).unwrap());
+            TupleBuilder::new().int(c1).varchar(&c2).build()
+        };
+        let want: Vec<_> = (0..ROWS).map(row).collect();
+        let have = run(&planner, &optimiser, "SELECT c1, c2 FROM t1 ORDER BY c1");
+        assert_eq!(want, have);
+
+        let have = run(&planner, &optimiser, "SELECT c1, c2 FROM t1 ORDER BY c1 DESC");
+        assert_eq!(want.into_iter().rev().collect::<Vec<_>>(), have);
+    }
+
+    #[test]
+    fn test_join() {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let row = |c1: i32, c2: &str| TupleBuilder::new().int(c1).varchar(c2).build();
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(&planner, &optimiser, "CREATE TABLE t2 (c1 INT, c3 VARCHAR)");
+        run(&planner, &optimiser, "CREATE TABLE t3 (c3 VARCHAR, c4 INT)");
+        run(
+            &planner,
+            &optimiser,
+            "INSERT INTO t1 VALUES (1, 'a'), (2, 'b'), (3, 'c'), (NULL, 'd')",
+        );
+        run(
+            &planner,
+            &optimiser,
+            "INSERT INTO t2 VALUES (2, 'x'), (1, 'y'), (2, 'z'), (NULL, 'w')",
+        );
+        run(&planner, &optimiser, "INSERT INTO t3 VALUES ('x', 10), ('z', 20)");
+
+        // Equi-join
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c1, t2.c3 FROM t1 JOIN t2 ON t1.c1 = t2.c1");
+        assert_eq!(vec![row(1, "y"), row(2, "x"), row(2, "z")], have);
+
+        // Equi-join with a residual predicate
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT t1.c1, t2.c3 FROM t1 JOIN t2 ON t2.c1 = t1.c1 AND t2.c3 != 'x'",
+        );
+        assert_eq!(vec![row(1, "y"), row(2, "z")], have);
+
+        // Nested loop
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c1, t2.c3 FROM t1 JOIN t2 ON t1.c1 > t2.c1");
+        assert_eq!(vec![row(2, "y"), row(3, "x"), row(3, "y"), row(3, "z")], have);
+
+        let have = run(&planner, &optimiser, "SELECT t1.c2, t2.c3 FROM t1 JOIN t2 USING (c1)");
+        let want: Vec<_> = [("a", "y"), ("b", "x"), ("b", "z")]
+            .map(|(c2, c3)| TupleBuilder::new().varchar(c2).varchar(c3).build())
+            .into();
+        assert_eq!(want, have);
+
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT t1.c2, t3.c4 FROM t1 JOIN t2 ON t1.c1 = t2.c1 JOIN t3 ON t2.c3 = t3.c3",
+        );
+        let want: Vec<_> = [("b", 10), ("b", 20)]
+            .map(|(c2, c4)| TupleBuilder::new().varchar(c2).int(c4).build())
+            .into();
+        assert_eq!(want, have);
+    }
+
+    #[test]
+    fn test_sort_merge_join() {
+        const MEMORY: usize = PAGE_SIZE * 512;
+        const K: usize = 2;
+        const ROWS: i32 = 2000;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let padding = "x".repeat(32);
+
+        // The right input is too large to build a hash table from
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT)");
+        run(&planner, &optimiser, "CREATE TABLE t2 (c1 INT, c2 VARCHAR)");
+        run(&planner, &optimiser, "INSERT INTO t1 VALUES (7), (3), (NULL), (3), (1999), (2000)");
+        for chunk in (0..ROWS).collect::<Vec<_>>().chunks(500) {
+            let values = chunk
+                .iter()
+                .map(|i| format!("({}, '{padding}')", i * 7919 % ROWS))
+                .collect::<Vec<_>>()
+                .join(", ");
+            run(&planner, &optimiser, &format!("INSERT INTO t2 VALUES {values}"));
+        }
+        run(&planner, &optimiser, "INSERT INTO t2 VALUES (7, 'y')");
+
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c1, t2.c2 FROM t1 JOIN t2 ON t1.c1 = t2.c1");
+        let row = |c1: i32, c2: &str| TupleBuilder::new().int(c1).varchar(c2).build();
+        let want = vec![
+            row(3, &padding),
+            row(3, &padding),
+            row(7, &padding),
+            row(7, "y"),
+            row(1999, &padding),
+        ];
+        assert_eq!(want, have);
+
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c1, t2.c2 FROM t1 LEFT JOIN t2 ON t1.c1 = t2.c1");
+        let null = |c1: Option<i32>| {
+            let tuple = match c1 {
+                Some(c1) => TupleBuilder::new().int(c1),
+                None => TupleBuilder::new().null(Type::Int),
+            };
+            tuple.null(Type::Varchar).build()
+        };
+        let want = [want, vec![null(Some(2000)), null(None)]].concat();
+        assert_eq!(want, have);
+
+        // All but 4 of the tuples on the right are unmatched
+        let count = |n: i32| vec![TupleBuilder::new().int(n).build()];
+        let have =
+            run(&planner, &optimiser, "SELECT COUNT(*) FROM t1 RIGHT JOIN t2 ON t1.c1 = t2.c1");
+        assert_eq!(count(5 + ROWS + 1 - 4), have);
+        let have =
+            run(&planner, &optimiser, "SELECT COUNT(*) FROM t1 FULL JOIN t2 ON t1.c1 = t2.c1");
+        assert_eq!(count(5 + ROWS + 1 - 4 + 2), have);
+    }
+
+    #[test]
+    fn test_outer_join() {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let rows = |rows: &[(Option<&str>, Option<&str>)]| {
+            rows.iter()
+                .map(|(c2, c3)| {
+                    let mut tuple = TupleBuilder::new();
+                    for value in [c2, c3] {
+                        tuple = match value {
+                            Some(value) => tuple.varchar(value),
+                            None => tuple.null(Type::Varchar),
+                        };
+                    }
+                    tuple.build()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(&planner, &optimiser, "CREATE TABLE t2 (c1 INT, c3 VARCHAR)");
+        run(
+            &planner,
+            &optimiser,
+            "INSERT INTO t1 VALUES (1, 'a'), (2, 'b'), (3, 'c'), (NULL, 'd')",
+        );
+        run(
+            &planner,
+            &optimiser,
+            "INSERT INTO t2 VALUES (2, 'x'), (1, 'y'), (2, 'z'), (NULL, 'w'), (4, 'v')",
+        );
+
+        // Hash joins
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c2, t2.c3 FROM t1 LEFT JOIN t2 ON t1.c1 = t2.c1");
+        let want = rows(&[
+            (Some("a"), Some("y")),
+            (Some("b"), Some("x")),
+            (Some("b"), Some("z")),
+            (Some("c"), None),
+            (Some("d"), None),
+        ]);
+        assert_eq!(want, have);
+
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c2, t2.c3 FROM t1 RIGHT JOIN t2 ON t1.c1 = t2.c1");
+        let want = rows(&[
+            (Some("a"), Some("y")),
+            (Some("b"), Some("x")),
+            (Some("b"), Some("z")),
+            (None, Some("w")),
+            (None, Some("v")),
+        ]);
+        assert_eq!(want, have);
+
+        let have = run(&planner, &optimiser, "SELECT t1.c2, t2.c3 FROM t1 FULL JOIN t2 USING (c1)");
+        let want = rows(&[
+            (Some("a"), Some("y")),
+            (Some("b"), Some("x")),
+            (Some("b"), Some("z")),
+            (Some("c"), None),
+            (Some("d"), None),
+            (None, Some("w")),
+            (None, Some("v")),
+        ]);
+        assert_eq!(want, have);
+
+        // The whole ON condition decides whether there is a match
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT t1.c2, t2.c3 FROM t1 LEFT OUTER JOIN t2 ON t1.c1 = t2.c1 AND t2.c3 = 'z'",
+        );
+        let want = rows(&[
+            (Some("a"), None),
+            (Some("b"), Some("z")),
+            (Some("c"), None),
+            (Some("d"), None),
+        ]);
+        assert_eq!(want, have);
+
+        // Nested loop joins
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c2, t2.c3 FROM t1 LEFT JOIN t2 ON t1.c1 > t2.c1");
+        let want = rows(&[
+            (Some("a"), None),
+            (Some("b"), Some("y")),
+            (Some("c"), Some("x")),
+            (Some("c"), Some("y")),
+            (Some("c"), Some("z")),
+            (Some("d"), None),
+        ]);
+        assert_eq!(want, have);
+
+        let have =
+            run(&planner, &optimiser, "SELECT t1.c2, t2.c3 FROM t1 RIGHT JOIN t2 ON t1.c1 > t2.c1");
+        let want = rows(&[
+            (Some("b"), Some("y")),
+            (Some("c"), Some("x")),
+            (Some("c"), Some("y")),
+            (Some("c"), Some("z")),
+            (None, Some("w")),
+            (None, Some("v")),
+        ]);
+        assert_eq!(want, have);
+
+        let have = run(&planner, &optimiser, "SELECT COUNT(*) FROM t1 CROSS JOIN t2");
+        assert_eq!(vec![TupleBuilder::new().int(20).build()], have);
+
+        let have = run(
+            &planner,
+            &optimiser,
+            "SELECT t1.c2, t2.c3 FROM t1, t2 WHERE t1.c1 = t2.c1 AND t2.c3 != 'x'",
+        );
+        assert_eq!(rows(&[(Some("a"), Some("y")), (Some("b"), Some("z"))]), have);
+    }
+
+    #[test]
+    fn test_join_using() {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let row = |c1: Option<i32>, c2: Option<&str>, c3: Option<&str>| {
+            let tuple = match c1 {
+                Some(c1) => TupleBuilder::new().int(c1),
+                None => TupleBuilder::new().null(Type::Int),
+            };
+            let tuple = match c2 {
+                Some(c2) => tuple.varchar(c2),
+                None => tuple.null(Type::Varchar),
+            };
+            match c3 {
+                Some(c3) => tuple.varchar(c3),
+                None => tuple.null(Type::Varchar),
+            }
+            .build()
+        };
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        run(&planner, &optimiser, "CREATE TABLE t2 (c1 INT, c3 VARCHAR)");
+        run(&planner, &optimiser, "CREATE TABLE t3 (c2 VARCHAR, c1 INT, c3 VARCHAR)");
+        // There's no BIGINT in SQL yet
+        let t4 =
+            catalog.lock().unwrap().create_table("t4", schema! { c1 BigInt }).unwrap().unwrap();
+        t4.table.insert(&TupleBuilder::new().big_int(1).build()).unwrap();
+        run(&planner, &optimiser, "INSERT INTO t1 VALUES (1, 'a'), (2, 'b'), (NULL, 'c')");
+        run(&planner, &optimiser, "INSERT INTO t2 VALUES (2, 'x'), (3, 'y')");
+        run(&planner, &optimiser, "INSERT INTO t3 VALUES ('a', 1, 'x'), ('b', 1, 'y')");
+
+        // The key is a single column at the start
+        let have = run(&planner, &optimiser, "SELECT * FROM t1 JOIN t2 USING (c1)");
+        assert_eq!(vec![row(Some(2), Some("b"), Some("x"))], have);
+
+        // The merged key is taken from whichever side was matched
+        let have = run(&planner, &optimiser, "SELECT * FROM t1 FULL JOIN t2 USING (c1)");
+        let want = vec![
+            row(Some(1), Some("a"), None),
+            row(Some(2), Some("b"), Some("x")),
+            row(None, Some("c"), None),
+            row(Some(3), None, Some("y")),
+        ];
+        assert_eq!(want, have);
+
+        // The keys of each side can still be referenced by their qualified names
+        let have =
+            run(&planner, &optimiser, "SELECT c1, t1.c1, t2.c1 FROM t1 RIGHT JOIN t2 USING (c1)");
+        let want = vec![
+            TupleBuilder::new().int(2).int(2).int(2).build(),
+            TupleBuilder::new().int(3).null(Type::Int).int(3).build(),
+        ];
+        assert_eq!(want, have);
+
+        let have = run(&planner, &optimiser, "SELECT t1.*, c3 FROM t1 NATURAL JOIN t2");
+        assert_eq!(vec![row(Some(2), Some("b"), Some("x"))], have);
+
+        let have = run(&planner, &optimiser, "SELECT * FROM t1 NATURAL LEFT JOIN t3 WHERE c1 = 1");
+        let want = vec![row(Some(1), Some("a"), Some("x"))];
+        assert_eq!(want, have);
+
+        // Keys of different integer types are merged into the wider type
+        let have = run(&planner, &optimiser, "SELECT * FROM t1 JOIN t4 USING (c1)");
+        assert_eq!(vec![TupleBuilder::new().big_int(1).varchar("a").build()], have);
+
+        for statement in [
+            "SELECT c1 FROM t1 JOIN t2 ON t1.c1 = t2.c1",
+            "SELECT t1.c2 FROM t1 JOIN t2 ON t1.c1 = t2.c1 WHERE c1 = 2",
+            "SELECT c2 FROM t1 JOIN t3 USING (c1)",
+        ] {
+            let mut parser = Parser::new(statement).unwrap();
+            let plan = planner.plan(parser.parse_statements().unwrap().pop().unwrap());
+            assert!(plan.is_err(), "{statement}");
+        }
+    }
+
+    #[test]
+    fn test_index_maintenance() {
+        const MEMORY: usize = PAGE_SIZE * 64;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 VARCHAR)");
+        let table = catalog.lock().unwrap().get_table_by_name("t1").unwrap();
+        catalog
+            .lock()
+            .unwrap()
+            .create_index("i1", "t1", IndexType::BTree, false, &table.schema, &["c1"])
+            .unwrap();
+
+        // Every live row should have exactly one entry in the index
+        let check = || {
+            let index = catalog.lock().unwrap().get_index("t1", "i1").unwrap();
+            let have =
+                BTree::<RID>::open(pc.clone(), index.root_page_id, &index.schema).scan().unwrap();
+
+            let mut want = Vec::new();
+            for result in table.table.iter().unwrap() {
+                let (meta, tuple, rid) = result.unwrap();
+                if !meta.deleted {
+                    want.push((index.key(&table.schema, &tuple, Some(&rid)), rid));
+                }
+            }
+            want.sort_by_key(|(key, _)| key.get_value(&index.schema.columns[0]));
+
+            assert_eq!(want, have);
+            have.len()
+        };
+
+        // Enough keys to split the root, which starts out empty
+        let values = (0..1000).map(|i| format!("({i}, 'row')")).collect::<Vec<_>>().join(", ");
+        run(&planner, &optimiser, &format!("INSERT INTO t1 VALUES {values}"));
+        assert_eq!(1000, check());
+
+        run(&planner, &optimiser, "UPDATE t1 SET c1 = 5000 WHERE c1 = 10");
+        assert_eq!(1000, check());
+
+        run(&planner, &optimiser, "DELETE FROM t1 WHERE c1 < 100");
+        assert_eq!(901, check());
+    }
+
+    /// Fails to read pages from `limit` on, as if the disk had failed
+    struct Limited {
+        memory: Memory,
+        limit: AtomicI32,
+    }
+
+    impl Disk for Limited {
+        fn read_page(&self, page_id: PageID) -> std::io::Result<PageBuf> {
+            if page_id >= self.limit.load(Relaxed) {
+                return Err(std::io::ErrorKind::Other.into());
+            }
+            self.memory.read_page(page_id)
+        }
+
+        fn write_page(&self, page_id: PageID, data: &PageBuf) -> std::io::Result<()> {
+            self.memory.write_page(page_id, data)
+        }
+    }
+
+    #[test]
+    fn test_index_write_failure() {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        const K: usize = 2;
+        let disk = Arc::new(Limited { memory: Memory::new::<MEMORY>(), limit: AtomicI32::new(16) });
+        let pc = PageCache::open(disk.clone(), MemoryLog::new(), LRU::new(K)).unwrap();
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let try_run = |statement: &str| {
+            let mut parser = Parser::new(statement).unwrap();
+            let plan = planner.plan(parser.parse_statements().unwrap().pop().unwrap())?;
+            execute(optimiser.implement(optimiser.transform(plan)).as_mut())
+        };
+
+        try_run("CREATE TABLE t1 (c1 INT, c2 VARCHAR)").unwrap();
+        try_run("CREATE INDEX i1 ON t1 (c1)").unwrap();
+
+        // The index's first node can't be read in, so the row is taken back out of the table
+        let next_page_id = pc.new_page().unwrap().id + 1;
+        disk.limit.store(next_page_id, Relaxed);
+        assert!(try_run("INSERT INTO t1 VALUES (1, 'a')").is_err());
+        assert!(try_run("SELECT * FROM t1").unwrap().is_empty());
+
+        disk.limit.store(16, Relaxed);
+        try_run("INSERT INTO t1 VALUES (1, 'a')").unwrap();
+        assert_eq!(1, try_run("SELECT * FROM t1").unwrap().len());
+        let index = catalog.lock().unwrap().get_index("t1", "i1").unwrap();
+        let entries = BTree::<RID>::open(pc.clone(), index.root_page_id, &index.schema).scan();
+        assert_eq!(1, entries.unwrap().len());
+
+        // Rows too large for a page are refused without losing the row being updated
+        let c2 = "x".repeat(PAGE_SIZE);
+        assert!(try_run(&format!("INSERT INTO t1 VALUES (2, '{c2}')")).is_err());
+        assert!(try_run(&format!("UPDATE t1 SET c2 = '{c2}'")).is_err());
+        let want = TupleBuilder::new().int(1).varchar("a").build();
+        assert_eq!(vec![want], try_run("SELECT * FROM t1").unwrap());
+    }
+
+    #[test]
+    fn test_statement_rollback() {
+        const MEMORY: usize = PAGE_SIZE * 32;
+        const K: usize = 2;
+        let pc = PageCache::open(Memory::new::<MEMORY>(), MemoryLog::new(), LRU::new(K)).unwrap();
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let try_run = |statement: &str| {
+            let mut parser = Parser::new(statement).unwrap();
+            let plan = planner.plan(parser.parse_statements().unwrap().pop().unwrap())?;
+            execute(optimiser.implement(optimiser.transform(plan)).as_mut())
+        };
+
+        try_run("CREATE TABLE t1 (c1 INT, c2 INT)").unwrap();
+        try_run("CREATE UNIQUE INDEX i1 ON t1 (c1)").unwrap();
+        try_run("CREATE UNIQUE INDEX i2 ON t1 (c2) USING HASH").unwrap();
+        try_run("INSERT INTO t1 VALUES (1, 1), (2, 2)").unwrap();
+
+        let check = || {
+            let want = vec![
+                TupleBuilder::new().int(1).int(1).build(),
+                TupleBuilder::new().int(2).int(2).build(),
+            ];
+            assert_eq!(want, try_run("SELECT * FROM t1 ORDER BY c1").unwrap());
+
+            let catalog = catalog.lock().unwrap();
+            let i1 = catalog.get_index("t1", "i1").unwrap();
+            let btree = BTree::<RID>::open(pc.clone(), i1.root_page_id, &i1.schema);
+            assert_eq!(2, btree.scan().unwrap().len());
+            let i2 = catalog.get_index("t1", "i2").unwrap();
+            let hash_table =
+                ExtendibleHashTable::<RID>::new(i2.root_page_id, pc.clone(), &i2.schema);
+            for (c2, n) in [(1, 1), (2, 1), (3, 0), (4, 0), (9, 0)] {
+                let rids = hash_table.get(&TupleBuilder::new().int(c2).build()).unwrap();
+                assert_eq!(n, rids.len(), "{c2}");
+            }
+        };
+
+        // The last row clashes with a row already in the table, so none of the rows are kept
+        assert!(try_run("INSERT INTO t1 VALUES (3, 3), (4, 4), (1, 9)").is_err());
+        check();
+
+        // The second row can't be given the key the first row was just given
+        assert!(try_run("UPDATE t1 SET c2 = 9").is_err());
+        check();
+    }
+
+    #[test]
+    fn test_concurrent_unique_inserts() {
+        const MEMORY: usize = PAGE_SIZE * 64;
+        const K: usize = 2;
+        const KEYS: i32 = 200;
+        let disk = Memory::new::<MEMORY>();
+        let pc = PageCache::new(disk, LRU::new(K), 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 INT)");
+        run(&planner, &optimiser, "CREATE UNIQUE INDEX i1 ON t1 (c1)");
+        run(&planner, &optimiser, "CREATE UNIQUE INDEX i2 ON t1 (c2) USING HASH");
+
+        // Both threads try to insert every key, so only one of them can succeed each time
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let planner = Planner::new(Arc::clone(&catalog));
+                    let optimiser = Optimiser::new(Arc::clone(&catalog));
+                    for i in 0..KEYS {
+                        let statement = format!("INSERT INTO t1 VALUES ({i}, {i})");
+                        let mut parser = Parser::new(&statement).unwrap();
+                        let plan = planner.plan(parser.parse_statements().unwrap().remove(0));
+                        let plan = optimiser.transform(plan.unwrap());
+                        let _ = execute(optimiser.implement(plan).as_mut());
+                    }
+                });
+            }
+        });
+
+        let have = run(&planner, &optimiser, "SELECT c1 FROM t1 ORDER BY c1");
+        let want: Vec<_> = (0..KEYS).map(|i| TupleBuilder::new().int(i).build()).collect();
+        assert_eq!(want, have);
+
+        // Each key is in both indexes once
+        let catalog = catalog.lock().unwrap();
+        let i1 = catalog.get_index("t1", "i1").unwrap();
+        let btree = BTree::<RID>::open(pc.clone(), i1.root_page_id, &i1.schema);
+        assert_eq!(KEYS as usize, btree.scan().unwrap().len());
+        let i2 = catalog.get_index("t1", "i2").unwrap();
+        let hash_table = ExtendibleHashTable::<RID>::new(i2.root_page_id, pc.clone(), &i2.schema);
+        for i in 0..KEYS {
+            let rids = hash_table.get(&TupleBuilder::new().int(i).build()).unwrap();
+            assert_eq!(1, rids.len(), "{i}");
+        }
+    }
+
+    #[test]
+    fn test_create_drop_index() {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let try_run = |statement: &str| {
+            let mut parser = Parser::new(statement).unwrap();
+            let plan = planner.plan(parser.parse_statements().unwrap().pop().unwrap())?;
+            execute(optimiser.implement(optimiser.transform(plan)).as_mut())
+        };
+
+        try_run("CREATE TABLE t1 (c1 INT, c2 VARCHAR)").unwrap();
+        try_run("INSERT INTO t1 VALUES (2, 'b'), (1, 'a')").unwrap();
+        try_run("CREATE UNIQUE INDEX i1 ON t1 (c1)").unwrap();
+
+        // The index is filled from the existing rows
+        let index = catalog.lock().unwrap().get_index("t1", "i1").unwrap();
+        assert!(index.unique);
+        let have = BTree::<RID>::open(pc.clone(), index.root_page_id, &index.schema)
+            .scan()
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key.get_value(&index.schema.columns[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![Value::Int(1), Value::Int(2)], have);
+
+        // Rows can't share a key in a unique index, unless it has a null
+        assert!(try_run("INSERT INTO t1 VALUES (1, 'c')").is_err());
+        assert!(try_run("UPDATE t1 SET c1 = 1 WHERE c1 = 2").is_err());
+        try_run("UPDATE t1 SET c1 = 2 WHERE c1 = 2").unwrap();
+        try_run("INSERT INTO t1 VALUES (NULL, 'c'), (NULL, 'd')").unwrap();
+        let rows = try_run("SELECT c1 FROM t1").unwrap();
+        assert_eq!(4, rows.len());
+        assert!(try_run("CREATE UNIQUE INDEX i2 ON t1 (c2)").is_ok());
+        assert!(try_run("INSERT INTO t1 VALUES (3, 'a')").is_err());
+        try_run("DROP INDEX i2").unwrap();
+        try_run("DELETE FROM t1 WHERE c1 IS NULL").unwrap();
+
+        assert!(try_run("CREATE INDEX i1 ON t1 (c2)").is_err());
+        assert!(try_run("CREATE INDEX i2 ON t1 (c3)").is_err());
+        assert!(try_run("CREATE INDEX i2 ON t2 (c1)").is_err());
+        assert!(try_run("CREATE INDEX i2 ON t1 (c2) USING HASH").is_err());
+
+        try_run("DROP INDEX i1").unwrap();
+        assert!(catalog.lock().unwrap().get_index("t1", "i1").is_none());
+        assert!(try_run("DROP INDEX i1").is_err());
+
+        // Writes still work once the index is gone
+        try_run("INSERT INTO t1 VALUES (3, 'c'), (3, 'd')").unwrap();
+        assert!(try_run("CREATE UNIQUE INDEX i1 ON t1 (c1)").is_err());
+        assert!(catalog.lock().unwrap().get_index("t1", "i1").is_none());
+        try_run("CREATE INDEX i1 ON t1 (c2, c1) USING BTREE").unwrap();
+        let index = catalog.lock().unwrap().get_index("t1", "i1").unwrap();
+        assert!(!index.unique);
+        assert_eq!(
+            4,
+            BTree::<RID>::open(pc.clone(), index.root_page_id, &index.schema).scan().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_index_scan() {
+        const MEMORY: usize = PAGE_SIZE * 64;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+
+        run(&planner, &optimiser, "CREATE TABLE t1 (c1 INT, c2 INT, c3 VARCHAR)");
+        let rows = (0..300).map(|i| format!("({}, {}, 'r{i}')", i / 10, i % 10));
+        let values = rows.collect::<Vec<_>>().join(", ");
+        run(&planner, &optimiser, &format!("INSERT INTO t1 VALUES {values}"));
+        run(&planner, &optimiser, "CREATE UNIQUE INDEX i1 ON t1 (c1, c2)");
+
+        // A row that's only in the table can't be found through the index
+        let table = catalog.lock().unwrap().get_table_by_name("t1").unwrap();
+        table
+            .table
+            .insert(&TupleBuilder::new().int(100).int(100).varchar("hidden").build())
+            .unwrap();
+
+        let rows = |keys: &[(i32, i32)]| {
+            keys.iter()
+                .map(|(c1, c2)| TupleBuilder::new().int(*c1).int(*c2).build())
+                .collect::<Vec<_>>()
+        };
+        let tests = [
+            ("c1 = 5 AND c2 = 3", vec![(5, 3)]),
+            ("c1 = 5", (0..10).map(|c2| (5, c2)).collect()),
+            ("c2 < 3 AND c1 = 5", vec![(5, 0), (5, 1), (5, 2)]),
+            ("c1 BETWEEN 3 AND 4 AND c2 >= 8", vec![(3, 8), (3, 9), (4, 8), (4, 9)]),
+            ("c1 IN (7, 2, 7) AND c2 = 1", vec![(2, 1), (7, 1)]),
+            ("28 < c1", (0..10).map(|c2| (29, c2)).collect()),
+            ("c1 = 100", vec![]),
+            ("c2 = 100", vec![(100, 100)]),
+        ];
+        for (predicate, want) in tests {
+            let have =
+                run(&planner, &optimiser, &format!("SELECT c1, c2 FROM t1 WHERE {predicate}"));
+            assert_eq!(rows(&want), have, "{predicate}");
+        }
+
+        // Rows that share a key are all found through a non-unique index, which is filled from the
+        // table
+        run(&planner, &optimiser, "CREATE INDEX i2 ON t1 (c2)");
+        let have = run(&planner, &optimiser, "SELECT c1, c2 FROM t1 WHERE c2 = 100");
+        assert_eq!(rows(&[(100, 100)]), have);
+        let have = run(&planner, &optimiser, "SELECT c1, c2 FROM t1 WHERE c2 = 3");
+        assert_eq!(rows(&(0..30).map(|c1| (c1, 3)).collect::<Vec<_>>()), have);
+    }
+
+    #[test]
+    fn test_hash_index() {
+        const MEMORY: usize = PAGE_SIZE * 128;
+        const K: usize = 2;
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pc = PageCache::new(disk, replacer, 0);
+
+        let catalog = Arc::new(Mutex::new(Catalog::new(pc.clone()).unwrap()));
+        let planner = Planner::new(Arc::clone(&catalog));
+        let optimiser = Optimiser::new(Arc::clone(&catalog));
+        let try_run = |statement: &str| {
+            let mut parser = Parser::new(statement).unwrap();
+            let plan = planner.plan(parser.parse_statements().unwrap().pop().unwrap())?;
+            execute(optimiser.implement(optimiser.transform(plan)).as_mut())
+        };
+
+        try_run("CREATE TABLE t1 (c1 INT, c2 INT, c3 VARCHAR)").unwrap();
+        let rows = (0..1000).map(|i| format!("({i}, {}, 'r{i}')", i % 7));
+        let values = rows.collect::<Vec<_>>().join(", ");
+        try_run(&format!("INSERT INTO t1 VALUES {values}")).unwrap();
+        try_run("CREATE UNIQUE INDEX i1 ON t1 (c1) USING HASH").unwrap();
+        try_run("CREATE INDEX i2 ON t1 (c2) USING HASH").unwrap();
+        assert!(try_run("CREATE INDEX i3 ON t1 (c3) USING HASH").is_err());
+
+        // A row that's only in the table can't be found through the index
+        let table = catalog.lock().unwrap().get_table_by_name("t1").unwrap();
+        table
+            .table
+            .insert(&TupleBuilder::new().int(5000).int(1).varchar("hidden").build())
+            .unwrap();
+
+        let rows = |keys: &[(i32, i32)]| {
+            keys.iter()
+                .map(|(c1, c2)| TupleBuilder::new().int(*c1).int(*c2).build())
+                .collect::<Vec<_>>()
+        };
+        let select = |predicate: &str| {
+            try_run(&format!("SELECT c1, c2 FROM t1 WHERE {predicate} ORDER BY c1")).unwrap()
+        };
+        let tests = [
+            ("c1 = 5", vec![(5, 5)]),
+            ("c1 IN (900, 3, 5000)", vec![(3, 3), (900, 4)]),
+            ("c1 = 5000", vec![]),
+            ("c2 = 3 AND c1 < 30", vec![(3, 3), (10, 3), (17, 3), (24, 3)]),
+            // Only equalities can be looked up in a hash table
+            ("c1 > 997", vec![(998, 4), (999, 5), (5000, 1)]),
+        ];
+        for (predicate, want) in tests {
+            assert_eq!(rows(&want), select(predicate), "{predicate}");
+        }
+
+        // The indexes follow the table's writes
+        try_run("UPDATE t1 SET c1 = 2000 WHERE c1 = 10").unwrap();
+        assert_eq!(rows(&[(2000, 3)]), select("c1 = 2000"));
+        assert_eq!(rows(&[]), select("c1 = 10"));
+        try_run("DELETE FROM t1 WHERE c2 = 0").unwrap();
+        assert_eq!(rows(&[]), select("c2 = 0"));
+        assert_eq!(rows(&[]), select("c1 = 14"));
+        try_run("INSERT INTO t1 VALUES (14, 0, 'r14')").unwrap();
+        assert_eq!(rows(&[(14, 0)]), select("c2 = 0"));
+
+        assert!(try_run("INSERT INTO t1 VALUES (5, 0, 'r5')").is_err());
+        assert!(try_run("UPDATE t1 SET c1 = 5 WHERE c1 = 6").is_err());
+        assert_eq!(rows(&[(5, 5)]), select("c1 = 5"));
+
+        // A key can have more rows than fit in a bucket
+        let values = (3000..3500).map(|i| format!("({i}, 100, 'r{i}')")).collect::<Vec<_>>();
+        try_run(&format!("INSERT INTO t1 VALUES {}", values.join(", "))).unwrap();
+        assert_eq!(rows(&(3000..3500).map(|c1| (c1, 100)).collect::<Vec<_>>()), select("c2 = 100"));
+
+        try_run("DROP INDEX i1").unwrap();
+        try_run("INSERT INTO t1 VALUES (5, 0, 'r5')").unwrap();
+        assert!(try_run("CREATE UNIQUE INDEX i1 ON t1 (c1) USING HASH").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::bitmap::BitMap;
+use crate::catalog::schema::Schema;
+use crate::page::{DiskObject, PageBuf, PageID, PAGE_LSN, PAGE_SIZE};
+
+// FreeMap:
+// | NextPageID (4) | Free (PAGE_SIZE - 12) | PageLSN (8) |
+//
+// Free pages are tracked by a chain of bitmap pages, starting at the page recorded in the header.
+// The nth page in the chain covers page IDs n * PAGES_PER_MAP up to (n + 1) * PAGES_PER_MAP, with
+// a set bit meaning the page is free.
+const NEXT_PAGE_ID_SIZE: usize = 4;
+const BITMAP_SIZE: usize = PAGE_LSN.start - NEXT_PAGE_ID_SIZE;
+pub const PAGES_PER_MAP: usize = BITMAP_SIZE * 8;
+
+pub struct FreeMap {
+    pub next: PageID,
+    free: BitMap<BITMAP_SIZE>,
+}
+
+impl DiskObject for FreeMap {
+    fn serialise(&self) -> PageBuf {
+        let mut ret = [0; PAGE_SIZE];
+
+        ret[0..NEXT_PAGE_ID_SIZE].copy_from_slice(&self.next.to_be_bytes());
+        ret[NEXT_PAGE_ID_SIZE..PAGE_LSN.start].copy_from_slice(self.free.as_slice());
+
+        ret
+    }
+
+    fn deserialise(buf: PageBuf, _: &Schema) -> Self {
+        let next = i32::from_be_bytes(buf[0..NEXT_PAGE_ID_SIZE].try_into().unwrap());
+        let mut free = BitMap::new();
+        free.as_mut_slice().copy_from_slice(&buf[NEXT_PAGE_ID_SIZE..PAGE_LSN.start]);
+
+        // A zeroed page is a new map with no next page
+        let next = if next == 0 { -1 } else { next };
+
+        Self { next, free }
+    }
+}
+
+impl FreeMap {
+    /// Marks the page as free, returning false if it already was
+    pub fn free(&mut self, page_id: PageID) -> bool {
+        let i = page_id as usize % PAGES_PER_MAP;
+        if self.free.check(i) {
+            return false;
+        }
+
+        self.free.set(i, true);
+        true
+    }
+
+    /// Takes the first free page covered by this map, `n` being the map's position in the chain
+    pub fn take(&mut self, n: usize) -> Option<PageID> {
+        let i = self.free.first_set()?;
+        self.free.set(i, false);
+
+        Some((n * PAGES_PER_MAP + i) as PageID)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::catalog::schema::Schema;
+    use crate::free_map::{FreeMap, PAGES_PER_MAP};
+    use crate::page::{DiskObject, PAGE_SIZE};
+
+    #[test]
+    fn test_free_map() {
+        let mut map = FreeMap::deserialise([0; PAGE_SIZE], &Schema::default());
+        assert_eq!(-1, map.next);
+        assert_eq!(None, map.take(0));
+
+        let n = 2;
+        assert!(map.free((n * PAGES_PER_MAP + 40) as i32));
+        assert!(map.free((n * PAGES_PER_MAP + 12) as i32));
+        assert!(!map.free((n * PAGES_PER_MAP + 12) as i32));
+        map.next = 7;
+
+        let mut map = FreeMap::deserialise(map.serialise(), &Schema::default());
+        assert_eq!(7, map.next);
+        assert_eq!(Some((n * PAGES_PER_MAP + 12) as i32), map.take(n));
+        assert_eq!(Some((n * PAGES_PER_MAP + 40) as i32), map.take(n));
+        assert_eq!(None, map.take(n));
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::Range;
 
 use crate::bitmap::BitMap;
 use crate::catalog::schema::Schema;
-use crate::page::{DiskObject, PageBuf, PAGE_SIZE};
+use crate::page::{DiskObject, PageBuf, PageID, PAGE_LSN, PAGE_SIZE};
 use crate::pair::Pair;
 use crate::storable::Storable;
 use crate::table::tuple::Data as TupleData;
@@ -13,10 +13,15 @@ pub const BITMAP_SIZE: usize = 512 / 8;
 
 const OCCUPIED: Range<usize> = 0..BITMAP_SIZE;
 const READABLE: Range<usize> = BITMAP_SIZE..BITMAP_SIZE + BITMAP_SIZE;
+const NEXT: Range<usize> = BITMAP_SIZE * 2..BITMAP_SIZE * 2 + size_of::<PageID>();
+const PAIRS: usize = NEXT.end;
 
 pub struct Bucket<V> {
     pub occupied: BitMap<BITMAP_SIZE>,
     pub readable: BitMap<BITMAP_SIZE>,
+    /// The overflow page that holds the pairs which don't fit in the bucket, or 0 if there isn't
+    /// one. Overflow pages are buckets themselves, so they can be chained.
+    pub next: PageID,
     pairs: [Option<Pair<TupleData, V>>; 512],
     key_size: usize,
 }
@@ -29,22 +34,23 @@ where
         let mut buf: PageBuf = [0; PAGE_SIZE];
 
         buf[OCCUPIED].copy_from_slice(self.occupied.as_slice());
-        buf[READABLE].copy_from_slice(self.occupied.as_slice());
+        buf[READABLE].copy_from_slice(self.readable.as_slice());
+        buf[NEXT].copy_from_slice(&self.next.to_be_bytes());
 
-        let mut pos = BITMAP_SIZE * 2;
+        // Each pair has a fixed position, so the occupied map can be used to find them again
+        let mut pos = PAIRS;
         let key_size = self.key_size;
         let pair_size = key_size + size_of::<V>();
-        for pair in &self.pairs {
-            if pos + pair_size > PAGE_SIZE {
+        for (i, pair) in self.pairs.iter().enumerate() {
+            if pos + pair_size > PAGE_LSN.start {
                 break;
             }
 
-            if let Some(pair) = pair {
+            if let Some(pair) = pair.as_ref().filter(|_| self.occupied.check(i)) {
                 buf[pos..pos + key_size].copy_from_slice(pair.a.as_bytes());
-                pos += pair.a.size();
-                pair.b.write_to(&mut buf, pos);
-                pos += pair.b.size();
+                pair.b.write_to(&mut buf, pos + key_size);
             }
+            pos += pair_size;
         }
 
         buf
@@ -57,14 +63,20 @@ where
         let mut readable = BitMap::<BITMAP_SIZE>::new();
         readable.as_mut_slice().copy_from_slice(&buf[READABLE]);
 
+        let next = PageID::from_be_bytes(buf[NEXT].try_into().unwrap());
+
         // Use the occupied map to find pairs to insert
         let mut pairs: [Option<Pair<TupleData, V>>; 512] = std::array::from_fn(|_| None);
 
         let key_size = schema.tuple_size();
         let value_size = size_of::<V>();
 
-        let mut pos = BITMAP_SIZE * 2;
+        let mut pos = PAIRS;
         for (i, pair) in pairs.iter_mut().enumerate() {
+            if pos + key_size + value_size > PAGE_LSN.start {
+                break;
+            }
+
             if !occupied.check(i) {
                 pos += key_size + value_size;
                 continue;
@@ -79,7 +91,7 @@ where
             *pair = Some(Pair::new(key, value));
         }
 
-        Self { occupied, readable, pairs, key_size }
+        Self { occupied, readable, next, pairs, key_size }
     }
 }
 
@@ -87,19 +99,19 @@ impl<V> Bucket<V>
 where
     V: Storable + Copy + Eq,
 {
+    /// Removes one occurrence of the pair, so that inserting it again undoes the remove
     pub fn remove(&mut self, key: &TupleData, value: &V) -> bool {
-        let mut ret = false;
         for (i, pair) in self.pairs.iter().enumerate() {
             if let Some(pair) = pair {
                 if pair.a == *key && pair.b == *value {
                     self.readable.set(i, false);
                     self.occupied.set(i, false);
-                    ret = true;
+                    return true;
                 }
             }
         }
 
-        ret
+        false
     }
 
     pub fn remove_at(&mut self, i: usize) {
@@ -143,9 +155,25 @@ where
         ret
     }
 
+    /// Whether every pair in the bucket has the key
+    pub fn holds_only(&self, key: &TupleData) -> bool {
+        self.pairs.iter().enumerate().all(|(i, pair)| match pair {
+            Some(pair) if self.occupied.check(i) => pair.a == *key,
+            _ => true,
+        })
+    }
+
     pub fn get_pairs(self) -> Vec<Pair<TupleData, V>> {
-        // TODO: this should only return the readable pairs
-        self.pairs.into_iter().flatten().collect()
+        let occupied = self.occupied;
+        self.pairs
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, pair)| pair.filter(|_| occupied.check(i)))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.occupied.len() == 0
     }
 
     #[inline]
@@ -153,7 +181,7 @@ where
         let len = self.occupied.len();
         let s = self.key_size + size_of::<V>();
 
-        len >= (PAGE_SIZE - 128) / s
+        len >= ((PAGE_LSN.start - PAIRS) / s).min(self.pairs.len())
     }
 }
 
@@ -175,7 +203,7 @@ mod test {
         let keys = [1, 3, 5, 7, 9].map(|n| TupleBuilder::new().int(n).build());
         let values = [2, 4, 6, 8, 10];
 
-        for (key, value) in std::iter::zip(keys.clone(), values.clone()) {
+        for (key, value) in std::iter::zip(keys.clone(), values) {
             bucket.insert(key, value);
         }
 
@@ -185,7 +213,9 @@ mod test {
         assert_eq!(bucket.get(3).unwrap(), &(keys[3].clone(), values[3]));
         assert_eq!(bucket.get(4).unwrap(), &(keys[4].clone(), values[4]));
         assert!(bucket.get(5).is_none());
+        assert_eq!(0, bucket.next);
 
+        bucket.next = 7;
         page_w.put(&bucket);
 
         // Make sure it reads back ok
@@ -196,6 +226,7 @@ mod test {
         assert_eq!(bucket.get(3).unwrap(), &(keys[3].clone(), values[3]));
         assert_eq!(bucket.get(4).unwrap(), &(keys[4].clone(), values[4]));
         assert!(bucket.get(5).is_none());
+        assert_eq!(7, bucket.next);
 
         bucket.insert(keys[0].clone(), 11);
         let found = bucket.find(&keys[0]);
@@ -5,10 +5,15 @@ use crate::page::{DiskObject, PageBuf, PageID, PAGE_SIZE};
 pub const PAGE_IDS_SIZE_U32: usize = 512;
 pub const PAGE_IDS_SIZE_U8: usize = 512 * 4;
 
+/// The deepest a directory can go, at which point it uses all of its page IDs
+pub const MAX_DEPTH: u32 = PAGE_IDS_SIZE_U32.ilog2();
+
 const GLOBAL_DEPTH: Range<usize> = 0..4;
-const LOCAL_DEPTHS: Range<usize> = 4..4 + PAGE_IDS_SIZE_U32;
-const PAGE_IDS: Range<usize> = PAGE_IDS_SIZE_U32..PAGE_IDS_SIZE_U32 + PAGE_IDS_SIZE_U8;
+const LOCAL_DEPTHS: Range<usize> = GLOBAL_DEPTH.end..GLOBAL_DEPTH.end + PAGE_IDS_SIZE_U32;
+const PAGE_IDS: Range<usize> = LOCAL_DEPTHS.end..LOCAL_DEPTHS.end + PAGE_IDS_SIZE_U8;
 
+/// Maps the low bits of a hash to page IDs. The table's header page is a directory of directory
+/// pages, and each directory page is a directory of buckets.
 #[derive(Debug)]
 pub struct Directory {
     global_depth: u32,
@@ -69,6 +74,14 @@ impl Directory {
         self.global_depth += 1;
     }
 
+    pub fn local_depth(&self, i: usize) -> u32 {
+        self.local_depths[i] as u32
+    }
+
+    pub fn set_local_depth(&mut self, i: usize, depth: u32) {
+        self.local_depths[i] = depth as u8;
+    }
+
     pub fn local_depth_mask(&self, i: usize) -> usize {
         depth_mask(self.local_depths[i] as u32)
     }
@@ -84,6 +97,58 @@ impl Directory {
     pub fn global_depth(&self) -> u32 {
         self.global_depth
     }
+
+    /// Splits the page at `i` by the next bit of its indexes, pointing the indexes with the bit set
+    /// to `id`. The directory doubles first if the page is as deep as the directory. The page at
+    /// `i` should be shallower than `MAX_DEPTH`.
+    pub fn split(&mut self, i: usize, id: PageID) {
+        let depth = self.local_depth(i);
+        assert!(depth < MAX_DEPTH, "a page at the maximum depth can't be split");
+        if depth == self.global_depth {
+            self.incr_global_depth();
+        }
+
+        // Every index that pointed to the page shares its low bits. Indexes past the global
+        // depth are kept up to date too, so the directory doubles just by increasing the depth.
+        let bit = self.get_local_high_bit(i);
+        for i in (i & (bit - 1)..PAGE_IDS_SIZE_U32).step_by(bit) {
+            if i & bit > 0 {
+                self.insert(i, id);
+            }
+            self.set_local_depth(i, depth + 1);
+        }
+    }
+
+    /// Returns the index of the page that the page at `i` was split from or split off of, which
+    /// differs from `i` in the highest bit of its local depth. The page shouldn't be at depth 0.
+    pub fn split_image(&self, i: usize) -> usize {
+        i ^ (self.get_local_high_bit(i) >> 1)
+    }
+
+    /// Undoes a split, pointing the indexes of the page at `i` and of its split image to `id`.
+    /// Both pages should be at the same local depth.
+    pub fn merge(&mut self, i: usize, id: PageID) {
+        let depth = self.local_depth(i);
+        assert!(
+            depth > 0 && depth == self.local_depth(self.split_image(i)),
+            "only a page and its split image can be merged"
+        );
+
+        let bit = 1 << (depth - 1);
+        for i in (i & (bit - 1)..PAGE_IDS_SIZE_U32).step_by(bit) {
+            self.insert(i, id);
+            self.set_local_depth(i, depth - 1);
+        }
+    }
+
+    /// Halves the directory for as long as every page is shallower than it
+    pub fn shrink(&mut self) {
+        while self.global_depth > 0
+            && (0..1 << self.global_depth).all(|i| self.local_depth(i) < self.global_depth)
+        {
+            self.global_depth -= 1;
+        }
+    }
 }
 
 #[inline]
@@ -98,8 +163,8 @@ fn depth_mask(depth: u32) -> usize {
 #[cfg(test)]
 mod test {
     use crate::catalog::schema::Schema;
-    use crate::hash_table::directory::Directory;
-    use crate::page::{DiskObject, Page};
+    use crate::hash_table::directory::{Directory, MAX_DEPTH, PAGE_IDS_SIZE_U32};
+    use crate::page::{DiskObject, Page, PageID};
 
     #[test]
     fn test_depth_mask() {
@@ -140,4 +205,77 @@ mod test {
         assert_eq!(dir.get(2), 2);
         assert_eq!(dir.get(10), 10);
     }
+
+    #[test]
+    fn test_split() {
+        let mut dir = Directory::default();
+        (0..PAGE_IDS_SIZE_U32).for_each(|i| dir.insert(i, 1));
+
+        dir.split(5, 2);
+        assert_eq!(dir.global_depth(), 1);
+        assert_eq!([1, 2, 1, 2], [0, 1, 2, 3].map(|i| dir.get(i)));
+
+        // Splitting a shallower page doesn't grow the directory
+        dir.split(3, 3);
+        dir.split(2, 4);
+        assert_eq!(dir.global_depth(), 2);
+        assert_eq!([1, 2, 4, 3], [0, 1, 2, 3].map(|i| dir.get(i)));
+        assert_eq!([2, 2, 2, 2], [0, 1, 2, 3].map(|i| dir.local_depth(i)));
+        assert_eq!(4, dir.get(PAGE_IDS_SIZE_U32 - 2));
+
+        for depth in 2..MAX_DEPTH {
+            dir.split(0, 5 + depth as PageID);
+        }
+        assert_eq!(dir.global_depth(), MAX_DEPTH);
+        assert_eq!(MAX_DEPTH, dir.local_depth(0));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut dir = Directory::default();
+        (0..PAGE_IDS_SIZE_U32).for_each(|i| dir.insert(i, 1));
+        dir.split(0, 2);
+        dir.split(1, 3);
+        dir.split(0, 4);
+        assert_eq!([1, 2, 4, 3], [0, 1, 2, 3].map(|i| dir.get(i)));
+
+        // The page at 1 is shallower than its split image at 3
+        assert_eq!(3, dir.split_image(1));
+        dir.shrink();
+        assert_eq!(dir.global_depth(), 2);
+
+        dir.merge(3, 2);
+        assert_eq!([1, 2, 4, 2], [0, 1, 2, 3].map(|i| dir.get(i)));
+        assert_eq!([2, 1, 2, 1], [0, 1, 2, 3].map(|i| dir.local_depth(i)));
+        dir.shrink();
+        assert_eq!(dir.global_depth(), 2);
+
+        dir.merge(2, 1);
+        dir.shrink();
+        assert_eq!(dir.global_depth(), 1);
+        assert_eq!([1, 2], [0, 1].map(|i| dir.get(i)));
+
+        dir.merge(0, 1);
+        dir.shrink();
+        assert_eq!(dir.global_depth(), 0);
+        assert!((0..PAGE_IDS_SIZE_U32).all(|i| dir.get(i) == 1 && dir.local_depth(i) == 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "split image")]
+    fn test_merge_shallower() {
+        let mut dir = Directory::default();
+        dir.split(0, 1);
+        dir.split(1, 2);
+        dir.merge(0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "maximum depth")]
+    fn test_split_max_depth() {
+        let mut dir = Directory::default();
+        for depth in 0..=MAX_DEPTH {
+            dir.split(0, depth as PageID + 1);
+        }
+    }
 }
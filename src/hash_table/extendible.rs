@@ -1,163 +1,701 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 use crate::catalog::schema::Schema;
 use crate::hash_table::bucket::Bucket;
-use crate::hash_table::directory::{self, Directory};
-use crate::page::{DiskObject, PageID};
-use crate::page_cache::SharedPageCache;
+use crate::hash_table::directory::{Directory, MAX_DEPTH, PAGE_IDS_SIZE_U32};
+use crate::page::{DiskObject, PageID, PageReadGuard, PageWriteGuard, PAGE_SIZE};
+use crate::page_cache::{Latch, SharedPageCache};
+use crate::pair::Pair;
 use crate::storable::Storable;
 use crate::table::tuple::Data as TupleData;
+use crate::wal::{IndexOp, LogicalUndo};
 
+type ReadLatch<'a> = Latch<'a, PageReadGuard<'a>>;
+type WriteLatch<'a> = Latch<'a, PageWriteGuard<'a>>;
+
+/// An extendible hash table over three levels of pages. The header page is a directory of
+/// directory pages, indexed by the bits of a key's hash just above the ones that a directory uses,
+/// and each directory page is a directory of buckets. Once a directory is as deep as it can go, a
+/// bucket in it that's full is made room for by splitting the whole directory in the header, so
+/// the table can grow to `PAGE_IDS_SIZE_U32` directories' worth of buckets.
+///
+/// The table can be used from several threads at once. Every operation latches its way down from
+/// the header, and releases each page once the page below it is latched, since a page can't be
+/// split or merged whilst the page above it is latched. Lookups only take read latches, and
+/// inserts and removes only write latch their bucket. When a bucket has to be split or merged,
+/// they start again from the header, write latching the directory, or the header as well if the
+/// directory has to be split or merged.
+///
+/// Pairs that no split could make room for, because they have the same key as every pair in their
+/// bucket or because the table is as deep as it can go, go on overflow pages chained from the
+/// bucket. Overflow pages are only ever reached through their bucket, so the bucket's latch covers
+/// them too. The first overflow page takes the place of a bucket that's emptied, so a bucket with
+/// overflow pages is never empty.
 pub struct ExtendibleHashTable<'a, V> {
-    dir_page_id: PageID,
+    header_page_id: PageID,
     pc: SharedPageCache,
     schema: &'a Schema,
     _data: PhantomData<V>,
 }
 
+/// What an insert that only write latches its bucket leads to
+enum Insert {
+    Inserted,
+    /// The bucket is full but can be split within its directory
+    SplitBucket,
+    /// The bucket is full and its directory can't grow any more, so the directory has to be split
+    /// in the header. An empty table gets its first directory in the same way.
+    SplitDirectory,
+}
+
 impl<'a, V> ExtendibleHashTable<'a, V>
 where
     V: Storable + Copy + Eq,
 {
-    pub fn new(dir_page_id: PageID, pc: SharedPageCache, schema: &'a Schema) -> Self {
-        Self { dir_page_id, pc, schema, _data: PhantomData }
+    pub fn new(header_page_id: PageID, pc: SharedPageCache, schema: &'a Schema) -> Self {
+        Self { header_page_id, pc, schema, _data: PhantomData }
     }
 
-    pub fn insert(&self, key: TupleData, value: V) -> crate::Result<bool> {
-        let dir_page = self.pc.fetch_page(self.dir_page_id)?;
+    /// Creates an empty table, allocating its header page. The directories and buckets are
+    /// allocated as they're needed.
+    pub fn create(pc: SharedPageCache, schema: &'a Schema) -> crate::Result<Self> {
+        let header_page_id = pc.new_page()?.id;
 
-        let mut dir = dir_page.write_object::<Directory>(&Schema::default());
+        Ok(Self::new(header_page_id, pc, schema))
+    }
+
+    /// Returns the ID of the header page, which the table is opened from
+    pub fn id(&self) -> PageID {
+        self.header_page_id
+    }
+
+    /// Adds the pair to the table. A key can have any number of values.
+    pub fn insert(&self, key: TupleData, value: V) -> crate::Result<()> {
+        let txn = self.pc.begin();
+        let undo =
+            LogicalUndo::new(IndexOp::HashRemove, self.header_page_id, self.schema, &key, &value);
+        self._insert(key, value)?;
+        txn.commit_logical(undo)
+    }
+
+    fn _insert(&self, key: TupleData, value: V) -> crate::Result<()> {
+        let hash = hash(&key);
 
-        let bucket_index = get_bucket_index(&key, &dir);
-        let bucket_page_id = dir.get(bucket_index);
-        let bucket_page = match bucket_page_id {
-            0 => {
-                let p = self.pc.new_page()?;
-                dir.insert(bucket_index, p.id);
-                p
+        // Other writers can get in between the attempts, so each split checks again that it's
+        // still needed
+        let mut overflow = false;
+        loop {
+            match self.try_insert(hash, &key, value, overflow)? {
+                Insert::Inserted => return Ok(()),
+                Insert::SplitBucket => self.grow_directory(hash)?,
+                Insert::SplitDirectory => overflow = !self.grow_header(hash)?,
             }
-            _ => self.pc.fetch_page(bucket_page_id)?,
+        }
+    }
+
+    /// Inserts the pair if its bucket has room, only write latching the bucket. Otherwise works
+    /// out what has to be split to make room, unless no split can, in which case the pair goes on
+    /// an overflow page. `overflow` says that the table is too deep to split the bucket.
+    fn try_insert(
+        &self,
+        hash: usize,
+        key: &TupleData,
+        value: V,
+        overflow: bool,
+    ) -> crate::Result<Insert> {
+        let Some((dir_page, dir)) = self.read_directory(hash)? else {
+            return Ok(Insert::SplitDirectory);
         };
 
+        let i = directory_index(hash, &dir);
+        let mut bucket_page = self.pc.fetch_page(dir.get(i))?.into_write();
+        drop(dir_page);
+
+        let mut bucket = Bucket::deserialise(bucket_page.data, self.schema);
+        if !bucket.is_full() {
+            bucket.insert(key.clone(), value);
+            bucket_page.put(&bucket);
+
+            return Ok(Insert::Inserted);
+        }
+
+        // Splitting can't separate pairs with the same key
+        if overflow || bucket.holds_only(key) {
+            self.insert_overflow(&mut bucket_page, bucket, key, value)?;
+            return Ok(Insert::Inserted);
+        }
+
+        Ok(if dir.local_depth(i) < MAX_DEPTH {
+            Insert::SplitBucket
+        } else {
+            Insert::SplitDirectory
+        })
+    }
+
+    /// Inserts the pair on the first of the full bucket's overflow pages that has room, chaining a
+    /// new one on the end if none do
+    fn insert_overflow<'b>(
+        &'b self,
+        bucket_page: &mut WriteLatch<'b>,
+        mut bucket: Bucket<V>,
+        key: &TupleData,
+        value: V,
+    ) -> crate::Result<()> {
+        let mut last: Option<WriteLatch<'b>> = None;
+        while bucket.is_full() {
+            let page = last.as_mut().unwrap_or(bucket_page);
+            let next = match bucket.next {
+                0 => {
+                    let id = self.pc.new_page()?.id;
+                    bucket.next = id;
+                    page.put(&bucket);
+                    id
+                }
+                id => id,
+            };
+
+            let next_page = self.pc.fetch_page(next)?.into_write();
+            bucket = Bucket::deserialise(next_page.data, self.schema);
+            last = Some(next_page);
+        }
+
+        bucket.insert(key.clone(), value);
+        last.as_mut().unwrap_or(bucket_page).put(&bucket);
+
+        Ok(())
+    }
+
+    /// Splits the full bucket that a hash leads to, write latching its directory. The pairs whose
+    /// hash has the next bit set move to a new bucket. If they all end up on the same side, the
+    /// bucket is split again on the next attempt.
+    fn grow_directory(&self, hash: usize) -> crate::Result<()> {
+        let (mut dir_page, mut dir) = self.write_directory(hash)?;
+
+        let i = directory_index(hash, &dir);
+        if dir.local_depth(i) < MAX_DEPTH && self.is_full(dir.get(i))? {
+            let bit = dir.get_local_high_bit(i);
+            let id = self.split_bucket(dir.get(i), |hash| hash & bit > 0)?;
+            dir.split(i, id);
+            dir_page.put(&dir);
+        }
+
+        Ok(())
+    }
+
+    /// Splits the directory that a hash leads to in the header, once the full bucket it leads to
+    /// can't be split within the directory, write latching the header and the directory. An empty
+    /// table gets its first directory instead. Returns false if the header can't grow any more.
+    fn grow_header(&self, hash: usize) -> crate::Result<bool> {
+        let mut header_page = self.pc.fetch_page(self.header_page_id)?.into_write();
+        let mut header = Directory::deserialise(header_page.data, &Schema::default());
+
+        // Every index in a directory points to a page, even the ones past the global depth, so
+        // the directory doubles in size just by increasing the global depth
+        if header.get(0) == 0 {
+            let id = self.new_directory()?;
+            (0..PAGE_IDS_SIZE_U32).for_each(|i| header.insert(i, id));
+            header_page.put(&header);
+
+            return Ok(true);
+        }
+
+        let d = header_index(hash, &header);
+        let dir_page = self.pc.fetch_page(header.get(d))?.into_write();
+        let dir = Directory::deserialise(dir_page.data, &Schema::default());
+
+        let i = directory_index(hash, &dir);
+        if dir.local_depth(i) < MAX_DEPTH || !self.is_full(dir.get(i))? {
+            return Ok(true);
+        }
+        if header.local_depth(d) == MAX_DEPTH {
+            return Ok(false);
+        }
+
+        let bit = header.get_local_high_bit(d);
+        let id = self.split_directory(&dir, |hash| (hash >> MAX_DEPTH) & bit > 0)?;
+        header.split(d, id);
+        header_page.put(&header);
+
+        Ok(true)
+    }
+
+    /// Allocates a directory with a single bucket
+    fn new_directory(&self) -> crate::Result<PageID> {
+        let dir_page = self.pc.new_page()?;
+        let mut dir = dir_page.write_object::<Directory>(&Schema::default());
+        let id = self.pc.new_page()?.id;
+        (0..PAGE_IDS_SIZE_U32).for_each(|i| dir.insert(i, id));
+
+        Ok(dir_page.id)
+    }
+
+    /// Moves the pairs of a bucket whose hash `moves` is true for to a new bucket, returning the
+    /// new bucket's page ID. The pairs on the bucket's overflow pages are moved as well, and the
+    /// overflow pages are rebuilt for whichever side still needs them.
+    fn split_bucket(&self, id: PageID, moves: impl Fn(usize) -> bool) -> crate::Result<PageID> {
+        let bucket_page = self.pc.fetch_page(id)?;
         let mut bucket_w = bucket_page.write();
-        let mut bucket = Bucket::deserialise(bucket_w.data, self.schema);
+        let bucket = Bucket::<V>::deserialise(bucket_w.data, self.schema);
+        let next = bucket.next;
+        let mut pairs = bucket.get_pairs();
+        for bucket in self.overflow(next)? {
+            pairs.extend(bucket.get_pairs());
+        }
+        self.deallocate_overflow(next)?;
 
-        bucket.insert(key, value);
+        let new_page = self.pc.new_page()?;
+        let mut new_w = new_page.write();
+        let (new, old) = pairs.into_iter().partition(|pair| moves(hash(&pair.a)));
+        self.fill(&mut bucket_w, old)?;
+        self.fill(&mut new_w, new)?;
+
+        Ok(new_page.id)
+    }
+
+    /// Writes the pairs to a bucket, chaining on as many overflow pages as they need
+    fn fill(
+        &self,
+        bucket_w: &mut PageWriteGuard<'_>,
+        pairs: Vec<Pair<TupleData, V>>,
+    ) -> crate::Result<()> {
+        let mut bucket = Bucket::deserialise([0; PAGE_SIZE], self.schema);
+        let mut pairs = pairs.into_iter();
+        while !bucket.is_full() {
+            let Some(pair) = pairs.next() else { break };
+            bucket.insert(pair.a, pair.b);
+        }
+
+        let rest = pairs.collect::<Vec<_>>();
+        if !rest.is_empty() {
+            let next_page = self.pc.new_page()?;
+            bucket.next = next_page.id;
+            self.fill(&mut next_page.write(), rest)?;
+        }
         bucket_w.put(&bucket);
 
-        if bucket.is_full() {
-            if dir.local_depth_mask(bucket_index) == dir.global_depth_mask() {
-                // The size of the directory implicitily doubles
-                dir.incr_global_depth();
+        Ok(())
+    }
+
+    /// Splits every bucket of a directory with `split_bucket`, returning the page ID of a new
+    /// directory that points to the new buckets in the same way
+    fn split_directory(
+        &self,
+        dir: &Directory,
+        moves: impl Fn(usize) -> bool,
+    ) -> crate::Result<PageID> {
+        let new_page = self.pc.new_page()?;
+        let mut new = new_page.write_object::<Directory>(&Schema::default());
+        new.set_global_depth(dir.global_depth());
+
+        let mut ids = HashMap::new();
+        for i in 0..PAGE_IDS_SIZE_U32 {
+            let id = match ids.get(&dir.get(i)) {
+                Some(id) => *id,
+                None => {
+                    let id = self.split_bucket(dir.get(i), &moves)?;
+                    *ids.entry(dir.get(i)).or_insert(id)
+                }
+            };
+            new.insert(i, id);
+            new.set_local_depth(i, dir.local_depth(i));
+        }
+
+        Ok(new_page.id)
+    }
+
+    /// Frees the header page and all of the directories and buckets
+    pub fn deallocate(self) -> crate::Result<()> {
+        let txn = self.pc.begin();
+
+        for dir_page_id in self.directories()? {
+            let ids = {
+                let dir_page = self.pc.fetch_page(dir_page_id)?;
+                let dir = dir_page.read_object::<Directory>(&Schema::default());
+                page_ids(&dir)
+            };
+            for id in ids {
+                let next = self.pc.fetch_page(id)?.read_object::<Bucket<V>>(self.schema).next;
+                self.deallocate_overflow(next)?;
+                self.pc.deallocate_page(id)?;
             }
+            self.pc.deallocate_page(dir_page_id)?;
+        }
+        self.pc.deallocate_page(self.header_page_id)?;
 
-            // 1. Create two new bucket pages and increase local depths for both of them
-            // 2. Get the high bit of the old bucket (1 << local_depth)
-            // 3. Reinsert into the new pages
-            // 4. Update the page ids in the directory
-            let page0 = self.pc.new_page()?;
-            let mut page0_w = page0.write();
-            let mut bucket0 = Bucket::deserialise(page0_w.data, self.schema);
+        txn.commit()
+    }
 
-            let page1 = self.pc.new_page()?;
-            let mut page1_w = page1.write();
-            let mut bucket1 = Bucket::deserialise(page1_w.data, self.schema);
+    /// Removes one occurrence of the pair, returning false if it isn't in the table
+    pub fn remove(&self, key: &TupleData, v: &V) -> crate::Result<bool> {
+        let txn = self.pc.begin();
+        if !self._remove(key, v)? {
+            txn.commit()?;
+            return Ok(false);
+        }
+        txn.commit_logical(LogicalUndo::new(
+            IndexOp::HashInsert,
+            self.header_page_id,
+            self.schema,
+            key,
+            v,
+        ))?;
 
-            let bit = dir.get_local_high_bit(bucket_index);
-            for pair in bucket.get_pairs() {
-                let i = get_bucket_index(&pair.a, &dir);
-                let new_bucket = if i & bit > 0 { &mut bucket1 } else { &mut bucket0 };
-                new_bucket.insert(pair.a, pair.b);
+        Ok(true)
+    }
+
+    fn _remove(&self, key: &TupleData, v: &V) -> crate::Result<bool> {
+        let hash = hash(key);
+        let Some((dir_page, dir)) = self.read_directory(hash)? else { return Ok(false) };
+
+        let mut bucket_page =
+            self.pc.fetch_page(dir.get(directory_index(hash, &dir)))?.into_write();
+        drop(dir_page);
+
+        let mut bucket = Bucket::deserialise(bucket_page.data, self.schema);
+        if bucket.remove(key, v) {
+            if bucket.is_empty() && bucket.next != 0 {
+                let id = bucket.next;
+                bucket = Bucket::deserialise(self.pc.fetch_page(id)?.read().data, self.schema);
+                self.pc.deallocate_page(id)?;
             }
+            bucket_page.put(&bucket);
+        } else if !self.remove_overflow(&mut bucket_page, bucket.next, key, v)? {
+            return Ok(false);
+        }
+        drop(bucket_page);
 
-            for i in (bucket_index & (bit - 1)..directory::PAGE_IDS_SIZE_U32).step_by(bit) {
-                let new_page_id = if i & bit > 0 { page0_w.id } else { page1_w.id };
+        // Other writers can get in between, so each merge checks again that it's still possible
+        if bucket.is_empty() && self.shrink_directory(hash)? {
+            self.shrink_header(hash)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Removes the pair from the overflow pages chained from `next`, unlinking the page that held
+    /// it if that leaves the page empty. The bucket's write latch has to be held.
+    fn remove_overflow<'b>(
+        &'b self,
+        bucket_page: &mut WriteLatch<'b>,
+        mut next: PageID,
+        key: &TupleData,
+        v: &V,
+    ) -> crate::Result<bool> {
+        let mut prev: Option<WriteLatch<'b>> = None;
+        while next != 0 {
+            let mut page = self.pc.fetch_page(next)?.into_write();
+            let mut bucket = Bucket::<V>::deserialise(page.data, self.schema);
+            if !bucket.remove(key, v) {
+                next = bucket.next;
+                prev = Some(page);
+                continue;
+            }
 
-                dir.insert(i, new_page_id);
+            if !bucket.is_empty() {
+                page.put(&bucket);
+                return Ok(true);
             }
 
-            page0_w.put(&bucket0);
-            page1_w.put(&bucket0);
+            let prev_page = prev.as_mut().unwrap_or(bucket_page);
+            let mut prev_bucket = Bucket::<V>::deserialise(prev_page.data, self.schema);
+            prev_bucket.next = bucket.next;
+            prev_page.put(&prev_bucket);
+            drop(page);
+            self.pc.deallocate_page(next)?;
 
-            // TODO: mark original page on disk as ready to be allocated
-            self.pc.remove_page(bucket_w.id);
+            return Ok(true);
         }
 
-        Ok(true)
+        Ok(false)
     }
 
-    pub fn remove(&self, key: &TupleData, v: &V) -> crate::Result<bool> {
-        let dir_page = self.pc.fetch_page(self.dir_page_id)?;
-        let dir = dir_page.read_object::<Directory>(&Schema::default());
-
-        let bucket_index = get_bucket_index(key, &dir);
-        let bucket_page_id = dir.get(bucket_index);
-        let bucket_page = match bucket_page_id {
-            0 => return Ok(false),
-            _ => self.pc.fetch_page(bucket_page_id)?,
-        };
-        let mut bucket_w = bucket_page.write();
-        let mut bucket = Bucket::deserialise(bucket_w.data, self.schema);
+    /// Reads the overflow pages chained from `next`
+    fn overflow(&self, mut next: PageID) -> crate::Result<Vec<Bucket<V>>> {
+        let mut buckets = Vec::new();
+        while next != 0 {
+            let bucket = Bucket::deserialise(self.pc.fetch_page(next)?.read().data, self.schema);
+            next = bucket.next;
+            buckets.push(bucket);
+        }
 
-        let ret = bucket.remove(key, v);
-        bucket_w.put(&bucket);
+        Ok(buckets)
+    }
 
-        Ok(ret)
+    /// Frees the overflow pages chained from `next`
+    fn deallocate_overflow(&self, next: PageID) -> crate::Result<()> {
+        let mut ids = Vec::new();
+        let mut id = next;
+        while id != 0 {
+            ids.push(id);
+            id = self.pc.fetch_page(id)?.read_object::<Bucket<V>>(self.schema).next;
+        }
+
+        ids.into_iter().try_for_each(|id| self.pc.deallocate_page(id))
     }
 
-    pub fn get(&self, key: &TupleData) -> crate::Result<Vec<V>> {
-        let dir_page = self.pc.fetch_page(self.dir_page_id)?;
-        let dir = dir_page.read_object::<Directory>(&Schema::default());
-
-        let bucket_index = get_bucket_index(key, &dir);
-        let bucket_page_id = dir.get(bucket_index);
-        let bucket_page = match bucket_page_id {
-            0 => return Ok(vec![]),
-            _ => self.pc.fetch_page(bucket_page_id)?,
+    /// Merges the emptied bucket that a hash leads to, write latching its directory. Returns true
+    /// if the directory is left with a single empty bucket.
+    fn shrink_directory(&self, hash: usize) -> crate::Result<bool> {
+        let (mut dir_page, mut dir) = self.write_directory(hash)?;
+        let i = directory_index(hash, &dir);
+        let merged = self.merge_buckets(&mut dir, i)?;
+        dir_page.put(&dir);
+
+        Ok(merged)
+    }
+
+    /// Merges the bucket at `i` with its split image for as long as one of the two is empty, then
+    /// shrinks the directory. Either of them can be the empty one, since a split can leave all of
+    /// the pairs on one side. Returns true if the directory is left with a single empty bucket.
+    fn merge_buckets(&self, dir: &mut Directory, i: usize) -> crate::Result<bool> {
+        while dir.local_depth(i) > 0 {
+            let image = dir.split_image(i);
+            if dir.local_depth(image) != dir.local_depth(i) {
+                break;
+            }
+
+            let (id, image_id) = (dir.get(i), dir.get(image));
+            let (empty, kept) = if self.is_empty(id)? {
+                (id, image_id)
+            } else if self.is_empty(image_id)? {
+                (image_id, id)
+            } else {
+                break;
+            };
+            dir.merge(i, kept);
+            self.pc.deallocate_page(empty)?;
+        }
+        dir.shrink();
+
+        Ok(dir.global_depth() == 0 && self.is_empty(dir.get(0))?)
+    }
+
+    /// Merges the directory that a hash leads to into its split image in the header if it only
+    /// has a single empty bucket, write latching the header and the directory, and frees the
+    /// directory and its bucket
+    fn shrink_header(&self, hash: usize) -> crate::Result<()> {
+        let mut header_page = self.pc.fetch_page(self.header_page_id)?.into_write();
+        let mut header = Directory::deserialise(header_page.data, &Schema::default());
+
+        let d = header_index(hash, &header);
+        if header.local_depth(d) == 0
+            || header.local_depth(header.split_image(d)) != header.local_depth(d)
+        {
+            return Ok(());
+        }
+
+        let id = header.get(d);
+        let bucket = {
+            let dir_page = self.pc.fetch_page(id)?.into_write();
+            let dir = Directory::deserialise(dir_page.data, &Schema::default());
+            if dir.global_depth() > 0 || !self.is_empty(dir.get(0))? {
+                return Ok(());
+            }
+
+            dir.get(0)
         };
+        header.merge(d, header.get(header.split_image(d)));
+        header.shrink();
+        header_page.put(&header);
+
+        self.pc.deallocate_page(bucket)?;
+        self.pc.deallocate_page(id)
+    }
+
+    fn is_empty(&self, id: PageID) -> crate::Result<bool> {
+        let bucket_page = self.pc.fetch_page(id)?;
+        let bucket = bucket_page.read_object::<Bucket<V>>(self.schema);
+
+        Ok(bucket.is_empty())
+    }
 
+    fn is_full(&self, id: PageID) -> crate::Result<bool> {
+        let bucket_page = self.pc.fetch_page(id)?;
         let bucket = bucket_page.read_object::<Bucket<V>>(self.schema);
 
-        Ok(bucket.find(key))
+        Ok(bucket.is_full())
     }
 
+    pub fn get(&self, key: &TupleData) -> crate::Result<Vec<V>> {
+        let hash = hash(key);
+        let Some((dir_page, dir)) = self.read_directory(hash)? else { return Ok(vec![]) };
+
+        let bucket_page = self.pc.fetch_page(dir.get(directory_index(hash, &dir)))?.into_read();
+        drop(dir_page);
+        let bucket = Bucket::<V>::deserialise(bucket_page.data, self.schema);
+
+        let mut values = bucket.find(key);
+        for bucket in self.overflow(bucket.next)? {
+            values.extend(bucket.find(key));
+        }
+
+        Ok(values)
+    }
+
+    /// Returns the number of indexes in use across all of the directories
     pub fn get_num_buckets(&self) -> crate::Result<u32> {
-        let dir_page = self.pc.fetch_page(self.dir_page_id)?;
-        let dir = dir_page.read_object::<Directory>(&Schema::default());
-        Ok(1 << dir.global_depth())
+        let mut n = 0;
+        for id in self.directories()? {
+            let dir_page = self.pc.fetch_page(id)?;
+            let dir = dir_page.read_object::<Directory>(&Schema::default());
+            n += 1 << dir.global_depth();
+        }
+
+        Ok(n)
+    }
+
+    /// Read latches the directory that a hash leads to, releasing the header once it's latched.
+    /// The directory can't be split or merged in the header, nor any of its buckets within it,
+    /// until it's released. Returns `None` if the table is empty.
+    fn read_directory(&self, hash: usize) -> crate::Result<Option<(ReadLatch<'_>, Directory)>> {
+        let header_page = self.pc.fetch_page(self.header_page_id)?.into_read();
+        let header = Directory::deserialise(header_page.data, &Schema::default());
+
+        let dir_page = match header.get(header_index(hash, &header)) {
+            0 => return Ok(None),
+            id => self.pc.fetch_page(id)?.into_read(),
+        };
+        let dir = Directory::deserialise(dir_page.data, &Schema::default());
+
+        Ok(Some((dir_page, dir)))
+    }
+
+    /// Write latches the directory that a hash leads to, releasing the header once it's latched.
+    /// The table shouldn't be empty.
+    fn write_directory(&self, hash: usize) -> crate::Result<(WriteLatch<'_>, Directory)> {
+        let header_page = self.pc.fetch_page(self.header_page_id)?.into_read();
+        let header = Directory::deserialise(header_page.data, &Schema::default());
+
+        let dir_page = self.pc.fetch_page(header.get(header_index(hash, &header)))?.into_write();
+        let dir = Directory::deserialise(dir_page.data, &Schema::default());
+
+        Ok((dir_page, dir))
+    }
+
+    /// Checks the structure of the table, returning the number of buckets in it. The header and
+    /// every directory must be valid, and every pair must be in the bucket that its hash leads to.
+    #[cfg(test)]
+    fn verify_integrity(&self) -> crate::Result<usize> {
+        let header_page = self.pc.fetch_page(self.header_page_id)?;
+        let header = header_page.read_object::<Directory>(&Schema::default());
+        if header.get(0) == 0 {
+            return Ok(0);
+        }
+        verify_directory(&header);
+
+        let mut buckets = 0;
+        // Each page is first pointed to by the index that's within its own local depth
+        for d in (0..1 << header.global_depth()).filter(|d| d & header.local_depth_mask(*d) == *d) {
+            let dir_page = self.pc.fetch_page(header.get(d))?;
+            let dir = dir_page.read_object::<Directory>(&Schema::default());
+            verify_directory(&dir);
+
+            for i in (0..1 << dir.global_depth()).filter(|i| i & dir.local_depth_mask(*i) == *i) {
+                let bucket_page = self.pc.fetch_page(dir.get(i))?;
+                let bucket = Bucket::<V>::deserialise(bucket_page.read().data, self.schema);
+                let overflow = self.overflow(bucket.next)?;
+                assert!(overflow.is_empty() || !bucket.is_empty(), "empty bucket with overflow");
+                assert!(overflow.iter().all(|bucket| !bucket.is_empty()), "empty overflow page");
+
+                let pairs = overflow.into_iter().flat_map(|bucket| bucket.get_pairs());
+                for pair in bucket.get_pairs().into_iter().chain(pairs) {
+                    let hash = hash(&pair.a);
+                    assert_eq!(i, hash & dir.local_depth_mask(i), "pair in the wrong bucket");
+                    let h = header_index(hash, &header) & header.local_depth_mask(d);
+                    assert_eq!(d, h, "pair in the wrong directory");
+                }
+                buckets += 1;
+            }
+        }
+
+        Ok(buckets)
+    }
+
+    fn directories(&self) -> crate::Result<Vec<PageID>> {
+        let header_page = self.pc.fetch_page(self.header_page_id)?;
+        let header = header_page.read_object::<Directory>(&Schema::default());
+
+        Ok(page_ids(&header))
     }
 }
 
+/// Hashes a key with 64-bit FNV-1a, followed by the finaliser of MurmurHash3, since the buckets
+/// are picked by the low bits of the hash and FNV-1a alone leaves them depending on only the low
+/// bits of each byte. The hash decides where pairs are stored on disk, so changing it means
+/// bumping the file format's `VERSION`.
 fn hash(key: &TupleData) -> usize {
-    let mut hasher = DefaultHasher::new();
-    key.hash(&mut hasher);
-    hasher.finish() as usize
+    let hash = key
+        .as_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325, |hash: u64, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3));
+
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51afd7ed558ccd);
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ceb9fe1a85ec53);
+    (hash ^ (hash >> 33)) as usize
 }
 
-fn get_bucket_index(key: &TupleData, directory: &Directory) -> usize {
-    let hash = hash(key);
-    let i = hash & directory.global_depth_mask();
+/// The header uses the bits of the hash above the ones that the directories use
+fn header_index(hash: usize, header: &Directory) -> usize {
+    (hash >> MAX_DEPTH) & header.global_depth_mask()
+}
+
+fn directory_index(hash: usize, directory: &Directory) -> usize {
+    hash & directory.global_depth_mask()
+}
+
+/// Checks that every page of a directory is at most as deep as the directory, that the indexes
+/// that share the low bits of a page's local depth all point to it and that no others do, and that
+/// the directory isn't any deeper than its pages need
+#[cfg(test)]
+fn verify_directory(directory: &Directory) {
+    let global_depth = directory.global_depth();
+    assert!(global_depth <= MAX_DEPTH, "directory deeper than the maximum");
+
+    let mut pages = HashMap::new();
+    for i in 0..PAGE_IDS_SIZE_U32 {
+        let (id, depth) = (directory.get(i), directory.local_depth(i));
+        assert!(depth <= global_depth, "page {id} deeper than its directory");
+        assert_ne!(0, id, "index {i} doesn't point to a page");
+
+        let first = i & directory.local_depth_mask(i);
+        assert_eq!(id, directory.get(first), "index {i} points to the wrong page");
+        assert_eq!(depth, directory.local_depth(first), "index {i} has the wrong depth");
+        assert_eq!(first, *pages.entry(id).or_insert(first), "page {id} has two sets of indexes");
+    }
+
+    let deepest = (0..1 << global_depth).map(|i| directory.local_depth(i)).max();
+    assert_eq!(Some(global_depth), deepest, "directory could shrink");
+}
 
-    i % directory::PAGE_IDS_SIZE_U32
+/// Returns the distinct pages that a directory points to
+fn page_ids(directory: &Directory) -> Vec<PageID> {
+    let mut ids = (0..PAGE_IDS_SIZE_U32).map(|i| directory.get(i)).collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    ids.retain(|id| *id != 0);
+    ids
 }
 
 #[cfg(test)]
 mod test {
     use rand::seq::SliceRandom;
     use rand::thread_rng;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
 
     use crate::catalog::schema::Schema;
     use crate::disk::Memory;
     use crate::hash_table::bucket::BITMAP_SIZE;
-    use crate::hash_table::directory::Directory;
-    use crate::hash_table::extendible::ExtendibleHashTable;
+    use crate::hash_table::directory::{Directory, PAGE_IDS_SIZE_U32};
+    use crate::hash_table::extendible::{hash, ExtendibleHashTable};
+    use crate::header::{Header, HEADER_PAGE_ID};
     use crate::page::{DiskObject, PAGE_SIZE};
     use crate::page_cache::PageCache;
     use crate::replacer::LRU;
     use crate::schema;
     use crate::table::tuple::{Builder as TupleBuilder, Data as TupleData};
+    use crate::wal::MemoryLog;
 
     #[test]
     fn test_extendible_hash_table() -> crate::Result<()> {
@@ -173,14 +711,12 @@ mod test {
         let table = ExtendibleHashTable::new(dir.id, pm.clone(), &key_schema);
 
         const BOUND: i32 = 50;
-        let mut pairs: Vec<(TupleData, i32)> = (-BOUND..BOUND)
-            .into_iter()
-            .map(|n| (TupleBuilder::new().int(n).build(), n + 10))
-            .collect();
+        let mut pairs: Vec<(TupleData, i32)> =
+            (-BOUND..BOUND).map(|n| (TupleBuilder::new().int(n).build(), n + 10)).collect();
         pairs.shuffle(&mut thread_rng());
 
         for (key, value) in &pairs {
-            table.insert(key.clone(), value.clone())?;
+            table.insert(key.clone(), *value)?;
         }
 
         let remove = rand::random::<usize>() % pairs.len();
@@ -210,6 +746,486 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_many_splits() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 128;
+        const K: usize = 2;
+
+        let disk = Memory::new::<MEMORY>();
+        let replacer = LRU::new(K);
+        let pm = PageCache::new(disk, replacer, 0);
+
+        let key_schema = schema! { c1 Int };
+        let table = ExtendibleHashTable::create(pm.clone(), &key_schema)?;
+        let key = |n: i32| TupleBuilder::new().int(n).build();
+
+        // Every key has two values
+        const KEYS: i32 = 5000;
+        for n in 0..KEYS {
+            table.insert(key(n), n)?;
+            table.insert(key(n), n + KEYS)?;
+        }
+        assert!(table.get_num_buckets()? > 16);
+        table.verify_integrity()?;
+
+        for n in (0..KEYS).step_by(2) {
+            assert!(table.remove(&key(n), &n)?);
+        }
+        table.verify_integrity()?;
+
+        for n in 0..KEYS {
+            let mut have = table.get(&key(n))?;
+            have.sort();
+            let want = if n % 2 == 0 { vec![n + KEYS] } else { vec![n, n + KEYS] };
+            assert_eq!(want, have, "key {n}");
+        }
+
+        // Removing every pair merges the buckets back into one
+        for n in 0..KEYS {
+            if n % 2 == 1 {
+                assert!(table.remove(&key(n), &n)?);
+            }
+            assert!(table.remove(&key(n), &(n + KEYS))?);
+            assert!(!table.remove(&key(n), &(n + KEYS))?);
+        }
+        assert_eq!(1, table.verify_integrity()?);
+        assert_eq!(1, table.get_num_buckets()?);
+        assert!(table.get(&key(1))?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 64;
+        const K: usize = 2;
+
+        let disk = Memory::new::<MEMORY>();
+        let pm = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+
+        let key_schema = schema! { c1 Int };
+        let table = ExtendibleHashTable::create(pm.clone(), &key_schema)?;
+        let key = |n: i32| TupleBuilder::new().int(n).build();
+
+        // A bucket holds 329 pairs, so one key's values spill onto a chain of overflow pages,
+        // whilst the other keys still split the buckets as usual
+        const VALUES: i32 = 1000;
+        for n in 0..VALUES {
+            table.insert(key(0), n)?;
+            table.insert(key(n + 1), n)?;
+        }
+        table.verify_integrity()?;
+        let mut have = table.get(&key(0))?;
+        have.sort();
+        assert_eq!((0..VALUES).collect::<Vec<_>>(), have);
+        assert_eq!(vec![VALUES - 1], table.get(&key(VALUES))?);
+
+        // Removing from the bucket itself pulls its first overflow page in, and emptied overflow
+        // pages are unlinked
+        for n in (0..VALUES).rev().step_by(2).chain((0..VALUES).step_by(2)) {
+            assert!(table.remove(&key(0), &n)?, "value {n}");
+            table.verify_integrity()?;
+        }
+        assert!(table.get(&key(0))?.is_empty());
+        for n in 0..VALUES {
+            assert!(table.remove(&key(n + 1), &n)?);
+        }
+        assert_eq!(1, table.verify_integrity()?);
+
+        // Every page but the file's header and the free map is given back
+        table.deallocate()?;
+        let pin = pm.fetch_page(HEADER_PAGE_ID)?;
+        let header = pin.read_object::<Header>(&Schema::default());
+        assert_eq!(header.next_page_id - 2, header.free_pages as i32);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash() {
+        // Pairs are stored by their hash, so it can't change without a new file format
+        let have = [0, 1, -1].map(|n| hash(&TupleBuilder::new().int(n).build()) as u64);
+        assert_eq!([17981696736247539396, 8927165791813088551, 11437205515091215108], have);
+    }
+
+    #[test]
+    fn test_split_directories() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 8192;
+        const K: usize = 2;
+
+        let disk = Memory::new::<MEMORY>();
+        let pm = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+        let header_depth = |id| -> crate::Result<u32> {
+            let header = pm.fetch_page(id)?;
+            let header = header.read_object::<Directory>(&Schema::default());
+            Ok(header.global_depth())
+        };
+
+        // Wide keys keep the buckets small, so that a single directory of full buckets holds fewer
+        // than 512 * 45 pairs
+        let key_schema = schema! {
+            c1 Int, c2 BigInt, c3 BigInt, c4 BigInt, c5 BigInt, c6 BigInt, c7 BigInt, c8 BigInt,
+            c9 BigInt, c10 BigInt, c11 BigInt
+        };
+        let key = |n: i32| {
+            let b = TupleBuilder::new().int(n).big_int(n.into());
+            (0..9).fold(b, |b, _| b.big_int(0)).build()
+        };
+        let table = ExtendibleHashTable::create(pm.clone(), &key_schema)?;
+
+        const KEYS: i32 = 40_000;
+        for n in 0..KEYS {
+            table.insert(key(n), n)?;
+        }
+        assert!(table.get_num_buckets()? > PAGE_IDS_SIZE_U32 as u32);
+        assert!(table.verify_integrity()? > PAGE_IDS_SIZE_U32);
+        assert!(header_depth(table.id())? > 0);
+
+        for n in (0..KEYS).step_by(3) {
+            assert!(table.remove(&key(n), &n)?);
+        }
+        table.verify_integrity()?;
+        for n in 0..KEYS {
+            let want = if n % 3 == 0 { vec![] } else { vec![n] };
+            assert_eq!(want, table.get(&key(n))?, "key {n}");
+        }
+
+        // Every page is freed, so they're all reused by the next table. Freeing them extends the
+        // free map by a page.
+        let pages = pm.new_page()?.id;
+        table.deallocate()?;
+        let table = ExtendibleHashTable::create(pm.clone(), &key_schema)?;
+        for n in 0..KEYS {
+            table.insert(key(n), n)?;
+        }
+        assert_eq!(pages + 2, pm.new_page()?.id);
+
+        // Emptying the table merges the directories back together, and frees their pages
+        for n in 0..KEYS {
+            assert!(table.remove(&key(n), &n)?, "key {n}");
+        }
+        assert_eq!(1, table.verify_integrity()?);
+        assert_eq!(0, header_depth(table.id())?);
+        assert!(pm.new_page()?.id < pages);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_inserts() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 4096;
+        const K: usize = 2;
+        const WRITERS: i32 = 8;
+        const READERS: usize = 4;
+        const KEYS: i32 = 4000;
+        let disk = Memory::new::<MEMORY>();
+        let pc = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+
+        // Wide keys keep the buckets small, so the writers split directories as well as buckets
+        let key_schema = schema! {
+            c1 Int, c2 BigInt, c3 BigInt, c4 BigInt, c5 BigInt, c6 BigInt, c7 BigInt, c8 BigInt,
+            c9 BigInt, c10 BigInt, c11 BigInt
+        };
+        let key = |n: i32| {
+            let b = TupleBuilder::new().int(n).big_int(n.into());
+            (0..9).fold(b, |b, _| b.big_int(0)).build()
+        };
+        let table = ExtendibleHashTable::create(pc.clone(), &key_schema)?;
+
+        // The readers look for keys that are inserted up front whilst the writers add the rest
+        let keep = (0..KEYS * WRITERS).step_by(37).collect::<Vec<_>>();
+        for k in &keep {
+            table.insert(key(*k), *k)?;
+        }
+
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| -> crate::Result<()> {
+            let readers = (0..READERS)
+                .map(|_| {
+                    let (table, keep, done, key) = (&table, &keep, &done, &key);
+                    s.spawn(move || -> crate::Result<()> {
+                        while !done.load(Ordering::Relaxed) {
+                            for k in keep {
+                                assert_eq!(vec![*k], table.get(&key(*k))?, "key {k}");
+                            }
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let (table, keep, key) = (&table, &keep, &key);
+                    s.spawn(move || -> crate::Result<()> {
+                        let mut keys = (0..KEYS)
+                            .map(|i| i * WRITERS + w)
+                            .filter(|k| keep.binary_search(k).is_err())
+                            .collect::<Vec<_>>();
+                        keys.shuffle(&mut thread_rng());
+                        for k in keys {
+                            table.insert(key(k), k)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+            done.store(true, Ordering::Relaxed);
+            for reader in readers {
+                reader.join().unwrap()?;
+            }
+
+            Ok(())
+        })?;
+
+        assert!(table.verify_integrity()? > PAGE_IDS_SIZE_U32);
+        for k in 0..KEYS * WRITERS {
+            assert_eq!(vec![k], table.get(&key(k))?, "key {k}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_removes() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 1024;
+        const K: usize = 2;
+        const WRITERS: i32 = 8;
+        const READERS: usize = 4;
+        const KEYS: i32 = 2000;
+        let disk = Memory::new::<MEMORY>();
+        let pc = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+
+        let key_schema = schema! { c1 Int };
+        let key = |n: i32| TupleBuilder::new().int(n).build();
+        let table = ExtendibleHashTable::create(pc.clone(), &key_schema)?;
+
+        let mut keys = (0..KEYS * WRITERS).collect::<Vec<_>>();
+        keys.shuffle(&mut thread_rng());
+        for k in &keys {
+            table.insert(key(*k), *k)?;
+        }
+        let buckets = table.verify_integrity()?;
+
+        // Every writer removes its own keys and inserts some of them back, whilst the readers
+        // look for the keys that are never touched
+        let keep = (0..KEYS * WRITERS).step_by(37).collect::<Vec<_>>();
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| -> crate::Result<()> {
+            let readers = (0..READERS)
+                .map(|_| {
+                    let (table, keep, done, key) = (&table, &keep, &done, &key);
+                    s.spawn(move || -> crate::Result<()> {
+                        while !done.load(Ordering::Relaxed) {
+                            for k in keep {
+                                assert_eq!(vec![*k], table.get(&key(*k))?, "key {k}");
+                            }
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let (table, keep, key) = (&table, &keep, &key);
+                    s.spawn(move || -> crate::Result<()> {
+                        let mut keys = (0..KEYS)
+                            .map(|i| i * WRITERS + w)
+                            .filter(|k| keep.binary_search(k).is_err())
+                            .collect::<Vec<_>>();
+                        keys.shuffle(&mut thread_rng());
+                        for k in &keys {
+                            assert!(table.remove(&key(*k), k)?, "key {k}");
+                            assert!(table.get(&key(*k))?.is_empty(), "key {k}");
+                        }
+                        for k in keys.iter().filter(|k| *k % 3 == 0) {
+                            table.insert(key(*k), *k)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+            done.store(true, Ordering::Relaxed);
+            for reader in readers {
+                reader.join().unwrap()?;
+            }
+
+            Ok(())
+        })?;
+
+        table.verify_integrity()?;
+        for k in 0..KEYS * WRITERS {
+            let want = if k % 3 == 0 || keep.binary_search(&k).is_ok() { vec![k] } else { vec![] };
+            assert_eq!(want, table.get(&key(k))?, "key {k}");
+        }
+
+        // Emptying the table from every writer at once merges it back into a single bucket
+        std::thread::scope(|s| -> crate::Result<()> {
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let (table, key) = (&table, &key);
+                    s.spawn(move || -> crate::Result<()> {
+                        for k in (0..KEYS).map(|i| i * WRITERS + w) {
+                            for v in table.get(&key(k))? {
+                                assert!(table.remove(&key(k), &v)?, "key {k}");
+                            }
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+
+            Ok(())
+        })?;
+        assert!(buckets > 1);
+        assert_eq!(1, table.verify_integrity()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_rollback() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 1024;
+        const K: usize = 2;
+        const WRITERS: i32 = 4;
+        const KEYS: i32 = 2000;
+        let disk = Memory::new::<MEMORY>();
+        let pc = PageCache::open(disk, MemoryLog::new(), LRU::new(K))?;
+
+        let key_schema = schema! { c1 Int };
+        let key = |n: i32| TupleBuilder::new().int(n).build();
+        let table = ExtendibleHashTable::create(pc.clone(), &key_schema)?;
+
+        // Every key past the writers' is left to the transactions that roll back, so they share
+        // buckets with the keys that are committed
+        let stride = WRITERS + 1;
+        let base = (0..KEYS).map(|i| -(i + 1)).collect::<Vec<_>>();
+        for k in &base {
+            table.insert(key(*k), *k)?;
+        }
+
+        std::thread::scope(|s| -> crate::Result<()> {
+            let writers = (0..WRITERS)
+                .map(|w| {
+                    let (table, key) = (&table, &key);
+                    s.spawn(move || -> crate::Result<()> {
+                        let mut keys = (0..KEYS).map(|i| i * stride + w).collect::<Vec<_>>();
+                        keys.shuffle(&mut thread_rng());
+                        for k in keys {
+                            table.insert(key(k), k)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let (pc, table, key, base) = (&pc, &table, &key, &base);
+            let aborter = s.spawn(move || -> crate::Result<()> {
+                for chunk in (0..KEYS).collect::<Vec<_>>().chunks(50) {
+                    let _txn = pc.begin();
+                    for i in chunk {
+                        let k = i * stride + WRITERS;
+                        table.insert(key(k), k)?;
+                        let b = base[*i as usize];
+                        assert!(table.remove(&key(b), &b)?);
+                    }
+                }
+
+                Ok(())
+            });
+
+            for writer in writers {
+                writer.join().unwrap()?;
+            }
+            aborter.join().unwrap()?;
+
+            Ok(())
+        })?;
+
+        table.verify_integrity()?;
+        for k in (0..KEYS * stride).chain(base.iter().copied()) {
+            let want = if k % stride != WRITERS || k < 0 { vec![k] } else { vec![] };
+            assert_eq!(want, table.get(&key(k))?, "key {k}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_rollback() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 256;
+        const K: usize = 2;
+        const KEYS: i32 = 3000;
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+        let key_schema = schema! { c1 Int };
+        let key = |n: i32| TupleBuilder::new().int(n).build();
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let table = ExtendibleHashTable::create(pc.clone(), &key_schema)?;
+            id = table.id();
+            for k in (0..KEYS).step_by(3) {
+                table.insert(key(k), k)?;
+            }
+
+            // The transaction never finishes, and the pairs committed after it land in the
+            // buckets it changed
+            let txn = pc.begin();
+            for k in (1..KEYS).step_by(3) {
+                table.insert(key(k), k)?;
+            }
+            for k in (0..KEYS).step_by(6) {
+                assert!(table.remove(&key(k), &k)?);
+            }
+
+            std::thread::scope(|s| {
+                s.spawn(|| -> crate::Result<()> {
+                    for k in (2..KEYS).step_by(3) {
+                        table.insert(key(k), k)?;
+                    }
+
+                    Ok(())
+                })
+                .join()
+                .unwrap()
+            })?;
+
+            pc.flush_all_pages()?;
+            std::mem::forget(txn);
+        }
+
+        let pc = PageCache::open(disk, log, LRU::new(K))?;
+        let table = ExtendibleHashTable::<i32>::new(id, pc.clone(), &key_schema);
+        table.verify_integrity()?;
+        for k in 0..KEYS {
+            let want = if k % 3 != 1 { vec![k] } else { vec![] };
+            assert_eq!(want, table.get(&key(k))?, "key {k}");
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_split() -> crate::Result<()> {
         const MEMORY: usize = PAGE_SIZE * 4;
@@ -223,13 +1239,13 @@ mod test {
         let key_schema = schema! { c1 Int };
         let table = ExtendibleHashTable::new(dir.id, pm.clone(), &key_schema);
 
-        assert_eq!(table.get_num_buckets().unwrap(), 1);
+        assert_eq!(table.get_num_buckets().unwrap(), 0);
 
         // (key = i32, value = usize) = 12 bytes
-        // (4096 - 128) / 12 = 330
+        // (4088 - 132) / 12 = 329
         for (k, v) in (0..BITMAP_SIZE as i32 * 8)
             .zip(0..BITMAP_SIZE * 8)
-            .take(330)
+            .take(329)
             .map(|(n, value)| (TupleBuilder::new().int(n).build(), value))
         {
             table.insert(k, v).unwrap();
@@ -237,7 +1253,11 @@ mod test {
 
         assert_eq!(table.get_num_buckets().unwrap(), 2);
 
-        let dir_page = pm.fetch_page(0).expect("there should be a page 0");
+        let header_page = pm.fetch_page(0).expect("there should be a page 0");
+        let header = Directory::deserialise(header_page.write().data, &Schema::default());
+        assert_eq!(header.global_depth(), 0);
+
+        let dir_page = pm.fetch_page(header.get(0))?;
         let dir_w = dir_page.write();
         let dir = Directory::deserialise(dir_w.data, &Schema::default());
 
@@ -0,0 +1,155 @@
+use crate::catalog::schema::Schema;
+use crate::page::{DiskObject, PageBuf, PageID, PAGE_SIZE};
+use crate::wal::{checksum, LSN};
+
+// Header:
+// | Magic (8) | Version (4) | PageSize (4) | NextPageID (4) | CatalogRoot (4) | FreeMap (4) |
+// | FreePages (4) | NextLSN (8) | Checksum (4) |
+//
+// The header always lives on the first page of the file. An all zero header means the file is new.
+pub const HEADER_PAGE_ID: PageID = 0;
+pub const MAGIC: &[u8; 8] = b"base\0db\0";
+pub const VERSION: u32 = 11;
+
+const MAGIC_RANGE: std::ops::Range<usize> = 0..8;
+const VERSION_RANGE: std::ops::Range<usize> = 8..12;
+const PAGE_SIZE_RANGE: std::ops::Range<usize> = 12..16;
+const NEXT_PAGE_ID_RANGE: std::ops::Range<usize> = 16..20;
+const CATALOG_ROOT_RANGE: std::ops::Range<usize> = 20..24;
+const FREE_MAP_RANGE: std::ops::Range<usize> = 24..28;
+const FREE_PAGES_RANGE: std::ops::Range<usize> = 28..32;
+const NEXT_LSN_RANGE: std::ops::Range<usize> = 32..40;
+const CHECKSUM_RANGE: std::ops::Range<usize> = 40..44;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Header {
+    pub version: u32,
+    pub page_size: u32,
+    pub next_page_id: PageID,
+    /// First page of the catalog's system tables, -1 until the catalog has been created
+    pub catalog_root: PageID,
+    /// First page of the free map, -1 until a page has been deallocated
+    pub free_map: PageID,
+    /// Number of pages marked as free in the free map
+    pub free_pages: u32,
+    /// The LSN that the log carries on from once it's been discarded
+    pub next_lsn: LSN,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            page_size: PAGE_SIZE as u32,
+            next_page_id: HEADER_PAGE_ID + 1,
+            catalog_root: -1,
+            free_map: -1,
+            free_pages: 0,
+            next_lsn: 1,
+        }
+    }
+}
+
+impl DiskObject for Header {
+    fn serialise(&self) -> PageBuf {
+        let mut ret = [0; PAGE_SIZE];
+
+        ret[MAGIC_RANGE].copy_from_slice(MAGIC);
+        ret[VERSION_RANGE].copy_from_slice(&self.version.to_be_bytes());
+        ret[PAGE_SIZE_RANGE].copy_from_slice(&self.page_size.to_be_bytes());
+        ret[NEXT_PAGE_ID_RANGE].copy_from_slice(&self.next_page_id.to_be_bytes());
+        ret[CATALOG_ROOT_RANGE].copy_from_slice(&self.catalog_root.to_be_bytes());
+        ret[FREE_MAP_RANGE].copy_from_slice(&self.free_map.to_be_bytes());
+        ret[FREE_PAGES_RANGE].copy_from_slice(&self.free_pages.to_be_bytes());
+        ret[NEXT_LSN_RANGE].copy_from_slice(&self.next_lsn.to_be_bytes());
+        let checksum = checksum(&ret[..CHECKSUM_RANGE.start]);
+        ret[CHECKSUM_RANGE].copy_from_slice(&checksum.to_be_bytes());
+
+        ret
+    }
+
+    fn deserialise(buf: PageBuf, _: &Schema) -> Self {
+        Self {
+            version: u32::from_be_bytes(buf[VERSION_RANGE].try_into().unwrap()),
+            page_size: u32::from_be_bytes(buf[PAGE_SIZE_RANGE].try_into().unwrap()),
+            next_page_id: i32::from_be_bytes(buf[NEXT_PAGE_ID_RANGE].try_into().unwrap()),
+            catalog_root: i32::from_be_bytes(buf[CATALOG_ROOT_RANGE].try_into().unwrap()),
+            free_map: i32::from_be_bytes(buf[FREE_MAP_RANGE].try_into().unwrap()),
+            free_pages: u32::from_be_bytes(buf[FREE_PAGES_RANGE].try_into().unwrap()),
+            next_lsn: u64::from_be_bytes(buf[NEXT_LSN_RANGE].try_into().unwrap()),
+        }
+    }
+}
+
+impl Header {
+    /// Reads the header from the first page of a file. `None` is returned if the file is new.
+    pub fn validate(buf: &PageBuf) -> Result<Option<Self>, String> {
+        if buf.iter().all(|b| *b == 0) {
+            return Ok(None);
+        }
+
+        if &buf[MAGIC_RANGE] != MAGIC {
+            Err("file is not a database")?
+        }
+
+        let want = u32::from_be_bytes(buf[CHECKSUM_RANGE].try_into().unwrap());
+        if checksum(&buf[..CHECKSUM_RANGE.start]) != want {
+            Err("header checksum mismatch, the file is corrupt")?
+        }
+
+        let header = Self::deserialise(*buf, &Schema::default());
+        if header.version != VERSION {
+            Err(format!("unsupported format version {}, expected {VERSION}", header.version))?
+        }
+
+        if header.page_size != PAGE_SIZE as u32 {
+            Err(format!("unsupported page size {}, expected {PAGE_SIZE}", header.page_size))?
+        }
+
+        if header.next_page_id <= HEADER_PAGE_ID {
+            Err(format!("invalid next page id {}", header.next_page_id))?
+        }
+
+        Ok(Some(header))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::header::{Header, MAGIC, VERSION};
+    use crate::page::{DiskObject, PAGE_SIZE};
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Ok(None), Header::validate(&[0; PAGE_SIZE]));
+
+        let want = Header {
+            next_page_id: 10,
+            catalog_root: 1,
+            free_map: 4,
+            free_pages: 3,
+            next_lsn: 42,
+            ..Default::default()
+        };
+        let buf = want.serialise();
+        assert_eq!(Ok(Some(want)), Header::validate(&buf));
+
+        let mut bad_magic = buf;
+        bad_magic[0] = b'x';
+        assert!(Header::validate(&bad_magic).is_err());
+
+        let mut corrupt = buf;
+        corrupt[20] ^= 1;
+        assert!(Header::validate(&corrupt).is_err());
+
+        let newer = Header { version: VERSION + 1, ..want }.serialise();
+        assert_eq!(
+            Err(format!("unsupported format version {}, expected {VERSION}", VERSION + 1)),
+            Header::validate(&newer)
+        );
+
+        let mut not_ours = [0; PAGE_SIZE];
+        not_ours[..MAGIC.len()].copy_from_slice(b"SQLite f");
+        assert_eq!(Err("file is not a database".to_string()), Header::validate(&not_ours));
+    }
+}
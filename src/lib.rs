@@ -4,7 +4,9 @@ pub mod catalog;
 pub mod disk;
 pub mod evaluation;
 pub mod execution;
+pub mod free_map;
 pub mod hash_table;
+pub mod header;
 pub mod logical_plan;
 pub mod optimiser;
 pub mod page;
@@ -16,6 +18,7 @@ pub mod replacer;
 pub mod sql;
 pub mod storable;
 pub mod table;
+pub mod wal;
 
 pub use page_cache::Result;
 
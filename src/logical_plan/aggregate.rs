@@ -1,20 +1,32 @@
-use crate::logical_plan::{write_iter, Expr, Function, LogicalOperator};
+use crate::catalog::schema::{Column, Schema, SchemaBuilder};
+use crate::column;
+use crate::logical_plan::{
+    expr_type, resolve, write_iter, Expr, Function, LogicalOperator, LogicalOperatorError,
+};
 
+/// Groups the input by `keys` and computes each of the aggregate `functions` for every group. The
+/// output has a column for each key followed by a column for each function, named after the
+/// expression so that later operators can refer to them.
 pub struct Aggregate {
-    pub function: Function,
     pub keys: Vec<Expr>,
+    pub functions: Vec<Function>,
+    pub schema: Schema,
     pub input: Box<LogicalOperator>,
 }
 
 impl std::fmt::Display for Aggregate {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Aggregate [{}]", self.function)?;
+        write!(f, "Aggregate [")?;
+        write_iter(f, &mut self.functions.iter(), ", ")?;
+        write!(f, "]")?;
+
         if !self.keys.is_empty() {
             write!(f, " keys:[")?;
-            write_iter(f, &mut self.keys.iter(), ",")?;
+            write_iter(f, &mut self.keys.iter(), ", ")?;
+            write!(f, "]")?;
         }
 
-        write!(f, "]")
+        Ok(())
     }
 }
 
@@ -25,7 +37,34 @@ impl From<Aggregate> for LogicalOperator {
 }
 
 impl Aggregate {
-    pub fn new(function: Function, keys: Vec<Expr>, input: impl Into<LogicalOperator>) -> Self {
-        Self { input: Box::new(input.into()), function, keys }
+    pub fn new(
+        keys: Vec<Expr>,
+        functions: Vec<Function>,
+        input: impl Into<LogicalOperator>,
+    ) -> Result<Self, LogicalOperatorError> {
+        let input = Box::new(input.into());
+        let input_schema = input.schema();
+
+        let mut schema = SchemaBuilder::new();
+        for key in &keys {
+            let column = match key {
+                Expr::Ident(ident) => {
+                    Column { hidden: false, ..resolve(ident, input_schema)?.clone() }
+                }
+                expr => column!(expr.to_string() => expr_type(expr, input_schema)?),
+            };
+            schema.append(column);
+        }
+
+        for function in &functions {
+            if !function.name.is_aggregate() {
+                Err(format!("not an aggregate function: {}", function.name))?
+            }
+
+            let ty = expr_type(&Expr::Function(function.clone()), input_schema)?;
+            schema.append(column!(function.to_string() => ty));
+        }
+
+        Ok(Self { keys, functions, schema: schema.build(), input })
     }
 }
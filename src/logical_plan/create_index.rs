@@ -0,0 +1,43 @@
+use crate::catalog::{schema::Schema, IndexType};
+use crate::logical_plan::{write_iter, LogicalOperator};
+use crate::schema;
+
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub index_ty: IndexType,
+    pub unique: bool,
+    pub schema: Schema,
+}
+
+impl std::fmt::Display for CreateIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CreateIndex index={} table={} type={}", self.name, self.table, self.index_ty)?;
+        if self.unique {
+            write!(f, " unique")?;
+        }
+
+        write!(f, " key=[")?;
+        write_iter(f, &mut self.columns.iter(), ", ")?;
+        write!(f, "]")
+    }
+}
+
+impl From<CreateIndex> for LogicalOperator {
+    fn from(create_index: CreateIndex) -> Self {
+        Self::CreateIndex(create_index)
+    }
+}
+
+impl CreateIndex {
+    pub fn new(
+        name: String,
+        table: String,
+        columns: Vec<String>,
+        index_ty: IndexType,
+        unique: bool,
+    ) -> Self {
+        Self { name, table, columns, index_ty, unique, schema: schema! { ok Int } }
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use crate::{
+    catalog::{schema::Schema, TableInfo},
+    logical_plan::LogicalOperator,
+    schema,
+    sql::Expr,
+};
+
+pub struct Delete {
+    pub table: Arc<TableInfo>,
+    pub filter: Option<Expr>,
+    pub schema: Schema,
+}
+
+impl std::fmt::Display for Delete {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Delete table={} oid={}", self.table.name, self.table.oid)?;
+
+        if let Some(filter) = &self.filter {
+            write!(f, " filter=[{filter}]")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Delete> for LogicalOperator {
+    fn from(delete: Delete) -> Self {
+        Self::Delete(delete)
+    }
+}
+
+impl Delete {
+    pub fn new(table: Arc<TableInfo>, filter: Option<Expr>) -> Self {
+        Self { table, filter, schema: schema! { count Int } }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::catalog::schema::Schema;
+use crate::logical_plan::LogicalOperator;
+use crate::schema;
+
+pub struct DropIndex {
+    pub name: String,
+    pub schema: Schema,
+}
+
+impl std::fmt::Display for DropIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DropIndex index={}", self.name)
+    }
+}
+
+impl From<DropIndex> for LogicalOperator {
+    fn from(drop_index: DropIndex) -> Self {
+        Self::DropIndex(drop_index)
+    }
+}
+
+impl DropIndex {
+    pub fn new(name: String) -> Self {
+        Self { name, schema: schema! { ok Int } }
+    }
+}
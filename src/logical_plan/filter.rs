@@ -1,4 +1,4 @@
-use crate::logical_plan::{Expr, LogicalOperator};
+use crate::logical_plan::{check_ambiguous, Expr, LogicalOperator, LogicalOperatorError};
 
 pub struct Filter {
     pub expr: Expr,
@@ -20,7 +20,13 @@ impl From<Filter> for LogicalOperator {
 }
 
 impl Filter {
-    pub fn new(expr: Expr, input: impl Into<LogicalOperator>) -> Self {
-        Self { expr, input: Box::new(input.into()) }
+    pub fn new(
+        expr: Expr,
+        input: impl Into<LogicalOperator>,
+    ) -> Result<Self, LogicalOperatorError> {
+        let input = Box::new(input.into());
+        check_ambiguous(&expr, input.schema())?;
+
+        Ok(Self { expr, input })
     }
 }
@@ -1,13 +1,21 @@
-use crate::catalog::schema::Schema;
-use crate::logical_plan::{write_iter, LogicalOperator, LogicalOperatorError};
-use crate::sql::{Expr, Ident};
+use crate::catalog::schema::{Schema, SchemaBuilder, Type};
+use crate::column;
+use crate::logical_plan::{
+    check_ambiguous, resolve, write_iter, LogicalOperator, LogicalOperatorError,
+};
+use crate::sql::{Expr, Ident, JoinType};
 
 pub enum JoinConstraint {
     On(Expr),
     Using(Vec<Ident>),
+    None,
 }
 
+/// For USING joins, the schema starts with a merged column for each key, followed by the columns of
+/// the left and right inputs. The key columns of the inputs are hidden, so they can only be
+/// referenced by their qualified names.
 pub struct Join {
+    pub ty: JoinType,
     pub constraint: JoinConstraint,
     pub schema: Schema,
     pub left_input: Box<LogicalOperator>,
@@ -16,14 +24,18 @@ pub struct Join {
 
 impl std::fmt::Display for Join {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Join ")?;
+        write!(f, "Join")?;
+        if self.ty != JoinType::Inner {
+            write!(f, " {}", self.ty)?;
+        }
         match &self.constraint {
-            JoinConstraint::On(expr) => write!(f, "ON {}", expr),
+            JoinConstraint::On(expr) => write!(f, " ON {}", expr),
             JoinConstraint::Using(columns) => {
-                write!(f, "USING (")?;
+                write!(f, " USING (")?;
                 write_iter(f, &mut columns.iter(), ", ")?;
                 write!(f, ")")
             }
+            JoinConstraint::None => Ok(()),
         }
     }
 }
@@ -36,50 +48,109 @@ impl From<Join> for LogicalOperator {
 
 impl Join {
     fn new(
+        ty: JoinType,
         constraint: JoinConstraint,
+        schema: Schema,
         left_input: LogicalOperator,
         right_input: LogicalOperator,
     ) -> Self {
         let left_input = Box::new(left_input);
         let right_input = Box::new(right_input);
-        let schema = left_input.schema().join(right_input.schema());
-        Self { constraint, schema, left_input, right_input }
+        Self { ty, constraint, schema, left_input, right_input }
     }
 
     pub fn on(
+        ty: JoinType,
         expr: Expr,
         left_input: impl Into<LogicalOperator>,
         right_input: impl Into<LogicalOperator>,
     ) -> Result<Self, LogicalOperatorError> {
         let left_input = left_input.into();
         let right_input = right_input.into();
+        let schema = left_input.schema().join(right_input.schema());
+        check_ambiguous(&expr, &schema)?;
 
-        // TODO: validate expr
-
-        Ok(Self::new(JoinConstraint::On(expr), left_input, right_input))
+        Ok(Self::new(ty, JoinConstraint::On(expr), schema, left_input, right_input))
     }
 
     pub fn using(
+        ty: JoinType,
         columns: Vec<Ident>,
         left_input: impl Into<LogicalOperator>,
         right_input: impl Into<LogicalOperator>,
     ) -> Result<Self, LogicalOperatorError> {
         let left_input = left_input.into();
         let right_input = right_input.into();
+        let mut left_schema = left_input.schema().clone();
+        let mut right_schema = right_input.schema().clone();
+
+        let mut schema = SchemaBuilder::new();
         for column in &columns {
-            // Assuming the identifiers only have one part
-            if left_input.schema().find_column_by_name(&column[0]).is_none() {
-                Err(format!("unknown column: {column}"))?
-            };
+            if let Ident::Compound(_) = column {
+                Err(format!("qualified column in USING: {column}"))?
+            }
 
-            if right_input.schema().find_column_by_name(&column[0]).is_none() {
-                Err(format!("unknown column: {column}"))?
+            let ty = match (hide(column, &mut left_schema)?, hide(column, &mut right_schema)?) {
+                (left, right) if left == right => left,
+                (
+                    left @ (Type::TinyInt | Type::Int | Type::BigInt),
+                    right @ (Type::TinyInt | Type::Int | Type::BigInt),
+                ) => {
+                    if left.size() > right.size() {
+                        left
+                    } else {
+                        right
+                    }
+                }
+                (left, right) => Err(format!("cannot join {column} of type {left} with {right}"))?,
             };
+            schema.append(column!(column.to_string() => ty));
+        }
+        schema.append_n(left_schema.columns).append_n(right_schema.columns);
 
-            // TODO: using columns aren't qualified so `schema.join()` should be updated or a new method
-            // created
+        let constraint = JoinConstraint::Using(columns);
+        Ok(Self::new(ty, constraint, schema.build(), left_input, right_input))
+    }
+
+    /// A USING join on every column name that appears in both inputs
+    pub fn natural(
+        ty: JoinType,
+        left_input: impl Into<LogicalOperator>,
+        right_input: impl Into<LogicalOperator>,
+    ) -> Result<Self, LogicalOperatorError> {
+        let left_input = left_input.into();
+        let right_input = right_input.into();
+
+        let mut columns: Vec<Ident> = Vec::new();
+        for column in left_input.schema().iter().filter(|column| !column.hidden) {
+            let ident = Ident::Single(column.name.clone());
+            if right_input.schema().find_column_by_name(&column.name).is_some()
+                && !columns.contains(&ident)
+            {
+                columns.push(ident);
+            }
         }
 
-        Ok(Self::new(JoinConstraint::Using(columns), left_input, right_input))
+        Self::using(ty, columns, left_input, right_input)
+    }
+
+    pub fn cross(
+        left_input: impl Into<LogicalOperator>,
+        right_input: impl Into<LogicalOperator>,
+    ) -> Self {
+        let left_input = left_input.into();
+        let right_input = right_input.into();
+        let schema = left_input.schema().join(right_input.schema());
+
+        Self::new(JoinType::Cross, JoinConstraint::None, schema, left_input, right_input)
     }
 }
+
+/// Hides a USING column in one of the inputs' schemas, returning its type
+fn hide(column: &Ident, schema: &mut Schema) -> Result<Type, LogicalOperatorError> {
+    let position = resolve(column, schema)?.position;
+    let column = schema.columns.iter_mut().find(|column| column.position == position).unwrap();
+    column.hidden = true;
+
+    Ok(column.ty)
+}
@@ -29,6 +29,8 @@ impl Limit {
     ) -> Result<Self, LogicalOperatorError> {
         let mut limit = eval(&expr, &schema! {}, &TupleData::empty())
             .map(|value| match value {
+                // Like LIMIT ALL
+                Value::Null => isize::MAX,
                 Value::TinyInt(limit) => limit as isize,
                 Value::Bool(bool) if bool => 1,
                 Value::Bool(_) => 0,
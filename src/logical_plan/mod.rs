@@ -1,27 +1,33 @@
 use std::sync::Arc;
 
-use crate::catalog::schema::{Schema, Type};
-use crate::catalog::TableInfo;
-use crate::sql::{Expr, Function, FunctionName, Ident, Literal, Op, SelectItem};
+use crate::catalog::schema::{Column, Schema, Type};
+use crate::catalog::{IndexType, TableInfo};
+use crate::sql::{
+    Assignment, Expr, Function, FunctionName, Ident, JoinType, Op, OrderByExpr, SelectItem,
+};
 
 mod aggregate;
 mod create;
+mod create_index;
+mod delete;
+mod drop_index;
 mod explain;
 mod filter;
-mod group;
 mod insert;
 mod join;
 mod limit;
 mod projection;
 mod scan;
 mod sort;
+mod update;
 mod values;
 
+pub use join::JoinConstraint;
 pub use projection::ProjectionAttributes;
 use {
-    aggregate::Aggregate, create::Create, explain::Explain, filter::Filter, group::Group,
-    insert::Insert, join::Join, limit::Limit, projection::Projection, scan::Scan, sort::Sort,
-    values::Values,
+    aggregate::Aggregate, create::Create, create_index::CreateIndex, delete::Delete,
+    drop_index::DropIndex, explain::Explain, filter::Filter, insert::Insert, join::Join,
+    limit::Limit, projection::Projection, scan::Scan, sort::Sort, update::Update, values::Values,
 };
 
 /// The first value will always be Some(..) unless it's a leaf node like Scan.
@@ -58,7 +64,6 @@ impl From<&str> for LogicalOperatorError {
 pub enum LogicalOperator {
     Aggregate(Aggregate),
     Filter(Filter),
-    Group(Group),
     Join(Join),
     Projection(Projection),
     Scan(Scan),
@@ -66,7 +71,11 @@ pub enum LogicalOperator {
     Sort(Sort),
     Values(Values),
     Insert(Insert),
+    Update(Update),
+    Delete(Delete),
     Create(Create),
+    CreateIndex(CreateIndex),
+    DropIndex(DropIndex),
     Explain(Explain),
 }
 
@@ -83,7 +92,6 @@ impl std::fmt::Display for LogicalOperator {
             match plan {
                 LogicalOperator::Aggregate(aggregate) => writeln!(f, "{aggregate}"),
                 LogicalOperator::Filter(filter) => writeln!(f, "{filter}"),
-                LogicalOperator::Group(group) => writeln!(f, "{group}"),
                 LogicalOperator::Join(join) => writeln!(f, "{join}"),
                 LogicalOperator::Projection(projection) => {
                     writeln!(f, "{projection}")
@@ -93,7 +101,11 @@ impl std::fmt::Display for LogicalOperator {
                 LogicalOperator::Sort(sort) => writeln!(f, "{sort}"),
                 LogicalOperator::Values(values) => writeln!(f, "{values}"),
                 LogicalOperator::Insert(insert) => writeln!(f, "{insert}"),
+                LogicalOperator::Update(update) => writeln!(f, "{update}"),
+                LogicalOperator::Delete(delete) => writeln!(f, "{delete}"),
                 LogicalOperator::Create(create) => writeln!(f, "{create}"),
+                LogicalOperator::CreateIndex(create_index) => writeln!(f, "{create_index}"),
+                LogicalOperator::DropIndex(drop_index) => writeln!(f, "{drop_index}"),
                 LogicalOperator::Explain(explain) => writeln!(f, "{explain}"),
             }?;
 
@@ -117,7 +129,6 @@ impl LogicalOperator {
         match self {
             LogicalOperator::Aggregate(aggregate) => (Some(aggregate.input.as_ref()), None),
             LogicalOperator::Filter(filter) => (Some(filter.input.as_ref()), None),
-            LogicalOperator::Group(group) => (Some(group.input.as_ref()), None),
             LogicalOperator::Join(join) => {
                 (Some(join.left_input.as_ref()), Some(join.right_input.as_ref()))
             }
@@ -127,16 +138,19 @@ impl LogicalOperator {
             LogicalOperator::Sort(sort) => (Some(sort.input.as_ref()), None),
             LogicalOperator::Values(_) => (None, None),
             LogicalOperator::Insert(insert) => (Some(insert.input.as_ref()), None),
+            LogicalOperator::Update(_) => (None, None),
+            LogicalOperator::Delete(_) => (None, None),
             LogicalOperator::Create(_) => (None, None),
+            LogicalOperator::CreateIndex(_) => (None, None),
+            LogicalOperator::DropIndex(_) => (None, None),
             LogicalOperator::Explain(_) => (None, None),
         }
     }
 
     pub fn schema(&self) -> &Schema {
         match self {
-            LogicalOperator::Aggregate(aggregate) => aggregate.input.schema(),
+            LogicalOperator::Aggregate(aggregate) => &aggregate.schema,
             LogicalOperator::Filter(filter) => filter.input.schema(),
-            LogicalOperator::Group(group) => group.input.schema(),
             LogicalOperator::Join(join) => &join.schema,
             LogicalOperator::Projection(projection) => projection.attributes.schema(),
             LogicalOperator::Scan(scan) => &scan.schema,
@@ -144,16 +158,19 @@ impl LogicalOperator {
             LogicalOperator::Sort(sort) => sort.input.schema(),
             LogicalOperator::Values(values) => &values.schema,
             LogicalOperator::Insert(insert) => &insert.schema,
+            LogicalOperator::Update(update) => &update.schema,
+            LogicalOperator::Delete(delete) => &delete.schema,
             LogicalOperator::Create(create) => &create.schema,
+            LogicalOperator::CreateIndex(create_index) => &create_index.schema,
+            LogicalOperator::DropIndex(drop_index) => &drop_index.schema,
             LogicalOperator::Explain(explain) => &explain.schema,
         }
     }
 
     pub fn schema_mut(&mut self) -> &mut Schema {
         match self {
-            LogicalOperator::Aggregate(aggregate) => aggregate.input.schema_mut(),
+            LogicalOperator::Aggregate(aggregate) => &mut aggregate.schema,
             LogicalOperator::Filter(filter) => filter.input.schema_mut(),
-            LogicalOperator::Group(group) => group.input.schema_mut(),
             LogicalOperator::Join(join) => &mut join.schema,
             LogicalOperator::Projection(projection) => projection.attributes.schema_mut(),
             LogicalOperator::Scan(scan) => &mut scan.schema,
@@ -161,7 +178,11 @@ impl LogicalOperator {
             LogicalOperator::Sort(sort) => sort.input.schema_mut(),
             LogicalOperator::Values(values) => &mut values.schema,
             LogicalOperator::Insert(insert) => &mut insert.schema,
+            LogicalOperator::Update(update) => &mut update.schema,
+            LogicalOperator::Delete(delete) => &mut delete.schema,
             LogicalOperator::Create(create) => &mut create.schema,
+            LogicalOperator::CreateIndex(create_index) => &mut create_index.schema,
+            LogicalOperator::DropIndex(drop_index) => &mut drop_index.schema,
             LogicalOperator::Explain(explain) => &mut explain.schema,
         }
     }
@@ -182,33 +203,93 @@ fn write_iter<T: std::fmt::Display, I: Iterator<Item = T>>(
     Ok(())
 }
 
-fn expr_type(expr: &Expr, schema: &Schema) -> Result<Type, LogicalOperatorError> {
-    let ty = match expr {
-        Expr::Ident(ident @ Ident::Single(column)) => {
-            schema.find_column_by_name(column).ok_or(format!("unknown column: {ident}"))?.ty
+/// Finds the column an identifier refers to. An unqualified name is ambiguous if more than one
+/// column has it, for example when joined tables have columns with the same name.
+fn resolve<'a>(ident: &Ident, schema: &'a Schema) -> Result<&'a Column, LogicalOperatorError> {
+    let column = match ident {
+        Ident::Single(name) if is_ambiguous(name, schema) => {
+            Err(format!("ambiguous column: {ident}"))?
         }
-        Expr::Ident(ident @ Ident::Compound(idents)) => {
-            schema
-                .find_column_by_name_and_table(&idents[0], &idents[1])
-                .ok_or(format!("unknown column: {ident}"))?
-                .ty
+        Ident::Single(name) => schema.find_column_by_name(name),
+        Ident::Compound(idents) => schema.find_column_by_name_and_table(&idents[0], &idents[1]),
+    };
+
+    Ok(column.ok_or(format!("unknown column: {ident}"))?)
+}
+
+fn is_ambiguous(name: &str, schema: &Schema) -> bool {
+    schema.iter().filter(|column| !column.hidden && column.name == name).count() > 1
+}
+
+/// Checks that none of the unqualified columns referenced by `expr` are ambiguous. Unknown columns
+/// aren't an error here, since filters may refer to aliases in the projection.
+fn check_ambiguous(expr: &Expr, schema: &Schema) -> Result<(), LogicalOperatorError> {
+    match expr {
+        Expr::Ident(ident @ Ident::Single(name)) if is_ambiguous(name, schema) => {
+            Err(format!("ambiguous column: {ident}"))?
         }
-        Expr::Literal(literal) => match literal {
-            Literal::Number(_) => Type::Int,
-            Literal::String(_) => Type::Varchar,
-            Literal::Bool(_) => Type::Bool,
-            Literal::Null | Literal::Decimal(_) => todo!(),
-        },
+        Expr::IsNull { expr, .. } => check_ambiguous(expr, schema)?,
+        Expr::InList { expr, list, .. } => {
+            check_ambiguous(expr, schema)?;
+            for expr in list {
+                check_ambiguous(expr, schema)?;
+            }
+        }
+        Expr::Between { expr, low, high, .. } => {
+            check_ambiguous(expr, schema)?;
+            check_ambiguous(low, schema)?;
+            check_ambiguous(high, schema)?;
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            check_ambiguous(left, schema)?;
+            check_ambiguous(right, schema)?;
+        }
+        Expr::Function(function) => {
+            for arg in &function.args {
+                check_ambiguous(arg, schema)?;
+            }
+        }
+        Expr::Ident(_)
+        | Expr::Literal(_)
+        | Expr::Wildcard
+        | Expr::QualifiedWildcard(_)
+        | Expr::SubQuery(_) => {}
+    }
+
+    Ok(())
+}
+
+fn expr_type(expr: &Expr, schema: &Schema) -> Result<Type, LogicalOperatorError> {
+    let ty = match expr {
+        Expr::Ident(ident) => resolve(ident, schema)?.ty,
+        Expr::Literal(literal) => Type::from(literal),
         Expr::IsNull { .. } | Expr::InList { .. } | Expr::Between { .. } => Type::Bool,
         Expr::BinaryOp { left: _, op, right: _ } => match op {
             Op::Eq | Op::Neq | Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::And | Op::Or => Type::Bool,
         },
         Expr::Function(function) => match function.name {
-            FunctionName::Min => Type::Int,
-            FunctionName::Max => Type::Int,
-            FunctionName::Sum => Type::Int,
-            FunctionName::Avg => Type::Int,
             FunctionName::Count => Type::Int,
+            FunctionName::Min | FunctionName::Max | FunctionName::Sum | FunctionName::Avg => {
+                let [arg] = function.args.as_slice() else {
+                    Err(format!(
+                        "{} expected 1 argument but got {}",
+                        function.name,
+                        function.args.len()
+                    ))?
+                };
+                if let Expr::Wildcard | Expr::QualifiedWildcard(_) = arg {
+                    Err(format!("{} doesn't accept *", function.name))?
+                }
+
+                // Sums are widened so they're less likely to overflow. There is no fractional type,
+                // so averages keep the argument's type and are truncated.
+                match (&function.name, expr_type(arg, schema)?) {
+                    (FunctionName::Min | FunctionName::Max, ty) => ty,
+                    (FunctionName::Sum, Type::TinyInt | Type::Int | Type::BigInt) => Type::BigInt,
+                    (FunctionName::Avg, ty @ (Type::TinyInt | Type::Int | Type::BigInt)) => ty,
+                    (name, ty) => Err(format!("cannot perform {name} on {ty}"))?,
+                }
+            }
             FunctionName::Contains => Type::Bool,
             FunctionName::Concat => Type::Varchar,
         },
@@ -233,6 +314,33 @@ pub fn create(name: String, schema: Schema) -> Builder {
     Builder { root: LogicalOperator::Create(Create::new(name, schema)) }
 }
 
+pub fn create_index(
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    index_ty: IndexType,
+    unique: bool,
+) -> Builder {
+    let create_index = CreateIndex::new(name, table, columns, index_ty, unique);
+    Builder { root: LogicalOperator::CreateIndex(create_index) }
+}
+
+pub fn drop_index(name: String) -> Builder {
+    Builder { root: LogicalOperator::DropIndex(DropIndex::new(name)) }
+}
+
+pub fn update(
+    table_info: Arc<TableInfo>,
+    set: Vec<Assignment>,
+    filter: Option<Expr>,
+) -> Result<Builder, LogicalOperatorError> {
+    Ok(Builder { root: LogicalOperator::Update(Update::new(table_info, set, filter)?) })
+}
+
+pub fn delete(table_info: Arc<TableInfo>, filter: Option<Expr>) -> Builder {
+    Builder { root: LogicalOperator::Delete(Delete::new(table_info, filter)) }
+}
+
 pub fn scan(table_info: Arc<TableInfo>) -> Builder {
     Builder { root: LogicalOperator::Scan(Scan::new(table_info)) }
 }
@@ -245,6 +353,13 @@ pub fn values(values: Vec<Vec<Expr>>) -> Result<Builder, LogicalOperatorError> {
     Ok(Builder { root: LogicalOperator::Values(Values::new(values)?) })
 }
 
+pub fn values_with_schema(
+    values: Vec<Vec<Expr>>,
+    schema: &Schema,
+) -> Result<Builder, LogicalOperatorError> {
+    Ok(Builder { root: LogicalOperator::Values(Values::new_with_schema(values, schema)?) })
+}
+
 pub fn values_with_alias(
     values: Vec<Vec<Expr>>,
     alias: String,
@@ -268,20 +383,21 @@ impl Builder {
         Ok(Self { root: projection.into() })
     }
 
-    pub fn filter(self, expr: Expr) -> Self {
+    pub fn filter(self, expr: Expr) -> Result<Self, LogicalOperatorError> {
         let input = self.root;
-        let filter = Filter::new(expr, input);
+        let filter = Filter::new(expr, input)?;
 
-        Self { root: filter.into() }
+        Ok(Self { root: filter.into() })
     }
 
     pub fn join_on(
         self,
         rhs: impl Into<LogicalOperator>,
+        ty: JoinType,
         expr: Expr,
     ) -> Result<Self, LogicalOperatorError> {
         let lhs = self.root;
-        let join = Join::on(expr, lhs, rhs)?;
+        let join = Join::on(ty, expr, lhs, rhs)?;
 
         Ok(Self { root: join.into() })
     }
@@ -289,38 +405,47 @@ impl Builder {
     pub fn join_using(
         self,
         rhs: impl Into<LogicalOperator>,
+        ty: JoinType,
         columns: Vec<Ident>,
     ) -> Result<Self, LogicalOperatorError> {
         let lhs = self.root;
-        let join = Join::using(columns, lhs, rhs)?;
+        let join = Join::using(ty, columns, lhs, rhs)?;
 
         Ok(Self { root: join.into() })
     }
 
-    pub fn group(self, keys: Vec<Expr>) -> Self {
-        let input = self.root;
-        let group = Group::new(keys, input);
+    pub fn natural_join(
+        self,
+        rhs: impl Into<LogicalOperator>,
+        ty: JoinType,
+    ) -> Result<Self, LogicalOperatorError> {
+        let lhs = self.root;
+        let join = Join::natural(ty, lhs, rhs)?;
 
-        Self { root: group.into() }
+        Ok(Self { root: join.into() })
     }
 
-    pub fn aggregate(self, function: Function, keys: Vec<Expr>) -> Self {
-        let input = self.root;
-        let aggregate = Aggregate::new(function, keys, input);
+    pub fn cross_join(self, rhs: impl Into<LogicalOperator>) -> Self {
+        let lhs = self.root;
+        let join = Join::cross(lhs, rhs);
 
-        Self { root: aggregate.into() }
+        Self { root: join.into() }
     }
 
-    pub fn sort(self, exprs: Vec<Expr>) -> Self {
+    pub fn aggregate(
+        self,
+        keys: Vec<Expr>,
+        functions: Vec<Function>,
+    ) -> Result<Self, LogicalOperatorError> {
         let input = self.root;
-        let sort = Sort::new(exprs, false, input);
+        let aggregate = Aggregate::new(keys, functions, input)?;
 
-        Self { root: sort.into() }
+        Ok(Self { root: aggregate.into() })
     }
 
-    pub fn sort_desc(self, exprs: Vec<Expr>) -> Self {
+    pub fn sort(self, exprs: Vec<OrderByExpr>) -> Self {
         let input = self.root;
-        let sort = Sort::new(exprs, true, input);
+        let sort = Sort::new(exprs, input);
 
         Self { root: sort.into() }
     }
@@ -356,6 +481,7 @@ mod test {
     use crate::replacer::LRU;
     use crate::schema;
     use crate::sql::expr_builder::{alias, concat, ident, lit, wildcard};
+    use crate::sql::JoinType;
 
     #[test]
     fn test_builder() -> Result<(), LogicalOperatorError> {
@@ -365,7 +491,7 @@ mod test {
         let replacer = LRU::new(K);
         let pc = PageCache::new(disk, replacer, 0);
 
-        let mut catalog = Catalog::new(pc);
+        let mut catalog = Catalog::new(pc).unwrap();
         let t1 = catalog
             .create_table("t1", schema! {c1 Int, c2 Varchar, c3 BigInt})
             .unwrap()
@@ -378,28 +504,29 @@ mod test {
             .clone();
 
         let plan = scan(t1)
-            .filter(ident("c1").is_not_null())
+            .filter(ident("c1").is_not_null())?
             .join_on(
-                scan(t2).filter(lit(1).eq(lit(1).and(lit("1").eq(lit("1"))))).build(),
+                scan(t2).filter(lit(1).eq(lit(1).and(lit("1").eq(lit("1")))))?.build(),
+                JoinType::Left,
                 ident("t1.c3").eq(ident("t2.c3")),
             )?
             .project(vec![
-                ident("c1").into(),
+                ident("t1.c1").into(),
                 concat(vec![lit(1), lit("2")]).into(),
                 ident("c5").is_null().into(),
                 alias(lit(1), "one"),
                 wildcard(),
             ])?
-            .sort(vec![ident("c1")])
+            .sort(vec![ident("c1").into(), ident("c5").desc().nulls_last()])
             .limit(lit(5))?
             .build();
 
         let have = plan.to_string();
         let want = "\
 Limit 5
-    Sort [c1] ASC
-        Projection [c1, CONCAT(1, '2'), c5 IS NULL, 1 AS one, *]
-            Join ON t1.c3 = t2.c3
+    Sort [c1 ASC, c5 DESC NULLS LAST]
+        Projection [t1.c1, CONCAT(1, '2'), c5 IS NULL, 1 AS one, *]
+            Join LEFT ON t1.c3 = t2.c3
                 Filter [c1 IS NOT NULL]
                     Scan table=t1 alias= oid=0
                 Filter [1 = 1 AND '1' = '1']
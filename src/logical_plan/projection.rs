@@ -1,8 +1,8 @@
 use crate::{
-    catalog::schema::{Column, Schema, SchemaBuilder, Type},
+    catalog::schema::{Column, Schema, SchemaBuilder},
     column,
-    logical_plan::{expr_type, write_iter, LogicalOperator, LogicalOperatorError},
-    sql::{Expr, Ident, SelectItem},
+    logical_plan::{expr_type, resolve, write_iter, LogicalOperator, LogicalOperatorError},
+    sql::{Expr, SelectItem},
 };
 
 /// `schema`, `projection` have the same length, each field has a corresponding field at the
@@ -13,7 +13,7 @@ use crate::{
 pub struct ProjectionAttributes {
     schema: Schema,
     projection: Vec<SelectItem>,
-    input_idents: Vec<(Type, usize)>,
+    input_idents: Vec<Column>,
 }
 
 impl ProjectionAttributes {
@@ -25,21 +25,10 @@ impl ProjectionAttributes {
         let mut schema = SchemaBuilder::new();
         for item in &projection {
             match item {
-                SelectItem::Expr(Expr::Ident(ident @ Ident::Single(column))) => {
-                    let column = input_schema
-                        .find_column_by_name(column)
-                        .cloned()
-                        .ok_or(format!("unknown column: {ident}"))?;
-                    input_idents.push((column.ty, column.offset));
-                    schema.append(column)
-                }
-                SelectItem::Expr(Expr::Ident(ident @ Ident::Compound(idents))) => {
-                    let column = input_schema
-                        .find_column_by_name_and_table(&idents[0], &idents[1])
-                        .cloned()
-                        .ok_or(format!("unknown column: {ident}"))?;
-                    input_idents.push((column.ty, column.offset));
-                    schema.append(column)
+                SelectItem::Expr(Expr::Ident(ident)) => {
+                    let column = resolve(ident, input_schema)?.clone();
+                    input_idents.push(column.clone());
+                    schema.append(Column { hidden: false, ..column })
                 }
                 SelectItem::Expr(expr) => {
                     schema.append(column!(expr.to_string() => expr_type(expr, input_schema)?))
@@ -47,7 +36,9 @@ impl ProjectionAttributes {
                 SelectItem::AliasedExpr { expr, alias } => {
                     schema.append(column!(alias.to_string() => expr_type(expr, input_schema)?))
                 }
-                SelectItem::Wildcard => schema.append_n(input_schema.columns.clone()),
+                SelectItem::Wildcard => {
+                    schema.append_n(input_schema.iter().filter(|column| !column.hidden).cloned())
+                }
                 SelectItem::QualifiedWildcard(ident) => schema.append_n(
                     input_schema
                         .columns
@@ -55,7 +46,8 @@ impl ProjectionAttributes {
                         .filter(|Column { table, .. }| {
                             table.as_ref().is_some_and(|table| table.as_str() == &ident[0])
                         })
-                        .cloned(),
+                        .cloned()
+                        .map(|column| Column { hidden: false, ..column }),
                 ),
             };
         }
@@ -73,7 +65,7 @@ impl ProjectionAttributes {
         &mut self.schema
     }
 
-    pub fn input_idents(&self) -> &Vec<(Type, usize)> {
+    pub fn input_idents(&self) -> &Vec<Column> {
         &self.input_idents
     }
 
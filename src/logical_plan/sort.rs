@@ -1,8 +1,8 @@
-use crate::logical_plan::{write_iter, Expr, LogicalOperator};
+use crate::logical_plan::{write_iter, LogicalOperator};
+use crate::sql::OrderByExpr;
 
 pub struct Sort {
-    pub exprs: Vec<Expr>,
-    pub desc: bool,
+    pub exprs: Vec<OrderByExpr>,
     pub input: Box<LogicalOperator>,
 }
 
@@ -10,7 +10,7 @@ impl std::fmt::Display for Sort {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Sort [")?;
         write_iter(f, &mut self.exprs.iter(), ", ")?;
-        write!(f, "] {}", if self.desc { "DESC" } else { "ASC" })
+        write!(f, "]")
     }
 }
 
@@ -21,7 +21,7 @@ impl From<Sort> for LogicalOperator {
 }
 
 impl Sort {
-    pub fn new(exprs: Vec<Expr>, desc: bool, input: impl Into<LogicalOperator>) -> Self {
-        Self { exprs, desc, input: Box::new(input.into()) }
+    pub fn new(exprs: Vec<OrderByExpr>, input: impl Into<LogicalOperator>) -> Self {
+        Self { exprs, input: Box::new(input.into()) }
     }
 }
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use crate::{
+    catalog::{schema::Schema, TableInfo},
+    logical_plan::{expr_type, LogicalOperator, LogicalOperatorError},
+    schema,
+    sql::{Assignment, Expr, Ident, Literal},
+};
+
+pub struct Update {
+    pub table: Arc<TableInfo>,
+    pub set: Vec<Assignment>,
+    pub filter: Option<Expr>,
+    pub schema: Schema,
+}
+
+impl std::fmt::Display for Update {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Update table={} oid={} set=[", self.table.name, self.table.oid)?;
+
+        let mut sep = "";
+        for Assignment { column, expr } in &self.set {
+            write!(f, "{sep}{column} = {expr}")?;
+            sep = ", ";
+        }
+        write!(f, "]")?;
+
+        if let Some(filter) = &self.filter {
+            write!(f, " filter=[{filter}]")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Update> for LogicalOperator {
+    fn from(update: Update) -> Self {
+        Self::Update(update)
+    }
+}
+
+impl Update {
+    pub fn new(
+        table: Arc<TableInfo>,
+        set: Vec<Assignment>,
+        filter: Option<Expr>,
+    ) -> Result<Self, LogicalOperatorError> {
+        let schema = schema! { count Int };
+
+        for Assignment { column, expr } in &set {
+            let Ident::Single(name) = column else {
+                Err(format!("qualified columns can't be updated: {column}"))?
+            };
+            let column =
+                table.schema.find_column_by_name(name).ok_or(format!("unknown column: {name}"))?;
+
+            // TODO: support type coercion
+            if *expr != Expr::Literal(Literal::Null) && expr_type(expr, &table.schema)? != column.ty
+            {
+                Err(format!("cannot assign {expr} to column {name} of type {}", column.ty))?
+            }
+        }
+
+        Ok(Self { table, set, filter, schema })
+    }
+}
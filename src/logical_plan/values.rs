@@ -1,4 +1,4 @@
-use crate::catalog::schema::{Schema, SchemaBuilder};
+use crate::catalog::schema::{Schema, SchemaBuilder, Type};
 use crate::column;
 use crate::logical_plan::{write_iter, LogicalOperator, LogicalOperatorError};
 use crate::sql::{Expr, Literal};
@@ -39,7 +39,18 @@ impl From<Values> for LogicalOperator {
 
 impl Values {
     pub fn new(values: Vec<Vec<Expr>>) -> Result<Self, LogicalOperatorError> {
-        let schema = infer_schema(&values)?;
+        let schema = infer_schema(&values, None)?;
+
+        Ok(Self { schema, values, alias: None })
+    }
+
+    /// Columns which are null in every row take their type from `schema`, such as the table being
+    /// inserted into
+    pub fn new_with_schema(
+        values: Vec<Vec<Expr>>,
+        schema: &Schema,
+    ) -> Result<Self, LogicalOperatorError> {
+        let schema = infer_schema(&values, Some(schema))?;
 
         Ok(Self { schema, values, alias: None })
     }
@@ -54,7 +65,10 @@ impl Values {
     }
 }
 
-fn infer_schema(values: &[Vec<Expr>]) -> Result<Schema, LogicalOperatorError> {
+fn infer_schema(
+    values: &[Vec<Expr>],
+    hint: Option<&Schema>,
+) -> Result<Schema, LogicalOperatorError> {
     let mut schema = SchemaBuilder::new();
 
     if values.is_empty() {
@@ -64,14 +78,21 @@ fn infer_schema(values: &[Vec<Expr>]) -> Result<Schema, LogicalOperatorError> {
     for (pos, expr) in values[0].iter().enumerate() {
         match expr {
             Expr::Ident(..) => Err("column references aren't supported inside VALUES")?,
+            Expr::Literal(Literal::Null) => {
+                // Use the first row with a value in this column
+                let ty = values
+                    .iter()
+                    .find_map(|row| match row.get(pos) {
+                        Some(Expr::Literal(Literal::Null)) | None => None,
+                        Some(Expr::Literal(literal)) => Some(Type::from(literal)),
+                        Some(_) => Some(Type::Bool),
+                    })
+                    .or(hint.and_then(|schema| schema.columns.get(pos)).map(|column| column.ty))
+                    .unwrap_or(Type::from(&Literal::Null));
+                schema.append(column!(format!("c{pos}") => ty));
+            }
             Expr::Literal(literal) => {
-                let column = match literal {
-                    Literal::Number(_) => column!(format!("c{pos}"), Int),
-                    Literal::String(_) => column!(format!("c{pos}"), Varchar),
-                    Literal::Bool(_) => column!(format!("c{pos}"), Bool),
-                    Literal::Decimal(_) | Literal::Null => todo!(),
-                };
-                schema.append(column);
+                schema.append(column!(format!("c{pos}") => Type::from(literal)));
             }
             Expr::IsNull { .. }
             | Expr::InList { .. }
@@ -1,11 +1,21 @@
+use std::cmp::Reverse;
 use std::sync::Arc;
 
 use crate::{
-    catalog::SharedCatalog,
-    logical_plan::LogicalOperator,
+    catalog::{
+        schema::{Column, Schema, Type},
+        IndexType, SharedCatalog, TableInfo,
+    },
+    evaluation::{eval, find_column},
+    logical_plan::{JoinConstraint, LogicalOperator},
+    page::PAGE_SIZE,
     physical_plan::{
-        Create, Explain, Filter, Insert, Limit, PhysicalOperator, Projection, Scan, Values,
+        Create, CreateIndex, Delete, DropIndex, Explain, Filter, HashAggregate, HashIndexScan,
+        HashJoin, IndexScan, Insert, KeyRange, Limit, MergeColumns, NestedLoopJoin,
+        PhysicalOperator, Projection, Scan, Sort, SortMergeJoin, Update, Values, HASH_JOIN_MEMORY,
     },
+    sql::{Expr, Op},
+    table::tuple::{Data as TupleData, Value},
 };
 
 pub struct Optimiser {
@@ -30,13 +40,85 @@ impl Optimiser {
     /// scan over a table scan.
     pub fn implement(&self, logical_plan: LogicalOperator) -> Box<dyn PhysicalOperator> {
         let exec: Box<dyn PhysicalOperator> = match logical_plan {
-            LogicalOperator::Aggregate(_aggregate) => todo!(),
+            LogicalOperator::Aggregate(aggregate) => {
+                let input = self.implement(*aggregate.input);
+                Box::new(HashAggregate::new(
+                    input,
+                    aggregate.keys,
+                    aggregate.functions,
+                    aggregate.schema,
+                ))
+            }
             LogicalOperator::Filter(filter) => {
-                let input = self.implement(*filter.input);
+                // The filter is kept on top of an index scan, since the index may only cover part
+                // of the predicate
+                let input = match *filter.input {
+                    LogicalOperator::Scan(scan) => {
+                        match self.index_scan(&scan.table, &scan.schema, &filter.expr) {
+                            Some(index_scan) => index_scan,
+                            None => self.implement(LogicalOperator::Scan(scan)),
+                        }
+                    }
+                    input => self.implement(input),
+                };
                 Box::new(Filter::new(input, filter.expr))
             }
-            LogicalOperator::Group(_group) => todo!(),
-            LogicalOperator::Join(_join) => todo!(),
+            LogicalOperator::Join(join) => {
+                let mut using = None;
+                let (left_keys, right_keys, predicate) = match join.constraint {
+                    JoinConstraint::On(expr) => split_join_predicate(
+                        expr,
+                        join.left_input.schema(),
+                        join.right_input.schema(),
+                    ),
+                    JoinConstraint::Using(columns) => {
+                        let keys: Vec<_> = columns.iter().cloned().map(Expr::Ident).collect();
+                        using = Some(columns);
+                        (keys.clone(), keys, None)
+                    }
+                    JoinConstraint::None => (vec![], vec![], None),
+                };
+
+                // The merged columns of a USING join are added on top of the join, which outputs
+                // the columns of both inputs
+                let schema = match using {
+                    Some(_) => join.left_input.schema().join(join.right_input.schema()),
+                    None => join.schema.clone(),
+                };
+
+                // The hash table is built from the right input, so it has to fit in memory.
+                // Otherwise both inputs are sorted, which can spill to disk, and merged.
+                let right_pages = estimate_pages(&join.right_input);
+                let left = self.implement(*join.left_input);
+                let right = self.implement(*join.right_input);
+                let (left_schema, right_schema) = (left.schema().clone(), right.schema().clone());
+                let exec: Box<dyn PhysicalOperator> = if left_keys.is_empty() {
+                    Box::new(NestedLoopJoin::new(left, right, join.ty, predicate, schema))
+                } else if right_pages * PAGE_SIZE <= HASH_JOIN_MEMORY {
+                    Box::new(HashJoin::new(
+                        left, right, join.ty, left_keys, right_keys, predicate, schema,
+                    ))
+                } else {
+                    let pc = self.catalog.lock().unwrap().page_cache();
+                    let order = |keys: &[Expr]| keys.iter().cloned().map(Expr::asc).collect();
+                    let left = Box::new(Sort::new(left, order(&left_keys), pc.clone()));
+                    let right = Box::new(Sort::new(right, order(&right_keys), pc));
+                    Box::new(SortMergeJoin::new(
+                        left, right, join.ty, left_keys, right_keys, predicate, schema,
+                    ))
+                };
+
+                match using {
+                    Some(columns) => Box::new(MergeColumns::new(
+                        exec,
+                        &columns,
+                        &left_schema,
+                        &right_schema,
+                        join.schema,
+                    )),
+                    None => exec,
+                }
+            }
             LogicalOperator::Projection(projection) => {
                 let input = self.implement(*projection.input);
                 Box::new(Projection::new(input, projection.attributes))
@@ -49,15 +131,44 @@ impl Optimiser {
                 let input = self.implement(*limit.input);
                 Box::new(Limit::new(input, limit.limit))
             }
-            LogicalOperator::Sort(_sort) => todo!(),
+            LogicalOperator::Sort(sort) => {
+                let input = self.implement(*sort.input);
+                let pc = self.catalog.lock().unwrap().page_cache();
+                Box::new(Sort::new(input, sort.exprs, pc))
+            }
             LogicalOperator::Values(values) => Box::new(Values::new(values.values, values.schema)),
             LogicalOperator::Insert(insert) => {
                 let input = self.implement(*insert.input);
-                Box::new(Insert::new(input, Arc::clone(&insert.table.table)))
+                Box::new(Insert::new(input, Arc::clone(&self.catalog), insert.table))
+            }
+            LogicalOperator::Update(update) => {
+                let iter = update.table.table.iter().unwrap();
+                Box::new(Update::new(
+                    Arc::clone(&self.catalog),
+                    update.table,
+                    iter,
+                    update.set,
+                    update.filter,
+                ))
+            }
+            LogicalOperator::Delete(delete) => {
+                let iter = delete.table.table.iter().unwrap();
+                Box::new(Delete::new(Arc::clone(&self.catalog), delete.table, iter, delete.filter))
             }
             LogicalOperator::Create(create) => {
                 Box::new(Create::new(Arc::clone(&self.catalog), create.name, create.schema))
             }
+            LogicalOperator::CreateIndex(create_index) => Box::new(CreateIndex::new(
+                Arc::clone(&self.catalog),
+                create_index.name,
+                create_index.table,
+                create_index.columns,
+                create_index.index_ty,
+                create_index.unique,
+            )),
+            LogicalOperator::DropIndex(drop_index) => {
+                Box::new(DropIndex::new(Arc::clone(&self.catalog), drop_index.name))
+            }
             LogicalOperator::Explain(explain) => {
                 Box::new(Explain::new(*explain.input, explain.schema))
             }
@@ -65,4 +176,354 @@ impl Optimiser {
 
         exec
     }
+
+    /// Chooses an index scan for a filtered table scan, if the predicate constrains a prefix of
+    /// one of the table's B-tree indexes, or gives a value for every column of a hash index. The
+    /// index that covers the most columns is used, and hash indexes are preferred over B-trees.
+    fn index_scan(
+        &self,
+        table: &Arc<TableInfo>,
+        schema: &Schema,
+        predicate: &Expr,
+    ) -> Option<Box<dyn PhysicalOperator>> {
+        let mut conjuncts = Vec::new();
+        split_conjunction(predicate.clone(), &mut conjuncts);
+
+        let catalog = self.catalog.lock().unwrap();
+        let (index, (ranges, _)) = catalog
+            .get_table_indexes(&table.name)
+            .into_iter()
+            .filter_map(|index| {
+                let ranges = key_ranges(&index.schema, &conjuncts, schema)?;
+                // A hash index can only look up keys with a value for every column
+                let (keys, _) = &ranges;
+                match index.index_ty {
+                    IndexType::HashTable if keys[0].prefix.len() < index.columns() => None,
+                    _ => Some((index, ranges)),
+                }
+            })
+            .max_by_key(|(index, (_, columns))| {
+                (*columns, index.index_ty == IndexType::HashTable, Reverse(index.oid))
+            })?;
+
+        let pc = catalog.page_cache();
+        Some(match index.index_ty {
+            IndexType::HashTable => {
+                let keys = ranges.into_iter().map(|range| range.prefix).collect();
+                Box::new(HashIndexScan::new(Arc::clone(table), index, pc, keys, schema.clone()))
+            }
+            IndexType::BTree => {
+                Box::new(IndexScan::new(Arc::clone(table), index, pc, ranges, schema.clone()))
+            }
+        })
+    }
+}
+
+/// Works out the ranges of an index's keys that can satisfy the conjuncts of a predicate, along
+/// with the number of key columns they constrain. Equalities and `IN` lists on each key column
+/// extend the prefix of the ranges, and the first column without one can be bounded by
+/// comparisons or `BETWEEN`. Returns `None` if the first key column isn't constrained.
+///
+/// The ranges may be wider than the predicate, e.g. `<` is treated as `<=`, so the predicate still
+/// has to be applied to the rows that are found.
+fn key_ranges(
+    index: &Schema,
+    conjuncts: &[Expr],
+    schema: &Schema,
+) -> Option<(Vec<KeyRange>, usize)> {
+    let mut prefixes = vec![Vec::new()];
+    let mut columns = 0;
+    let (mut low, mut high) = (None, None);
+    // The RID columns at the end of the key can't be constrained
+    for column in index.iter().filter(|column| !column.hidden) {
+        if let Some(values) = conjuncts.iter().find_map(|expr| equal_values(expr, column, schema)) {
+            prefixes = prefixes
+                .into_iter()
+                .flat_map(|prefix: Vec<Value>| {
+                    values.iter().map(move |value| [prefix.clone(), vec![value.clone()]].concat())
+                })
+                .collect();
+            columns += 1;
+            continue;
+        }
+
+        (low, high) = bounds(conjuncts, column, schema);
+        columns += usize::from(low.is_some() || high.is_some());
+        break;
+    }
+
+    if columns == 0 {
+        return None;
+    }
+
+    let ranges = prefixes
+        .into_iter()
+        .map(|prefix| KeyRange { prefix, low: low.clone(), high: high.clone() })
+        .collect();
+
+    Some((ranges, columns))
+}
+
+/// The values a column has to equal to satisfy `expr`, if it's an equality or an `IN` list
+/// comparing the column to literals
+fn equal_values(expr: &Expr, column: &Column, schema: &Schema) -> Option<Vec<Value>> {
+    let mut values = match expr {
+        Expr::BinaryOp { left, op: Op::Eq, right } => match (&**left, &**right) {
+            (ident, literal) | (literal, ident) if is_column(ident, column, schema) => {
+                vec![literal_value(literal, column.ty)?]
+            }
+            _ => return None,
+        },
+        Expr::InList { expr, list, negated: false } if is_column(expr, column, schema) => {
+            list.iter().map(|literal| literal_value(literal, column.ty)).collect::<Option<_>>()?
+        }
+        _ => return None,
+    };
+
+    // Each value is looked up separately, so they can't be repeated
+    values.sort();
+    values.dedup();
+
+    Some(values)
+}
+
+/// The tightest inclusive bounds on a column that are implied by comparisons with literals
+fn bounds(conjuncts: &[Expr], column: &Column, schema: &Schema) -> (Option<Value>, Option<Value>) {
+    let (mut low, mut high): (Option<Value>, Option<Value>) = (None, None);
+    let mut bound = |lower: bool, literal: &Expr| {
+        let Some(value) = literal_value(literal, column.ty) else { return };
+        if lower {
+            low = Some(low.take().map_or(value.clone(), |low| low.max(value)));
+        } else {
+            high = Some(high.take().map_or(value.clone(), |high| high.min(value)));
+        }
+    };
+
+    for expr in conjuncts {
+        match expr {
+            Expr::BinaryOp { left, op, right } => {
+                let (lower, literal) = match (op, &**left, &**right) {
+                    (Op::Gt | Op::Ge, ident, literal) if is_column(ident, column, schema) => {
+                        (true, literal)
+                    }
+                    (Op::Lt | Op::Le, ident, literal) if is_column(ident, column, schema) => {
+                        (false, literal)
+                    }
+                    (Op::Lt | Op::Le, literal, ident) if is_column(ident, column, schema) => {
+                        (true, literal)
+                    }
+                    (Op::Gt | Op::Ge, literal, ident) if is_column(ident, column, schema) => {
+                        (false, literal)
+                    }
+                    _ => continue,
+                };
+                bound(lower, literal);
+            }
+            Expr::Between { expr, negated: false, low, high }
+                if is_column(expr, column, schema) =>
+            {
+                bound(true, low);
+                bound(false, high);
+            }
+            _ => {}
+        }
+    }
+
+    (low, high)
+}
+
+/// Whether `expr` is an identifier that refers to the column of an index
+fn is_column(expr: &Expr, column: &Column, schema: &Schema) -> bool {
+    match expr {
+        Expr::Ident(ident) => find_column(ident, schema).is_some_and(|c| c.name == column.name),
+        _ => false,
+    }
+}
+
+/// Evaluates a literal as a value of the column's type. Returns `None` if it isn't a literal, or
+/// it's a null or a value that doesn't fit the type, none of which can be used to look up a key.
+fn literal_value(expr: &Expr, ty: Type) -> Option<Value> {
+    let Expr::Literal(_) = expr else { return None };
+    let value = eval(expr, &Schema::default(), &TupleData::empty()).ok()?;
+
+    match (value, ty) {
+        (Value::Int(value), Type::TinyInt) => i8::try_from(value).ok().map(Value::TinyInt),
+        (Value::Int(value), Type::BigInt) => Some(Value::BigInt(value as i64)),
+        (value @ Value::Int(_), Type::Int)
+        | (value @ Value::Bool(_), Type::Bool)
+        | (value @ Value::Varchar(_), Type::Varchar) => Some(value),
+        _ => None,
+    }
+}
+
+/// Splits a join predicate into the keys of an equi-join and whatever is left over. Each
+/// conjunct that is an equality between an expression on the left input and an expression on the
+/// right input becomes a pair of keys.
+fn split_join_predicate(
+    expr: Expr,
+    left: &Schema,
+    right: &Schema,
+) -> (Vec<Expr>, Vec<Expr>, Option<Expr>) {
+    let mut conjuncts = Vec::new();
+    split_conjunction(expr, &mut conjuncts);
+
+    let mut left_keys = Vec::new();
+    let mut right_keys = Vec::new();
+    let mut rest = Vec::new();
+    for expr in conjuncts {
+        match expr {
+            Expr::BinaryOp { left: lhs, op: Op::Eq, right: rhs }
+                if references(&lhs, left) && references(&rhs, right) =>
+            {
+                left_keys.push(*lhs);
+                right_keys.push(*rhs);
+            }
+            Expr::BinaryOp { left: lhs, op: Op::Eq, right: rhs }
+                if references(&rhs, left) && references(&lhs, right) =>
+            {
+                left_keys.push(*rhs);
+                right_keys.push(*lhs);
+            }
+            expr => rest.push(expr),
+        }
+    }
+
+    (left_keys, right_keys, rest.into_iter().reduce(|predicate, expr| predicate.and(expr)))
+}
+
+fn split_conjunction(expr: Expr, conjuncts: &mut Vec<Expr>) {
+    match expr {
+        Expr::BinaryOp { left, op: Op::And, right } => {
+            split_conjunction(*left, conjuncts);
+            split_conjunction(*right, conjuncts);
+        }
+        expr => conjuncts.push(expr),
+    }
+}
+
+/// Whether `expr` references at least one column, and all of them are in `schema`
+fn references(expr: &Expr, schema: &Schema) -> bool {
+    fn visit(expr: &Expr, schema: &Schema, found: &mut bool) -> bool {
+        match expr {
+            Expr::Ident(ident) => {
+                *found = true;
+                find_column(ident, schema).is_some()
+            }
+            Expr::Literal(_) => true,
+            Expr::IsNull { expr, .. } => visit(expr, schema, found),
+            Expr::InList { expr, list, .. } => {
+                visit(expr, schema, found) && list.iter().all(|expr| visit(expr, schema, found))
+            }
+            Expr::Between { expr, low, high, .. } => {
+                visit(expr, schema, found)
+                    && visit(low, schema, found)
+                    && visit(high, schema, found)
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                visit(left, schema, found) && visit(right, schema, found)
+            }
+            Expr::Function(function) => function.args.iter().all(|arg| visit(arg, schema, found)),
+            Expr::Wildcard | Expr::QualifiedWildcard(_) | Expr::SubQuery(_) => false,
+        }
+    }
+
+    let mut found = false;
+    visit(expr, schema, &mut found) && found
+}
+
+/// A rough estimate of the number of pages the output of `plan` would fill
+fn estimate_pages(plan: &LogicalOperator) -> usize {
+    match (plan, plan.inputs()) {
+        (LogicalOperator::Scan(scan), _) => scan.table.table.pages().unwrap(),
+        (_, (None, None)) => 1,
+        (_, (left, right)) => {
+            left.map(estimate_pages).unwrap_or(0) + right.map(estimate_pages).unwrap_or(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        optimiser::{key_ranges, split_conjunction, split_join_predicate},
+        physical_plan::KeyRange,
+        schema,
+        sql::expr_builder::{ident, lit},
+        table::tuple::Value,
+    };
+
+    #[test]
+    fn test_split_join_predicate() {
+        let mut left = schema! { c1 Int, c2 Int };
+        left.qualify("t1");
+        let mut right = schema! { c1 Int, c3 Int };
+        right.qualify("t2");
+
+        let expr = ident("t1.c1")
+            .eq(ident("t2.c1"))
+            .and(ident("c3").eq(ident("c2")))
+            .and(ident("c2").gt(lit(1)))
+            .and(ident("t1.c1").eq(ident("t1.c2")))
+            .and(lit(1).eq(lit(1)));
+
+        let (left_keys, right_keys, predicate) = split_join_predicate(expr, &left, &right);
+        assert_eq!(vec![ident("t1.c1"), ident("c2")], left_keys);
+        assert_eq!(vec![ident("t2.c1"), ident("c3")], right_keys);
+        assert_eq!(
+            Some(
+                ident("c2")
+                    .gt(lit(1))
+                    .and(ident("t1.c1").eq(ident("t1.c2")))
+                    .and(lit(1).eq(lit(1)))
+            ),
+            predicate
+        );
+    }
+
+    #[test]
+    fn test_key_ranges() {
+        let mut schema = schema! { c1 Int, c2 BigInt, c3 Int };
+        schema.qualify("t1");
+        let index = schema.filter(&["c2", "c1"]).compact();
+
+        let tests = [
+            (ident("c3").eq(lit(1)), None),
+            (ident("c1").eq(lit(1)), None),
+            (
+                ident("t1.c2").eq(lit(1)).and(lit(2).eq(ident("c1"))),
+                Some((vec![(vec![Value::BigInt(1), Value::Int(2)], None, None)], 2)),
+            ),
+            (
+                ident("c2").lt(lit(5)).and(ident("c1").eq(lit(2))).and(ident("c2").le(lit(3))),
+                Some((vec![(vec![], None, Some(Value::BigInt(3)))], 1)),
+            ),
+            (
+                ident("c2").between(lit(1), lit(9)).and(lit(4).lt(ident("c2"))),
+                Some((vec![(vec![], Some(Value::BigInt(4)), Some(Value::BigInt(9)))], 1)),
+            ),
+            (
+                ident("c2").in_list(vec![lit(3), lit(1), lit(3)]).and(ident("c1").ge(lit(0))),
+                Some((
+                    vec![
+                        (vec![Value::BigInt(1)], Some(Value::Int(0)), None),
+                        (vec![Value::BigInt(3)], Some(Value::Int(0)), None),
+                    ],
+                    2,
+                )),
+            ),
+        ];
+
+        for (expr, want) in tests {
+            let mut conjuncts = Vec::new();
+            split_conjunction(expr.clone(), &mut conjuncts);
+            let want = want.map(|(ranges, columns): (Vec<_>, _)| {
+                let ranges = ranges
+                    .into_iter()
+                    .map(|(prefix, low, high)| KeyRange { prefix, low, high })
+                    .collect::<Vec<_>>();
+                (ranges, columns)
+            });
+            assert_eq!(want, key_ranges(&index, &conjuncts, &schema), "{expr}");
+        }
+    }
 }
@@ -1,14 +1,29 @@
+use std::mem::size_of;
 use std::ops::Range;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use crate::catalog::schema::Schema;
+use crate::wal::{LogManager, LSN};
 
 pub const PAGE_SIZE: usize = 4 * 1024;
 
+/// Every page ends with the LSN of the last log record that changed it. Objects stored on pages
+/// leave these bytes zeroed, and the page cache fills them in as it writes the page to disk, which
+/// lets recovery skip the records that a page already has.
+pub const PAGE_LSN: Range<usize> = PAGE_SIZE - size_of::<LSN>()..PAGE_SIZE;
+
 pub type PageID = i32;
 pub type PageBuf = [u8; PAGE_SIZE];
 pub type PageReadGuard<'a> = RwLockReadGuard<'a, PageInner>;
-pub type PageWriteGuard<'a> = RwLockWriteGuard<'a, PageInner>;
+
+/// Returns the LSN stamped on a page that was read from disk
+pub fn page_lsn(buf: &PageBuf) -> LSN {
+    LSN::from_be_bytes(buf[PAGE_LSN].try_into().unwrap())
+}
+
+pub fn set_page_lsn(buf: &mut PageBuf, lsn: LSN) {
+    buf[PAGE_LSN].copy_from_slice(&lsn.to_be_bytes());
+}
 
 pub trait DiskObject {
     fn serialise(&self) -> PageBuf;
@@ -28,11 +43,59 @@ impl<'a, T> std::ops::Deref for ObjectReadGuard<'a, T> {
     }
 }
 
-pub struct ObjectWriteGuard<'a, T: DiskObject> {
+/// Write lock on a page. If the page was locked through the page cache, the changes made whilst
+/// the lock was held are written to the log when the guard is dropped.
+pub struct PageWriteGuard<'a> {
     guard: RwLockWriteGuard<'a, PageInner>,
+    log: Option<(&'a LogManager, Box<PageBuf>)>,
+}
+
+impl<'a> PageWriteGuard<'a> {
+    pub fn new(guard: RwLockWriteGuard<'a, PageInner>, log: Option<&'a LogManager>) -> Self {
+        let log = log.map(|log| (log, Box::new(guard.data)));
+
+        Self { guard, log }
+    }
+}
+
+impl Drop for PageWriteGuard<'_> {
+    fn drop(&mut self) {
+        let Some((log, before)) = self.log.take() else { return };
+        if let Some(lsn) = log.log_page(self.guard.id, &before, &self.guard.data) {
+            self.guard.lsn = lsn;
+        }
+    }
+}
+
+impl std::ops::Deref for PageWriteGuard<'_> {
+    type Target = PageInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl std::ops::DerefMut for PageWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+pub struct ObjectWriteGuard<'a, T: DiskObject> {
+    guard: PageWriteGuard<'a>,
     data: T,
 }
 
+impl<'a, T> ObjectWriteGuard<'a, T>
+where
+    T: DiskObject,
+{
+    pub fn new(guard: PageWriteGuard<'a>, schema: &Schema) -> Self {
+        let data: T = DiskObject::deserialise(guard.data, schema);
+        Self { guard, data }
+    }
+}
+
 impl<'a, T> Drop for ObjectWriteGuard<'a, T>
 where
     T: DiskObject,
@@ -78,7 +141,11 @@ impl Page {
     }
 
     pub fn write(&self) -> PageWriteGuard<'_> {
-        self.0.write().unwrap()
+        PageWriteGuard::new(self.0.write().unwrap(), None)
+    }
+
+    pub fn write_logged<'a>(&'a self, log: &'a LogManager) -> PageWriteGuard<'a> {
+        PageWriteGuard::new(self.0.write().unwrap(), Some(log))
     }
 
     pub fn read_object<T>(&self, schema: &Schema) -> ObjectReadGuard<'_, T>
@@ -94,21 +161,23 @@ impl Page {
     where
         T: DiskObject,
     {
-        let guard = self.0.write().unwrap();
-        let data: T = DiskObject::deserialise(guard.data, schema);
-        ObjectWriteGuard { guard, data }
+        ObjectWriteGuard::new(self.write(), schema)
     }
 }
 
 pub struct PageInner {
     pub id: PageID,
     pub dirty: bool,
+    /// LSN of the last log record that changed the page. The log must be flushed up to this
+    /// point before the page can be written to disk. It's only stamped on the copy of the page that
+    /// goes to disk, `data` keeps `PAGE_LSN` zeroed.
+    pub lsn: LSN,
     pub data: PageBuf,
 }
 
 impl Default for PageInner {
     fn default() -> Self {
-        Self { id: -1, dirty: false, data: [0; PAGE_SIZE] }
+        Self { id: -1, dirty: false, lsn: 0, data: [0; PAGE_SIZE] }
     }
 }
 
@@ -125,6 +194,7 @@ impl PageInner {
     pub fn reset(&mut self) {
         self.id = 0;
         self.dirty = false;
+        self.lsn = 0;
         self.data.fill(0);
     }
 }
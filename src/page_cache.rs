@@ -5,10 +5,16 @@ use std::sync::{Arc, RwLock};
 
 use crate::catalog::schema::Schema;
 use crate::disk::Disk;
+use crate::free_map::{FreeMap, PAGES_PER_MAP};
+use crate::header::{Header, HEADER_PAGE_ID};
 use crate::page::{
-    DiskObject, ObjectReadGuard, ObjectWriteGuard, Page, PageID, PageReadGuard, PageWriteGuard,
+    page_lsn, set_page_lsn, DiskObject, ObjectReadGuard, ObjectWriteGuard, Page, PageID, PageInner,
+    PageReadGuard, PageWriteGuard, PAGE_SIZE,
+};
+use crate::replacer::{AccessType, Replacer};
+use crate::wal::{
+    autocommit, defer_deallocation, note_allocation, LogManager, LogStorage, Transaction, LSN,
 };
-use crate::replacer::{AccessType, LRU};
 
 pub const CACHE_SIZE: usize = 64;
 
@@ -77,7 +83,8 @@ pub struct Pin<'a> {
     page: &'a Page,
     pub id: PageID,
     i: FrameID,
-    replacer: Arc<LRU>,
+    replacer: Arc<dyn Replacer>,
+    log: Option<&'a LogManager>,
 }
 
 impl Drop for Pin<'_> {
@@ -87,12 +94,21 @@ impl Drop for Pin<'_> {
 }
 
 impl<'a> Pin<'a> {
-    pub fn new(page: &'a Page, i: FrameID, id: PageID, replacer: Arc<LRU>) -> Self {
-        Self { page, i, id, replacer }
+    pub fn new(
+        page: &'a Page,
+        i: FrameID,
+        id: PageID,
+        replacer: Arc<dyn Replacer>,
+        log: Option<&'a LogManager>,
+    ) -> Self {
+        Self { page, i, id, replacer, log }
     }
 
     pub fn write(&self) -> PageWriteGuard<'_> {
-        let w = self.page.write();
+        let w = match self.log {
+            Some(log) => self.page.write_logged(log),
+            None => self.page.write(),
+        };
         assert!(self.id == w.id, "page was swapped out whilst a pin was held");
         w
     }
@@ -112,7 +128,68 @@ impl<'a> Pin<'a> {
     where
         T: DiskObject,
     {
-        self.page.write_object::<T>(schema)
+        ObjectWriteGuard::new(self.write(), schema)
+    }
+
+    /// Write locks the page, keeping the pin alongside the lock
+    pub fn into_write(self) -> Latch<'a, PageWriteGuard<'a>> {
+        let guard = match self.log {
+            Some(log) => self.page.write_logged(log),
+            None => self.page.write(),
+        };
+        assert!(self.id == guard.id, "page was swapped out whilst a pin was held");
+
+        Latch { guard, _pin: self }
+    }
+
+    /// Read locks the page, keeping the pin alongside the lock
+    pub fn into_read(self) -> Latch<'a, PageReadGuard<'a>> {
+        let guard = self.page.read();
+
+        Latch { guard, _pin: self }
+    }
+}
+
+/// A lock on a page along with its pin. Unlike a lock taken through `Pin::read` or `Pin::write`,
+/// it doesn't borrow the pin, so latches on several pages can be held and released in any order,
+/// such as whilst walking down a B-tree.
+pub struct Latch<'a, G> {
+    // The lock is released before the page is unpinned
+    guard: G,
+    _pin: Pin<'a>,
+}
+
+impl<G> std::ops::Deref for Latch<'_, G> {
+    type Target = G;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<G> std::ops::DerefMut for Latch<'_, G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// A pin which holds on to the page cache rather than borrowing it, so that it can be kept by
+/// something that outlives the borrow, such as a cursor stored in an operator
+pub struct OwnedPin {
+    pc: SharedPageCache,
+    pub id: PageID,
+    i: FrameID,
+}
+
+impl Drop for OwnedPin {
+    fn drop(&mut self) {
+        self.pc.replacer.unpin(self.i);
+    }
+}
+
+impl OwnedPin {
+    pub fn read(&self) -> PageReadGuard<'_> {
+        self.pc.pages[self.i].read()
     }
 }
 
@@ -120,6 +197,7 @@ impl<'a> Pin<'a> {
 pub enum PageCacheError {
     Disk(std::io::ErrorKind),
     OutOfMemory,
+    InvalidHeader(String),
 }
 
 impl std::error::Error for PageCacheError {}
@@ -130,6 +208,7 @@ impl std::fmt::Display for PageCacheError {
         match self {
             Self::Disk(e) => write!(f, "{e}"),
             Self::OutOfMemory => write!(f, "out of memory"),
+            Self::InvalidHeader(e) => write!(f, "invalid header: {e}"),
         }
     }
 }
@@ -142,71 +221,318 @@ pub struct PageCache {
     free: FreeList<CACHE_SIZE>,
     disk: Box<dyn Disk>,
     next_page_id: AtomicI32,
-    replacer: Arc<LRU>,
+    replacer: Arc<dyn Replacer>,
+    log: Option<LogManager>,
+    /// Whether page allocation is tracked by the header page. Otherwise `next_page_id` is only
+    /// kept in memory.
+    header: bool,
 }
 pub type SharedPageCache = Arc<PageCache>;
 
 impl PageCache {
-    pub fn new<D: Disk + 'static>(disk: D, replacer: Arc<LRU>, next_page_id: PageID) -> Arc<Self> {
+    pub fn new<D: Disk + 'static>(
+        disk: D,
+        replacer: Arc<dyn Replacer>,
+        next_page_id: PageID,
+    ) -> Arc<Self> {
         let pages = Box::new(std::array::from_fn(|_| Page::default()));
         let page_table = RwLock::new(HashMap::new());
         let free = FreeList::default();
         let next_page_id = AtomicI32::new(next_page_id);
         let disk = Box::new(disk);
 
-        Arc::new(Self { pages, page_table, free, disk, next_page_id, replacer })
+        Arc::new(Self {
+            pages,
+            page_table,
+            free,
+            disk,
+            next_page_id,
+            replacer,
+            log: None,
+            header: false,
+        })
+    }
+
+    /// Opens a database file, creating its header if the file is new. Every page write is logged
+    /// before it reaches the disk. Any changes left in the log by a previous run are redone before
+    /// the header is read, and those of unfinished transactions are then undone through the cache.
+    pub fn open<D: Disk + 'static, S: LogStorage + 'static>(
+        disk: D,
+        log: S,
+        replacer: Arc<dyn Replacer>,
+    ) -> Result<Arc<Self>> {
+        let log = LogManager::new(log);
+        let unfinished = log.redo(&disk).map_err(|e| PageCacheError::Disk(e.kind()))?;
+
+        let buf = disk.read_page(HEADER_PAGE_ID).map_err(|e| PageCacheError::Disk(e.kind()))?;
+        let header = match Header::validate(&buf).map_err(PageCacheError::InvalidHeader)? {
+            Some(header) => header,
+            None => {
+                let header = Header::default();
+                disk.write_page(HEADER_PAGE_ID, &header.serialise())
+                    .and_then(|_| disk.sync())
+                    .map_err(|e| PageCacheError::Disk(e.kind()))?;
+
+                header
+            }
+        };
+        log.carry_on_from(header.next_lsn);
+
+        let pages = Box::new(std::array::from_fn(|_| Page::default()));
+        let page_table = RwLock::new(HashMap::new());
+        let free = FreeList::default();
+        let next_page_id = AtomicI32::new(header.next_page_id);
+        let disk = Box::new(disk);
+
+        let pc = Arc::new(Self {
+            pages,
+            page_table,
+            free,
+            disk,
+            next_page_id,
+            replacer,
+            log: Some(log),
+            header: true,
+        });
+
+        if !unfinished.is_empty() {
+            pc.log().expect("the page cache should have a log").undo(&pc, unfinished)?;
+        }
+        pc.checkpoint()?;
+
+        Ok(pc)
+    }
+
+    pub fn log(&self) -> Option<&LogManager> {
+        self.log.as_ref()
+    }
+
+    /// Starts a transaction on the calling thread. If the thread is already in a transaction, the
+    /// new one is nested inside it.
+    pub fn begin(self: &Arc<Self>) -> Transaction {
+        Transaction::begin(Arc::clone(self))
+    }
+
+    /// Returns the ID of a page that is free to use, and whether it had previously been freed
+    fn allocate_page(&self) -> Result<(PageID, bool)> {
+        if !self.header {
+            return Ok((self.next_page_id.fetch_add(1, Relaxed), false));
+        }
+
+        // Allocations are never rolled back, a transaction that aborts could otherwise hand back
+        // pages that another transaction has since been given
+        let pin = self.fetch_page(HEADER_PAGE_ID)?;
+        let (page_id, reused) = autocommit(self.log(), || {
+            let mut header = pin.write_object::<Header>(&Schema::default());
+            if header.free_pages > 0 {
+                if let Some(page_id) = self.take_free_page(&header)? {
+                    header.free_pages -= 1;
+                    return Ok((page_id, true));
+                }
+            }
+
+            let page_id = header.next_page_id;
+            header.next_page_id += 1;
+
+            Ok((page_id, false))
+        })?;
+        self.next_page_id.fetch_max(page_id + 1, Relaxed);
+
+        Ok((page_id, reused))
+    }
+
+    fn take_free_page(&self, header: &Header) -> Result<Option<PageID>> {
+        let mut map_id = header.free_map;
+        let mut n = 0;
+        while map_id != -1 {
+            let pin = self.fetch_page(map_id)?;
+            let mut map = pin.write_object::<FreeMap>(&Schema::default());
+            if let Some(page_id) = map.take(n) {
+                return Ok(Some(page_id));
+            }
+
+            map_id = map.next;
+            n += 1;
+        }
+
+        Ok(None)
+    }
+
+    /// Gives the page back so it can be reused by a later allocation. If the thread is in a
+    /// transaction, the page is only freed once the transaction commits. Pages are only reused if
+    /// the cache was opened with a header, otherwise this does nothing.
+    pub fn deallocate_page(&self, page_id: PageID) -> Result<()> {
+        if !self.header {
+            return Ok(());
+        }
+
+        if self.log.as_ref().is_some_and(|log| defer_deallocation(log, page_id)) {
+            return Ok(());
+        }
+
+        self.free_page(page_id)
     }
 
-    fn allocate_page(&self) -> PageID {
-        self.next_page_id.fetch_add(1, Relaxed)
+    /// Marks the page as free in the free map, extending the map if it doesn't yet cover the page
+    pub(crate) fn free_page(&self, page_id: PageID) -> Result<()> {
+        assert!(page_id != HEADER_PAGE_ID, "the header page can't be freed");
+
+        let pin = self.fetch_page(HEADER_PAGE_ID)?;
+        autocommit(self.log(), || {
+            let mut header = pin.write_object::<Header>(&Schema::default());
+
+            let mut map_id = header.free_map;
+            let mut prev = -1;
+            for i in 0..=page_id as usize / PAGES_PER_MAP {
+                if map_id == -1 {
+                    // Map pages always come from the end of the file, otherwise freeing a page
+                    // could need a page to be allocated from the map being extended
+                    map_id = header.next_page_id;
+                    header.next_page_id += 1;
+
+                    match prev {
+                        -1 => header.free_map = map_id,
+                        prev => {
+                            let prev = self.fetch_page(prev)?;
+                            prev.write_object::<FreeMap>(&Schema::default()).next = map_id;
+                        }
+                    }
+                }
+
+                if i == page_id as usize / PAGES_PER_MAP {
+                    break;
+                }
+
+                prev = map_id;
+                map_id = self.fetch_page(map_id)?.read_object::<FreeMap>(&Schema::default()).next;
+            }
+
+            let map_pin = self.fetch_page(map_id)?;
+            let mut map = map_pin.write_object::<FreeMap>(&Schema::default());
+            if map.free(page_id) {
+                header.free_pages += 1;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns the first page of the catalog, if it has been created
+    pub fn catalog_root(&self) -> Result<Option<PageID>> {
+        if !self.header {
+            return Ok(None);
+        }
+
+        let pin = self.fetch_page(HEADER_PAGE_ID)?;
+        let header = pin.read_object::<Header>(&Schema::default());
+
+        Ok(Some(header.catalog_root).filter(|root| *root != -1))
+    }
+
+    pub fn set_catalog_root(&self, root: PageID) -> Result<()> {
+        assert!(self.header, "page cache was created without a header");
+
+        let pin = self.fetch_page(HEADER_PAGE_ID)?;
+        let mut header = pin.write_object::<Header>(&Schema::default());
+        header.catalog_root = root;
+
+        Ok(())
     }
 
     pub fn new_page(&self) -> Result<Pin<'_>> {
-        let page_id = self.allocate_page();
+        let (page_id, reused) = self.allocate_page()?;
+        if let Some(log) = &self.log {
+            note_allocation(log, page_id);
+        }
+        let pin = self.fetch_page(page_id)?;
 
-        self.try_get_page(page_id)
+        if reused {
+            // Clear out whatever the page held before it was freed
+            autocommit(self.log(), || pin.write().put_range(&[0; PAGE_SIZE], 0..PAGE_SIZE));
+        }
+
+        Ok(pin)
     }
 
     pub fn fetch_page(&self, page_id: PageID) -> Result<Pin<'_>> {
         if let Some(i) = self.page_table.read().expect("todo").get(&page_id) {
-            let mut replacer = self.replacer.lock();
-            replacer.record_access(*i, AccessType::Get);
-            replacer.pin(*i);
-
-            return Ok(Pin::new(&self.pages[*i], *i, page_id, self.replacer.clone()));
+            self.replacer.record_access(*i, page_id, AccessType::Get);
+            self.replacer.pin(*i);
+
+            return Ok(Pin::new(
+                &self.pages[*i],
+                *i,
+                page_id,
+                self.replacer.clone(),
+                self.log.as_ref(),
+            ));
         };
 
         self.try_get_page(page_id)
     }
 
+    /// Fetches a page like `fetch_page`, returning a pin that keeps the page cache alive
+    pub fn fetch_page_owned(self: &Arc<Self>, page_id: PageID) -> Result<OwnedPin> {
+        let pin = self.fetch_page(page_id)?;
+        self.replacer.pin(pin.i);
+
+        Ok(OwnedPin { pc: Arc::clone(self), id: pin.id, i: pin.i })
+    }
+
     fn try_get_page(&self, page_id: PageID) -> Result<Pin<'_>> {
+        // The page table is held throughout, so that no other thread can pin the evicted frame or
+        // load the same page into another frame. Locks are always taken in the order page table,
+        // frame, replacer.
+        let mut page_table = self.page_table.write().expect("todo");
+        if let Some(i) = page_table.get(&page_id) {
+            self.replacer.record_access(*i, page_id, AccessType::Get);
+            self.replacer.pin(*i);
+
+            return Ok(Pin::new(
+                &self.pages[*i],
+                *i,
+                page_id,
+                self.replacer.clone(),
+                self.log.as_ref(),
+            ));
+        }
+
         let i = match self.free.pop() {
             Some(i) => i,
             None => self.replacer.evict().ok_or(PageCacheError::OutOfMemory)?, // All pages are pinned
         };
 
         let mut page_w = self.pages[i].write();
-        let mut replacer = self.replacer.lock();
-        replacer.remove(i);
-        replacer.record_access(i, AccessType::Get);
-        replacer.pin(i);
+        self.replacer.remove(i);
+        self.replacer.record_access(i, page_id, AccessType::Get);
+        self.replacer.pin(i);
 
         if page_w.dirty {
-            self.disk
-                .write_page(page_w.id, &page_w.data)
-                .map_err(|e| PageCacheError::Disk(e.kind()))?;
+            self.write_page(&page_w)?;
         }
 
-        let mut page_table = self.page_table.write().expect("todo");
         page_table.remove(&page_w.id);
         page_table.insert(page_id, i);
 
-        let data = self.disk.read_page(page_id).map_err(|e| PageCacheError::Disk(e.kind()))?;
+        let mut data = self.disk.read_page(page_id).map_err(|e| PageCacheError::Disk(e.kind()))?;
         page_w.reset();
         page_w.id = page_id;
+        page_w.lsn = page_lsn(&data);
+        set_page_lsn(&mut data, 0);
         page_w.data = data;
 
-        Ok(Pin::new(&self.pages[i], i, page_id, self.replacer.clone()))
+        Ok(Pin::new(&self.pages[i], i, page_id, self.replacer.clone(), self.log.as_ref()))
+    }
+
+    /// Writes the page to disk, making sure the log records describing it are written first
+    fn write_page(&self, page: &PageInner) -> Result<()> {
+        if let Some(log) = &self.log {
+            log.flush(page.lsn).map_err(|e| PageCacheError::Disk(e.kind()))?;
+        }
+
+        let mut data = page.data;
+        set_page_lsn(&mut data, page.lsn);
+        self.disk.write_page(page.id, &data).map_err(|e| PageCacheError::Disk(e.kind()))
     }
 
     pub fn remove_page(&self, page_id: PageID) {
@@ -232,31 +558,63 @@ impl PageCache {
 
         let mut page_w = self.pages[*i].write();
 
-        self.disk
-            .write_page(page_w.id, &page_w.data)
-            .map_err(|e| PageCacheError::Disk(e.kind()))?;
+        self.write_page(&page_w)?;
         page_w.dirty = false;
 
         Ok(())
     }
 
     pub fn flush_all_pages(&self) -> Result<()> {
-        for page_id in self.page_table.read().expect("todo").keys() {
-            self.flush_page(*page_id)?;
+        let page_ids = self.page_table.read().expect("todo").keys().copied().collect::<Vec<_>>();
+        for page_id in page_ids {
+            self.flush_page(page_id)?;
         }
 
         Ok(())
     }
+
+    /// Writes every page to disk so the log can be discarded. The log is only truncated if no
+    /// transactions are running and nothing was logged whilst the pages were being written.
+    pub fn checkpoint(&self) -> Result<()> {
+        let since = match &self.log {
+            Some(log) => Some(self.save_next_lsn(log)?),
+            None => None,
+        };
+
+        self.flush_all_pages()?;
+        self.disk.sync().map_err(|e| PageCacheError::Disk(e.kind()))?;
+
+        if let (Some(log), Some(since)) = (&self.log, since) {
+            log.truncate(since).map_err(|e| PageCacheError::Disk(e.kind()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Records in the header the LSN that the log carries on from if it's discarded, which is the
+    /// one just past the record of this change. Pages on disk keep their LSNs once the log is
+    /// gone, and the records logged after the next start have to come after them.
+    fn save_next_lsn(&self, log: &LogManager) -> Result<LSN> {
+        let next_lsn = log.next_lsn() + 1;
+        let pin = self.fetch_page(HEADER_PAGE_ID)?;
+        autocommit(Some(log), || {
+            pin.write_object::<Header>(&Schema::default()).next_lsn = next_lsn;
+        });
+
+        Ok(next_lsn)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::{sync::Arc, thread};
 
-    use crate::disk::Memory;
+    use crate::disk::{Disk, Memory};
+    use crate::header::HEADER_PAGE_ID;
     use crate::page::PAGE_SIZE;
     use crate::page_cache::{FreeList, PageCache, PageCacheError, CACHE_SIZE};
     use crate::replacer::LRU;
+    use crate::wal::MemoryLog;
 
     #[test]
     fn test_pm_read() -> Result<(), PageCacheError> {
@@ -318,6 +676,92 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_pm_open() -> Result<(), PageCacheError> {
+        const MEMORY: usize = PAGE_SIZE * 8;
+        const K: usize = 2;
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            assert_eq!(1, pc.new_page()?.id);
+            assert_eq!(2, pc.new_page()?.id);
+            pc.set_catalog_root(1)?;
+            pc.checkpoint()?;
+        }
+
+        // Allocations carry on from where they left off, rather than overwriting existing pages
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            assert_eq!(Some(1), pc.catalog_root()?);
+            let txn = pc.begin();
+            assert_eq!(3, pc.new_page()?.id);
+            txn.commit()?;
+
+            // Not checkpointed, the allocation should be recovered from the log
+        }
+
+        let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+        assert_eq!(4, pc.new_page()?.id);
+        drop(pc);
+
+        let mut header = disk.read_page(HEADER_PAGE_ID).unwrap();
+        header[17] ^= 1;
+        disk.write_page(HEADER_PAGE_ID, &header).unwrap();
+        let have = PageCache::open(disk, log, LRU::new(K)).err();
+        assert_eq!(
+            Some(PageCacheError::InvalidHeader(
+                "header checksum mismatch, the file is corrupt".into()
+            )),
+            have
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pm_deallocate() -> Result<(), PageCacheError> {
+        const MEMORY: usize = PAGE_SIZE * 8;
+        const K: usize = 2;
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+        let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+
+        let a = pc.new_page()?.id;
+        let b = pc.new_page()?.id;
+        pc.fetch_page(a)?.write().put_range(b"page a", 0..6);
+
+        // Freeing within a transaction waits for the commit
+        {
+            let _txn = pc.begin();
+            pc.deallocate_page(a)?;
+        }
+        let c = pc.new_page()?.id;
+        assert!(c != a);
+
+        let txn = pc.begin();
+        pc.deallocate_page(a)?;
+        txn.commit()?;
+
+        // The freed page is handed out again, cleared
+        let page = pc.new_page()?;
+        assert_eq!(a, page.id);
+        assert_eq!(&page.read().data[0..6], &[0; 6]);
+        drop(page);
+
+        // The free map should survive a restart
+        pc.deallocate_page(b)?;
+        pc.checkpoint()?;
+        drop(pc);
+
+        let pc = PageCache::open(disk, log, LRU::new(K))?;
+        assert_eq!(b, pc.new_page()?.id);
+        assert!(pc.new_page()?.id > c);
+
+        Ok(())
+    }
+
     #[test]
     fn test_free_list() {
         thread::scope(|s| {
@@ -0,0 +1,83 @@
+use crate::{
+    catalog::{
+        schema::{Schema, Type},
+        IndexError, IndexType, SharedCatalog,
+    },
+    physical_plan::{ExecutionError, PhysicalOperator},
+    schema,
+    table::tuple::{Builder as TupleBuilder, Data as TupleData},
+};
+
+pub struct CreateIndex {
+    catalog: SharedCatalog,
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    index_ty: IndexType,
+    unique: bool,
+    schema: Schema,
+    invoked: bool,
+}
+
+impl CreateIndex {
+    pub fn new(
+        catalog: SharedCatalog,
+        name: String,
+        table: String,
+        columns: Vec<String>,
+        index_ty: IndexType,
+        unique: bool,
+    ) -> Self {
+        Self {
+            catalog,
+            name,
+            table,
+            columns,
+            index_ty,
+            unique,
+            schema: schema! { ok Int },
+            invoked: false,
+        }
+    }
+}
+
+impl PhysicalOperator for CreateIndex {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.invoked {
+            return Ok(None);
+        }
+
+        let mut catalog = self.catalog.lock().unwrap();
+
+        let CreateIndex { name, table, columns, index_ty, unique, .. } = &self;
+
+        let Some(info) = catalog.get_table_by_name(table) else {
+            Err(format!("unknown table: {table}"))?
+        };
+        let key = columns.iter().map(String::as_str).collect::<Vec<_>>();
+
+        // The buckets of a hash table hold keys of a fixed size
+        if *index_ty == IndexType::HashTable
+            && info.schema.filter(&key).iter().any(|column| column.ty == Type::Varchar)
+        {
+            Err("hash indexes can't be built on VARCHAR columns".to_string())?
+        }
+        let created = catalog
+            .create_index(name, table, *index_ty, *unique, &info.schema, &key)
+            .map_err(|e| match e {
+                IndexError::Sort(e) => e,
+                e => ExecutionError(e.to_string()),
+            })?;
+        if created.is_none() {
+            Err(format!("{name} already exists"))?
+        }
+
+        self.invoked = true;
+
+        Ok(Some(TupleBuilder::new().int(1).build()))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
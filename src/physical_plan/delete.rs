@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use crate::catalog::{schema::Schema, SharedCatalog, TableInfo};
+use crate::evaluation::eval;
+use crate::physical_plan::{filter::is_true, index::Indexes, ExecutionError, PhysicalOperator};
+use crate::schema;
+use crate::sql::Expr;
+use crate::table::list::{Iter as TableIter, ListRef as TableRef};
+use crate::table::tuple::{Builder as TupleBuilder, Data as TupleData};
+
+pub struct Delete {
+    table: TableRef,
+    indexes: Indexes,
+    iter: TableIter,
+    table_schema: Schema,
+    filter: Option<Expr>,
+    schema: Schema,
+    invoked: bool,
+}
+
+impl Delete {
+    pub fn new(
+        catalog: SharedCatalog,
+        table: Arc<TableInfo>,
+        iter: TableIter,
+        filter: Option<Expr>,
+    ) -> Self {
+        Self {
+            table: Arc::clone(&table.table),
+            table_schema: table.schema.clone(),
+            indexes: Indexes::new(catalog, table),
+            iter,
+            filter,
+            schema: schema! { count Int },
+            invoked: false,
+        }
+    }
+}
+
+impl PhysicalOperator for Delete {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.invoked {
+            return Ok(None);
+        }
+
+        let mut count = 0;
+        let write = self.indexes.begin();
+        for result in self.iter.by_ref() {
+            let (meta, tuple, rid) = result.map_err(|e| ExecutionError(e.to_string()))?;
+            if meta.deleted {
+                continue;
+            }
+
+            if let Some(filter) = &self.filter {
+                let value = eval(filter, &self.table_schema, &tuple)
+                    .map_err(|e| ExecutionError(e.to_string()))?;
+                if !is_true(&value) {
+                    continue;
+                }
+            }
+
+            if self.table.delete(rid).map_err(|e| ExecutionError(e.to_string()))? {
+                write.delete(&tuple, &rid)?;
+                count += 1;
+            }
+        }
+        write.commit()?;
+
+        self.invoked = true;
+
+        Ok(Some(TupleBuilder::new().int(count).build()))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
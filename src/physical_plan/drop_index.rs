@@ -0,0 +1,41 @@
+use crate::{
+    catalog::{schema::Schema, SharedCatalog},
+    physical_plan::{ExecutionError, PhysicalOperator},
+    schema,
+    table::tuple::{Builder as TupleBuilder, Data as TupleData},
+};
+
+pub struct DropIndex {
+    catalog: SharedCatalog,
+    name: String,
+    schema: Schema,
+    invoked: bool,
+}
+
+impl DropIndex {
+    pub fn new(catalog: SharedCatalog, name: String) -> Self {
+        Self { catalog, name, schema: schema! { ok Int }, invoked: false }
+    }
+}
+
+impl PhysicalOperator for DropIndex {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.invoked {
+            return Ok(None);
+        }
+
+        let mut catalog = self.catalog.lock().unwrap();
+
+        if !catalog.drop_index(&self.name).map_err(|e| ExecutionError(e.to_string()))? {
+            Err(format!("index {} doesn't exist", self.name))?
+        }
+
+        self.invoked = true;
+
+        Ok(Some(TupleBuilder::new().int(1).build()))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
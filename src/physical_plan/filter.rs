@@ -23,12 +23,8 @@ impl PhysicalOperator for Filter {
             let Some(input_tuple) = self.input.next()? else { break Ok(None) };
             let value = eval(&self.expr, self.input.schema(), &input_tuple)
                 .map_err(|e| ExecutionError(e.to_string()))?;
-            match value {
-                Value::TinyInt(0) | Value::Bool(false) | Value::Int(0) | Value::BigInt(0) => {
-                    continue
-                }
-                Value::Varchar(s) if s.is_empty() => continue,
-                _ => break Ok(Some(input_tuple)),
+            if is_true(&value) {
+                break Ok(Some(input_tuple));
             }
         }
     }
@@ -37,3 +33,11 @@ impl PhysicalOperator for Filter {
         self.input.schema()
     }
 }
+
+/// Null, zero and empty values are false, everything else is true
+pub(super) fn is_true(value: &Value) -> bool {
+    !matches!(
+        value,
+        Value::Null | Value::TinyInt(0) | Value::Bool(false) | Value::Int(0) | Value::BigInt(0)
+    ) && !matches!(value, Value::Varchar(s) if s.is_empty())
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    catalog::schema::{Schema, Type},
+    evaluation::eval,
+    physical_plan::{ExecutionError, PhysicalOperator},
+    sql::{Expr, Function, FunctionName},
+    table::tuple::{Builder as TupleBuilder, Data as TupleData, Value},
+};
+
+/// Builds a hash table of every group in the input before returning any results. Groups are
+/// returned in the order they were first seen.
+pub struct HashAggregate {
+    input: Box<dyn PhysicalOperator>,
+    keys: Vec<Expr>,
+    functions: Vec<Function>,
+    schema: Schema,
+    results: Option<std::vec::IntoIter<TupleData>>,
+}
+
+impl HashAggregate {
+    pub fn new(
+        input: Box<dyn PhysicalOperator>,
+        keys: Vec<Expr>,
+        functions: Vec<Function>,
+        schema: Schema,
+    ) -> Self {
+        Self { input, keys, functions, schema, results: None }
+    }
+
+    fn aggregate(&mut self) -> Result<Vec<TupleData>, ExecutionError> {
+        let input_schema = self.input.schema().clone();
+        let eval = |expr: &Expr, tuple: &TupleData| {
+            eval(expr, &input_schema, tuple).map_err(|e| ExecutionError(e.to_string()))
+        };
+
+        let mut index = HashMap::new();
+        let mut groups = Vec::new();
+        while let Some(tuple) = self.input.next()? {
+            let key =
+                self.keys.iter().map(|key| eval(key, &tuple)).collect::<Result<Vec<_>, _>>()?;
+            let i = *index.entry(key).or_insert_with_key(|key| {
+                groups.push((key.clone(), self.functions.iter().map(Accumulator::new).collect()));
+                groups.len() - 1
+            });
+
+            let (_, accumulators): &mut (Vec<Value>, Vec<Accumulator>) = &mut groups[i];
+            for (accumulator, function) in accumulators.iter_mut().zip(&self.functions) {
+                let args = match &function.args[0] {
+                    Expr::Wildcard | Expr::QualifiedWildcard(_) => {
+                        input_schema.iter().map(|column| tuple.get_value(column)).collect()
+                    }
+                    arg => vec![eval(arg, &tuple)?],
+                };
+
+                accumulator.accumulate(args)?;
+            }
+        }
+
+        // Without any keys there is always a single group, even if the input is empty
+        if groups.is_empty() && self.keys.is_empty() {
+            groups.push((vec![], self.functions.iter().map(Accumulator::new).collect()));
+        }
+
+        let results = groups
+            .into_iter()
+            .map(|(key, accumulators)| {
+                let mut columns = self.schema.iter();
+                let mut tuple = TupleBuilder::new();
+                for value in key {
+                    tuple = tuple.add(&value, columns.next().unwrap().ty);
+                }
+                for accumulator in accumulators {
+                    let ty = columns.next().unwrap().ty;
+                    tuple = tuple.add(&accumulator.finish(ty), ty);
+                }
+
+                tuple.build()
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+impl PhysicalOperator for HashAggregate {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.results.is_none() {
+            self.results = Some(self.aggregate()?.into_iter());
+        }
+
+        Ok(self.results.as_mut().and_then(Iterator::next))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// The running state of an aggregate function for a single group
+struct Accumulator {
+    name: FunctionName,
+    /// Arguments which have already been seen if the function is `DISTINCT`
+    seen: Option<HashSet<Vec<Value>>>,
+    /// Whether the function was given `*`, in which case the arguments are every column of the row
+    wildcard: bool,
+    count: i64,
+    sum: i64,
+    value: Value,
+}
+
+impl Accumulator {
+    fn new(function: &Function) -> Self {
+        Self {
+            name: function.name.clone(),
+            seen: function.distinct.then(HashSet::new),
+            wildcard: matches!(function.args[0], Expr::Wildcard | Expr::QualifiedWildcard(_)),
+            count: 0,
+            sum: 0,
+            value: Value::Null,
+        }
+    }
+
+    /// Adds the function's arguments for a row. A single null argument is ignored, but rows
+    /// given to `COUNT(*)` are always counted.
+    fn accumulate(&mut self, args: Vec<Value>) -> Result<(), ExecutionError> {
+        if let (false, [Value::Null]) = (self.wildcard, args.as_slice()) {
+            return Ok(());
+        }
+
+        if let Some(seen) = &mut self.seen {
+            if seen.contains(&args) {
+                return Ok(());
+            }
+            seen.insert(args.clone());
+        }
+
+        self.count += 1;
+        match self.name {
+            FunctionName::Count => {}
+            FunctionName::Sum | FunctionName::Avg => {
+                let value = match args[0] {
+                    Value::TinyInt(v) => v as i64,
+                    Value::Int(v) => v as i64,
+                    Value::BigInt(v) => v,
+                    ref value => Err(format!("cannot perform {} on {}", self.name, value.ty()))?,
+                };
+                self.sum = self.sum.checked_add(value).ok_or("integer out of range".to_string())?;
+            }
+            FunctionName::Min => {
+                let [value] = <[Value; 1]>::try_from(args).unwrap();
+                if self.value == Value::Null || value < self.value {
+                    self.value = value;
+                }
+            }
+            FunctionName::Max => {
+                let [value] = <[Value; 1]>::try_from(args).unwrap();
+                if value > self.value {
+                    self.value = value;
+                }
+            }
+            FunctionName::Contains | FunctionName::Concat => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the result as a value of type `ty`. Aside from `COUNT`, the result is null if there
+    /// were no rows.
+    fn finish(self, ty: Type) -> Value {
+        match self.name {
+            FunctionName::Count => Value::Int(self.count as i32),
+            _ if self.count == 0 => Value::Null,
+            FunctionName::Sum => Value::BigInt(self.sum),
+            // The average has the type of its argument, so the fractional part is truncated
+            // towards zero
+            FunctionName::Avg => {
+                let avg = self.sum / self.count;
+                match ty {
+                    Type::TinyInt => Value::TinyInt(avg as i8),
+                    Type::Int => Value::Int(avg as i32),
+                    _ => Value::BigInt(avg),
+                }
+            }
+            FunctionName::Min | FunctionName::Max => self.value,
+            FunctionName::Contains | FunctionName::Concat => unreachable!(),
+        }
+    }
+}
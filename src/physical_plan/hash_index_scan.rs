@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use crate::{
+    catalog::{schema::Schema, IndexInfo, TableInfo},
+    hash_table::extendible::ExtendibleHashTable,
+    page_cache::SharedPageCache,
+    physical_plan::{ExecutionError, PhysicalOperator},
+    table::{
+        node::RID,
+        tuple::{Builder as TupleBuilder, Data as TupleData, Value},
+    },
+};
+
+/// Looks up the rows of a table through a hash index. Each key gives a value for every column of
+/// the index, and the rows with that key are read from the table before moving on to the next.
+pub struct HashIndexScan {
+    table: Arc<TableInfo>,
+    index: Arc<IndexInfo>,
+    pc: SharedPageCache,
+    keys: std::vec::IntoIter<Vec<Value>>,
+    schema: Schema,
+    /// The rows with the key that's being looked up
+    rids: std::vec::IntoIter<RID>,
+}
+
+impl HashIndexScan {
+    pub fn new(
+        table: Arc<TableInfo>,
+        index: Arc<IndexInfo>,
+        pc: SharedPageCache,
+        keys: Vec<Vec<Value>>,
+        schema: Schema,
+    ) -> Self {
+        Self { table, index, pc, keys: keys.into_iter(), schema, rids: Vec::new().into_iter() }
+    }
+
+    fn next_rid(&mut self) -> crate::Result<Option<RID>> {
+        loop {
+            if let Some(rid) = self.rids.next() {
+                return Ok(Some(rid));
+            }
+
+            let Some(values) = self.keys.next() else { return Ok(None) };
+            let key = values
+                .iter()
+                .zip(self.index.schema.iter())
+                .fold(TupleBuilder::new(), |key, (value, column)| key.add(value, column.ty))
+                .build();
+
+            let schema = &self.index.schema;
+            let hash_table =
+                ExtendibleHashTable::<RID>::new(self.index.root_page_id, self.pc.clone(), schema);
+            self.rids = hash_table.get(&key)?.into_iter();
+        }
+    }
+}
+
+impl PhysicalOperator for HashIndexScan {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        while let Some(rid) = self.next_rid().map_err(|e| ExecutionError(e.to_string()))? {
+            let row = self.table.table.get(rid).map_err(|e| ExecutionError(e.to_string()))?;
+            match row {
+                Some((meta, data)) if !meta.deleted => return Ok(Some(data)),
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::{
+    catalog::schema::Schema,
+    page::PAGE_SIZE,
+    physical_plan::{
+        join::{join_key, join_tuples, outer, satisfies},
+        ExecutionError, PhysicalOperator,
+    },
+    sql::{Expr, JoinType},
+    table::tuple::{Data as TupleData, Value},
+};
+
+/// The largest right input, in bytes, that the optimiser will build a hash table for. Larger
+/// inputs are joined with a `SortMergeJoin`, which can spill to disk.
+pub const HASH_JOIN_MEMORY: usize = PAGE_SIZE * 16;
+
+/// An equi-join which builds a hash table of the right input on the first call to `next`, then
+/// probes it with each tuple from the left input. Any conditions which aren't equalities between
+/// the two sides are checked by the predicate after the keys match.
+pub struct HashJoin {
+    left: Box<dyn PhysicalOperator>,
+    right: Box<dyn PhysicalOperator>,
+    left_keys: Vec<Expr>,
+    right_keys: Vec<Expr>,
+    predicate: Option<Expr>,
+    schema: Schema,
+    left_outer: bool,
+    right_outer: bool,
+    /// Maps each key to the positions of the tuples in `inner` that have it
+    table: Option<HashMap<Vec<Value>, Vec<usize>>>,
+    inner: Vec<TupleData>,
+    /// Whether each tuple on the right has been matched, for right outer joins
+    inner_matched: Vec<bool>,
+    outer: Option<(TupleData, Option<Vec<Value>>)>,
+    outer_matched: bool,
+    pos: usize,
+    left_done: bool,
+}
+
+impl HashJoin {
+    pub fn new(
+        left: Box<dyn PhysicalOperator>,
+        right: Box<dyn PhysicalOperator>,
+        ty: JoinType,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        predicate: Option<Expr>,
+        schema: Schema,
+    ) -> Self {
+        let (left_outer, right_outer) = outer(ty);
+        Self {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            predicate,
+            schema,
+            left_outer,
+            right_outer,
+            table: None,
+            inner: Vec::new(),
+            inner_matched: Vec::new(),
+            outer: None,
+            outer_matched: false,
+            pos: 0,
+            left_done: false,
+        }
+    }
+
+    /// Tuples with null keys are kept in `inner` but not the table, since they can't match
+    fn build(&mut self) -> Result<HashMap<Vec<Value>, Vec<usize>>, ExecutionError> {
+        let mut table: HashMap<_, Vec<_>> = HashMap::new();
+        while let Some(tuple) = self.right.next()? {
+            if let Some(key) = join_key(&self.right_keys, self.right.schema(), &tuple)? {
+                table.entry(key).or_default().push(self.inner.len());
+            }
+            self.inner.push(tuple);
+        }
+        self.inner_matched = vec![false; self.inner.len()];
+
+        Ok(table)
+    }
+}
+
+impl PhysicalOperator for HashJoin {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.table.is_none() {
+            self.table = Some(self.build()?);
+        }
+        let table = self.table.as_ref().unwrap();
+
+        loop {
+            let Some((outer, key)) = &self.outer else {
+                if !self.left_done {
+                    match self.left.next()? {
+                        Some(outer) => {
+                            let key = join_key(&self.left_keys, self.left.schema(), &outer)?;
+                            self.outer = Some((outer, key));
+                            self.outer_matched = false;
+                            self.pos = 0;
+                            continue;
+                        }
+                        None => {
+                            self.left_done = true;
+                            self.pos = 0;
+                        }
+                    }
+                }
+
+                // Once the left input is exhausted, any unmatched tuples on the right are left
+                if !self.right_outer {
+                    return Ok(None);
+                }
+                while let Some(right) = self.inner.get(self.pos) {
+                    self.pos += 1;
+                    if !self.inner_matched[self.pos - 1] {
+                        let (left_schema, right_schema) = (self.left.schema(), self.right.schema());
+                        return Ok(Some(join_tuples(None, left_schema, Some(right), right_schema)));
+                    }
+                }
+
+                return Ok(None);
+            };
+
+            let matches =
+                key.as_ref().and_then(|key| table.get(key)).map(Vec::as_slice).unwrap_or_default();
+            while let Some(&i) = matches.get(self.pos) {
+                self.pos += 1;
+
+                let right = &self.inner[i];
+                let tuple =
+                    join_tuples(Some(outer), self.left.schema(), Some(right), self.right.schema());
+                if satisfies(self.predicate.as_ref(), &self.schema, &tuple)? {
+                    self.inner_matched[i] = true;
+                    self.outer_matched = true;
+                    return Ok(Some(tuple));
+                }
+            }
+
+            let outer = self.outer.take().map(|(outer, _)| outer);
+            if self.left_outer && !self.outer_matched {
+                let (left_schema, right_schema) = (self.left.schema(), self.right.schema());
+                return Ok(Some(join_tuples(outer.as_ref(), left_schema, None, right_schema)));
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::{Arc, MutexGuard};
+
+use crate::{
+    btree::BTree,
+    catalog::{Catalog, IndexError, IndexInfo, IndexType, SharedCatalog, TableInfo},
+    hash_table::extendible::ExtendibleHashTable,
+    page_cache::SharedPageCache,
+    physical_plan::ExecutionError,
+    table::{node::RID, tuple::Data as TupleData},
+    wal::Transaction,
+};
+
+/// Keeps the indexes of a table in sync as its rows are written. The indexes are looked up from
+/// the catalog for each statement, since they can be created or dropped between statements.
+pub(super) struct Indexes {
+    catalog: SharedCatalog,
+    table: Arc<TableInfo>,
+    pc: SharedPageCache,
+}
+
+impl Indexes {
+    pub fn new(catalog: SharedCatalog, table: Arc<TableInfo>) -> Self {
+        let pc = catalog.lock().unwrap().page_cache();
+        Self { catalog, table, pc }
+    }
+
+    /// Starts writing a statement's rows. Every row and its index entries should be written in the
+    /// returned transaction, so that if any row can't be written, none of the statement is kept.
+    ///
+    /// The catalog stays locked until the statement finishes, so the table's indexes can't change
+    /// part way through, and no other row can be given the same key in a unique index between a
+    /// row being checked and its key being inserted.
+    pub fn begin(&self) -> Write<'_> {
+        let catalog = self.catalog.lock().unwrap();
+        let indexes = catalog.get_table_indexes(&self.table.name);
+        let txn = self.pc.begin();
+
+        Write { txn, _catalog: catalog, indexes, table: &self.table, pc: &self.pc }
+    }
+}
+
+/// The writes of a single statement, in a transaction of their own
+pub(super) struct Write<'a> {
+    /// Declared first so that it's rolled back before the catalog is unlocked
+    txn: Transaction,
+    _catalog: MutexGuard<'a, Catalog>,
+    indexes: Vec<Arc<IndexInfo>>,
+    table: &'a TableInfo,
+    pc: &'a SharedPageCache,
+}
+
+impl Write<'_> {
+    /// Checks that writing the row won't give it the same key as another row in a unique index.
+    /// When a row is being updated, `rid` is where it currently is, so its own key is ignored.
+    /// This should be called before the row is written to the table.
+    pub fn check(&self, tuple: &TupleData, rid: Option<&RID>) -> Result<(), ExecutionError> {
+        for index in self.indexes.iter().filter(|index| index.unique) {
+            match index.duplicate(self.pc, &self.table.schema, tuple, rid) {
+                Ok(None) => {}
+                Ok(Some(_)) => Err(IndexError::UniqueViolation(index.name.clone()).to_string())?,
+                Err(e) => Err(ExecutionError(e.to_string()))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the row's key to every index
+    pub fn insert(&self, tuple: &TupleData, rid: &RID) -> Result<(), ExecutionError> {
+        for index in &self.indexes {
+            insert(index, self.pc, &index.key(&self.table.schema, tuple, Some(rid)), rid)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the row's key from every index
+    pub fn delete(&self, tuple: &TupleData, rid: &RID) -> Result<(), ExecutionError> {
+        for index in &self.indexes {
+            delete(index, self.pc, &index.key(&self.table.schema, tuple, Some(rid)), rid)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the row to its new key in every index where either the key or the row's RID changed
+    pub fn update(
+        &self,
+        old: (&TupleData, &RID),
+        new: (&TupleData, &RID),
+    ) -> Result<(), ExecutionError> {
+        for index in &self.indexes {
+            let old_key = index.key(&self.table.schema, old.0, Some(old.1));
+            let new_key = index.key(&self.table.schema, new.0, Some(new.1));
+            if old_key == new_key && old.1 == new.1 {
+                continue;
+            }
+
+            delete(index, self.pc, &old_key, old.1)?;
+            insert(index, self.pc, &new_key, new.1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Commits the statement's transaction, and then unlocks the catalog
+    pub fn commit(self) -> Result<(), ExecutionError> {
+        self.txn.commit().map_err(|e| ExecutionError(e.to_string()))
+    }
+}
+
+fn insert(
+    index: &IndexInfo,
+    pc: &SharedPageCache,
+    key: &TupleData,
+    rid: &RID,
+) -> Result<(), ExecutionError> {
+    match index.index_ty {
+        IndexType::HashTable => {
+            ExtendibleHashTable::new(index.root_page_id, pc.clone(), &index.schema)
+                .insert(key.clone(), *rid)
+        }
+        IndexType::BTree => {
+            BTree::open(pc.clone(), index.root_page_id, &index.schema).insert(key, rid)
+        }
+    }
+    .map_err(|e| ExecutionError(e.to_string()))
+}
+
+fn delete(
+    index: &IndexInfo,
+    pc: &SharedPageCache,
+    key: &TupleData,
+    rid: &RID,
+) -> Result<(), ExecutionError> {
+    match index.index_ty {
+        IndexType::HashTable => {
+            ExtendibleHashTable::new(index.root_page_id, pc.clone(), &index.schema).remove(key, rid)
+        }
+        IndexType::BTree => {
+            BTree::<RID>::open(pc.clone(), index.root_page_id, &index.schema).delete(key)
+        }
+    }
+    .map(|_| ())
+    .map_err(|e| ExecutionError(e.to_string()))
+}
@@ -0,0 +1,118 @@
+use std::ops::Bound;
+use std::sync::Arc;
+
+use crate::{
+    btree::{cursor::Range, BTree},
+    catalog::{schema::Schema, IndexInfo, TableInfo},
+    page_cache::SharedPageCache,
+    physical_plan::{ExecutionError, PhysicalOperator},
+    table::{
+        node::RID,
+        tuple::{Builder as TupleBuilder, Data as TupleData, Value},
+    },
+};
+
+/// The keys of an index to scan. A key is in the range if it starts with the values of `prefix`
+/// and its next value lies between `low` and `high`, inclusive. A missing bound is unbounded.
+#[derive(PartialEq, Debug)]
+pub struct KeyRange {
+    pub prefix: Vec<Value>,
+    pub low: Option<Value>,
+    pub high: Option<Value>,
+}
+
+/// Looks up the rows of a table through a B-tree index. The key ranges are scanned one at a time
+/// with a cursor on the index, and each row is read from the table as its RID is reached.
+pub struct IndexScan {
+    table: Arc<TableInfo>,
+    index: Arc<IndexInfo>,
+    pc: SharedPageCache,
+    ranges: std::vec::IntoIter<KeyRange>,
+    schema: Schema,
+    /// The range being scanned, along with the entries of the index from its start
+    current: Option<(KeyRange, Range<RID>)>,
+}
+
+impl IndexScan {
+    pub fn new(
+        table: Arc<TableInfo>,
+        index: Arc<IndexInfo>,
+        pc: SharedPageCache,
+        ranges: Vec<KeyRange>,
+        schema: Schema,
+    ) -> Self {
+        Self { table, index, pc, ranges: ranges.into_iter(), schema, current: None }
+    }
+
+    fn next_rid(&mut self) -> crate::Result<Option<RID>> {
+        loop {
+            if let Some((range, entries)) = &mut self.current {
+                match entries.next().transpose()? {
+                    Some((key, rid)) if within(&self.index.schema, &key, range) => {
+                        return Ok(Some(rid))
+                    }
+                    _ => self.current = None,
+                }
+                continue;
+            }
+
+            let Some(range) = self.ranges.next() else { return Ok(None) };
+            let schema = &self.index.schema;
+            let btree = BTree::<RID>::open(self.pc.clone(), self.index.root_page_id, schema);
+
+            // Nulls sort first, so padding the key with them gives the smallest key in the range.
+            // Each key ends with the RID of its row, so even a full set of values is a range.
+            let from = key(schema, &range.prefix, range.low.as_ref());
+            let entries = btree.iter(Bound::Included(from), Bound::Unbounded);
+            self.current = Some((range, entries));
+        }
+    }
+}
+
+impl PhysicalOperator for IndexScan {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        while let Some(rid) = self.next_rid().map_err(|e| ExecutionError(e.to_string()))? {
+            let row = self.table.table.get(rid).map_err(|e| ExecutionError(e.to_string()))?;
+            match row {
+                Some((meta, data)) if !meta.deleted => return Ok(Some(data)),
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// Whether a key that sorts after the start of the range is still in it
+fn within(schema: &Schema, key: &TupleData, range: &KeyRange) -> bool {
+    let mut columns = schema.iter();
+    for (value, column) in range.prefix.iter().zip(columns.by_ref()) {
+        if key.get_value(column) != *value {
+            return false;
+        }
+    }
+
+    match (&range.high, columns.next()) {
+        (Some(high), Some(column)) => key.get_value(column) <= *high,
+        _ => true,
+    }
+}
+
+/// Builds a key of the index from the values of a prefix and the next value, filling the rest of
+/// the columns with nulls
+fn key(schema: &Schema, prefix: &[Value], next: Option<&Value>) -> TupleData {
+    let mut values = prefix.iter().chain(next);
+    let mut key = TupleBuilder::new();
+    for column in schema.iter() {
+        key = match values.next() {
+            Some(value) => key.add(value, column.ty),
+            None => key.null(column.ty),
+        };
+    }
+
+    key.build()
+}
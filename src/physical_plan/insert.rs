@@ -1,19 +1,32 @@
-use crate::catalog::schema::Schema;
-use crate::physical_plan::{ExecutionError, PhysicalOperator};
+use std::sync::Arc;
+
+use crate::catalog::{schema::Schema, SharedCatalog, TableInfo};
+use crate::physical_plan::{index::Indexes, ExecutionError, PhysicalOperator};
+use crate::schema;
 use crate::table::list::ListRef as TableRef;
 use crate::table::tuple::{Builder as TupleBuilder, Data as TupleData};
-use crate::schema;
 
 pub struct Insert {
     table: TableRef,
+    indexes: Indexes,
     schema: Schema,
     input: Box<dyn PhysicalOperator>,
     invoked: bool,
 }
 
 impl Insert {
-    pub fn new(input: Box<dyn PhysicalOperator>, table: TableRef) -> Self {
-        Self { table, input, schema: schema! { ok Int }, invoked: false }
+    pub fn new(
+        input: Box<dyn PhysicalOperator>,
+        catalog: SharedCatalog,
+        table: Arc<TableInfo>,
+    ) -> Self {
+        Self {
+            table: Arc::clone(&table.table),
+            indexes: Indexes::new(catalog, table),
+            input,
+            schema: schema! { ok Int },
+            invoked: false,
+        }
     }
 }
 
@@ -23,9 +36,17 @@ impl PhysicalOperator for Insert {
             return Ok(None);
         }
 
+        let write = self.indexes.begin();
         while let Some(tuple) = self.input.next()? {
-            self.table.insert(&tuple).unwrap();
+            write.check(&tuple, None)?;
+            let rid = self
+                .table
+                .insert(&tuple)
+                .map_err(|e| ExecutionError(e.to_string()))?
+                .ok_or_else(|| ExecutionError("row is too large to fit on a page".into()))?;
+            write.insert(&tuple, &rid)?;
         }
+        write.commit()?;
 
         self.invoked = true;
 
@@ -0,0 +1,138 @@
+use crate::{
+    catalog::schema::{Column, Schema, Type},
+    evaluation::{eval, find_column},
+    physical_plan::{filter::is_true, ExecutionError, PhysicalOperator},
+    sql::{Expr, Ident, JoinType},
+    table::tuple::{Builder as TupleBuilder, Data as TupleData, Value},
+};
+
+/// Puts the merged key columns of a USING join in front of each joined tuple. A merged column takes
+/// its value from the left side, or the right side if that's null, so it's set for unmatched tuples
+/// from either side of an outer join.
+pub struct MergeColumns {
+    input: Box<dyn PhysicalOperator>,
+    /// The key columns on the left and right of each joined tuple
+    columns: Vec<(Column, Column)>,
+    schema: Schema,
+}
+
+impl MergeColumns {
+    pub fn new(
+        input: Box<dyn PhysicalOperator>,
+        columns: &[Ident],
+        left_schema: &Schema,
+        right_schema: &Schema,
+        schema: Schema,
+    ) -> Self {
+        let joined = input.schema();
+        let columns = columns
+            .iter()
+            .map(|column| {
+                let left = find_column(column, left_schema).unwrap().position;
+                let right = find_column(column, right_schema).unwrap().position;
+                (joined.columns[left].clone(), joined.columns[left_schema.len() + right].clone())
+            })
+            .collect();
+
+        Self { input, columns, schema }
+    }
+}
+
+impl PhysicalOperator for MergeColumns {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        let Some(joined) = self.input.next()? else { return Ok(None) };
+
+        let mut tuple = TupleBuilder::new();
+        for ((left, right), column) in self.columns.iter().zip(self.schema.iter()) {
+            let value = match joined.get_value(left) {
+                Value::Null => joined.get_value(right),
+                value => value,
+            };
+            tuple = tuple.add(&widen(value, column.ty), column.ty);
+        }
+        for column in self.input.schema().iter() {
+            tuple = tuple.add(&joined.get_value(column), column.ty);
+        }
+
+        Ok(Some(tuple.build()))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// Converts an integer to a wider integer type, for keys that are merged into a wider column
+fn widen(value: Value, ty: Type) -> Value {
+    match (value, ty) {
+        (Value::TinyInt(value), Type::Int) => Value::Int(value as i32),
+        (Value::TinyInt(value), Type::BigInt) => Value::BigInt(value as i64),
+        (Value::Int(value), Type::BigInt) => Value::BigInt(value as i64),
+        (value, _) => value,
+    }
+}
+
+/// Concatenates a tuple from each side of a join, in the layout of `Schema::join`. A missing side
+/// is padded with nulls, for outer joins.
+pub(super) fn join_tuples(
+    left: Option<&TupleData>,
+    left_schema: &Schema,
+    right: Option<&TupleData>,
+    right_schema: &Schema,
+) -> TupleData {
+    let mut tuple = TupleBuilder::new();
+    for (side, schema) in [(left, left_schema), (right, right_schema)] {
+        for column in schema.iter() {
+            tuple = match side {
+                Some(side) => tuple.add(&side.get_value(column), column.ty),
+                None => tuple.null(column.ty),
+            };
+        }
+    }
+
+    tuple.build()
+}
+
+/// Whether tuples on the left and right without a match are kept, padded with nulls
+pub(super) fn outer(ty: JoinType) -> (bool, bool) {
+    match ty {
+        JoinType::Inner | JoinType::Cross => (false, false),
+        JoinType::Left => (true, false),
+        JoinType::Right => (false, true),
+        JoinType::Full => (true, true),
+    }
+}
+
+/// Evaluates the join keys for one side of an equi-join. Returns None if any of the keys are null,
+/// since null never compares equal to anything. Integers are widened so that keys of different
+/// widths can be compared.
+pub(super) fn join_key(
+    keys: &[Expr],
+    schema: &Schema,
+    tuple: &TupleData,
+) -> Result<Option<Vec<Value>>, ExecutionError> {
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        let value = match eval(key, schema, tuple).map_err(|e| ExecutionError(e.to_string()))? {
+            Value::Null => return Ok(None),
+            Value::TinyInt(value) => Value::BigInt(value as i64),
+            Value::Int(value) => Value::BigInt(value as i64),
+            value => value,
+        };
+        values.push(value);
+    }
+
+    Ok(Some(values))
+}
+
+/// Whether the joined tuple satisfies the join's predicate, if there is one
+pub(super) fn satisfies(
+    predicate: Option<&Expr>,
+    schema: &Schema,
+    tuple: &TupleData,
+) -> Result<bool, ExecutionError> {
+    let Some(predicate) = predicate else { return Ok(true) };
+    let value = eval(predicate, schema, tuple).map_err(|e| ExecutionError(e.to_string()))?;
+
+    Ok(is_true(&value))
+}
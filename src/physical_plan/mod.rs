@@ -2,19 +2,51 @@ use crate::catalog::schema::Schema;
 use crate::table::tuple::Data as TupleData;
 
 mod create;
+mod create_index;
+mod delete;
+mod drop_index;
 mod explain;
 mod filter;
+mod hash_aggregate;
+mod hash_index_scan;
+mod hash_join;
+mod index;
+mod index_scan;
 mod insert;
+mod join;
 mod limit;
+mod nested_loop_join;
 mod projection;
 mod scan;
+mod sort;
+mod sort_merge_join;
+mod update;
 mod values;
 
 pub use {
-    create::Create, explain::Explain, filter::Filter, insert::Insert, limit::Limit,
-    projection::Projection, scan::Scan, values::Values,
+    create::Create,
+    create_index::CreateIndex,
+    delete::Delete,
+    drop_index::DropIndex,
+    explain::Explain,
+    filter::Filter,
+    hash_aggregate::HashAggregate,
+    hash_index_scan::HashIndexScan,
+    hash_join::{HashJoin, HASH_JOIN_MEMORY},
+    index_scan::{IndexScan, KeyRange},
+    insert::Insert,
+    join::MergeColumns,
+    limit::Limit,
+    nested_loop_join::NestedLoopJoin,
+    projection::Projection,
+    scan::Scan,
+    sort::Sort,
+    sort_merge_join::SortMergeJoin,
+    update::Update,
+    values::Values,
 };
 
+#[derive(PartialEq)]
 pub struct ExecutionError(String);
 impl std::error::Error for ExecutionError {}
 
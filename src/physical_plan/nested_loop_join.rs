@@ -0,0 +1,122 @@
+use crate::{
+    catalog::schema::Schema,
+    physical_plan::{
+        join::{join_tuples, outer, satisfies},
+        ExecutionError, PhysicalOperator,
+    },
+    sql::{Expr, JoinType},
+    table::tuple::Data as TupleData,
+};
+
+/// Joins every tuple on the left with every tuple on the right, keeping those which satisfy the
+/// predicate. The right input is read into memory on the first call to `next`.
+pub struct NestedLoopJoin {
+    left: Box<dyn PhysicalOperator>,
+    right: Box<dyn PhysicalOperator>,
+    predicate: Option<Expr>,
+    schema: Schema,
+    left_outer: bool,
+    right_outer: bool,
+    inner: Option<Vec<TupleData>>,
+    /// Whether each tuple on the right has been matched, for right outer joins
+    inner_matched: Vec<bool>,
+    outer: Option<TupleData>,
+    outer_matched: bool,
+    pos: usize,
+    left_done: bool,
+}
+
+impl NestedLoopJoin {
+    pub fn new(
+        left: Box<dyn PhysicalOperator>,
+        right: Box<dyn PhysicalOperator>,
+        ty: JoinType,
+        predicate: Option<Expr>,
+        schema: Schema,
+    ) -> Self {
+        let (left_outer, right_outer) = outer(ty);
+        Self {
+            left,
+            right,
+            predicate,
+            schema,
+            left_outer,
+            right_outer,
+            inner: None,
+            inner_matched: Vec::new(),
+            outer: None,
+            outer_matched: false,
+            pos: 0,
+            left_done: false,
+        }
+    }
+}
+
+impl PhysicalOperator for NestedLoopJoin {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.inner.is_none() {
+            let mut inner = Vec::new();
+            while let Some(tuple) = self.right.next()? {
+                inner.push(tuple);
+            }
+            self.inner_matched = vec![false; inner.len()];
+            self.inner = Some(inner);
+        }
+        let inner = self.inner.as_ref().unwrap();
+
+        loop {
+            let Some(outer) = &self.outer else {
+                if !self.left_done {
+                    match self.left.next()? {
+                        Some(outer) => {
+                            self.outer = Some(outer);
+                            self.outer_matched = false;
+                            self.pos = 0;
+                            continue;
+                        }
+                        None => {
+                            self.left_done = true;
+                            self.pos = 0;
+                        }
+                    }
+                }
+
+                // Once the left input is exhausted, any unmatched tuples on the right are left
+                if !self.right_outer {
+                    return Ok(None);
+                }
+                while let Some(right) = inner.get(self.pos) {
+                    self.pos += 1;
+                    if !self.inner_matched[self.pos - 1] {
+                        let (left_schema, right_schema) = (self.left.schema(), self.right.schema());
+                        return Ok(Some(join_tuples(None, left_schema, Some(right), right_schema)));
+                    }
+                }
+
+                return Ok(None);
+            };
+
+            while let Some(right) = inner.get(self.pos) {
+                self.pos += 1;
+
+                let tuple =
+                    join_tuples(Some(outer), self.left.schema(), Some(right), self.right.schema());
+                if satisfies(self.predicate.as_ref(), &self.schema, &tuple)? {
+                    self.inner_matched[self.pos - 1] = true;
+                    self.outer_matched = true;
+                    return Ok(Some(tuple));
+                }
+            }
+
+            let outer = self.outer.take();
+            if self.left_outer && !self.outer_matched {
+                let (left_schema, right_schema) = (self.left.schema(), self.right.schema());
+                return Ok(Some(join_tuples(outer.as_ref(), left_schema, None, right_schema)));
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
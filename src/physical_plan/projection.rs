@@ -27,26 +27,27 @@ impl PhysicalOperator for Projection {
         for select_item in self.attributes.projection() {
             match select_item {
                 SelectItem::Expr(Expr::Ident(_)) => {
-                    let (ty, offset) = input_idents.next().unwrap();
-                    let value = input_tuple.get_value(*offset, *ty);
-                    tuple = tuple.add(&value);
+                    let column = input_idents.next().unwrap();
+                    let value = input_tuple.get_value(column);
+                    tuple = tuple.add(&value, column.ty);
                 }
                 SelectItem::Expr(expr) | SelectItem::AliasedExpr { expr, alias: _ } => {
                     let value = eval(expr, input_schema, &input_tuple).unwrap();
-                    tuple = tuple.add(&value);
+                    let ty = self.attributes.schema().columns[tuple.len()].ty;
+                    tuple = tuple.add(&value, ty);
                 }
                 SelectItem::Wildcard => {
-                    for column in input_schema.iter() {
-                        let value = input_tuple.get_value(column.offset, column.ty);
-                        tuple = tuple.add(&value);
+                    for column in input_schema.iter().filter(|column| !column.hidden) {
+                        let value = input_tuple.get_value(column);
+                        tuple = tuple.add(&value, column.ty);
                     }
                 }
                 SelectItem::QualifiedWildcard(ident) => {
                     for column in input_schema.columns.iter().filter(|Column { table, .. }| {
                         table.as_ref().is_some_and(|table| table.as_str() == &ident[0])
                     }) {
-                        let value = input_tuple.get_value(column.offset, column.ty);
-                        tuple = tuple.add(&value);
+                        let value = input_tuple.get_value(column);
+                        tuple = tuple.add(&value, column.ty);
                     }
                 }
             }
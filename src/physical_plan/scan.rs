@@ -16,15 +16,16 @@ impl Scan {
 
 impl PhysicalOperator for Scan {
     fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
-        let next = match self.iter.next() {
-            Some(result) => {
-                let (_meta, data, _rid) = result.unwrap();
-                Some(data)
+        for result in self.iter.by_ref() {
+            let (meta, data, _rid) = result.unwrap();
+            if meta.deleted {
+                continue;
             }
-            None => None,
-        };
 
-        Ok(next)
+            return Ok(Some(data));
+        }
+
+        Ok(None)
     }
 
     fn schema(&self) -> &Schema {
@@ -0,0 +1,312 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use crate::{
+    catalog::schema::Schema,
+    evaluation::eval,
+    page::PAGE_SIZE,
+    page_cache::SharedPageCache,
+    physical_plan::{ExecutionError, PhysicalOperator},
+    sql::OrderByExpr,
+    table::{
+        list::{Iter as TableIter, List, ListRef},
+        tuple::{Data as TupleData, Value},
+    },
+};
+
+/// The number of bytes of tuples held in memory before a sorted run is written out
+pub const SORT_MEMORY: usize = PAGE_SIZE * 16;
+
+/// An external merge sort. The input is read into memory until it reaches the memory budget, at
+/// which point it's sorted and written out as a run to temporary pages. Once the input is
+/// exhausted, the runs are merged. If the whole input fits in memory, nothing is written out.
+pub struct Sort {
+    input: Box<dyn PhysicalOperator>,
+    order: Vec<OrderByExpr>,
+    pc: SharedPageCache,
+    state: Option<State>,
+}
+
+enum State {
+    Memory(std::vec::IntoIter<(Vec<SortValue>, TupleData)>),
+    Merge(Merge),
+}
+
+impl Sort {
+    pub fn new(
+        input: Box<dyn PhysicalOperator>,
+        order: Vec<OrderByExpr>,
+        pc: SharedPageCache,
+    ) -> Self {
+        Self { input, order, pc, state: None }
+    }
+
+    fn sort(&mut self) -> Result<State, ExecutionError> {
+        let mut runs = Vec::new();
+        let mut buffer = Vec::new();
+        let mut size = 0;
+        while let Some(tuple) = self.input.next()? {
+            size += tuple.0.len();
+            buffer.push((key(&self.order, self.input.schema(), &tuple)?, tuple));
+
+            if size >= SORT_MEMORY {
+                runs.push(self.write_run(&mut buffer)?);
+                size = 0;
+            }
+        }
+
+        if runs.is_empty() {
+            buffer.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            return Ok(State::Memory(buffer.into_iter()));
+        }
+
+        if !buffer.is_empty() {
+            runs.push(self.write_run(&mut buffer)?);
+        }
+
+        let mut merge = Merge { runs, heap: BinaryHeap::new() };
+        for run in 0..merge.runs.len() {
+            merge.advance(run, &self.order, self.input.schema())?;
+        }
+
+        Ok(State::Merge(merge))
+    }
+
+    /// Sorts the buffer and writes it out to a new list, leaving the buffer empty. The run is
+    /// written in a single transaction, so the log is only synced once per run.
+    fn write_run(
+        &self,
+        buffer: &mut Vec<(Vec<SortValue>, TupleData)>,
+    ) -> Result<Run, ExecutionError> {
+        buffer.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+        let txn = self.pc.begin();
+        let list = Arc::new(
+            List::default(Arc::clone(&self.pc)).map_err(|e| ExecutionError(e.to_string()))?,
+        );
+        let written = buffer.drain(..).try_for_each(|(_, tuple)| {
+            match list.insert(&tuple).map_err(|e| ExecutionError(e.to_string()))? {
+                Some(_) => Ok(()),
+                None => Err(ExecutionError("tuple is too large to sort".into())),
+            }
+        });
+        let iter =
+            match written.and_then(|_| list.iter().map_err(|e| ExecutionError(e.to_string()))) {
+                Ok(iter) => iter,
+                Err(e) => {
+                    let _ = list.deallocate();
+                    let _ = txn.commit();
+                    return Err(e);
+                }
+            };
+
+        // From here the pages are given back when the run is dropped
+        let run = Run { list, iter };
+        txn.commit().map_err(|e| ExecutionError(e.to_string()))?;
+
+        Ok(run)
+    }
+}
+
+impl PhysicalOperator for Sort {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.state.is_none() {
+            self.state = Some(self.sort()?);
+        }
+
+        match self.state.as_mut().unwrap() {
+            State::Memory(iter) => Ok(iter.next().map(|(_, tuple)| tuple)),
+            State::Merge(merge) => {
+                let Some(Entry { run, tuple, .. }) = merge.heap.pop() else { return Ok(None) };
+                merge.advance(run, &self.order, self.input.schema())?;
+
+                Ok(Some(tuple))
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+}
+
+/// A sorted run written out to temporary pages, which are given back when the run is dropped
+struct Run {
+    list: ListRef,
+    iter: TableIter,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = self.list.deallocate();
+    }
+}
+
+/// Holds the next tuple of each run in a heap, so the smallest is always at the top
+struct Merge {
+    runs: Vec<Run>,
+    heap: BinaryHeap<Entry>,
+}
+
+impl Merge {
+    /// Pushes the next tuple from `run` onto the heap, if there is one
+    fn advance(
+        &mut self,
+        run: usize,
+        order: &[OrderByExpr],
+        schema: &Schema,
+    ) -> Result<(), ExecutionError> {
+        let Some(result) = self.runs[run].iter.next() else { return Ok(()) };
+        let (_, tuple, _) = result.map_err(|e| ExecutionError(e.to_string()))?;
+        let key = key(order, schema, &tuple)?;
+        self.heap.push(Entry { key, run, tuple });
+
+        Ok(())
+    }
+}
+
+struct Entry {
+    key: Vec<SortValue>,
+    run: usize,
+    tuple: TupleData,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    /// Reversed, since `BinaryHeap` is a max heap. Equal keys are taken from the earliest run first
+    /// to keep the sort stable.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.cmp(&self.key).then(other.run.cmp(&self.run))
+    }
+}
+
+fn key(
+    order: &[OrderByExpr],
+    schema: &Schema,
+    tuple: &TupleData,
+) -> Result<Vec<SortValue>, ExecutionError> {
+    order
+        .iter()
+        .map(|OrderByExpr { expr, desc, nulls_first }| {
+            let value = eval(expr, schema, tuple).map_err(|e| ExecutionError(e.to_string()))?;
+            Ok(SortValue { value, desc: *desc, nulls_first: *nulls_first })
+        })
+        .collect()
+}
+
+/// A value of a sort key, which orders itself by the direction and null ordering of its `ORDER BY`
+/// expression. Keys are compared one value at a time, like `Comparand`.
+#[derive(PartialEq, Eq)]
+struct SortValue {
+    value: Value,
+    desc: bool,
+    nulls_first: bool,
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.value, &other.value) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) if self.nulls_first => Ordering::Less,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) if self.nulls_first => Ordering::Greater,
+            (_, Value::Null) => Ordering::Less,
+            (lhs, rhs) if self.desc => rhs.cmp(lhs),
+            (lhs, rhs) => lhs.cmp(rhs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        catalog::schema::Schema,
+        disk::Memory,
+        header::{Header, HEADER_PAGE_ID},
+        page::PAGE_SIZE,
+        page_cache::PageCache,
+        physical_plan::{ExecutionError, PhysicalOperator, Sort},
+        replacer::LRU,
+        schema,
+        sql::{expr_builder::ident, OrderByExpr},
+        table::tuple::{Builder as TupleBuilder, Data as TupleData},
+        wal::MemoryLog,
+    };
+
+    /// Returns `rows` rows and then fails
+    struct Failing {
+        rows: i32,
+        width: usize,
+        schema: Schema,
+    }
+
+    impl PhysicalOperator for Failing {
+        fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+            if self.rows == 0 {
+                Err("input failed".to_string())?
+            }
+            self.rows -= 1;
+
+            Ok(Some(TupleBuilder::new().int(self.rows).varchar(&"x".repeat(self.width)).build()))
+        }
+
+        fn schema(&self) -> &Schema {
+            &self.schema
+        }
+    }
+
+    #[test]
+    fn test_free_runs_on_error() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 128;
+        const K: usize = 2;
+        let pc = PageCache::open(Memory::new::<MEMORY>(), MemoryLog::new(), LRU::new(K))?;
+        let order = vec![OrderByExpr { expr: ident("c1"), desc: false, nulls_first: false }];
+        let schema = schema! { c1 Int, c2 Varchar };
+
+        let header = || -> crate::Result<_> {
+            let pin = pc.fetch_page(HEADER_PAGE_ID)?;
+            let header = pin.read_object::<Header>(&Schema::default());
+            Ok((header.next_page_id, header.free_pages as i32))
+        };
+
+        // Several runs are written before the input fails, and then every page but the free
+        // map's is given back
+        let input = Failing { rows: 2000, width: 100, schema: schema.clone() };
+        let mut sort = Sort::new(Box::new(input), order.clone(), pc.clone());
+        assert_eq!("execution error: input failed", sort.next().unwrap_err().to_string());
+        drop(sort);
+        let (next_page_id, free_pages) = header()?;
+        assert!(next_page_id > 4);
+        assert_eq!(next_page_id - 2, free_pages);
+
+        // A tuple that doesn't fit on a page can't be written to a run
+        let input = Failing { rows: 20, width: PAGE_SIZE, schema };
+        let mut sort = Sort::new(Box::new(input), order, pc.clone());
+        let have = sort.next().unwrap_err().to_string();
+        assert_eq!("execution error: tuple is too large to sort", have);
+        drop(sort);
+        let (next_page_id, free_pages) = header()?;
+        assert_eq!(next_page_id - 2, free_pages);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    catalog::schema::Schema,
+    physical_plan::{
+        join::{join_key, join_tuples, outer, satisfies},
+        ExecutionError, PhysicalOperator,
+    },
+    sql::{Expr, JoinType},
+    table::tuple::{Data as TupleData, Value},
+};
+
+/// A tuple and its key, which is None if any of the key values are null
+type Keyed = (TupleData, Option<Vec<Value>>);
+
+/// An equi-join of two inputs which are both sorted in ascending order by their keys. Tuples on
+/// the right with the same key are held in memory as a group, which is joined with each tuple on
+/// the left that has that key.
+pub struct SortMergeJoin {
+    left: Box<dyn PhysicalOperator>,
+    right: Box<dyn PhysicalOperator>,
+    left_keys: Vec<Expr>,
+    right_keys: Vec<Expr>,
+    predicate: Option<Expr>,
+    schema: Schema,
+    left_outer: bool,
+    right_outer: bool,
+    outer: Option<(TupleData, Vec<Value>)>,
+    outer_matched: bool,
+    /// Whether the group has been moved to the current outer tuple's key
+    positioned: bool,
+    /// The next tuple on the right which hasn't been added to a group yet
+    peeked: Option<Keyed>,
+    started: bool,
+    left_done: bool,
+    group: Vec<TupleData>,
+    group_matched: Vec<bool>,
+    group_key: Option<Vec<Value>>,
+    /// The position of the next unmatched tuple to check for when the group is discarded
+    discarded: usize,
+    pos: usize,
+}
+
+impl SortMergeJoin {
+    pub fn new(
+        left: Box<dyn PhysicalOperator>,
+        right: Box<dyn PhysicalOperator>,
+        ty: JoinType,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        predicate: Option<Expr>,
+        schema: Schema,
+    ) -> Self {
+        let (left_outer, right_outer) = outer(ty);
+        Self {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            predicate,
+            schema,
+            left_outer,
+            right_outer,
+            outer: None,
+            outer_matched: false,
+            positioned: false,
+            peeked: None,
+            started: false,
+            left_done: false,
+            group: Vec::new(),
+            group_matched: Vec::new(),
+            group_key: None,
+            discarded: 0,
+            pos: 0,
+        }
+    }
+
+    fn next_right(&mut self) -> Result<Option<Keyed>, ExecutionError> {
+        let Some(tuple) = self.right.next()? else { return Ok(None) };
+        let key = join_key(&self.right_keys, self.right.schema(), &tuple)?;
+
+        Ok(Some((tuple, key)))
+    }
+
+    fn pad_left(&self, right: &TupleData) -> TupleData {
+        join_tuples(None, self.left.schema(), Some(right), self.right.schema())
+    }
+
+    fn pad_right(&self, left: &TupleData) -> TupleData {
+        join_tuples(Some(left), self.left.schema(), None, self.right.schema())
+    }
+
+    /// Empties the group. For right outer joins, each unmatched tuple in the group is returned
+    /// first, one per call.
+    fn discard_group(&mut self) -> Option<TupleData> {
+        if self.right_outer {
+            while self.discarded < self.group.len() {
+                self.discarded += 1;
+                if !self.group_matched[self.discarded - 1] {
+                    return Some(self.pad_left(&self.group[self.discarded - 1]));
+                }
+            }
+        }
+
+        self.group.clear();
+        self.group_matched.clear();
+        self.group_key = None;
+        self.discarded = 0;
+
+        None
+    }
+}
+
+impl PhysicalOperator for SortMergeJoin {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if !self.started {
+            self.peeked = self.next_right()?;
+            self.started = true;
+        }
+
+        loop {
+            let Some((_, key)) = &self.outer else {
+                if self.left_done {
+                    // Once the left input is exhausted, any unmatched tuples on the right are left
+                    if let Some(tuple) = self.discard_group() {
+                        return Ok(Some(tuple));
+                    }
+                    let Some((right, _)) = self.peeked.take() else { return Ok(None) };
+                    self.peeked = self.next_right()?;
+                    if self.right_outer {
+                        return Ok(Some(self.pad_left(&right)));
+                    }
+                    continue;
+                }
+
+                let Some(outer) = self.left.next()? else {
+                    self.left_done = true;
+                    continue;
+                };
+                match join_key(&self.left_keys, self.left.schema(), &outer)? {
+                    Some(key) => {
+                        self.outer = Some((outer, key));
+                        self.outer_matched = false;
+                        self.positioned = false;
+                        self.pos = 0;
+                    }
+                    None if self.left_outer => return Ok(Some(self.pad_right(&outer))),
+                    None => {}
+                }
+                continue;
+            };
+
+            if !self.positioned && self.group_key.as_ref() != Some(key) {
+                let key = key.clone();
+                if let Some(tuple) = self.discard_group() {
+                    return Ok(Some(tuple));
+                }
+
+                // Skip past any tuples on the right with smaller or null keys
+                match &self.peeked {
+                    Some((_, right_key)) if right_key.as_ref().is_none_or(|right| *right < key) => {
+                        let (right, _) = self.peeked.take().unwrap();
+                        self.peeked = self.next_right()?;
+                        if self.right_outer {
+                            return Ok(Some(self.pad_left(&right)));
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                while let Some((_, Some(right_key))) = &self.peeked {
+                    if *right_key != key {
+                        break;
+                    }
+                    let (right, _) = self.peeked.take().unwrap();
+                    self.group.push(right);
+                    self.group_matched.push(false);
+                    self.group_key = Some(key.clone());
+                    self.peeked = self.next_right()?;
+                }
+            }
+            self.positioned = true;
+
+            let (outer, key) = self.outer.as_ref().unwrap();
+            if self.group_key.as_ref() == Some(key) {
+                while let Some(right) = self.group.get(self.pos) {
+                    self.pos += 1;
+
+                    let tuple = join_tuples(
+                        Some(outer),
+                        self.left.schema(),
+                        Some(right),
+                        self.right.schema(),
+                    );
+                    if satisfies(self.predicate.as_ref(), &self.schema, &tuple)? {
+                        self.group_matched[self.pos - 1] = true;
+                        self.outer_matched = true;
+                        return Ok(Some(tuple));
+                    }
+                }
+            }
+
+            let (outer, _) = self.outer.take().unwrap();
+            if self.left_outer && !self.outer_matched {
+                return Ok(Some(self.pad_right(&outer)));
+            }
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use crate::catalog::{schema::Schema, SharedCatalog, TableInfo};
+use crate::evaluation::eval;
+use crate::physical_plan::{filter::is_true, index::Indexes, ExecutionError, PhysicalOperator};
+use crate::schema;
+use crate::sql::{Assignment, Expr, Ident};
+use crate::table::list::{Iter as TableIter, List, ListRef as TableRef};
+use crate::table::tuple::{Builder as TupleBuilder, Data as TupleData};
+
+pub struct Update {
+    table: TableRef,
+    indexes: Indexes,
+    iter: TableIter,
+    table_schema: Schema,
+    set: Vec<Assignment>,
+    filter: Option<Expr>,
+    schema: Schema,
+    invoked: bool,
+}
+
+impl Update {
+    pub fn new(
+        catalog: SharedCatalog,
+        table: Arc<TableInfo>,
+        iter: TableIter,
+        set: Vec<Assignment>,
+        filter: Option<Expr>,
+    ) -> Self {
+        Self {
+            table: Arc::clone(&table.table),
+            table_schema: table.schema.clone(),
+            indexes: Indexes::new(catalog, table),
+            iter,
+            set,
+            filter,
+            schema: schema! { count Int },
+            invoked: false,
+        }
+    }
+
+    fn update(&self, tuple: &TupleData) -> Result<TupleData, ExecutionError> {
+        let mut builder = TupleBuilder::new();
+        for column in self.table_schema.iter() {
+            let assignment = self.set.iter().find(|Assignment { column: ident, .. }| {
+                matches!(ident, Ident::Single(name) if name == &column.name)
+            });
+
+            let value = match assignment {
+                Some(Assignment { expr, .. }) => eval(expr, &self.table_schema, tuple)
+                    .map_err(|e| ExecutionError(e.to_string()))?,
+                None => tuple.get_value(column),
+            };
+            builder = builder.add(&value, column.ty);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+impl PhysicalOperator for Update {
+    fn next(&mut self) -> Result<Option<TupleData>, ExecutionError> {
+        if self.invoked {
+            return Ok(None);
+        }
+
+        let mut count = 0;
+        let write = self.indexes.begin();
+        while let Some(result) = self.iter.next() {
+            let (meta, tuple, rid) = result.map_err(|e| ExecutionError(e.to_string()))?;
+            if meta.deleted {
+                continue;
+            }
+
+            if let Some(filter) = &self.filter {
+                let value = eval(filter, &self.table_schema, &tuple)
+                    .map_err(|e| ExecutionError(e.to_string()))?;
+                if !is_true(&value) {
+                    continue;
+                }
+            }
+
+            let updated = self.update(&tuple)?;
+            if !List::fits(&updated) {
+                Err("row is too large to fit on a page".to_string())?
+            }
+
+            write.check(&updated, Some(&rid))?;
+            let Some(new_rid) =
+                self.table.update(rid, &updated).map_err(|e| ExecutionError(e.to_string()))?
+            else {
+                continue;
+            };
+            write.update((&tuple, &rid), (&updated, &new_rid))?;
+            count += 1;
+        }
+        write.commit()?;
+
+        self.invoked = true;
+
+        Ok(Some(TupleBuilder::new().int(count).build()))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
@@ -25,9 +25,9 @@ impl PhysicalOperator for Values {
         self.pos += 1;
 
         let mut tuple = TupleBuilder::new();
-        for (i, _column) in self.schema.iter().enumerate() {
+        for (i, column) in self.schema.iter().enumerate() {
             let value = eval(&values[i], &schema! {}, &TupleData::empty()).unwrap();
-            tuple = tuple.add(&value);
+            tuple = tuple.add(&value, column.ty);
         }
 
         Ok(Some(tuple.build()))
@@ -1,15 +1,17 @@
 use std::sync::MutexGuard;
 
 use crate::{
-    catalog::{schema::SchemaBuilder, Catalog, SharedCatalog},
+    catalog::{schema::SchemaBuilder, Catalog, IndexType, SharedCatalog},
     column,
     logical_plan::{
-        create, explain, scan, scan_with_alias, values, values_with_alias,
-        Builder as LogicalOperatorBuilder, LogicalOperator, LogicalOperatorError,
+        create, create_index, delete, drop_index, explain, scan, scan_with_alias, update, values,
+        values_with_alias, values_with_schema, Builder as LogicalOperatorBuilder, LogicalOperator,
+        LogicalOperatorError,
     },
     sql::{
-        ColumnDef, ColumnType, Create, Explain, FromTable, Ident, Insert, InsertInput, Join,
-        JoinConstraint, JoinType, OrderByExpr, Query, Select, Statement,
+        ColumnDef, ColumnType, Create, CreateIndex, Delete, DropIndex, Explain, Expr, FromTable,
+        Function, Ident, IndexMethod, Insert, InsertInput, Join, JoinConstraint, OrderByExpr,
+        Query, Select, SelectItem, Statement, Update,
     },
 };
 
@@ -68,12 +70,16 @@ impl Planner {
         statement: Statement,
     ) -> Result<LogicalOperator, PlannerError> {
         let statement = match statement {
-            Statement::Select(select) => self.build_select(&catalog, select)?,
-            Statement::Insert(insert) => self.build_insert(&catalog, insert)?,
-            Statement::Update(_) => todo!(),
-            Statement::Delete(_) => todo!(),
-            Statement::Create(create) => self.build_create(&catalog, create)?,
-            Statement::Explain(explain) => self.build_explain(&catalog, explain)?,
+            Statement::Select(select) => self.build_select(catalog, select)?,
+            Statement::Insert(insert) => self.build_insert(catalog, insert)?,
+            Statement::Update(update) => self.build_update(catalog, update)?,
+            Statement::Delete(delete) => self.build_delete(catalog, delete)?,
+            Statement::Create(create) => self.build_create(catalog, create)?,
+            Statement::CreateIndex(create_index) => {
+                self.build_create_index(catalog, create_index)?
+            }
+            Statement::DropIndex(drop_index) => self.build_drop_index(drop_index)?,
+            Statement::Explain(explain) => self.build_explain(catalog, explain)?,
         };
 
         Ok(statement.build())
@@ -86,13 +92,7 @@ impl Planner {
     ) -> Result<LogicalOperatorBuilder, PlannerError> {
         let Select { body, order, limit } = select;
 
-        let mut query = self.build_query(catalog, body)?;
-
-        query = match order {
-            Some(OrderByExpr { exprs, desc: false }) => query.sort(exprs),
-            Some(OrderByExpr { exprs, desc: true }) => query.sort_desc(exprs),
-            None => query,
-        };
+        let mut query = self.build_query(catalog, body, order)?;
 
         if let Some(expr) = limit {
             query = query.limit(expr)?;
@@ -101,10 +101,13 @@ impl Planner {
         Ok(query)
     }
 
+    /// Builds the query, sorting it by `order` before the projection so that the order can use
+    /// columns which aren't selected
     fn build_query(
         &self,
         catalog: &MutexGuard<'_, Catalog>,
-        Query { projection, from, joins, filter, group }: Query,
+        Query { mut projection, from, joins, filter, group, mut having }: Query,
+        mut order: Vec<OrderByExpr>,
     ) -> Result<LogicalOperatorBuilder, PlannerError> {
         let (mut query, _) = self.build_from(catalog, from)?;
 
@@ -112,11 +115,11 @@ impl Planner {
             let Join { from, ty, constraint } = join;
             let (rhs, _) = self.build_from(catalog, from)?;
 
-            let JoinType::Inner = ty;
-
             query = match constraint {
-                JoinConstraint::On(expr) => query.join_on(rhs.build(), expr)?,
-                JoinConstraint::Using(columns) => query.join_using(rhs.build(), columns)?,
+                JoinConstraint::On(expr) => query.join_on(rhs.build(), ty, expr)?,
+                JoinConstraint::Using(columns) => query.join_using(rhs.build(), ty, columns)?,
+                JoinConstraint::Natural => query.natural_join(rhs.build(), ty)?,
+                JoinConstraint::None => query.cross_join(rhs.build()),
             };
         }
 
@@ -124,17 +127,64 @@ impl Planner {
             // This filter might reference some aliased columns in the projection, we may need to
             // build the projection schema first and replace any aliases with table.column
             // references.
-            query = query.filter(filter);
+            query = query.filter(filter)?;
+        }
+
+        // The order is applied before the projection has named anything, so aliases are swapped
+        // for the expressions they stand for
+        for OrderByExpr { expr, .. } in &mut order {
+            if let Expr::Ident(Ident::Single(name)) = expr {
+                let aliased = projection.iter().find_map(|item| match item {
+                    SelectItem::AliasedExpr { expr, alias } if alias == name => Some(expr),
+                    _ => None,
+                });
+                if let Some(aliased) = aliased {
+                    *expr = aliased.clone();
+                }
+            }
         }
 
-        // There may or may not be an aggregate function in the projection. If there isn't, then it
-        // should still group by, where the first/last processed tuple columns are in the result
-        if !group.is_empty() {
-            todo!()
+        // The projection, HAVING clause and order may have some aggregate functions. If they do,
+        // or if there is a GROUP BY, then they're computed by an aggregate step before the
+        // projection. Any references to the keys or functions are replaced with the aggregate's
+        // columns.
+        let mut functions = Vec::new();
+        for item in &projection {
+            if let SelectItem::Expr(expr) | SelectItem::AliasedExpr { expr, .. } = item {
+                collect_aggregates(expr, &mut functions);
+            }
+        }
+        if let Some(having) = &having {
+            collect_aggregates(having, &mut functions);
+        }
+        for OrderByExpr { expr, .. } in &order {
+            collect_aggregates(expr, &mut functions);
+        }
+
+        if !group.is_empty() || !functions.is_empty() || having.is_some() {
+            for item in &mut projection {
+                if let SelectItem::Expr(expr) | SelectItem::AliasedExpr { expr, .. } = item {
+                    replace_aggregates(expr, &group);
+                }
+            }
+            if let Some(having) = &mut having {
+                replace_aggregates(having, &group);
+            }
+            for OrderByExpr { expr, .. } in &mut order {
+                replace_aggregates(expr, &group);
+            }
+
+            query = query.aggregate(group, functions)?;
+
+            if let Some(having) = having {
+                query = query.filter(having)?;
+            }
+        }
+
+        if !order.is_empty() {
+            query = query.sort(order);
         }
 
-        // The projection may have some aggregate functions. If they exist then the projection is
-        // followed by a aggregate step.
         query = query.project(projection)?;
 
         Ok(query)
@@ -162,7 +212,7 @@ impl Planner {
                 }
             }
             FromTable::Derived { query, alias } => {
-                let mut query = self.build_query(catalog, *query)?;
+                let mut query = self.build_query(catalog, *query, Vec::new())?;
 
                 // Alias applies to all columns in the query, all tables
                 let Some(alias) = alias else {
@@ -196,13 +246,45 @@ impl Planner {
             catalog.get_table_by_name(&name).ok_or(format!("unknown table: {name}"))?;
 
         let builder = match input {
-            InsertInput::Values(rows) => values(rows)?.insert(table_info)?,
-            InsertInput::Query(query) => self.build_query(catalog, query)?.insert(table_info)?,
+            InsertInput::Values(rows) => {
+                values_with_schema(rows, &table_info.schema)?.insert(table_info)?
+            }
+            InsertInput::Query(query) => {
+                self.build_query(catalog, query, Vec::new())?.insert(table_info)?
+            }
         };
 
         Ok(builder)
     }
 
+    fn build_update(
+        &self,
+        catalog: &MutexGuard<'_, Catalog>,
+        Update { table, set, filter }: Update,
+    ) -> Result<LogicalOperatorBuilder, PlannerError> {
+        let Ident::Single(name) = table else {
+            Err(format!("multiple schemas aren't supported: {table}"))?
+        };
+        let table_info =
+            catalog.get_table_by_name(&name).ok_or(format!("unknown table: {name}"))?;
+
+        Ok(update(table_info, set, filter)?)
+    }
+
+    fn build_delete(
+        &self,
+        catalog: &MutexGuard<'_, Catalog>,
+        Delete { table, filter }: Delete,
+    ) -> Result<LogicalOperatorBuilder, PlannerError> {
+        let Ident::Single(name) = table else {
+            Err(format!("multiple schemas aren't supported: {table}"))?
+        };
+        let table_info =
+            catalog.get_table_by_name(&name).ok_or(format!("unknown table: {name}"))?;
+
+        Ok(delete(table_info, filter))
+    }
+
     fn build_create(
         &self,
         _catalog: &MutexGuard<'_, Catalog>,
@@ -229,6 +311,53 @@ impl Planner {
         Ok(create(name, schema))
     }
 
+    fn build_create_index(
+        &self,
+        catalog: &MutexGuard<'_, Catalog>,
+        CreateIndex { name, table, columns, unique, method }: CreateIndex,
+    ) -> Result<LogicalOperatorBuilder, PlannerError> {
+        let Ident::Single(name) = name else {
+            Err(format!("multiple schemas aren't supported: {name}"))?
+        };
+        let Ident::Single(table) = table else {
+            Err(format!("multiple schemas aren't supported: {table}"))?
+        };
+        let info = catalog.get_table_by_name(&table).ok_or(format!("unknown table: {table}"))?;
+
+        let mut key = Vec::new();
+        for column in columns {
+            let Ident::Single(column) = column else {
+                Err(format!("qualified columns can't be indexed: {column}"))?
+            };
+            if info.schema.find_column_by_name(&column).is_none() {
+                Err(format!("unknown column: {column}"))?
+            }
+            if key.contains(&column) {
+                Err(format!("column {column} appears more than once in the key"))?
+            }
+            key.push(column);
+        }
+
+        let index_ty = match method {
+            IndexMethod::BTree => IndexType::BTree,
+            IndexMethod::Hash => IndexType::HashTable,
+        };
+
+        // Like `Create`, the catalog is updated by the physical operator
+        Ok(create_index(name, table, key, index_ty, unique))
+    }
+
+    fn build_drop_index(
+        &self,
+        DropIndex { name }: DropIndex,
+    ) -> Result<LogicalOperatorBuilder, PlannerError> {
+        let Ident::Single(name) = name else {
+            Err(format!("multiple schemas aren't supported: {name}"))?
+        };
+
+        Ok(drop_index(name))
+    }
+
     fn build_explain(
         &self,
         catalog: &MutexGuard<'_, Catalog>,
@@ -240,6 +369,81 @@ impl Planner {
     }
 }
 
+/// Appends any aggregate functions in `expr` to `functions`, skipping duplicates
+fn collect_aggregates(expr: &Expr, functions: &mut Vec<Function>) {
+    match expr {
+        Expr::Function(function) if function.name.is_aggregate() => {
+            if !functions.contains(function) {
+                functions.push(function.clone());
+            }
+        }
+        Expr::Function(Function { args, .. }) => {
+            args.iter().for_each(|arg| collect_aggregates(arg, functions))
+        }
+        Expr::IsNull { expr, .. } => collect_aggregates(expr, functions),
+        Expr::InList { expr, list, .. } => {
+            collect_aggregates(expr, functions);
+            list.iter().for_each(|expr| collect_aggregates(expr, functions));
+        }
+        Expr::Between { expr, low, high, .. } => {
+            collect_aggregates(expr, functions);
+            collect_aggregates(low, functions);
+            collect_aggregates(high, functions);
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            collect_aggregates(left, functions);
+            collect_aggregates(right, functions);
+        }
+        Expr::Wildcard
+        | Expr::QualifiedWildcard(_)
+        | Expr::Ident(_)
+        | Expr::Literal(_)
+        | Expr::SubQuery(_) => {}
+    }
+}
+
+/// Replaces aggregate functions and grouping keys in `expr` with references to the columns
+/// produced by the aggregate step
+fn replace_aggregates(expr: &mut Expr, keys: &[Expr]) {
+    if let Expr::Ident(_) = expr {
+        // Key columns keep their name and table
+        return;
+    }
+
+    if keys.contains(expr) {
+        *expr = Expr::Ident(Ident::Single(expr.to_string()));
+        return;
+    }
+
+    match expr {
+        Expr::Function(function) if function.name.is_aggregate() => {
+            *expr = Expr::Ident(Ident::Single(function.to_string()))
+        }
+        Expr::Function(Function { args, .. }) => {
+            args.iter_mut().for_each(|arg| replace_aggregates(arg, keys))
+        }
+        Expr::IsNull { expr, .. } => replace_aggregates(expr, keys),
+        Expr::InList { expr, list, .. } => {
+            replace_aggregates(expr, keys);
+            list.iter_mut().for_each(|expr| replace_aggregates(expr, keys));
+        }
+        Expr::Between { expr, low, high, .. } => {
+            replace_aggregates(expr, keys);
+            replace_aggregates(low, keys);
+            replace_aggregates(high, keys);
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            replace_aggregates(left, keys);
+            replace_aggregates(right, keys);
+        }
+        Expr::Wildcard
+        | Expr::QualifiedWildcard(_)
+        | Expr::Ident(_)
+        | Expr::Literal(_)
+        | Expr::SubQuery(_) => {}
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::{Arc, Mutex};
@@ -253,13 +457,13 @@ mod test {
         ($name:ident, {$( $table:expr => $columns:expr )+}, $statement:expr, $want:expr) => {
             #[test]
             fn $name() {
-                const MEMORY: usize = PAGE_SIZE * 3;
+                const MEMORY: usize = PAGE_SIZE * 8;
                 const K: usize = 2;
                 let disk = Memory::new::<MEMORY>();
                 let replacer = LRU::new(K);
                 let pc = PageCache::new(disk, replacer, 0);
 
-                let shared_catalog = Arc::new(Mutex::new(Catalog::new(pc)));
+                let shared_catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
 
                 {
                     let mut catalog = shared_catalog.lock().unwrap();
@@ -283,13 +487,13 @@ mod test {
         ($name:ident, $statement:expr, $want:expr) => {
             #[test]
             fn $name() {
-                const MEMORY: usize = PAGE_SIZE * 3;
+                const MEMORY: usize = PAGE_SIZE * 8;
                 const K: usize = 2;
                 let disk = Memory::new::<MEMORY>();
                 let replacer = LRU::new(K);
                 let pc = PageCache::new(disk, replacer, 0);
 
-                let shared_catalog = Arc::new(Mutex::new(Catalog::new(pc)));
+                let shared_catalog = Arc::new(Mutex::new(Catalog::new(pc).unwrap()));
 
                 let statement = $statement;
                 let mut parser = Parser::new(&statement).unwrap();
@@ -335,7 +539,7 @@ Projection [*]
         select_with_join_using,
         {
             "t1" => schema! { c1 Int, c2 Varchar, c3 BigInt }
-            "t2" => schema! { c2 Int, c3 Varchar, c4 BigInt }
+            "t2" => schema! { c2 Varchar, c3 BigInt, c4 BigInt }
             "t3" => schema! { c1 Int, c2 Varchar, c4 BigInt }
         },
         "SELECT * FROM t1 JOIN t2 USING (c2, c3) JOIN t3 USING (c1, c4) where c1 > 5",
@@ -368,7 +572,7 @@ Projection [c1, c2, c3, c4 AS column_four]
         select_with_derived_tables,
         {
             "t1" => schema! { c1 Int, c2 Varchar, c3 BigInt }
-            "t2" => schema! { c2 Int, c3 Varchar, c4 BigInt }
+            "t2" => schema! { c2 Varchar, c3 BigInt, c4 BigInt }
         },
         "SELECT d1.*, d2.c3, d2.c4 FROM (SELECT * FROM t1 WHERE c1 IN (1, 2, 3)) d1
         JOIN (SELECT c2, c3, c4 FROM t2 WHERE c2 != '') d2 USING (c2)",
@@ -423,6 +627,150 @@ Insert table=t1 oid=0
         "CREATE TABLE t1 (c1 INT, c2 VARCHAR)",
         "\
 Create table=t1 schema=[c1 INT, c2 VARCHAR]
+"
+    );
+
+    test_statement!(
+        create_index,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar }
+        },
+        "CREATE UNIQUE INDEX i1 ON t1 (c2, c1)",
+        "\
+CreateIndex index=i1 table=t1 type=BTree unique key=[c2, c1]
+"
+    );
+
+    test_statement!(
+        drop_index,
+        "DROP INDEX i1",
+        "\
+DropIndex index=i1
+"
+    );
+
+    test_statement!(
+        update_with_filter,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "UPDATE t1 SET c2 = 'a', c3 = c1 WHERE c1 > 5",
+        "\
+Update table=t1 oid=0 set=[c2 = 'a', c3 = c1] filter=[c1 > 5]
+"
+    );
+
+    test_statement!(
+        delete_all,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "DELETE FROM t1",
+        "\
+Delete table=t1 oid=0
+"
+    );
+
+    test_statement!(
+        select_with_group_by,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "SELECT c2, COUNT(*), SUM(c1) FROM t1 WHERE c3 > 0 GROUP BY c2 HAVING MAX(c1) < 10",
+        "\
+Projection [c2, COUNT(*), SUM(c1)]
+    Filter [MAX(c1) < 10]
+        Aggregate [COUNT(*), SUM(c1), MAX(c1)] keys:[c2]
+            Filter [c3 > 0]
+                Scan table=t1 alias= oid=0
+"
+    );
+
+    test_statement!(
+        select_aggregate_without_group_by,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "SELECT MIN(c2), AVG(DISTINCT c1) FROM t1",
+        "\
+Projection [MIN(c2), AVG(DISTINCT c1)]
+    Aggregate [MIN(c2), AVG(DISTINCT c1)]
+        Scan table=t1 alias= oid=0
+"
+    );
+
+    test_statement!(
+        select_with_order_by,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "SELECT * FROM t1 ORDER BY c1 DESC, c2, c3 NULLS FIRST LIMIT 5",
+        "\
+Limit 5
+    Projection [*]
+        Sort [c1 DESC, c2 ASC, c3 ASC NULLS FIRST]
+            Scan table=t1 alias= oid=0
+"
+    );
+
+    test_statement!(
+        select_order_by_unselected_column,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "SELECT c1, c3 AS total FROM t1 ORDER BY c2, total DESC",
+        "\
+Projection [c1, c3 AS total]
+    Sort [c2 ASC, c3 DESC]
+        Scan table=t1 alias= oid=0
+"
+    );
+
+    test_statement!(
+        select_order_by_aggregate,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 Int }
+        },
+        "SELECT c2 FROM t1 GROUP BY c2 ORDER BY COUNT(*) DESC",
+        "\
+Projection [c2]
+    Sort [COUNT(*) DESC]
+        Aggregate [COUNT(*)] keys:[c2]
+            Scan table=t1 alias= oid=0
+"
+    );
+
+    test_statement!(
+        select_with_outer_and_cross_joins,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar }
+            "t2" => schema! { c1 Int, c3 Varchar }
+            "t3" => schema! { c4 Int }
+        },
+        "SELECT * FROM t1 LEFT JOIN t2 ON t1.c1 = t2.c1, t3 WHERE c4 > 1",
+        "\
+Projection [*]
+    Filter [c4 > 1]
+        Join CROSS
+            Join LEFT ON t1.c1 = t2.c1
+                Scan table=t1 alias= oid=0
+                Scan table=t2 alias= oid=1
+            Scan table=t3 alias= oid=2
+"
+    );
+
+    test_statement!(
+        select_with_natural_join,
+        {
+            "t1" => schema! { c1 Int, c2 Varchar, c3 BigInt }
+            "t2" => schema! { c3 BigInt, c4 Int, c1 Int }
+        },
+        "SELECT c1, t1.c1, t2.c1 FROM t1 NATURAL RIGHT JOIN t2",
+        "\
+Projection [c1, t1.c1, t2.c1]
+    Join RIGHT USING (c1, c3)
+        Scan table=t1 alias= oid=0
+        Scan table=t2 alias= oid=1
 "
     );
 }
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::page::PageID;
+use crate::page_cache::FrameID;
+use crate::replacer::{AccessType, Queue, Replacer};
+
+/// Frames are put on T1 when their page is read in, and move to T2 when they're accessed again.
+/// The pages evicted from T1 and T2 are remembered on B1 and B2, and a page that's read back in
+/// whilst it's remembered goes straight to T2.
+pub struct ARCReplacer {
+    t1: Queue<FrameID>,
+    t2: Queue<FrameID>,
+    b1: Queue<PageID>,
+    b2: Queue<PageID>,
+    pages: HashMap<FrameID, PageID>,
+    pins: HashMap<FrameID, u64>,
+    /// How many frames T1 should hold, which grows whilst pages evicted from T1 are read back in
+    /// and shrinks whilst pages evicted from T2 are
+    target: usize,
+    capacity: usize,
+}
+
+impl ARCReplacer {
+    /// Creates a replacer for a cache of `capacity` frames, which remembers as many evicted pages
+    /// as the cache holds
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            t1: Queue::new(),
+            t2: Queue::new(),
+            b1: Queue::new(),
+            b2: Queue::new(),
+            pages: HashMap::new(),
+            pins: HashMap::new(),
+            target: 0,
+            capacity,
+        }
+    }
+
+    pub fn evict(&mut self) -> Option<FrameID> {
+        let unpinned = |queue: &Queue<FrameID>| {
+            queue.iter().copied().find(|i| self.pins.get(i).is_none_or(|pins| *pins == 0))
+        };
+        let i = match self.t1.len() > self.target {
+            true => unpinned(&self.t1).or_else(|| unpinned(&self.t2)),
+            false => unpinned(&self.t2).or_else(|| unpinned(&self.t1)),
+        }?;
+
+        let page_id = self.pages.remove(&i).expect("a tracked frame should hold a page");
+        if self.t1.remove(&i) {
+            self.b1.push(page_id);
+        } else {
+            self.t2.remove(&i);
+            self.b2.push(page_id);
+        }
+        self.pins.remove(&i);
+
+        // T1 and B1 hold at most a cache's worth of pages, and all four lists at most two
+        while self.t1.len() + self.b1.len() > self.capacity && self.b1.pop().is_some() {}
+        while self.len() > self.capacity * 2 && self.b2.pop().is_some() {}
+
+        Some(i)
+    }
+
+    pub fn record_access(&mut self, i: FrameID, page_id: PageID) {
+        if self.t1.remove(&i) || self.t2.contains(&i) {
+            self.t2.push(i);
+            return;
+        }
+
+        // A page that's read back in shows which list was evicted from too soon
+        self.pages.insert(i, page_id);
+        if self.b1.contains(&page_id) {
+            let delta = (self.b2.len() / self.b1.len()).max(1);
+            self.target = (self.target + delta).min(self.capacity);
+            self.b1.remove(&page_id);
+            self.t2.push(i);
+        } else if self.b2.contains(&page_id) {
+            let delta = (self.b1.len() / self.b2.len()).max(1);
+            self.target = self.target.saturating_sub(delta);
+            self.b2.remove(&page_id);
+            self.t2.push(i);
+        } else {
+            self.t1.push(i);
+        }
+    }
+
+    pub fn pin(&mut self, i: FrameID) {
+        if self.pages.contains_key(&i) {
+            *self.pins.entry(i).or_default() += 1;
+        }
+    }
+
+    pub fn unpin(&mut self, i: FrameID) {
+        if let Some(pins) = self.pins.get_mut(&i) {
+            *pins -= 1;
+        }
+    }
+
+    pub fn remove(&mut self, i: FrameID) {
+        self.t1.remove(&i);
+        self.t2.remove(&i);
+        self.pages.remove(&i);
+        self.pins.remove(&i);
+    }
+
+    fn len(&self) -> usize {
+        self.t1.len() + self.t2.len() + self.b1.len() + self.b2.len()
+    }
+}
+
+/// The ARC policy, which splits the cache between the pages that have been accessed once recently
+/// and the pages that have been accessed more than once, and adapts the split to the workload by
+/// remembering the pages it evicted from each side
+pub struct ARC {
+    inner: Mutex<ARCReplacer>,
+}
+
+impl ARC {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self { inner: Mutex::new(ARCReplacer::new(capacity)) })
+    }
+}
+
+impl Replacer for ARC {
+    fn record_access(&self, i: FrameID, page_id: PageID, _access_type: AccessType) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.record_access(i, page_id)
+    }
+
+    fn pin(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.pin(i)
+    }
+
+    fn unpin(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.unpin(i)
+    }
+
+    fn evict(&self) -> Option<FrameID> {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.evict()
+    }
+
+    fn remove(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.remove(i)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::page::PageID;
+    use crate::replacer::{AccessType, Replacer, ARC};
+
+    #[test]
+    fn test_arc() {
+        let replacer = ARC::new(4);
+        for i in 0..4 {
+            replacer.record_access(i, 10 + i as PageID, AccessType::Get);
+        }
+
+        // Frames 0 and 1 move to T2, and T1 is evicted from since it's over its target of 0
+        replacer.record_access(0, 10, AccessType::Get);
+        replacer.record_access(1, 11, AccessType::Get);
+        assert_eq!(Some(2), replacer.evict());
+        replacer.remove(2);
+
+        // Page 12 was evicted from T1 too soon, so T1 is given another frame and page 12 goes to
+        // T2. T1 only holds frame 3, so T2 is evicted from now.
+        replacer.record_access(2, 12, AccessType::Get);
+        assert_eq!(Some(0), replacer.evict());
+        replacer.remove(0);
+
+        // Reading page 10 back in takes the frame back from T1
+        replacer.record_access(0, 10, AccessType::Get);
+        assert_eq!(Some(3), replacer.evict());
+        replacer.remove(3);
+
+        // Pinned frames are skipped
+        replacer.pin(1);
+        replacer.pin(2);
+        replacer.pin(0);
+        assert_eq!(None, replacer.evict());
+        replacer.unpin(2);
+        assert_eq!(Some(2), replacer.evict());
+    }
+}
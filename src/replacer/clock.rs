@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex};
+
+use crate::page::PageID;
+use crate::page_cache::FrameID;
+use crate::replacer::{AccessType, Replacer};
+
+struct ClockFrame {
+    referenced: bool,
+    pins: u64,
+}
+
+/// Frames sit on a circle in order of their IDs, and the hand sweeps around the circle clearing
+/// reference bits until it finds an unpinned frame whose bit is already clear
+#[derive(Default)]
+pub struct ClockReplacer {
+    frames: Vec<Option<ClockFrame>>,
+    hand: usize,
+}
+
+impl ClockReplacer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn evict(&mut self) -> Option<FrameID> {
+        // Every bit has been cleared after one turn, so an unpinned frame is found by the second
+        for _ in 0..self.frames.len() * 2 {
+            let i = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            match &mut self.frames[i] {
+                Some(frame) if frame.pins == 0 && frame.referenced => frame.referenced = false,
+                Some(frame) if frame.pins == 0 => return Some(i),
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    pub fn record_access(&mut self, i: FrameID) {
+        if self.frames.len() <= i {
+            self.frames.resize_with(i + 1, || None);
+        }
+
+        let frame = self.frames[i].get_or_insert(ClockFrame { referenced: false, pins: 0 });
+        frame.referenced = true;
+    }
+
+    pub fn pin(&mut self, i: FrameID) {
+        if let Some(Some(frame)) = self.frames.get_mut(i) {
+            frame.pins += 1;
+        }
+    }
+
+    pub fn unpin(&mut self, i: FrameID) {
+        if let Some(Some(frame)) = self.frames.get_mut(i) {
+            frame.pins -= 1;
+        }
+    }
+
+    pub fn remove(&mut self, i: FrameID) {
+        if let Some(frame) = self.frames.get_mut(i) {
+            *frame = None;
+        }
+    }
+}
+
+/// The CLOCK policy, an approximation of LRU where an access only sets a frame's reference bit.
+/// A frame that's been accessed since the hand last passed it is given another turn.
+pub struct Clock {
+    inner: Mutex<ClockReplacer>,
+}
+
+impl Clock {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { inner: Mutex::new(ClockReplacer::new()) })
+    }
+}
+
+impl Replacer for Clock {
+    fn record_access(&self, i: FrameID, _page_id: PageID, _access_type: AccessType) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.record_access(i)
+    }
+
+    fn pin(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.pin(i)
+    }
+
+    fn unpin(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.unpin(i)
+    }
+
+    fn evict(&self) -> Option<FrameID> {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.evict()
+    }
+
+    fn remove(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.remove(i)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::page::PageID;
+    use crate::replacer::{AccessType, Clock, Replacer};
+
+    #[test]
+    fn test_clock() {
+        let replacer = Clock::new();
+        for i in 0..4 {
+            replacer.record_access(i, i as PageID, AccessType::Get);
+        }
+
+        // Every frame has been referenced, so the hand goes all the way round once
+        assert_eq!(Some(0), replacer.evict());
+        replacer.remove(0);
+
+        // Frame 1 is pinned and frame 2 is referenced again, so they're both skipped
+        replacer.pin(1);
+        replacer.record_access(2, 2, AccessType::Get);
+        assert_eq!(Some(3), replacer.evict());
+        replacer.remove(3);
+        assert_eq!(Some(2), replacer.evict());
+
+        replacer.remove(2);
+        assert_eq!(None, replacer.evict());
+        replacer.unpin(1);
+        assert_eq!(Some(1), replacer.evict());
+    }
+}
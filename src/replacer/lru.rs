@@ -1,9 +1,11 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{Arc, Mutex},
 };
 
+use crate::page::PageID;
 use crate::page_cache::FrameID;
+use crate::replacer::{AccessType, Replacer};
 
 #[derive(Debug)]
 struct LRUKNode {
@@ -37,11 +39,6 @@ pub struct LRUKReplacer {
     k: usize,
 }
 
-pub enum AccessType {
-    Get,
-    Scan,
-}
-
 impl LRUKReplacer {
     pub fn new(k: usize) -> Self {
         Self { k, ..Default::default() }
@@ -84,7 +81,7 @@ impl LRUKReplacer {
         Some(earliest.0)
     }
 
-    pub fn record_access(&mut self, i: FrameID, _access_type: AccessType) {
+    pub fn record_access(&mut self, i: FrameID, _page_id: PageID, _access_type: AccessType) {
         match self.nodes.entry(i) {
             Entry::Occupied(mut node) => {
                 node.get_mut().history.push(self.current_ts);
@@ -124,6 +121,8 @@ impl LRUKReplacer {
     }
 }
 
+/// The LRU-K policy, which evicts the frame whose Kth most recent access is the oldest. Frames
+/// with fewer than K accesses are evicted first, in order of their most recent access.
 pub struct LRU {
     inner: Mutex<LRUKReplacer>,
 }
@@ -132,32 +131,30 @@ impl LRU {
     pub fn new(k: usize) -> Arc<Self> {
         Arc::new(Self { inner: Mutex::new(LRUKReplacer::new(k)) })
     }
+}
 
-    pub fn lock(&self) -> MutexGuard<'_, LRUKReplacer> {
-        self.inner.lock().expect("todo")
-    }
-
-    pub fn evict(&self) -> Option<FrameID> {
+impl Replacer for LRU {
+    fn record_access(&self, i: FrameID, page_id: PageID, access_type: AccessType) {
         let mut replacer = self.inner.lock().expect("todo");
-        replacer.evict()
+        replacer.record_access(i, page_id, access_type)
     }
 
-    pub fn record_access(&self, i: FrameID, a: AccessType) {
+    fn pin(&self, i: FrameID) {
         let mut replacer = self.inner.lock().expect("todo");
-        replacer.record_access(i, a)
+        replacer.pin(i)
     }
 
-    pub fn pin(&self, i: FrameID) {
+    fn unpin(&self, i: FrameID) {
         let mut replacer = self.inner.lock().expect("todo");
-        replacer.pin(i)
+        replacer.unpin(i)
     }
 
-    pub fn unpin(&self, i: FrameID) {
+    fn evict(&self) -> Option<FrameID> {
         let mut replacer = self.inner.lock().expect("todo");
-        replacer.unpin(i)
+        replacer.evict()
     }
 
-    pub fn remove(&self, i: FrameID) {
+    fn remove(&self, i: FrameID) {
         let mut replacer = self.inner.lock().expect("todo");
         replacer.remove(i)
     }
@@ -165,7 +162,8 @@ impl LRU {
 
 #[cfg(test)]
 mod test {
-    use super::{AccessType, LRU};
+    use crate::page::PageID;
+    use crate::replacer::{AccessType, Replacer, LRU};
 
     #[test]
     fn test_evict() {
@@ -175,7 +173,7 @@ mod test {
         {
             for i in 0..8 {
                 replacer.remove(i);
-                replacer.record_access(i, AccessType::Get);
+                replacer.record_access(i, i as PageID, AccessType::Get);
                 replacer.pin(i);
             }
 
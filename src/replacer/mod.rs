@@ -0,0 +1,115 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::page::PageID;
+use crate::page_cache::FrameID;
+
+mod arc;
+mod clock;
+mod lru;
+mod two_q;
+
+pub use {
+    arc::{ARCReplacer, ARC},
+    clock::{Clock, ClockReplacer},
+    lru::{LRUKReplacer, LRU},
+    two_q::{TwoQ, TwoQReplacer},
+};
+
+pub enum AccessType {
+    Get,
+    Scan,
+}
+
+/// Picks the frame of the page cache to evict when a page is read into a full cache. A frame is
+/// tracked from its first access until it's removed, and can't be evicted whilst it's pinned. The
+/// page cache shares its replacer between threads, so each replacer does its own locking.
+pub trait Replacer: Send + Sync {
+    /// Records an access to the page held in frame `i`
+    fn record_access(&self, i: FrameID, page_id: PageID, access_type: AccessType);
+
+    fn pin(&self, i: FrameID);
+
+    fn unpin(&self, i: FrameID);
+
+    /// Returns an unpinned frame to evict, or `None` if every frame is pinned. The frame should
+    /// be removed before it's used for another page.
+    fn evict(&self) -> Option<FrameID>;
+
+    /// Stops tracking the frame, once its page has been evicted or dropped
+    fn remove(&self, i: FrameID);
+}
+
+/// A set that keeps its items in the order they were last pushed, for the lists of frames and
+/// pages that the replacers evict from in order of recency
+struct Queue<T> {
+    order: BTreeMap<u64, T>,
+    positions: HashMap<T, u64>,
+    next: u64,
+}
+
+impl<T> Queue<T>
+where
+    T: Copy + Eq + Hash,
+{
+    fn new() -> Self {
+        Self { order: BTreeMap::new(), positions: HashMap::new(), next: 0 }
+    }
+
+    /// Moves the item to the back of the queue, adding it if it isn't there yet
+    fn push(&mut self, item: T) {
+        self.remove(&item);
+        self.order.insert(self.next, item);
+        self.positions.insert(item, self.next);
+        self.next += 1;
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        match self.positions.remove(item) {
+            Some(position) => self.order.remove(&position).is_some(),
+            None => false,
+        }
+    }
+
+    /// Removes the item at the front of the queue, which was pushed the longest ago
+    fn pop(&mut self) -> Option<T> {
+        let (_, item) = self.order.pop_first()?;
+        self.positions.remove(&item);
+
+        Some(item)
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        self.positions.contains_key(item)
+    }
+
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Iterates from the front of the queue
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.order.values()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Queue;
+
+    #[test]
+    fn test_queue() {
+        let mut queue = Queue::new();
+        for i in 0..5 {
+            queue.push(i);
+        }
+
+        queue.push(1);
+        assert!(queue.remove(&3));
+        assert!(!queue.remove(&3));
+        assert_eq!(vec![0, 2, 4, 1], queue.iter().copied().collect::<Vec<_>>());
+        assert_eq!(Some(0), queue.pop());
+        assert!(!queue.contains(&0) && queue.contains(&2));
+        assert_eq!(3, queue.len());
+    }
+}
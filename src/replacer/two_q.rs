@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::page::PageID;
+use crate::page_cache::FrameID;
+use crate::replacer::{AccessType, Queue, Replacer};
+
+/// Frames are first put on A1in, and a page evicted from A1in is remembered on A1out. A page
+/// that's read back in whilst A1out still remembers it goes on Am, which is kept in LRU order.
+pub struct TwoQReplacer {
+    a1in: Queue<FrameID>,
+    a1out: Queue<PageID>,
+    am: Queue<FrameID>,
+    pages: HashMap<FrameID, PageID>,
+    pins: HashMap<FrameID, u64>,
+    /// How many frames A1in holds before it's evicted from ahead of Am
+    kin: usize,
+    /// How many evicted pages A1out remembers
+    kout: usize,
+}
+
+impl TwoQReplacer {
+    /// Creates a replacer for a cache of `capacity` frames, with A1in taking a quarter of the
+    /// cache and A1out remembering half as many pages as the cache holds
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            a1in: Queue::new(),
+            a1out: Queue::new(),
+            am: Queue::new(),
+            pages: HashMap::new(),
+            pins: HashMap::new(),
+            kin: (capacity / 4).max(1),
+            kout: (capacity / 2).max(1),
+        }
+    }
+
+    pub fn evict(&mut self) -> Option<FrameID> {
+        let unpinned = |queue: &Queue<FrameID>| {
+            queue.iter().copied().find(|i| self.pins.get(i).is_none_or(|pins| *pins == 0))
+        };
+        let i = match self.a1in.len() > self.kin {
+            true => unpinned(&self.a1in).or_else(|| unpinned(&self.am)),
+            false => unpinned(&self.am).or_else(|| unpinned(&self.a1in)),
+        }?;
+
+        let page_id = self.pages.remove(&i).expect("a tracked frame should hold a page");
+        if self.a1in.remove(&i) {
+            self.a1out.push(page_id);
+            while self.a1out.len() > self.kout {
+                self.a1out.pop();
+            }
+        }
+        self.am.remove(&i);
+        self.pins.remove(&i);
+
+        Some(i)
+    }
+
+    pub fn record_access(&mut self, i: FrameID, page_id: PageID) {
+        // A page isn't promoted by accesses whilst it's on A1in, since they're usually close
+        // together, such as every row on a page being read by a scan
+        if self.a1in.contains(&i) {
+            return;
+        }
+        if self.am.contains(&i) {
+            self.am.push(i);
+            return;
+        }
+
+        self.pages.insert(i, page_id);
+        if self.a1out.remove(&page_id) {
+            self.am.push(i);
+        } else {
+            self.a1in.push(i);
+        }
+    }
+
+    pub fn pin(&mut self, i: FrameID) {
+        if self.pages.contains_key(&i) {
+            *self.pins.entry(i).or_default() += 1;
+        }
+    }
+
+    pub fn unpin(&mut self, i: FrameID) {
+        if let Some(pins) = self.pins.get_mut(&i) {
+            *pins -= 1;
+        }
+    }
+
+    pub fn remove(&mut self, i: FrameID) {
+        self.a1in.remove(&i);
+        self.am.remove(&i);
+        self.pages.remove(&i);
+        self.pins.remove(&i);
+    }
+}
+
+/// The full version of the 2Q policy. A page is only kept on the LRU list once it's been read in
+/// again shortly after being evicted, so a scan only churns through a quarter of the cache instead
+/// of pushing out the pages that are used repeatedly.
+pub struct TwoQ {
+    inner: Mutex<TwoQReplacer>,
+}
+
+impl TwoQ {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self { inner: Mutex::new(TwoQReplacer::new(capacity)) })
+    }
+}
+
+impl Replacer for TwoQ {
+    fn record_access(&self, i: FrameID, page_id: PageID, _access_type: AccessType) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.record_access(i, page_id)
+    }
+
+    fn pin(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.pin(i)
+    }
+
+    fn unpin(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.unpin(i)
+    }
+
+    fn evict(&self) -> Option<FrameID> {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.evict()
+    }
+
+    fn remove(&self, i: FrameID) {
+        let mut replacer = self.inner.lock().expect("todo");
+        replacer.remove(i)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::page::PageID;
+    use crate::replacer::{AccessType, Replacer, TwoQ};
+
+    #[test]
+    fn test_two_q() {
+        // A1in holds a single frame and A1out remembers two pages
+        let replacer = TwoQ::new(4);
+        for i in 0..4 {
+            replacer.record_access(i, 10 + i as PageID, AccessType::Get);
+        }
+
+        // Accessing a page again whilst it's on A1in doesn't save it
+        replacer.record_access(0, 10, AccessType::Get);
+        assert_eq!(Some(0), replacer.evict());
+        replacer.remove(0);
+        assert_eq!(Some(1), replacer.evict());
+        replacer.remove(1);
+
+        // Page 10 is read back in whilst A1out remembers it, so it goes on Am, and A1in is still
+        // evicted from first whilst it holds more than its share
+        replacer.record_access(0, 10, AccessType::Get);
+        assert_eq!(Some(2), replacer.evict());
+        replacer.remove(2);
+
+        // Otherwise Am is evicted from, unless every frame on it is pinned
+        replacer.pin(0);
+        assert_eq!(Some(3), replacer.evict());
+        replacer.remove(3);
+        assert_eq!(None, replacer.evict());
+        replacer.unpin(0);
+
+        // A1out has forgotten page 11 for 12 and 13, so only page 12 goes on Am
+        replacer.record_access(1, 11, AccessType::Get);
+        replacer.record_access(2, 12, AccessType::Get);
+        replacer.record_access(3, 14, AccessType::Get);
+        assert_eq!(Some(1), replacer.evict());
+        replacer.remove(1);
+        assert_eq!(Some(0), replacer.evict());
+        replacer.remove(0);
+        assert_eq!(Some(2), replacer.evict());
+    }
+}
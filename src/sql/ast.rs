@@ -7,6 +7,8 @@ pub enum Statement {
     Update(Update),
     Delete(Delete),
     Create(Create),
+    CreateIndex(CreateIndex),
+    DropIndex(DropIndex),
     Explain(Explain),
 }
 
@@ -120,6 +122,19 @@ pub enum FunctionName {
     Concat,
 }
 
+impl FunctionName {
+    pub fn is_aggregate(&self) -> bool {
+        matches!(
+            self,
+            FunctionName::Min
+                | FunctionName::Max
+                | FunctionName::Sum
+                | FunctionName::Avg
+                | FunctionName::Count
+        )
+    }
+}
+
 impl std::fmt::Display for FunctionName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -226,6 +241,7 @@ pub struct Query {
     pub joins: Vec<Join>,
     pub filter: Option<Expr>,
     pub group: Vec<Expr>,
+    pub having: Option<Expr>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -239,11 +255,31 @@ pub enum FromTable {
 pub enum JoinConstraint {
     On(Expr),
     Using(Vec<Ident>),
+    /// A USING join on all of the column names the tables have in common
+    Natural,
+    /// Cross joins, including tables separated by commas in the FROM clause
+    None,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum JoinType {
     Inner,
+    Left,
+    Right,
+    Full,
+    Cross,
+}
+
+impl std::fmt::Display for JoinType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinType::Inner => write!(f, "INNER"),
+            JoinType::Left => write!(f, "LEFT"),
+            JoinType::Right => write!(f, "RIGHT"),
+            JoinType::Full => write!(f, "FULL"),
+            JoinType::Cross => write!(f, "CROSS"),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -253,10 +289,24 @@ pub struct Join {
     pub constraint: JoinConstraint,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct OrderByExpr {
-    pub exprs: Vec<Expr>,
+    pub expr: Expr,
     pub desc: bool,
+    /// Unless given, nulls come last in ascending order and first in descending order, like
+    /// Postgres
+    pub nulls_first: bool,
+}
+
+impl std::fmt::Display for OrderByExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.expr, if self.desc { "DESC" } else { "ASC" })?;
+        match (self.desc, self.nulls_first) {
+            (false, true) => write!(f, " NULLS FIRST"),
+            (true, false) => write!(f, " NULLS LAST"),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -281,7 +331,7 @@ impl std::fmt::Display for SelectItem {
 #[derive(PartialEq, Debug)]
 pub struct Select {
     pub body: Query,
-    pub order: Option<OrderByExpr>,
+    pub order: Vec<OrderByExpr>,
     pub limit: Option<Expr>,
 }
 
@@ -322,6 +372,27 @@ pub struct Create {
     pub columns: Vec<ColumnDef>,
 }
 
+#[derive(PartialEq, Debug)]
+pub struct CreateIndex {
+    pub name: Ident,
+    pub table: Ident,
+    pub columns: Vec<Ident>,
+    pub unique: bool,
+    pub method: IndexMethod,
+}
+
+/// The kind of index given by `USING`, which is a B-tree if it's left out
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum IndexMethod {
+    BTree,
+    Hash,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct DropIndex {
+    pub name: Ident,
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Explain {
     pub statement: Box<Statement>,
@@ -1,5 +1,5 @@
 use crate::catalog::schema::Type;
-use crate::sql::{Expr, Function, FunctionName, Literal, Op, Parser, SelectItem};
+use crate::sql::{Expr, Function, FunctionName, Literal, Op, OrderByExpr, Parser, SelectItem};
 
 impl From<i32> for Literal {
     fn from(int: i32) -> Self {
@@ -32,7 +32,8 @@ impl From<&Literal> for Type {
             Literal::String(_) => Type::Varchar,
             Literal::Bool(_) => Type::Bool,
             Literal::Decimal(_) => todo!(),
-            Literal::Null => todo!(),
+            // Untyped nulls are treated as text, like in Postgres
+            Literal::Null => Type::Varchar,
         }
     }
 }
@@ -43,6 +44,12 @@ impl From<Expr> for SelectItem {
     }
 }
 
+impl From<Expr> for OrderByExpr {
+    fn from(expr: Expr) -> Self {
+        expr.asc()
+    }
+}
+
 pub fn alias(expr: Expr, alias: impl Into<String>) -> SelectItem {
     SelectItem::AliasedExpr { expr, alias: alias.into() }
 }
@@ -167,4 +174,22 @@ impl Expr {
     pub fn or(self, rhs: impl Into<Expr>) -> Self {
         Expr::BinaryOp { left: Box::new(self), op: Op::Or, right: Box::new(rhs.into()) }
     }
+
+    pub fn asc(self) -> OrderByExpr {
+        OrderByExpr { expr: self, desc: false, nulls_first: false }
+    }
+
+    pub fn desc(self) -> OrderByExpr {
+        OrderByExpr { expr: self, desc: true, nulls_first: true }
+    }
+}
+
+impl OrderByExpr {
+    pub fn nulls_first(self) -> Self {
+        Self { nulls_first: true, ..self }
+    }
+
+    pub fn nulls_last(self) -> Self {
+        Self { nulls_first: false, ..self }
+    }
 }
@@ -2,9 +2,9 @@ use crate::sql::Explain;
 
 use super::{
     ast::{
-        Assignment, ColumnDef, ColumnType, Create, Delete, Expr, FromTable, Function, FunctionName,
-        Ident, Insert, InsertInput, Join, JoinConstraint, JoinType, Literal, Op, OrderByExpr,
-        Query, Select, SelectItem, Statement, Update,
+        Assignment, ColumnDef, ColumnType, Create, CreateIndex, Delete, DropIndex, Expr, FromTable,
+        Function, FunctionName, Ident, IndexMethod, Insert, InsertInput, Join, JoinConstraint,
+        JoinType, Literal, Op, OrderByExpr, Query, Select, SelectItem, Statement, Update,
     },
     tokeniser::{Keyword, Location, Token, Tokeniser},
 };
@@ -64,8 +64,7 @@ impl Parser {
     pub fn parse_statements(&mut self) -> Result<Vec<Statement>> {
         let mut statements = Vec::new();
 
-        loop {
-            let Some(statement) = self.parse_statement()? else { break };
+        while let Some(statement) = self.parse_statement()? {
             statements.push(statement);
         }
 
@@ -82,7 +81,11 @@ impl Parser {
                         Keyword::Insert => Statement::Insert(self.parse_insert()?),
                         Keyword::Update => Statement::Update(self.parse_update()?),
                         Keyword::Delete => Statement::Delete(self.parse_delete()?),
-                        Keyword::Create => Statement::Create(self.parse_create()?),
+                        Keyword::Create if self.peek_n(1).0 == Token::Keyword(Keyword::Table) => {
+                            Statement::Create(self.parse_create()?)
+                        }
+                        Keyword::Create => Statement::CreateIndex(self.parse_create_index()?),
+                        Keyword::Drop => Statement::DropIndex(self.parse_drop_index()?),
                         Keyword::Explain => Statement::Explain(self.parse_explain()?),
                         _ => Err(Unexpected(&token, &location))?,
                     };
@@ -112,24 +115,42 @@ impl Parser {
     fn parse_select(&mut self) -> Result<Select> {
         let body = self.parse_query()?;
 
-        let order = if self.check_keywords(&[Keyword::Order, Keyword::By]) {
-            let mut exprs = Vec::new();
+        let mut order = Vec::new();
+        if self.check_keywords(&[Keyword::Order, Keyword::By]) {
             while {
-                exprs.push(self.parse_expr(0)?);
+                order.push(self.parse_order_by_expr()?);
                 self.check_tokens(&[Token::Comma])
             } {}
+        }
 
-            let order = OrderByExpr { exprs, desc: self.check_keywords(&[Keyword::Desc]) };
+        let limit =
+            if self.check_keywords(&[Keyword::Limit]) { Some(self.parse_expr(0)?) } else { None };
 
-            Some(order)
+        Ok(Select { body, order, limit })
+    }
+
+    fn parse_order_by_expr(&mut self) -> Result<OrderByExpr> {
+        let expr = self.parse_expr(0)?;
+
+        let desc = if self.check_keywords(&[Keyword::Desc]) {
+            true
         } else {
-            None
+            self.check_keywords(&[Keyword::Asc]);
+            false
         };
 
-        let limit =
-            if self.check_keywords(&[Keyword::Limit]) { Some(self.parse_expr(0)?) } else { None };
+        let nulls_first = if self.check_keywords(&[Keyword::Nulls]) {
+            if self.check_keywords(&[Keyword::First]) {
+                true
+            } else {
+                self.parse_keywords(&[Keyword::Last])?;
+                false
+            }
+        } else {
+            desc
+        };
 
-        Ok(Select { body, order, limit })
+        Ok(OrderByExpr { expr, desc, nulls_first })
     }
 
     fn parse_query(&mut self) -> Result<Query> {
@@ -157,7 +178,10 @@ impl Parser {
             vec![]
         };
 
-        Ok(Query { projection, from, joins, filter, group })
+        let having =
+            if self.check_keywords(&[Keyword::Having]) { Some(self.parse_expr(0)?) } else { None };
+
+        Ok(Query { projection, from, joins, filter, group, having })
     }
 
     fn parse_from(&mut self) -> Result<FromTable> {
@@ -209,16 +233,22 @@ impl Parser {
     fn parse_joins(&mut self) -> Result<Vec<Join>> {
         let mut joins = Vec::new();
 
-        if !self.check_keywords(&[Keyword::Join]) {
-            return Ok(joins);
-        }
-
-        while {
+        loop {
+            let natural = self.check_keywords(&[Keyword::Natural]);
+            let (token, location) = self.peek();
+            let ty = match self.parse_join_type()? {
+                Some(JoinType::Cross) | None if natural => Err(Unexpected(&token, &location))?,
+                Some(ty) => ty,
+                None => break,
+            };
             let from = self.parse_from()?;
-            if self.check_keywords(&[Keyword::On]) {
-                let constraint = JoinConstraint::On(self.parse_expr(0)?);
-                let ty = JoinType::Inner;
-                joins.push(Join { from, ty, constraint })
+
+            let constraint = if natural {
+                JoinConstraint::Natural
+            } else if ty == JoinType::Cross {
+                JoinConstraint::None
+            } else if self.check_keywords(&[Keyword::On]) {
+                JoinConstraint::On(self.parse_expr(0)?)
             } else if self.check_keywords(&[Keyword::Using]) {
                 let mut columns = Vec::new();
 
@@ -229,17 +259,47 @@ impl Parser {
                 } {}
                 self.parse_tokens(&[Token::RParen])?;
 
-                let constraint = JoinConstraint::Using(columns);
-                let ty = JoinType::Inner;
-                joins.push(Join { from, ty, constraint })
-            }
+                JoinConstraint::Using(columns)
+            } else {
+                let (token, location) = self.peek();
+                Err(Unexpected(&token, &location))?
+            };
 
-            self.check_keywords(&[Keyword::Join])
-        } {}
+            joins.push(Join { from, ty, constraint })
+        }
 
         Ok(joins)
     }
 
+    /// Parses the keywords before a joined table, or a comma which is the same as a cross join
+    fn parse_join_type(&mut self) -> Result<Option<JoinType>> {
+        let ty = if self.check_tokens(&[Token::Comma])
+            || self.check_keywords(&[Keyword::Cross, Keyword::Join])
+        {
+            JoinType::Cross
+        } else if self.check_keywords(&[Keyword::Join])
+            || self.check_keywords(&[Keyword::Inner, Keyword::Join])
+        {
+            JoinType::Inner
+        } else if self.check_keywords(&[Keyword::Left]) {
+            self.check_keywords(&[Keyword::Outer]);
+            self.parse_keywords(&[Keyword::Join])?;
+            JoinType::Left
+        } else if self.check_keywords(&[Keyword::Right]) {
+            self.check_keywords(&[Keyword::Outer]);
+            self.parse_keywords(&[Keyword::Join])?;
+            JoinType::Right
+        } else if self.check_keywords(&[Keyword::Full]) {
+            self.check_keywords(&[Keyword::Outer]);
+            self.parse_keywords(&[Keyword::Join])?;
+            JoinType::Full
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(ty))
+    }
+
     fn parse_insert(&mut self) -> Result<Insert> {
         self.parse_keywords(&[Keyword::Insert, Keyword::Into])?;
 
@@ -325,6 +385,44 @@ impl Parser {
         Ok(Create { name, columns })
     }
 
+    fn parse_create_index(&mut self) -> Result<CreateIndex> {
+        self.parse_keywords(&[Keyword::Create])?;
+        let unique = self.check_keywords(&[Keyword::Unique]);
+        self.parse_keywords(&[Keyword::Index])?;
+
+        let name = self.parse_ident()?;
+        self.parse_keywords(&[Keyword::On])?;
+        let table = self.parse_ident()?;
+
+        self.parse_tokens(&[Token::LParen])?;
+        let mut columns = Vec::new();
+        while {
+            columns.push(self.parse_ident()?);
+            self.check_tokens(&[Token::Comma])
+        } {}
+        self.parse_tokens(&[Token::RParen])?;
+
+        let method = if self.check_keywords(&[Keyword::Using]) {
+            let (token, location) = self.next();
+            match token {
+                Token::Keyword(Keyword::Btree) => IndexMethod::BTree,
+                Token::Keyword(Keyword::Hash) => IndexMethod::Hash,
+                _ => Err(Unexpected(&token, &location))?,
+            }
+        } else {
+            IndexMethod::BTree
+        };
+
+        Ok(CreateIndex { name, table, columns, unique, method })
+    }
+
+    fn parse_drop_index(&mut self) -> Result<DropIndex> {
+        self.parse_keywords(&[Keyword::Drop, Keyword::Index])?;
+        let name = self.parse_ident()?;
+
+        Ok(DropIndex { name })
+    }
+
     fn parse_column_def(&mut self) -> Result<ColumnDef> {
         let (token, location) = self.next();
         let name = match token {
@@ -729,9 +827,9 @@ mod test {
     use crate::sql::Explain;
 
     use super::{
-        Assignment, ColumnDef, ColumnType, Create, Delete, Expr, FromTable, Function, FunctionName,
-        Ident, Insert, InsertInput, Join, JoinConstraint, JoinType, Literal, Op, OrderByExpr,
-        Parser, Query, Select, SelectItem, Statement, Update,
+        Assignment, ColumnDef, ColumnType, Create, CreateIndex, Delete, DropIndex, Expr, FromTable,
+        Function, FunctionName, Ident, IndexMethod, Insert, InsertInput, Join, JoinConstraint,
+        JoinType, Literal, Op, OrderByExpr, Parser, Query, Select, SelectItem, Statement, Update,
     };
 
     #[test]
@@ -754,6 +852,44 @@ mod test {
         assert_eq!(want, have)
     }
 
+    #[test]
+    fn test_index_statements() {
+        let input = "
+            CREATE INDEX i1 ON t1 (c1);
+            CREATE UNIQUE INDEX i2 ON t1 (c2, c1) USING HASH;
+            create index i3 on t1 (c1) using btree;
+            DROP INDEX i1";
+
+        let index = |name: &str, columns: &[&str], unique, method| {
+            Statement::CreateIndex(CreateIndex {
+                name: Ident::Single(name.into()),
+                table: Ident::Single("t1".into()),
+                columns: columns.iter().map(|column| Ident::Single(column.to_string())).collect(),
+                unique,
+                method,
+            })
+        };
+        let want = vec![
+            index("i1", &["c1"], false, IndexMethod::BTree),
+            index("i2", &["c2", "c1"], true, IndexMethod::Hash),
+            index("i3", &["c1"], false, IndexMethod::BTree),
+            Statement::DropIndex(DropIndex { name: Ident::Single("i1".into()) }),
+        ];
+
+        let have = Parser::new(input).unwrap().parse_statements().unwrap();
+        assert_eq!(want, have);
+
+        assert!(Parser::new("CREATE INDEX i1 ON t1").unwrap().parse_statements().is_err());
+        assert!(Parser::new("CREATE INDEX i1 ON t1 (c1) USING t1")
+            .unwrap()
+            .parse_statements()
+            .is_err());
+        assert!(Parser::new("CREATE UNIQUE TABLE t1 (c1 INT)")
+            .unwrap()
+            .parse_statements()
+            .is_err());
+    }
+
     #[test]
     fn test_parse_projection() {
         let input = "t1.*, *, s1.t1.c1";
@@ -942,7 +1078,8 @@ mod test {
                     op: Op::Gt,
                     right: Box::new(Expr::Ident(Ident::Compound(vec!["t2".into(), "c2".into()])))
                 }),
-                group: vec![]
+                group: vec![],
+                having: None,
             }))),
         }
     );
@@ -1015,6 +1152,47 @@ mod test {
         assert_eq!(want, have)
     }
 
+    #[test]
+    fn test_parse_join_types() {
+        let input = "left join t2 using (c1) right outer join t3 using (c1) full join t4 using (c1)
+            cross join t5, t6 inner join t7 using (c1)";
+
+        let join = |table: &str, ty| Join {
+            from: FromTable::Table { name: Ident::Single(table.into()), alias: None },
+            ty,
+            constraint: if ty == JoinType::Cross {
+                JoinConstraint::None
+            } else {
+                JoinConstraint::Using(vec![Ident::Single("c1".into())])
+            },
+        };
+        let want = vec![
+            join("t2", JoinType::Left),
+            join("t3", JoinType::Right),
+            join("t4", JoinType::Full),
+            join("t5", JoinType::Cross),
+            join("t6", JoinType::Cross),
+            join("t7", JoinType::Inner),
+        ];
+        let have = Parser::new(input).unwrap().parse_joins().unwrap();
+        assert_eq!(want, have);
+
+        let input = "natural join t2 natural full outer join t3";
+        let join = |table: &str, ty| Join {
+            from: FromTable::Table { name: Ident::Single(table.into()), alias: None },
+            ty,
+            constraint: JoinConstraint::Natural,
+        };
+        let want = vec![join("t2", JoinType::Inner), join("t3", JoinType::Full)];
+        let have = Parser::new(input).unwrap().parse_joins().unwrap();
+        assert_eq!(want, have);
+
+        assert!(Parser::new("natural cross join t2").unwrap().parse_joins().is_err());
+        assert!(Parser::new("natural, t2").unwrap().parse_joins().is_err());
+        assert!(Parser::new("left t2 on c1 = c2").unwrap().parse_joins().is_err());
+        assert!(Parser::new("join t2").unwrap().parse_joins().is_err());
+    }
+
     #[test]
     fn test_parse_with_derived() {
         let input = "join (select * from t1) t1 using (c1)";
@@ -1027,6 +1205,7 @@ mod test {
                     joins: vec![],
                     filter: None,
                     group: vec![],
+                    having: None,
                 }),
                 alias: Some("t1".into()),
             },
@@ -1071,6 +1250,7 @@ mod test {
                 joins: vec![],
                 filter: None,
                 group: vec![],
+                having: None,
             }),
         };
         let have = Parser::new(input).unwrap().parse_insert().unwrap();
@@ -1124,7 +1304,8 @@ mod test {
 
     #[test]
     fn test_parse_select() {
-        let input = "select c1, count(distinct *), min(c1) from t1 group by c1 order by c1 limit 5";
+        let input = "select c1, count(distinct *), min(c1) from t1 group by c1 having min(c1) > 1
+            order by c1, c2 desc, c3 asc nulls first, c4 desc nulls last limit 5";
 
         let want = Select {
             body: Query {
@@ -1145,11 +1326,38 @@ mod test {
                 joins: vec![],
                 filter: None,
                 group: vec![Expr::Ident(Ident::Single("c1".into()))],
+                having: Some(Expr::BinaryOp {
+                    left: Box::new(Expr::Function(Function {
+                        name: FunctionName::Min,
+                        args: vec![Expr::Ident(Ident::Single("c1".into()))],
+                        distinct: false,
+                    })),
+                    op: Op::Gt,
+                    right: Box::new(Expr::Literal(Literal::Number("1".into()))),
+                }),
             },
-            order: Some(OrderByExpr {
-                exprs: vec![Expr::Ident(Ident::Single("c1".into()))],
-                desc: false,
-            }),
+            order: vec![
+                OrderByExpr {
+                    expr: Expr::Ident(Ident::Single("c1".into())),
+                    desc: false,
+                    nulls_first: false,
+                },
+                OrderByExpr {
+                    expr: Expr::Ident(Ident::Single("c2".into())),
+                    desc: true,
+                    nulls_first: true,
+                },
+                OrderByExpr {
+                    expr: Expr::Ident(Ident::Single("c3".into())),
+                    desc: false,
+                    nulls_first: true,
+                },
+                OrderByExpr {
+                    expr: Expr::Ident(Ident::Single("c4".into())),
+                    desc: true,
+                    nulls_first: false,
+                },
+            ],
             limit: Some(Expr::Literal(Literal::Number("5".into()))),
         };
         let have = Parser::new(input).unwrap().parse_select().unwrap();
@@ -1169,8 +1377,9 @@ mod test {
                     joins: vec![],
                     filter: None,
                     group: vec![],
+                    having: None,
                 },
-                order: None,
+                order: vec![],
                 limit: None,
             })),
         };
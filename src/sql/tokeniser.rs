@@ -48,37 +48,53 @@ pub(crate) enum Keyword {
     Asc,
     Avg,
     Between,
+    Btree,
     By,
     Concat,
     Contains,
     Count,
     Create,
+    Cross,
     Delete,
     Desc,
     Distinct,
+    Drop,
     Explain,
     False,
+    First,
     From,
+    Full,
     Group,
+    Hash,
+    Having,
     In,
+    Index,
+    Inner,
     Insert,
     Int,
     Into,
     Is,
     Join,
+    Last,
+    Left,
     Limit,
     Max,
     Min,
+    Natural,
     Not,
     Null,
+    Nulls,
     On,
     Or,
     Order,
+    Outer,
+    Right,
     Select,
     Set,
     Sum,
     Table,
     True,
+    Unique,
     Update,
     Using,
     Values,
@@ -96,37 +112,53 @@ impl TryFrom<String> for Keyword {
             "ASC" => Keyword::Asc,
             "AVG" => Keyword::Avg,
             "BETWEEN" => Keyword::Between,
+            "BTREE" => Keyword::Btree,
             "BY" => Keyword::By,
             "CONCAT" => Keyword::Concat,
             "CONTAINS" => Keyword::Contains,
             "COUNT" => Keyword::Count,
             "CREATE" => Keyword::Create,
+            "CROSS" => Keyword::Cross,
             "DELETE" => Keyword::Delete,
             "DESC" => Keyword::Desc,
             "DISTINCT" => Keyword::Distinct,
+            "DROP" => Keyword::Drop,
             "EXPLAIN" => Keyword::Explain,
             "FALSE" => Keyword::False,
+            "FIRST" => Keyword::First,
             "FROM" => Keyword::From,
+            "FULL" => Keyword::Full,
             "GROUP" => Keyword::Group,
+            "HASH" => Keyword::Hash,
+            "HAVING" => Keyword::Having,
             "IN" => Keyword::In,
+            "INDEX" => Keyword::Index,
+            "INNER" => Keyword::Inner,
             "INSERT" => Keyword::Insert,
             "INT" => Keyword::Int,
             "INTO" => Keyword::Into,
             "IS" => Keyword::Is,
             "JOIN" => Keyword::Join,
+            "LAST" => Keyword::Last,
+            "LEFT" => Keyword::Left,
             "LIMIT" => Keyword::Limit,
             "MAX" => Keyword::Max,
             "MIN" => Keyword::Min,
+            "NATURAL" => Keyword::Natural,
             "NOT" => Keyword::Not,
             "NULL" => Keyword::Null,
+            "NULLS" => Keyword::Nulls,
             "ON" => Keyword::On,
             "OR" => Keyword::Or,
             "ORDER" => Keyword::Order,
+            "OUTER" => Keyword::Outer,
+            "RIGHT" => Keyword::Right,
             "SELECT" => Keyword::Select,
             "SET" => Keyword::Set,
             "SUM" => Keyword::Sum,
             "TABLE" => Keyword::Table,
             "TRUE" => Keyword::True,
+            "UNIQUE" => Keyword::Unique,
             "UPDATE" => Keyword::Update,
             "USING" => Keyword::Using,
             "VALUES" => Keyword::Values,
@@ -37,3 +37,25 @@ macro_rules! storable_impl {
 }
 
 storable_impl!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// A value that's only known by its bytes, such as one that recovery puts back into an index
+/// without knowing the index's value type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Raw<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> Storable for Raw<N> {
+    const SIZE: usize = N;
+    type ByteArray = [u8; N];
+
+    fn into_bytes(self) -> [u8; N] {
+        self.0
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self(bytes.try_into().unwrap())
+    }
+
+    fn write_to(&self, dst: &mut [u8], pos: usize) {
+        dst[pos..pos + N].copy_from_slice(&self.0);
+    }
+}
@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use crate::catalog::schema::Schema;
-use crate::page::{DiskObject, PageID};
+use crate::page::{DiskObject, PageID, PAGE_SIZE};
 use crate::page_cache::{Result, SharedPageCache};
 use crate::table::node::Node;
 use crate::table::node::{TupleMeta, RID};
@@ -9,8 +9,8 @@ use crate::table::tuple::Data as TupleData;
 
 #[derive(Debug, Clone, Copy)]
 pub struct TableMeta {
-    first_page_id: PageID,
-    last_page_id: PageID,
+    pub first_page_id: PageID,
+    pub last_page_id: PageID,
 }
 
 impl Default for TableMeta {
@@ -43,6 +43,17 @@ impl List {
             last_page_id = page.id;
         }
 
+        // Pages may have been added since the meta was recorded
+        loop {
+            let page = pc.fetch_page(last_page_id)?;
+            let node = page.read_object::<Node>(&Schema::default());
+            if node.next_page_id == 0 {
+                break;
+            }
+
+            last_page_id = node.next_page_id;
+        }
+
         Ok(Arc::new(Self { pc, first_page_id, last_page_id: Mutex::new(last_page_id) }))
     }
 
@@ -59,6 +70,10 @@ impl List {
         *self.last_page_id.lock().expect("todo")
     }
 
+    pub fn meta(&self) -> TableMeta {
+        TableMeta { first_page_id: self.first_page_id, last_page_id: self.last_page_id() }
+    }
+
     fn last_page_id_mut(&self) -> std::sync::MutexGuard<'_, PageID> {
         self.last_page_id.lock().expect("todo")
     }
@@ -75,6 +90,7 @@ impl List {
         })
     }
 
+    /// Appends the tuple to the list, returning `None` if it's too large to fit on a page
     pub fn insert(&self, tuple_data: &TupleData) -> Result<Option<RID>> {
         self.insert_with_meta(tuple_data, &TupleMeta { deleted: false })
     }
@@ -84,6 +100,14 @@ impl List {
         tuple_data: &TupleData,
         meta: &TupleMeta,
     ) -> Result<Option<RID>> {
+        let txn = self.pc.begin();
+        let rid = self._insert_with_meta(tuple_data, meta)?;
+        txn.commit()?;
+
+        Ok(rid)
+    }
+
+    fn _insert_with_meta(&self, tuple_data: &TupleData, meta: &TupleMeta) -> Result<Option<RID>> {
         let mut last_page_id = self.last_page_id_mut();
         let page = self.pc.fetch_page(*last_page_id)?;
         let mut page_w = page.write();
@@ -94,8 +118,9 @@ impl List {
             return Ok(Some(RID { page_id: *last_page_id, slot_id }));
         }
 
-        if node.len() == 0 {
-            todo!("tuple too large error")
+        // The tuple won't fit on a new page either
+        if !Self::fits(tuple_data) {
+            return Ok(None);
         }
 
         // Insert into a new page and set the next pointer
@@ -125,8 +150,91 @@ impl List {
         Ok(node.get(&rid))
     }
 
-    pub fn update(&mut self, _meta: &TupleMeta) -> Result<()> {
-        todo!()
+    /// Marks the tuple as deleted, returning false if it doesn't exist or was already deleted
+    pub fn delete(&self, rid: RID) -> Result<bool> {
+        let txn = self.pc.begin();
+
+        let page = self.pc.fetch_page(rid.page_id)?;
+        let mut node = page.write_object::<Node>(&Schema::default());
+        let deleted = node.delete(&rid);
+        drop(node);
+
+        txn.commit()?;
+
+        Ok(deleted)
+    }
+
+    /// Gives back every page in the list. The list must not be used afterwards.
+    pub fn deallocate(&self) -> Result<()> {
+        let txn = self.pc.begin();
+
+        let mut page_id = self.first_page_id;
+        loop {
+            let page = self.pc.fetch_page(page_id)?;
+            let next_page_id = page.read_object::<Node>(&Schema::default()).next_page_id;
+            self.pc.deallocate_page(page_id)?;
+
+            if next_page_id == 0 {
+                break;
+            }
+            page_id = next_page_id;
+        }
+
+        txn.commit()
+    }
+
+    /// Returns the number of pages in the list
+    pub fn pages(&self) -> Result<usize> {
+        let mut pages = 1;
+        let mut page_id = self.first_page_id;
+        loop {
+            let page = self.pc.fetch_page(page_id)?;
+            let next_page_id = page.read_object::<Node>(&Schema::default()).next_page_id;
+            if next_page_id == 0 {
+                break;
+            }
+
+            pages += 1;
+            page_id = next_page_id;
+        }
+
+        Ok(pages)
+    }
+
+    /// Replaces the tuple, returning its new RID or None if it doesn't exist or has been deleted.
+    /// The tuple is updated in place if it fits, otherwise it's deleted and reinserted at the end
+    /// of the list.
+    pub fn update(&self, rid: RID, tuple_data: &TupleData) -> Result<Option<RID>> {
+        let txn = self.pc.begin();
+
+        let page = self.pc.fetch_page(rid.page_id)?;
+        let mut node = page.write_object::<Node>(&Schema::default());
+        if node.update(&rid, tuple_data) {
+            drop(node);
+            txn.commit()?;
+            return Ok(Some(rid));
+        }
+
+        if node.get(&rid).is_none_or(|(meta, _)| meta.deleted) {
+            return Ok(None);
+        }
+        drop(node);
+
+        // The old tuple is only deleted once the new one has somewhere to go
+        let Some(new_rid) = self._insert_with_meta(tuple_data, &TupleMeta { deleted: false })?
+        else {
+            return Ok(None);
+        };
+        page.write_object::<Node>(&Schema::default()).delete(&rid);
+        txn.commit()?;
+
+        Ok(Some(new_rid))
+    }
+
+    /// Whether the tuple is small enough to fit on a page
+    pub fn fits(tuple_data: &TupleData) -> bool {
+        let mut empty = Node::deserialise([0; PAGE_SIZE], &Schema::default());
+        empty.insert(tuple_data, &TupleMeta { deleted: false }).is_some()
     }
 }
 
@@ -206,6 +314,10 @@ mod test {
         let rid_a = list.insert(&want_a)?.unwrap();
         let rid_b = list.insert(&want_b)?.unwrap();
 
+        // A tuple that wouldn't fit on an empty page isn't inserted
+        let too_large = TupleData(BytesMut::from(&[0; PAGE_SIZE][..]));
+        assert_eq!(None, list.insert(&too_large)?);
+
         let list = List::new(
             pc,
             TableMeta { first_page_id: list.first_page_id, last_page_id: list.last_page_id() },
@@ -257,4 +369,44 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_update_delete() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 4;
+        const K: usize = 2;
+
+        let disk = Memory::new::<MEMORY>();
+        let lru = LRU::new(K);
+        let pc = PageCache::new(disk, lru, 0);
+
+        let list = List::new(pc, TableMeta::default())?;
+        let tuple = |len: usize, b: u8| TupleData(BytesMut::from(&vec![b; len][..]));
+
+        let rid_a = list.insert(&tuple(10, 1))?.unwrap();
+        let rid_b = list.insert(&tuple(10, 2))?.unwrap();
+
+        // Fits in the old slot
+        assert_eq!(Some(rid_a), list.update(rid_a, &tuple(8, 3))?);
+        assert_eq!(tuple(8, 3), list.get(rid_a)?.unwrap().1);
+
+        // Grows, so is moved to a new slot
+        let rid_c = list.update(rid_b, &tuple(20, 4))?.unwrap();
+        assert_ne!(rid_b, rid_c);
+        assert!(list.get(rid_b)?.unwrap().0.deleted);
+        assert_eq!((TupleMeta { deleted: false }, tuple(20, 4)), list.get(rid_c)?.unwrap());
+
+        assert!(list.delete(rid_a)?);
+        assert!(!list.delete(rid_a)?);
+        assert_eq!(None, list.update(rid_a, &tuple(1, 5))?);
+
+        let live = list
+            .iter()?
+            .filter_map(|result| result.ok())
+            .filter(|(meta, _, _)| !meta.deleted)
+            .map(|(_, tuple, rid)| (tuple, rid))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(tuple(20, 4), rid_c)], live);
+
+        Ok(())
+    }
 }
@@ -1,4 +1,4 @@
-use crate::page::{DiskObject, PageBuf, PageID, PAGE_SIZE};
+use crate::page::{DiskObject, PageBuf, PageID, PAGE_LSN, PAGE_SIZE};
 use crate::storable::Storable;
 use crate::table::tuple::Data as TupleData;
 
@@ -6,7 +6,7 @@ use bytes::BytesMut;
 use std::ops::Range;
 
 // TablePage:
-// NextPageID | NumTuples | NumDeletedTuples | Slots | Free | Tuples
+// NextPageID | NumTuples | NumDeletedTuples | Slots | Free | Tuples | PageLSN
 //
 // Slot:
 // TupleInfo
@@ -52,7 +52,7 @@ pub struct TupleMeta {
 
 impl From<&[u8]> for TupleMeta {
     fn from(value: &[u8]) -> Self {
-        let deleted = u8::from_be_bytes(value[0..1].try_into().unwrap()) > 1;
+        let deleted = u8::from_be_bytes(value[0..1].try_into().unwrap()) > 0;
         Self { deleted }
     }
 }
@@ -102,7 +102,7 @@ const SLOTS_START: usize = 12;
 
 #[derive(Debug, PartialEq)]
 pub struct Node {
-    page_start: *mut u8,
+    data: Box<PageBuf>,
     pub next_page_id: PageID,
     deleted_tuples_len: u32,
     slots: Vec<TupleSlot>,
@@ -128,19 +128,14 @@ impl DiskObject for Node {
             Some(o) => o.offset as usize,
             None => return ret,
         };
-        assert!(offset < PAGE_SIZE, "tuple being written at PAGE_SIZE or greater");
+        assert!(offset < PAGE_LSN.start, "tuple being written over the page's LSN");
 
-        unsafe {
-            let tuples_ptr = self.page_start.add(offset);
-            let tuples = std::slice::from_raw_parts(tuples_ptr, PAGE_SIZE - offset);
-            ret[offset..].copy_from_slice(tuples);
-        }
+        ret[offset..PAGE_LSN.start].copy_from_slice(&self.data[offset..PAGE_LSN.start]);
 
         ret
     }
 
     fn deserialise(buf: PageBuf, _: &crate::catalog::schema::Schema) -> Self {
-        let page_start = buf.as_ptr() as *mut u8;
         let next_page_id = i32::from_be_bytes(buf[NEXT_PAGE_ID].try_into().unwrap());
         let tuples_len = u32::from_be_bytes(buf[TUPLES_LEN].try_into().unwrap());
         let deleted_tuples_len = u32::from_be_bytes(buf[DELETED_TUPLES_LEN].try_into().unwrap());
@@ -158,7 +153,7 @@ impl DiskObject for Node {
             rem -= 1;
         }
 
-        Self { page_start, next_page_id, deleted_tuples_len, slots }
+        Self { data: Box::new(buf), next_page_id, deleted_tuples_len, slots }
     }
 }
 
@@ -169,19 +164,19 @@ impl Node {
         self.slots.len() as u32
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
     pub fn next_tuple_offset(&self, tuple: &TupleData) -> Option<usize> {
         let offset = match self.slots.last() {
             Some(slot) => slot.offset as usize,
-            None => PAGE_SIZE,
+            None => PAGE_LSN.start,
         };
 
-        let tuple_offset = offset - tuple.size();
-
         // Ensure tuple isn't written over header/slots
         let size = Self::HEADER_SIZE + TupleSlot::SIZE * (self.len() as usize + 1);
-        if tuple_offset < size {
-            return None;
-        }
+        let tuple_offset = offset.checked_sub(tuple.size()).filter(|offset| *offset >= size)?;
 
         Some(tuple_offset)
     }
@@ -191,31 +186,50 @@ impl Node {
         let slot_id = self.len();
         self.slots.push(TupleSlot { offset: offset as u32, len: tuple.size() as u32, meta: *meta });
 
-        unsafe {
-            let tuples_ptr = self.page_start.add(offset);
-            let tuples = std::slice::from_raw_parts_mut(tuples_ptr, PAGE_SIZE - offset);
-            tuples[..tuple.size()].copy_from_slice(&tuple.0);
-        }
+        self.data[offset..offset + tuple.size()].copy_from_slice(&tuple.0);
 
         Some(slot_id)
     }
 
-    pub fn get(&self, rid: &RID) -> Option<(TupleMeta, TupleData)> {
-        let slot_id = rid.slot_id;
-        if slot_id > self.len() {
-            todo!()
+    /// Marks the tuple as deleted, returning false if it already was
+    pub fn delete(&mut self, rid: &RID) -> bool {
+        let Some(slot) = self.slots.get_mut(rid.slot_id as usize) else {
+            return false;
+        };
+
+        if slot.meta.deleted {
+            return false;
         }
 
-        let TupleSlot { offset, len, meta } = self.slots[slot_id as usize];
-        let mut tuple_dst = BytesMut::zeroed(len as usize);
+        slot.meta.deleted = true;
+        self.deleted_tuples_len += 1;
+
+        true
+    }
+
+    /// Overwrites the tuple in place. Returns false if the tuple doesn't exist, has been deleted,
+    /// or the new tuple is larger than the old one, in which case it has to be relocated.
+    pub fn update(&mut self, rid: &RID, tuple: &TupleData) -> bool {
+        let Some(slot) = self.slots.get_mut(rid.slot_id as usize) else {
+            return false;
+        };
 
-        unsafe {
-            let tuple_ptr = self.page_start.add(offset as usize);
-            let tuple_src = std::slice::from_raw_parts(tuple_ptr, len as usize);
-            tuple_dst[..].copy_from_slice(tuple_src);
+        if slot.meta.deleted || tuple.size() > slot.len as usize {
+            return false;
         }
 
-        Some((meta, TupleData(tuple_dst)))
+        let offset = slot.offset as usize;
+        slot.len = tuple.size() as u32;
+        self.data[offset..offset + tuple.size()].copy_from_slice(&tuple.0);
+
+        true
+    }
+
+    pub fn get(&self, rid: &RID) -> Option<(TupleMeta, TupleData)> {
+        let TupleSlot { offset, len, meta } = *self.slots.get(rid.slot_id as usize)?;
+        let (offset, len) = (offset as usize, len as usize);
+
+        Some((meta, TupleData(BytesMut::from(&self.data[offset..offset + len]))))
     }
 }
 
@@ -225,32 +239,33 @@ mod test {
 
     use crate::{
         catalog::schema::Schema,
-        page::{DiskObject, PAGE_SIZE},
+        page::{DiskObject, PAGE_LSN, PAGE_SIZE},
         table::node::{Node, TupleData, TupleMeta, TupleSlot, RID},
     };
 
     #[test]
     fn test_serde() {
         let mut buf = [0; PAGE_SIZE];
+        let end = PAGE_LSN.start;
 
         let tuple_a = std::array::from_fn::<u8, 10, _>(|i| (i * 2) as u8);
         let tuple_b = std::array::from_fn::<u8, 15, _>(|i| (i * 3) as u8);
 
-        buf[PAGE_SIZE - 10..].copy_from_slice(&tuple_a);
-        buf[PAGE_SIZE - 25..PAGE_SIZE - 10].copy_from_slice(&tuple_b);
+        buf[end - 10..end].copy_from_slice(&tuple_a);
+        buf[end - 25..end - 10].copy_from_slice(&tuple_b);
 
         let mut table = Node {
-            page_start: buf.as_mut_ptr(),
+            data: Box::new(buf),
             next_page_id: 10,
             deleted_tuples_len: 0,
             slots: vec![
                 TupleSlot {
-                    offset: (PAGE_SIZE - 10) as u32,
+                    offset: (end - 10) as u32,
                     len: 10,
                     meta: TupleMeta { deleted: false },
                 },
                 TupleSlot {
-                    offset: (PAGE_SIZE - 25) as u32,
+                    offset: (end - 25) as u32,
                     len: 15,
                     meta: TupleMeta { deleted: false },
                 },
@@ -262,25 +277,20 @@ mod test {
         let mut table2 = Node::deserialise(bytes, &Schema::default());
 
         let offset = table.slots.last().unwrap().offset as usize;
-        let tuples = unsafe {
-            let tuples_ptr = table2.page_start.add(offset);
-            std::slice::from_raw_parts(tuples_ptr, PAGE_SIZE - offset)
-        };
+        let tuples = &table2.data[offset..end];
         assert_eq!(&tuples[0..15], &tuple_b);
         assert_eq!(&tuples[15..], &tuple_a);
 
-        table.page_start = std::ptr::null_mut();
-        table2.page_start = std::ptr::null_mut();
+        *table.data = [0; PAGE_SIZE];
+        *table2.data = [0; PAGE_SIZE];
 
         assert_eq!(table, table2);
     }
 
     #[test]
     fn test_insert() {
-        let mut buf = [0; PAGE_SIZE];
-
         let mut table = Node {
-            page_start: buf.as_mut_ptr(),
+            data: Box::new([0; PAGE_SIZE]),
             next_page_id: 0,
             deleted_tuples_len: 0,
             slots: Vec::new(),
@@ -304,4 +314,34 @@ mod test {
         assert_eq!(want_a, have_a);
         assert_eq!(want_b, have_b)
     }
+
+    #[test]
+    fn test_update() {
+        let mut table = Node {
+            data: Box::new([0; PAGE_SIZE]),
+            next_page_id: 0,
+            deleted_tuples_len: 0,
+            slots: Vec::new(),
+        };
+
+        let meta = TupleMeta { deleted: false };
+        let rid = RID { page_id: 0, slot_id: 0 };
+        let next = RID { page_id: 0, slot_id: 1 };
+        table.insert(&TupleData(BytesMut::from(&[1; 10][..])), &meta);
+        table.insert(&TupleData(BytesMut::from(&[2; 10][..])), &meta);
+
+        // Smaller tuples are written in place
+        let want = TupleData(BytesMut::from(&[3; 5][..]));
+        assert!(table.update(&rid, &want));
+        assert_eq!((meta, want), table.get(&rid).unwrap());
+        assert_eq!(TupleData(BytesMut::from(&[2; 10][..])), table.get(&next).unwrap().1);
+
+        // Larger tuples need to be relocated
+        assert!(!table.update(&rid, &TupleData(BytesMut::from(&[4; 11][..]))));
+
+        assert!(table.delete(&rid));
+        assert!(!table.delete(&rid));
+        assert!(!table.update(&rid, &TupleData(BytesMut::from(&[5; 1][..]))));
+        assert!(!table.update(&RID { page_id: 0, slot_id: 2 }, &TupleData(BytesMut::new())));
+    }
 }
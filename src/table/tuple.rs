@@ -1,9 +1,11 @@
-use crate::catalog::schema::{Column, Schema, Type};
+use crate::catalog::schema::{null_bitmap_size, Column, Schema, Type};
 use bytes::{BufMut, BytesMut};
 use std::cmp::Ordering::{self, *};
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+/// `Null` is declared first so that it sorts before every other value
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
 pub enum Value {
+    Null,
     TinyInt(i8),
     Bool(bool),
     Int(i32),
@@ -14,6 +16,7 @@ pub enum Value {
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Value::Null => f.pad("NULL"),
             Value::TinyInt(v) => v.fmt(f),
             Value::Bool(v) => v.fmt(f),
             Value::Int(v) => v.fmt(f),
@@ -24,8 +27,10 @@ impl std::fmt::Display for Value {
 }
 
 impl Value {
+    /// Panics if the value is `Null`, which has no type of its own
     pub fn ty(&self) -> Type {
         match self {
+            Value::Null => unreachable!("NULL has no type"),
             Value::TinyInt(_) => Type::TinyInt,
             Value::Bool(_) => Type::Bool,
             Value::Int(_) => Type::Int,
@@ -36,6 +41,7 @@ impl Value {
 
     pub fn display_len(&self) -> usize {
         match self {
+            Value::Null => "NULL".len(),
             Value::TinyInt(v) => (v.checked_ilog10().unwrap_or_default() + 1) as usize,
             Value::Bool(v) if *v => "true".len(),
             Value::Bool(_) => "false".len(),
@@ -46,6 +52,11 @@ impl Value {
     }
 }
 
+// Tuple:
+// | NullBitmap | Fixed | Variable |
+//
+// The null bitmap has a bit for each column. Null columns still take up their space in the fixed
+// section so the offsets of the other columns don't change, but are zeroed.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct Data(pub BytesMut);
 
@@ -54,9 +65,9 @@ pub struct Data(pub BytesMut);
 /// non-contiguous columns.
 pub fn fit_tuple_with_schema(tuple: &Data, schema: &Schema) -> Data {
     let mut builder = Builder::new();
-    for Column { offset, ty, .. } in &schema.columns {
-        let value = tuple.get_value(*offset, *ty);
-        builder = builder.add(&value);
+    for column in &schema.columns {
+        let value = tuple.get_value(column);
+        builder = builder.add(&value, column.ty);
     }
 
     builder.build()
@@ -73,13 +84,21 @@ pub fn bytes_to_tuple(data: &[u8], schema: &Schema) -> Data {
     let mut tuple = BytesMut::new();
     let mut vars = Vec::new();
 
+    let mut nulls = vec![0; null_bitmap_size(schema.len())];
+    for (i, Column { position, .. }) in schema.columns.iter().enumerate() {
+        if is_null(data, *position) {
+            nulls[i / 8] |= 1 << (i % 8);
+        }
+    }
+    tuple.put(&nulls[..]);
+
     // `buf` could go extend beyond the tuple, use schema to read the correct amount of bytes
     // This assumes the tuple begins at the zeroth byte
-    for Column { name: _, ty, offset, table: _ } in &schema.columns {
+    for Column { ty, offset, position, .. } in &schema.columns {
         let start = tuple.len();
         tuple.put(&data[*offset..*offset + ty.size()]);
 
-        if ty == &Type::Varchar {
+        if ty == &Type::Varchar && !is_null(data, *position) {
             let (var_offset, length) = (
                 u16::from_be_bytes((&data[*offset..*offset + 2]).try_into().unwrap()) as usize,
                 u16::from_be_bytes((&data[*offset + 2..*offset + 4]).try_into().unwrap()) as usize,
@@ -104,6 +123,10 @@ pub fn bytes_to_tuple(data: &[u8], schema: &Schema) -> Data {
     Data(tuple)
 }
 
+fn is_null(data: &[u8], position: usize) -> bool {
+    data[position / 8] & (1 << (position % 8)) > 0
+}
+
 impl Data {
     pub fn new(data: &[u8]) -> Self {
         Self(BytesMut::from(data))
@@ -126,46 +149,65 @@ impl Data {
         *self = self.next(schema);
     }
 
-    /// Gets the next tuple, which has the value of the first column incremented by 1. This returns
-    /// a new tuple, use `increment` to modify the current tuple.
+    /// Gets the next tuple, which is the smallest tuple that sorts after this one. This returns a
+    /// new tuple, use `increment` to modify the current tuple.
+    /// The last column is incremented, carrying into the previous column if it already holds the
+    /// largest value of its type, in which case it's reset to null:
     /// For integers it's a simple increment
-    /// Bool will always be set to true
-    /// Varchars will have their first char incremented by 1
+    /// Bool goes from false to true
+    /// Varchars have a nul char appended
+    /// Null becomes the smallest value of the column's type
+    /// If every column holds its largest value then the tuple is returned unchanged.
     pub fn next(&self, schema: &Schema) -> Self {
-        assert!(schema.len() > 0);
-
-        // Increment the first column
-        let value = self.get_value(schema.columns[0].offset, schema.columns[0].ty);
-
-        let value = match value {
-            Value::TinyInt(v) => Value::TinyInt(v + 1),
-            Value::Bool(_) => Value::Bool(true),
-            Value::Int(v) => Value::Int(v + 1),
-            Value::BigInt(v) => Value::BigInt(v + 1),
-            Value::Varchar(mut v) => {
-                if let Some(c) = v.chars().nth(0) {
-                    let next = char::from_u32(c as u32 + 1).expect("invalid char");
-                    let len = char::len_utf8(next);
-                    let buf = unsafe { v.as_bytes_mut() };
-                    buf[0..len].copy_from_slice(&u32::to_be_bytes(next as u32)[0..len]);
-
-                    Value::Varchar(v)
-                } else {
-                    Value::Varchar("\0".into())
+        assert!(!schema.is_empty());
+
+        let mut values = schema.iter().map(|column| self.get_value(column)).collect::<Vec<_>>();
+        for (i, column) in schema.iter().enumerate().rev() {
+            let next = match &values[i] {
+                // Null sorts first, so the next value is the smallest of the column's type
+                Value::Null => Some(match column.ty {
+                    Type::TinyInt => Value::TinyInt(i8::MIN),
+                    Type::Bool => Value::Bool(false),
+                    Type::Int => Value::Int(i32::MIN),
+                    Type::BigInt => Value::BigInt(i64::MIN),
+                    Type::Varchar => Value::Varchar("".into()),
+                }),
+                Value::TinyInt(v) => v.checked_add(1).map(Value::TinyInt),
+                Value::Bool(v) => (!v).then_some(Value::Bool(true)),
+                Value::Int(v) => v.checked_add(1).map(Value::Int),
+                Value::BigInt(v) => v.checked_add(1).map(Value::BigInt),
+                Value::Varchar(v) => Some(Value::Varchar(format!("{v}\0"))),
+            };
+
+            match next {
+                Some(next) => {
+                    values[i] = next;
+                    return schema
+                        .iter()
+                        .zip(&values)
+                        .fold(Builder::new(), |builder, (column, value)| {
+                            builder.add(value, column.ty)
+                        })
+                        .build();
                 }
+                None => values[i] = Value::Null,
             }
-        };
-
-        let mut builder = Builder::new().add(&value);
-        for column in &schema.columns[1..] {
-            builder = builder.add(&self.get_value(column.offset, column.ty));
         }
 
-        builder.build()
+        self.clone()
+    }
+
+    pub fn is_null(&self, column: &Column) -> bool {
+        is_null(&self.0, column.position)
     }
 
-    pub fn get_value(&self, offset: usize, ty: Type) -> Value {
+    pub fn get_value(&self, column: &Column) -> Value {
         let Self(buf) = self;
+        let Column { ty, offset, .. } = *column;
+
+        if self.is_null(column) {
+            return Value::Null;
+        }
 
         match ty {
             Type::Varchar => {
@@ -217,8 +259,8 @@ impl<'a, 'b> PartialOrd for Comparand<'a, &'b Data> {
 impl<'a, 'b> Ord for Comparand<'a, &'b Data> {
     fn cmp(&self, other: &Self) -> Ordering {
         for column in self.0.iter() {
-            let lhs = self.1.get_value(column.offset, column.ty);
-            let rhs = other.1.get_value(column.offset, column.ty);
+            let lhs = self.1.get_value(column);
+            let rhs = other.1.get_value(column);
 
             match lhs.cmp(&rhs) {
                 Less => return Less,
@@ -265,6 +307,7 @@ struct Variable {
 pub struct Builder {
     data: BytesMut,
     variable: Vec<Variable>,
+    nulls: Vec<bool>,
 }
 
 impl Builder {
@@ -276,8 +319,11 @@ impl Builder {
         Self { data: BytesMut::with_capacity(size), ..Default::default() }
     }
 
-    pub fn add(self, value: &Value) -> Self {
+    /// Adds a value to a column of type `ty`, which is needed to size the column if it's null
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, value: &Value, ty: Type) -> Self {
         match value {
+            Value::Null => self.null(ty),
             Value::TinyInt(v) => self.tiny_int(*v),
             Value::Bool(v) => self.bool(*v),
             Value::Int(v) => self.int(*v),
@@ -286,27 +332,47 @@ impl Builder {
         }
     }
 
+    /// Returns the number of values added so far
+    pub fn len(&self) -> usize {
+        self.nulls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nulls.is_empty()
+    }
+
+    pub fn null(mut self, ty: Type) -> Self {
+        self.nulls.push(true);
+        self.data.put_bytes(0, ty.size());
+        self
+    }
+
     pub fn tiny_int(mut self, value: i8) -> Self {
+        self.nulls.push(false);
         self.data.put(&value.to_be_bytes()[..]);
         self
     }
 
     pub fn bool(mut self, value: bool) -> Self {
+        self.nulls.push(false);
         self.data.put(&u8::to_be_bytes(if value { 1 } else { 0 })[..]);
         self
     }
 
     pub fn int(mut self, value: i32) -> Self {
+        self.nulls.push(false);
         self.data.put(&value.to_be_bytes()[..]);
         self
     }
 
     pub fn big_int(mut self, value: i64) -> Self {
+        self.nulls.push(false);
         self.data.put(&value.to_be_bytes()[..]);
         self
     }
 
     pub fn varchar(mut self, value: &str) -> Self {
+        self.nulls.push(false);
         let offset = self.data.len();
 
         // First two bytes is the offset, which we won't know until build()
@@ -319,19 +385,30 @@ impl Builder {
         self
     }
 
-    pub fn build(mut self) -> Data {
+    pub fn build(self) -> Data {
+        let nulls_size = null_bitmap_size(self.nulls.len());
+        let mut tuple = BytesMut::with_capacity(nulls_size + self.data.len());
+
+        let mut nulls = vec![0; nulls_size];
+        for (i, _) in self.nulls.iter().enumerate().filter(|(_, null)| **null) {
+            nulls[i / 8] |= 1 << (i % 8);
+        }
+        tuple.put(&nulls[..]);
+        tuple.put(self.data);
+
         for Variable { data, offset_offset } in self.variable {
-            let offset = self.data.len();
+            let offset = tuple.len();
 
-            // Update offset
-            self.data[offset_offset..offset_offset + 2]
+            // Update offset, which is shifted along by the null bitmap
+            let offset_offset = nulls_size + offset_offset;
+            tuple[offset_offset..offset_offset + 2]
                 .copy_from_slice(&u16::to_be_bytes(offset as u16));
 
             // Write variable length data to end of tuple
-            self.data.put(data);
+            tuple.put(data);
         }
 
-        Data(self.data)
+        Data(tuple)
     }
 }
 
@@ -339,7 +416,7 @@ impl Builder {
 mod test {
     use crate::catalog::schema::{Column, Schema, Type};
     use crate::schema;
-    use crate::table::tuple::{fit_tuple_with_schema, Builder, Comparand};
+    use crate::table::tuple::{bytes_to_tuple, fit_tuple_with_schema, Builder, Comparand, Value};
 
     use std::cmp::Ordering::*;
 
@@ -370,8 +447,22 @@ mod test {
     test_fit_tuple_with_schema! (
         fit_last_columns,
         Schema::new(vec![
-            Column { name: "col_b".into(), ty: Type::Varchar, offset: 4, table: None },
-            Column { name: "col_c".into(), ty: Type::BigInt, offset: 8, table: None },
+            Column {
+                name: "col_b".into(),
+                ty: Type::Varchar,
+                offset: 5,
+                position: 1,
+                table: None,
+                hidden: false,
+            },
+            Column {
+                name: "col_c".into(),
+                ty: Type::BigInt,
+                offset: 9,
+                position: 2,
+                table: None,
+                hidden: false,
+            },
         ]),
         tuple: Builder::new().int(10).varchar("row_a").big_int(20).build(),
         want: Builder::new().varchar("row_a").big_int(20).build()
@@ -382,8 +473,10 @@ mod test {
         Schema::new(vec![Column {
             name: "col_b".into(),
             ty: Type::Varchar,
-            offset: 4,
-            table: None
+            offset: 5,
+            position: 1,
+            table: None,
+            hidden: false
         }]),
         tuple: Builder::new().int(10).varchar("row_a").big_int(20).build(),
         want: Builder::new().varchar("row_a").build()
@@ -392,13 +485,51 @@ mod test {
     test_fit_tuple_with_schema! (
         fit_outer_columns,
         Schema::new(vec![
-            Column { name: "col_a".into(), ty: Type::Int, offset: 0, table: None },
-            Column { name: "col_c".into(), ty: Type::BigInt, offset: 8, table: None },
+            Column {
+                name: "col_a".into(),
+                ty: Type::Int,
+                offset: 1,
+                position: 0,
+                table: None,
+                hidden: false,
+            },
+            Column {
+                name: "col_c".into(),
+                ty: Type::BigInt,
+                offset: 9,
+                position: 2,
+                table: None,
+                hidden: false,
+            },
         ]),
         tuple: Builder::new().int(10).varchar("row_a").big_int(20).build(),
         want: Builder::new().int(10).big_int(20).build()
     );
 
+    test_fit_tuple_with_schema! (
+        fit_null_columns,
+        Schema::new(vec![
+            Column {
+                name: "col_b".into(),
+                ty: Type::Varchar,
+                offset: 5,
+                position: 1,
+                table: None,
+                hidden: false,
+            },
+            Column {
+                name: "col_c".into(),
+                ty: Type::BigInt,
+                offset: 9,
+                position: 2,
+                table: None,
+                hidden: false,
+            },
+        ]),
+        tuple: Builder::new().int(10).null(Type::Varchar).big_int(20).build(),
+        want: Builder::new().null(Type::Varchar).big_int(20).build()
+    );
+
     macro_rules! test_comparator {
         ($name:tt, $schema:expr, lhs: $lhs:expr, rhs: $rhs:expr, $want:expr) => {
             #[test]
@@ -457,4 +588,51 @@ mod test {
         rhs: Builder::new().varchar("Column").tiny_int(1).build(),
         Greater
     );
+
+    test_comparator!(
+        t7,
+        schema! {c1 Int, c2 Varchar},
+        lhs: Builder::new().null(Type::Int).varchar("Column").build(),
+        rhs: Builder::new().int(i32::MIN).varchar("Column").build(),
+        Less
+    );
+
+    #[test]
+    fn test_nulls() {
+        let schema = schema! {c1 Int, c2 Varchar, c3 Bool, c4 Int, c5 Int, c6 Int, c7 Int, c8 Int,
+        c9 Varchar};
+        let tuple = Builder::new()
+            .int(1)
+            .null(Type::Varchar)
+            .bool(true)
+            .int(4)
+            .int(5)
+            .int(6)
+            .int(7)
+            .int(8)
+            .add(&Value::Null, Type::Varchar)
+            .build();
+
+        assert_eq!(schema.tuple_size(), tuple.size());
+
+        let have = schema.iter().map(|column| tuple.get_value(column)).collect::<Vec<_>>();
+        let want = vec![
+            Value::Int(1),
+            Value::Null,
+            Value::Bool(true),
+            Value::Int(4),
+            Value::Int(5),
+            Value::Int(6),
+            Value::Int(7),
+            Value::Int(8),
+            Value::Null,
+        ];
+        assert_eq!(want, have);
+
+        let bytes = [tuple.as_bytes(), &[0; 16]].concat();
+        assert_eq!(tuple, bytes_to_tuple(&bytes, &schema));
+
+        // NULL lines up with the other values in a column
+        assert_eq!(format!("{:>6}|{:<6}|", Value::Null, Value::Int(1)), "  NULL|1     |");
+    }
 }
@@ -0,0 +1,1413 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering::*};
+use std::sync::{Arc, Mutex};
+
+use bytes::{BufMut, BytesMut};
+
+use crate::btree::BTree;
+use crate::catalog::schema::{Column, Schema};
+use crate::catalog::system::{type_from_i8, type_to_i8};
+use crate::disk::Disk;
+use crate::hash_table::extendible::ExtendibleHashTable;
+use crate::page::{page_lsn, set_page_lsn, PageBuf, PageID};
+use crate::page_cache::{PageCacheError, SharedPageCache};
+use crate::storable::{Raw, Storable};
+use crate::table::tuple::Data as TupleData;
+
+pub type LSN = u64;
+pub type TxnID = u64;
+
+/// Updates made outside of a transaction are logged with this ID. They are always redone and
+/// never undone during recovery.
+pub const AUTOCOMMIT: TxnID = 0;
+
+/// Changed ranges that are separated by fewer unchanged bytes than this are logged as one record
+const MERGE_GAP: usize = 16;
+
+pub trait LogStorage: Send + Sync {
+    fn append(&self, buf: &[u8]) -> io::Result<()>;
+    fn read_all(&self) -> io::Result<Vec<u8>>;
+    /// Cuts the log down to its first `len` bytes
+    fn truncate(&self, len: u64) -> io::Result<()>;
+    fn sync(&self) -> io::Result<()>;
+}
+
+impl<T: LogStorage + ?Sized> LogStorage for Arc<T> {
+    fn append(&self, buf: &[u8]) -> io::Result<()> {
+        (**self).append(buf)
+    }
+
+    fn read_all(&self) -> io::Result<Vec<u8>> {
+        (**self).read_all()
+    }
+
+    fn truncate(&self, len: u64) -> io::Result<()> {
+        (**self).truncate(len)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        (**self).sync()
+    }
+}
+
+pub struct FileLog {
+    file: File,
+}
+
+impl LogStorage for FileLog {
+    fn append(&self, buf: &[u8]) -> io::Result<()> {
+        (&self.file).write_all(buf)
+    }
+
+    fn read_all(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        (&self.file).seek(SeekFrom::Start(0))?;
+        (&self.file).read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    fn truncate(&self, len: u64) -> io::Result<()> {
+        self.file.set_len(len)?;
+        self.file.sync_all()
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+impl FileLog {
+    pub fn new(file: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).append(true).create(true).open(file)?;
+
+        Ok(Self { file })
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryLog {
+    buf: Mutex<Vec<u8>>,
+}
+
+impl LogStorage for MemoryLog {
+    fn append(&self, buf: &[u8]) -> io::Result<()> {
+        self.buf.lock().expect("todo").extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn read_all(&self) -> io::Result<Vec<u8>> {
+        Ok(self.buf.lock().expect("todo").clone())
+    }
+
+    fn truncate(&self, len: u64) -> io::Result<()> {
+        self.buf.lock().expect("todo").truncate(len as usize);
+        Ok(())
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MemoryLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cuts `n` bytes off the end of the log, simulating a torn write
+    #[cfg(test)]
+    pub fn tear(&self, n: usize) {
+        let mut buf = self.buf.lock().expect("todo");
+        let len = buf.len().saturating_sub(n);
+        buf.truncate(len);
+    }
+}
+
+const RECORD_BEGIN: u8 = 1;
+const RECORD_COMMIT: u8 = 2;
+const RECORD_ABORT: u8 = 3;
+const RECORD_UPDATE: u8 = 4;
+const RECORD_OPERATION: u8 = 5;
+const RECORD_UNDONE: u8 = 6;
+
+// Record:
+// | Size (4) | LSN (8) | TxnID (8) | Kind (1) | Body | Checksum (4) |
+//
+// Update body:
+// | PageID (4) | Offset (2) | Len (2) | Before (Len) | After (Len) |
+//
+// Operation body:
+// | Start (8) | Op (1) | PageID (4) | NumColumns (1) | Columns | KeyLen (2) | Key | ValueLen (1) |
+// | Value |
+//
+// Column:
+// | Type (1) | Offset (2) | Position (2) |
+//
+// Undone body:
+// | UndoNext (8) |
+const RECORD_HEADER_SIZE: usize = 4 + 8 + 8 + 1;
+const RECORD_CHECKSUM_SIZE: usize = 4;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LogRecord {
+    Begin {
+        txn: TxnID,
+    },
+    Commit {
+        txn: TxnID,
+    },
+    Abort {
+        txn: TxnID,
+    },
+    /// Physical change to a range of a page. Recovery only redoes it if the LSN stamped on the page
+    /// on disk is older than the record's.
+    Update {
+        txn: TxnID,
+        page_id: PageID,
+        offset: u16,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+    /// An index operation whose updates, from `start` onwards, are undone by running `undo` rather
+    /// than by putting back their before images. Other transactions may have changed the same
+    /// pages as soon as the operation released its latches.
+    Operation {
+        txn: TxnID,
+        start: LSN,
+        undo: LogicalUndo,
+    },
+    /// Logged as each change of a rolled back transaction is undone, saying that its records from
+    /// `undo_next` onwards have been undone. If the process dies part way through a rollback,
+    /// recovery carries on from there rather than undoing the undo.
+    Undone {
+        txn: TxnID,
+        undo_next: LSN,
+    },
+}
+
+impl LogRecord {
+    pub fn txn(&self) -> TxnID {
+        match self {
+            LogRecord::Begin { txn }
+            | LogRecord::Commit { txn }
+            | LogRecord::Abort { txn }
+            | LogRecord::Update { txn, .. }
+            | LogRecord::Operation { txn, .. }
+            | LogRecord::Undone { txn, .. } => *txn,
+        }
+    }
+
+    pub fn encode(&self, lsn: LSN) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(RECORD_HEADER_SIZE + RECORD_CHECKSUM_SIZE);
+        buf.put_u32(0); // Size is written once the body is known
+        buf.put_u64(lsn);
+        buf.put_u64(self.txn());
+
+        match self {
+            LogRecord::Begin { .. } => buf.put_u8(RECORD_BEGIN),
+            LogRecord::Commit { .. } => buf.put_u8(RECORD_COMMIT),
+            LogRecord::Abort { .. } => buf.put_u8(RECORD_ABORT),
+            LogRecord::Update { page_id, offset, before, after, .. } => {
+                assert!(before.len() == after.len());
+
+                buf.put_u8(RECORD_UPDATE);
+                buf.put_i32(*page_id);
+                buf.put_u16(*offset);
+                buf.put_u16(before.len() as u16);
+                buf.put(&before[..]);
+                buf.put(&after[..]);
+            }
+            LogRecord::Operation { start, undo, .. } => {
+                buf.put_u8(RECORD_OPERATION);
+                buf.put_u64(*start);
+                undo.encode(&mut buf);
+            }
+            LogRecord::Undone { undo_next, .. } => {
+                buf.put_u8(RECORD_UNDONE);
+                buf.put_u64(*undo_next);
+            }
+        }
+
+        let size = (buf.len() + RECORD_CHECKSUM_SIZE) as u32;
+        buf[0..4].copy_from_slice(&size.to_be_bytes());
+        let checksum = checksum(&buf);
+        buf.put_u32(checksum);
+
+        buf
+    }
+
+    /// Decodes the record at the start of `buf`, returning it along with its LSN and size. `None`
+    /// is returned if the record is incomplete or corrupt, which is expected for the last record
+    /// if the process died whilst appending it.
+    pub fn decode(buf: &[u8]) -> Option<(LSN, LogRecord, usize)> {
+        if buf.len() < RECORD_HEADER_SIZE + RECORD_CHECKSUM_SIZE {
+            return None;
+        }
+
+        let size = u32::from_be_bytes(buf[0..4].try_into().unwrap()) as usize;
+        if size < RECORD_HEADER_SIZE + RECORD_CHECKSUM_SIZE || size > buf.len() {
+            return None;
+        }
+
+        let body_end = size - RECORD_CHECKSUM_SIZE;
+        let want = u32::from_be_bytes(buf[body_end..size].try_into().unwrap());
+        if checksum(&buf[..body_end]) != want {
+            return None;
+        }
+
+        let lsn = u64::from_be_bytes(buf[4..12].try_into().unwrap());
+        let txn = u64::from_be_bytes(buf[12..20].try_into().unwrap());
+        let body = &buf[RECORD_HEADER_SIZE..body_end];
+
+        let record = match buf[20] {
+            RECORD_BEGIN => LogRecord::Begin { txn },
+            RECORD_COMMIT => LogRecord::Commit { txn },
+            RECORD_ABORT => LogRecord::Abort { txn },
+            RECORD_UPDATE => {
+                let page_id = i32::from_be_bytes(body[0..4].try_into().unwrap());
+                let offset = u16::from_be_bytes(body[4..6].try_into().unwrap());
+                let len = u16::from_be_bytes(body[6..8].try_into().unwrap()) as usize;
+                let before = body[8..8 + len].to_vec();
+                let after = body[8 + len..8 + len * 2].to_vec();
+
+                LogRecord::Update { txn, page_id, offset, before, after }
+            }
+            RECORD_OPERATION => {
+                let start = u64::from_be_bytes(body[0..8].try_into().unwrap());
+                let undo = LogicalUndo::decode(&body[8..])?;
+
+                LogRecord::Operation { txn, start, undo }
+            }
+            RECORD_UNDONE => {
+                let undo_next = u64::from_be_bytes(body[0..8].try_into().unwrap());
+
+                LogRecord::Undone { txn, undo_next }
+            }
+            _ => return None,
+        };
+
+        Some((lsn, record, size))
+    }
+}
+
+/// The kind of change that undoes an index operation
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IndexOp {
+    /// Puts a key back into a B-tree, undoing a delete or an insert that replaced its value
+    BTreeInsert,
+    /// Deletes a key from a B-tree, undoing an insert of a new key
+    BTreeDelete,
+    /// Adds a pair back to a hash table, undoing a remove
+    HashInsert,
+    /// Removes a pair from a hash table, undoing an insert
+    HashRemove,
+}
+
+/// Undoes an operation on an index by making the opposite change to it. The index is opened from
+/// page `id`, and the value is kept as bytes since recovery doesn't know the index's value type.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogicalUndo {
+    pub op: IndexOp,
+    pub id: PageID,
+    pub schema: Schema,
+    pub key: TupleData,
+    pub value: Vec<u8>,
+}
+
+impl LogicalUndo {
+    pub fn new<V: Storable>(
+        op: IndexOp,
+        id: PageID,
+        schema: &Schema,
+        key: &TupleData,
+        value: &V,
+    ) -> Self {
+        debug_assert!(matches!(V::SIZE, 1 | 2 | 4 | 8 | 16), "can't undo {} byte values", V::SIZE);
+
+        let mut bytes = vec![0; V::SIZE];
+        value.write_to(&mut bytes, 0);
+
+        // Only the layout of the key is logged, so the schema is kept the way it's decoded
+        let columns = schema
+            .iter()
+            .enumerate()
+            .map(|(i, column)| Column {
+                name: i.to_string(),
+                table: None,
+                hidden: false,
+                ..column.clone()
+            })
+            .collect();
+
+        Self { op, id, schema: Schema::new(columns), key: key.clone(), value: bytes }
+    }
+
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u8(match self.op {
+            IndexOp::BTreeInsert => 0,
+            IndexOp::BTreeDelete => 1,
+            IndexOp::HashInsert => 2,
+            IndexOp::HashRemove => 3,
+        });
+        buf.put_i32(self.id);
+        buf.put_u8(self.schema.len() as u8);
+        for column in self.schema.iter() {
+            buf.put_i8(type_to_i8(column.ty));
+            buf.put_u16(column.offset as u16);
+            buf.put_u16(column.position as u16);
+        }
+        buf.put_u16(self.key.0.len() as u16);
+        buf.put(&self.key.0[..]);
+        buf.put_u8(self.value.len() as u8);
+        buf.put(&self.value[..]);
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let op = match buf[0] {
+            0 => IndexOp::BTreeInsert,
+            1 => IndexOp::BTreeDelete,
+            2 => IndexOp::HashInsert,
+            3 => IndexOp::HashRemove,
+            _ => return None,
+        };
+        let id = i32::from_be_bytes(buf[1..5].try_into().unwrap());
+
+        let mut columns = Vec::new();
+        let mut rest = &buf[6..];
+        for i in 0..buf[5] as usize {
+            columns.push(Column {
+                name: i.to_string(),
+                ty: type_from_i8(rest[0] as i8),
+                offset: u16::from_be_bytes(rest[1..3].try_into().unwrap()) as usize,
+                position: u16::from_be_bytes(rest[3..5].try_into().unwrap()) as usize,
+                table: None,
+                hidden: false,
+            });
+            rest = &rest[5..];
+        }
+
+        let len = u16::from_be_bytes(rest[0..2].try_into().unwrap()) as usize;
+        let key = TupleData(BytesMut::from(&rest[2..2 + len]));
+        rest = &rest[2 + len..];
+        let value = rest[1..1 + rest[0] as usize].to_vec();
+
+        Some(Self { op, id, schema: Schema::new(columns), key, value })
+    }
+
+    fn apply(&self, pc: &SharedPageCache) -> crate::Result<()> {
+        match self.value.len() {
+            1 => self.apply_as::<Raw<1>>(pc),
+            2 => self.apply_as::<Raw<2>>(pc),
+            4 => self.apply_as::<Raw<4>>(pc),
+            8 => self.apply_as::<Raw<8>>(pc),
+            16 => self.apply_as::<Raw<16>>(pc),
+            n => unreachable!("can't undo {n} byte values"),
+        }
+    }
+
+    fn apply_as<V: Storable + Copy + Eq>(&self, pc: &SharedPageCache) -> crate::Result<()> {
+        let value = V::from_bytes(&self.value);
+        match self.op {
+            IndexOp::BTreeInsert => {
+                BTree::open(pc.clone(), self.id, &self.schema).insert(&self.key, &value)
+            }
+            IndexOp::BTreeDelete => {
+                BTree::<V>::open(pc.clone(), self.id, &self.schema).delete(&self.key).map(|_| ())
+            }
+            IndexOp::HashInsert => ExtendibleHashTable::new(self.id, pc.clone(), &self.schema)
+                .insert(self.key.clone(), value),
+            IndexOp::HashRemove => ExtendibleHashTable::new(self.id, pc.clone(), &self.schema)
+                .remove(&self.key, &value)
+                .map(|_| ()),
+        }
+    }
+}
+
+pub(crate) fn checksum(buf: &[u8]) -> u32 {
+    // FNV-1a
+    buf.iter().fold(0x811c9dc5, |hash, b| (hash ^ *b as u32).wrapping_mul(0x01000193))
+}
+
+/// Returns the ranges of the page which differ between `before` and `after`
+fn diff(before: &PageBuf, after: &PageBuf) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut i = 0;
+    while i < before.len() {
+        if before[i] == after[i] {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < before.len() && before[i] != after[i] {
+            i += 1;
+        }
+
+        match ranges.last_mut() {
+            Some(last) if start - last.end < MERGE_GAP => last.end = i,
+            _ => ranges.push(start..i),
+        }
+    }
+
+    ranges
+}
+
+struct LogBuffer {
+    buf: Vec<u8>,
+    next_lsn: LSN,
+    /// Number of transactions that have begun but not yet committed or aborted
+    active: usize,
+}
+
+pub struct LogManager {
+    id: u64,
+    storage: Box<dyn LogStorage>,
+    buffer: Mutex<LogBuffer>,
+    /// Held whilst the buffer is written out and synced, which is done without holding the buffer
+    /// so that records can still be appended. Transactions that commit whilst a sync is under way
+    /// wait here, and the first of them syncs all of their commit records at once.
+    flushing: Mutex<()>,
+    flushed_lsn: AtomicU64,
+    next_txn_id: AtomicU64,
+}
+
+impl LogManager {
+    pub fn new<S: LogStorage + 'static>(storage: S) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            id: NEXT_ID.fetch_add(1, Relaxed),
+            storage: Box::new(storage),
+            buffer: Mutex::new(LogBuffer { buf: Vec::new(), next_lsn: 1, active: 0 }),
+            flushing: Mutex::new(()),
+            flushed_lsn: AtomicU64::new(0),
+            next_txn_id: AtomicU64::new(AUTOCOMMIT + 1),
+        }
+    }
+
+    fn buffer(&self) -> std::sync::MutexGuard<'_, LogBuffer> {
+        self.buffer.lock().expect("todo")
+    }
+
+    pub fn append(&self, record: &LogRecord) -> LSN {
+        let mut buffer = self.buffer();
+        Self::append_locked(&mut buffer, record)
+    }
+
+    fn append_locked(buffer: &mut LogBuffer, record: &LogRecord) -> LSN {
+        let lsn = buffer.next_lsn;
+        buffer.next_lsn += 1;
+        buffer.buf.extend_from_slice(&record.encode(lsn));
+
+        lsn
+    }
+
+    /// Makes sure every record up to and including `lsn` is on stable storage
+    pub fn flush(&self, lsn: LSN) -> io::Result<()> {
+        if lsn <= self.flushed_lsn.load(Acquire) {
+            return Ok(());
+        }
+
+        let _flushing = self.flushing.lock().expect("todo");
+
+        // Whoever flushed before us may have taken our records along with theirs
+        if lsn <= self.flushed_lsn.load(Acquire) {
+            return Ok(());
+        }
+
+        let (buf, last) = {
+            let mut buffer = self.buffer();
+            (std::mem::take(&mut buffer.buf), buffer.next_lsn - 1)
+        };
+        if let Err(e) = self.write_out(&buf) {
+            self.buffer().buf.splice(0..0, buf);
+            return Err(e);
+        }
+        self.flushed_lsn.store(last, Release);
+
+        Ok(())
+    }
+
+    /// Flushes the buffer whilst it's held, so nothing can be appended in the meantime. Callers
+    /// have to hold `flushing` as well.
+    fn flush_locked(&self, buffer: &mut LogBuffer) -> io::Result<()> {
+        self.write_out(&buffer.buf)?;
+        buffer.buf.clear();
+        self.flushed_lsn.store(buffer.next_lsn - 1, Release);
+
+        Ok(())
+    }
+
+    fn write_out(&self, buf: &[u8]) -> io::Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        self.storage.append(buf)?;
+        self.storage.sync()
+    }
+
+    pub fn flush_all(&self) -> io::Result<()> {
+        let _flushing = self.flushing.lock().expect("todo");
+        let mut buffer = self.buffer();
+        self.flush_locked(&mut buffer)
+    }
+
+    /// Returns the LSN that the next appended record will be given
+    pub fn next_lsn(&self) -> LSN {
+        self.buffer().next_lsn
+    }
+
+    /// Discards the log if nothing has been appended since `since` and no transactions are in
+    /// progress. Callers are expected to have written every dirty page to disk beforehand.
+    /// Returns true if the log was truncated.
+    pub fn truncate(&self, since: LSN) -> io::Result<bool> {
+        let _flushing = self.flushing.lock().expect("todo");
+        let mut buffer = self.buffer();
+        if buffer.active != 0 || buffer.next_lsn != since {
+            return Ok(false);
+        }
+
+        self.flush_locked(&mut buffer)?;
+        self.storage.truncate(0)?;
+
+        Ok(true)
+    }
+
+    fn begin(&self) -> TxnID {
+        let txn = self.next_txn_id.fetch_add(1, Relaxed);
+
+        let mut buffer = self.buffer();
+        buffer.active += 1;
+        Self::append_locked(&mut buffer, &LogRecord::Begin { txn });
+
+        txn
+    }
+
+    fn commit(&self, txn: TxnID) -> io::Result<()> {
+        let lsn = {
+            let mut buffer = self.buffer();
+            buffer.active -= 1;
+            Self::append_locked(&mut buffer, &LogRecord::Commit { txn })
+        };
+
+        // A transaction is only durable once its commit record is on disk. Transactions that
+        // commit at the same time share a sync.
+        self.flush(lsn)
+    }
+
+    fn abort(&self, txn: TxnID) {
+        let mut buffer = self.buffer();
+        buffer.active -= 1;
+        Self::append_locked(&mut buffer, &LogRecord::Abort { txn });
+    }
+
+    /// Logs the changes made to a page whilst it was write locked. The changes are attributed to
+    /// the calling thread's transaction, if it has one. Returns the LSN of the last record, or
+    /// `None` if the page was not changed.
+    pub fn log_page(&self, page_id: PageID, before: &PageBuf, after: &PageBuf) -> Option<LSN> {
+        let ranges = diff(before, after);
+        if ranges.is_empty() {
+            return None;
+        }
+
+        TXN.with_borrow_mut(|active| {
+            let mut txn = active.iter_mut().find(|txn| txn.log_id == self.id);
+
+            let mut lsn = 0;
+            for range in ranges {
+                let record = LogRecord::Update {
+                    txn: txn.as_ref().map_or(AUTOCOMMIT, |txn| txn.id),
+                    page_id,
+                    offset: range.start as u16,
+                    before: before[range.clone()].to_vec(),
+                    after: after[range.clone()].to_vec(),
+                };
+                lsn = self.append(&record);
+
+                if let Some(txn) =
+                    txn.as_mut().filter(|txn| !txn.aborting && !txn.allocated.contains(&page_id))
+                {
+                    txn.undo.push(Undo::Page { lsn, page_id, range, before: record.into_before() });
+                }
+            }
+
+            Some(lsn)
+        })
+    }
+
+    /// Brings the disk up to date with the log, returning the records of the transactions which
+    /// never committed or aborted. They're rolled back by `undo` once the page cache is running,
+    /// since index operations are undone by running their opposites.
+    ///
+    /// Every update that a page doesn't already have is redone in log order, repeating history so
+    /// the disk reflects the moment the process died. Aborted transactions need no undo because
+    /// their compensating updates were logged and have just been redone. A torn record at the end
+    /// of the log is cut off, so the records logged from now on follow on from the intact ones.
+    pub fn redo(&self, disk: &dyn Disk) -> io::Result<Vec<(LSN, LogRecord)>> {
+        let log = self.storage.read_all()?;
+
+        // Analysis
+        let mut records = Vec::new();
+        let mut finished = HashSet::from([AUTOCOMMIT]);
+        let mut rest = &log[..];
+        while let Some((lsn, record, size)) = LogRecord::decode(rest) {
+            match record {
+                LogRecord::Commit { txn } | LogRecord::Abort { txn } => {
+                    finished.insert(txn);
+                }
+                _ => {}
+            }
+
+            records.push((lsn, record));
+            rest = &rest[size..];
+        }
+
+        let mut pages: HashMap<PageID, PageBuf> = HashMap::new();
+        for (lsn, record) in &records {
+            if let LogRecord::Update { page_id, offset, after, .. } = record {
+                let page = match pages.entry(*page_id) {
+                    std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        entry.insert(disk.read_page(*page_id)?)
+                    }
+                };
+
+                // The page was written to disk after this change was made
+                if page_lsn(page) >= *lsn {
+                    continue;
+                }
+
+                let offset = *offset as usize;
+                page[offset..offset + after.len()].copy_from_slice(after);
+                set_page_lsn(page, *lsn);
+            }
+        }
+
+        for (page_id, data) in &pages {
+            disk.write_page(*page_id, data)?;
+        }
+        disk.sync()?;
+
+        // New records and transactions carry on from the ones in the log
+        if let Some((lsn, _)) = records.last() {
+            self.buffer().next_lsn = lsn + 1;
+        }
+        if let Some(txn) = records.iter().map(|(_, record)| record.txn()).max() {
+            self.next_txn_id.fetch_max(txn + 1, Relaxed);
+        }
+
+        if !rest.is_empty() {
+            self.storage.truncate((log.len() - rest.len()) as u64)?;
+        }
+
+        records.retain(|(_, record)| !finished.contains(&record.txn()));
+        Ok(records)
+    }
+
+    /// Makes sure that the LSNs given out from now on start at `lsn` or later
+    pub(crate) fn carry_on_from(&self, lsn: LSN) {
+        let mut buffer = self.buffer();
+        buffer.next_lsn = buffer.next_lsn.max(lsn);
+    }
+
+    /// Rolls back the transactions that `redo` found unfinished. Their records are undone newest
+    /// first, skipping any that a rollback under way when the process died had already undone.
+    pub(crate) fn undo(
+        &self,
+        pc: &SharedPageCache,
+        records: Vec<(LSN, LogRecord)>,
+    ) -> crate::Result<()> {
+        let txns = records.iter().map(|(_, record)| record.txn()).collect::<HashSet<_>>();
+        self.buffer().active += txns.len();
+
+        // The LSN from which each transaction's records have been undone
+        let mut undone: HashMap<TxnID, LSN> = HashMap::new();
+        for (lsn, record) in records.into_iter().rev() {
+            let txn = record.txn();
+            if undone.get(&txn).is_some_and(|from| lsn >= *from) {
+                continue;
+            }
+
+            let undo = match record {
+                LogRecord::Update { page_id, offset, before, .. } => {
+                    let offset = offset as usize;
+                    Undo::Page { lsn, page_id, range: offset..offset + before.len(), before }
+                }
+                LogRecord::Operation { start, undo, .. } => Undo::Logical { start, undo },
+                LogRecord::Undone { undo_next, .. } => {
+                    undone.insert(txn, undo_next);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            // The transaction is put back on the thread whilst the change is undone, so the
+            // compensating updates are logged under it
+            TXN.with_borrow_mut(|active| {
+                active.push(ActiveTxn {
+                    log_id: self.id,
+                    id: txn,
+                    depth: 0,
+                    undo: Vec::new(),
+                    aborting: true,
+                    deallocated: Vec::new(),
+                    allocated: HashSet::new(),
+                })
+            });
+            let result = undo.apply(pc, txn);
+            TXN.with_borrow_mut(|active| active.pop());
+
+            undone.insert(txn, result?);
+        }
+
+        for txn in txns {
+            self.abort(txn);
+        }
+
+        Ok(())
+    }
+}
+
+impl LogRecord {
+    fn into_before(self) -> Vec<u8> {
+        match self {
+            LogRecord::Update { before, .. } => before,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A change that a transaction would have to undo if it rolled back
+enum Undo {
+    /// Puts back the bytes that the update logged at `lsn` overwrote
+    Page { lsn: LSN, page_id: PageID, range: Range<usize>, before: Vec<u8> },
+    /// Reverses the index operation whose updates were logged from `start` onwards
+    Logical { start: LSN, undo: LogicalUndo },
+}
+
+impl Undo {
+    /// Undoes the change on behalf of the transaction, which has to be aborting, and logs that its
+    /// records have been undone from the LSN that's returned onwards
+    fn apply(self, pc: &SharedPageCache, txn: TxnID) -> crate::Result<LSN> {
+        let undo_next = match self {
+            Undo::Page { lsn, page_id, range, before } => {
+                let pin = pc.fetch_page(page_id)?;
+                pin.write().put_range(&before, range);
+                lsn
+            }
+            Undo::Logical { start, undo } => {
+                undo.apply(pc)?;
+                start
+            }
+        };
+
+        let log = pc.log().expect("an aborting transaction should have a log");
+        log.append(&LogRecord::Undone { txn, undo_next });
+
+        Ok(undo_next)
+    }
+}
+
+struct ActiveTxn {
+    log_id: u64,
+    id: TxnID,
+    /// Number of nested `begin` calls which have not yet finished
+    depth: usize,
+    undo: Vec<Undo>,
+    aborting: bool,
+    /// Pages to give back once the transaction has committed
+    deallocated: Vec<PageID>,
+    /// Pages given out to the transaction. If it rolls back, whatever it linked them into is put
+    /// back, so nothing can reach them and their writes don't need undoing.
+    allocated: HashSet<PageID>,
+}
+
+thread_local! {
+    /// The transactions the current thread is running, at most one per `LogManager`
+    static TXN: RefCell<Vec<ActiveTxn>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `f` with the thread's transaction on `log` set aside, so the page writes made by `f` are
+/// logged as `AUTOCOMMIT` and are kept if the transaction rolls back
+pub fn autocommit<T>(log: Option<&LogManager>, f: impl FnOnce() -> T) -> T {
+    let Some(log) = log else { return f() };
+
+    let txn = TXN.with_borrow_mut(|active| {
+        let i = active.iter().position(|txn| txn.log_id == log.id)?;
+        Some(active.swap_remove(i))
+    });
+
+    let ret = f();
+
+    if let Some(txn) = txn {
+        TXN.with_borrow_mut(|active| active.push(txn));
+    }
+
+    ret
+}
+
+/// Holds on to the page until the thread's transaction on `log` commits, returning false if the
+/// thread isn't in a transaction
+pub(crate) fn defer_deallocation(log: &LogManager, page_id: PageID) -> bool {
+    TXN.with_borrow_mut(|active| match active.iter_mut().find(|txn| txn.log_id == log.id) {
+        Some(txn) => {
+            txn.deallocated.push(page_id);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Notes that the page was given to the thread's transaction on `log`, if it's in one, so the
+/// transaction doesn't hold on to the page's old contents
+pub(crate) fn note_allocation(log: &LogManager, page_id: PageID) {
+    TXN.with_borrow_mut(|active| {
+        if let Some(txn) = active.iter_mut().find(|txn| txn.log_id == log.id) {
+            txn.allocated.insert(page_id);
+        }
+    })
+}
+
+enum TxnState {
+    /// The page cache isn't logging
+    Unlogged,
+    /// Started whilst the thread was already running a transaction, the outermost transaction
+    /// decides the outcome
+    Nested(Start),
+    Active(TxnID),
+    Finished,
+}
+
+/// Where a nested transaction's changes start within the outermost one
+struct Start {
+    lsn: LSN,
+    undo: usize,
+    deallocated: usize,
+}
+
+/// An atomic unit of work against the page cache. Every page write made by the thread whilst the
+/// transaction is open is attributed to it. Dropping a transaction without committing rolls back
+/// its changes.
+///
+/// Until it finishes, the outermost transaction holds on to the old contents of every page it has
+/// written, including those written by the transactions nested inside it, other than the pages it
+/// was given itself. Index operations only hold on to what's needed to reverse them.
+pub struct Transaction {
+    pc: SharedPageCache,
+    state: TxnState,
+}
+
+impl Transaction {
+    pub(crate) fn begin(pc: SharedPageCache) -> Self {
+        let Some(log) = pc.log() else { return Self { pc, state: TxnState::Unlogged } };
+
+        let lsn = log.next_lsn();
+        let nested = TXN.with_borrow_mut(|active| {
+            let txn = active.iter_mut().find(|txn| txn.log_id == log.id)?;
+            txn.depth += 1;
+
+            Some(Start { lsn, undo: txn.undo.len(), deallocated: txn.deallocated.len() })
+        });
+
+        if let Some(start) = nested {
+            return Self { pc, state: TxnState::Nested(start) };
+        }
+
+        let id = log.begin();
+        TXN.with_borrow_mut(|active| {
+            active.push(ActiveTxn {
+                log_id: log.id,
+                id,
+                depth: 0,
+                undo: Vec::new(),
+                aborting: false,
+                deallocated: Vec::new(),
+                allocated: HashSet::new(),
+            })
+        });
+
+        Self { pc, state: TxnState::Active(id) }
+    }
+
+    pub fn id(&self) -> Option<TxnID> {
+        match self.state {
+            TxnState::Active(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn commit(mut self) -> crate::Result<()> {
+        match std::mem::replace(&mut self.state, TxnState::Finished) {
+            TxnState::Active(id) => {
+                let log = self.pc.log().expect("an active transaction should have a log");
+                let deallocated = self.finish(log.id).deallocated;
+                log.commit(id).map_err(|e| PageCacheError::Disk(e.kind()))?;
+                self.free(deallocated);
+
+                Ok(())
+            }
+            TxnState::Nested(_) => {
+                self.leave_nested();
+                Ok(())
+            }
+            TxnState::Unlogged | TxnState::Finished => Ok(()),
+        }
+    }
+
+    /// Commits the transaction like `commit`, but if it's nested, its changes are from now on
+    /// undone by running `undo` rather than by putting back the pages it wrote. Index operations
+    /// are committed this way because other transactions can change the same pages as soon as the
+    /// operation's latches are released, and putting the pages back would lose those changes. The
+    /// pages given back during the operation are freed straight away, as `undo` doesn't need them.
+    pub fn commit_logical(mut self, undo: LogicalUndo) -> crate::Result<()> {
+        let start = match std::mem::replace(&mut self.state, TxnState::Finished) {
+            TxnState::Nested(start) => start,
+            state => {
+                self.state = state;
+                return self.commit();
+            }
+        };
+
+        let log = self.pc.log().expect("a nested transaction should have a log");
+        let deallocated = TXN.with_borrow_mut(|active| {
+            let txn = active
+                .iter_mut()
+                .find(|txn| txn.log_id == log.id)
+                .expect("a nested transaction should be on the thread");
+            txn.depth -= 1;
+
+            // Operations run to roll the transaction back are never undone themselves, and nor
+            // are operations on an index that the transaction made, which can't be reached once
+            // it has rolled back
+            if !txn.aborting && !txn.allocated.contains(&undo.id) {
+                log.append(&LogRecord::Operation {
+                    txn: txn.id,
+                    start: start.lsn,
+                    undo: undo.clone(),
+                });
+                txn.undo.truncate(start.undo);
+                txn.undo.push(Undo::Logical { start: start.lsn, undo });
+            }
+
+            txn.deallocated.split_off(start.deallocated)
+        });
+        self.free(deallocated);
+
+        Ok(())
+    }
+
+    /// Gives back pages outside of the transaction so that freeing can never be undone once
+    /// another transaction may have been given the page. If we die before this happens the pages
+    /// are leaked.
+    fn free(&self, pages: Vec<PageID>) {
+        for page_id in pages {
+            if let Err(e) = self.pc.free_page(page_id) {
+                eprintln!("WARN: could not free page {page_id} - {e}");
+            }
+        }
+    }
+
+    fn leave_nested(&self) {
+        let Some(log) = self.pc.log() else { return };
+        TXN.with_borrow_mut(|active| {
+            if let Some(txn) = active.iter_mut().find(|txn| txn.log_id == log.id) {
+                txn.depth -= 1;
+            }
+        });
+    }
+
+    /// Removes the transaction from the thread
+    fn finish(&self, log_id: u64) -> ActiveTxn {
+        TXN.with_borrow_mut(|active| {
+            let i = active
+                .iter()
+                .position(|txn| txn.log_id == log_id)
+                .expect("an active transaction should be on the thread");
+            active.swap_remove(i)
+        })
+    }
+
+    fn rollback(&self, id: TxnID) -> crate::Result<()> {
+        let log = self.pc.log().expect("an active transaction should have a log");
+
+        let undo = TXN.with_borrow_mut(|active| {
+            match active.iter_mut().find(|txn| txn.log_id == log.id) {
+                Some(txn) => {
+                    txn.aborting = true;
+                    std::mem::take(&mut txn.undo)
+                }
+                None => Vec::new(),
+            }
+        });
+
+        // The compensating writes are logged under this transaction, followed by how far the
+        // rollback has got, so if we die part way through recovery carries on from there
+        let result = undo.into_iter().rev().try_for_each(|undo| {
+            undo.apply(&self.pc, id)?;
+            Ok(())
+        });
+
+        self.finish(log.id);
+        log.abort(id);
+
+        result
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        match std::mem::replace(&mut self.state, TxnState::Finished) {
+            TxnState::Active(id) => {
+                if let Err(e) = self.rollback(id) {
+                    eprintln!("ERROR: could not roll back transaction {id} - {e}");
+                }
+            }
+            TxnState::Nested(_) => self.leave_nested(),
+            TxnState::Unlogged | TxnState::Finished => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::disk::{Disk, Memory};
+    use crate::hash_table::extendible::ExtendibleHashTable;
+    use crate::page::{page_lsn, PAGE_SIZE};
+    use crate::page_cache::PageCache;
+    use crate::replacer::LRU;
+    use crate::schema;
+    use crate::table::tuple::Builder;
+    use crate::wal::{IndexOp, LogRecord, LogStorage, LogicalUndo, MemoryLog, TXN};
+
+    const MEMORY: usize = PAGE_SIZE * 8;
+    const K: usize = 2;
+
+    #[test]
+    fn test_record_serde() {
+        let records = [
+            LogRecord::Begin { txn: 1 },
+            LogRecord::Update {
+                txn: 1,
+                page_id: 3,
+                offset: 100,
+                before: vec![0, 0, 0],
+                after: vec![1, 2, 3],
+            },
+            LogRecord::Operation {
+                txn: 1,
+                start: 2,
+                undo: LogicalUndo::new(
+                    IndexOp::BTreeDelete,
+                    5,
+                    &schema! {c1 Int, c2 Varchar}.compact(),
+                    &Builder::new().int(1).varchar("key").build(),
+                    &7i32,
+                ),
+            },
+            LogRecord::Commit { txn: 1 },
+            LogRecord::Undone { txn: 2, undo_next: 4 },
+            LogRecord::Abort { txn: 2 },
+        ];
+
+        for (lsn, want) in records.into_iter().enumerate() {
+            let buf = want.encode(lsn as u64);
+            let (have_lsn, have, size) = LogRecord::decode(&buf).expect("record should decode");
+
+            assert_eq!(lsn as u64, have_lsn);
+            assert_eq!(want, have);
+            assert_eq!(buf.len(), size);
+
+            // A torn record should be rejected
+            assert!(LogRecord::decode(&buf[..buf.len() - 1]).is_none());
+        }
+    }
+
+    #[test]
+    fn test_recover_committed() -> crate::Result<()> {
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let txn = pc.begin();
+            let page = pc.new_page()?;
+            id = page.id;
+            page.write().put_range(b"committed", 0..9);
+            drop(page);
+            txn.commit()?;
+
+            // The page cache is dropped without flushing, as if the process had died
+        }
+
+        assert_eq!(&disk.read_page(id).unwrap()[0..9], &[0; 9]);
+
+        let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+        let page = pc.fetch_page(id)?;
+        assert_eq!(&page.read().data[0..9], b"committed");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_uncommitted() -> crate::Result<()> {
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let page = pc.new_page()?;
+            id = page.id;
+            drop(page);
+
+            let txn = pc.begin();
+            pc.fetch_page(id)?.write().put_range(b"first", 0..5);
+            txn.commit()?;
+
+            let txn = pc.begin();
+            pc.fetch_page(id)?.write().put_range(b"second", 0..6);
+
+            // The uncommitted change makes it to disk, recovery should remove it
+            pc.flush_page(id)?;
+            assert_eq!(&disk.read_page(id).unwrap()[0..6], b"second");
+
+            std::mem::forget(txn);
+        }
+
+        let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+        let page = pc.fetch_page(id)?;
+        assert_eq!(&page.read().data[0..6], b"first\0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback() -> crate::Result<()> {
+        let disk = Memory::new::<MEMORY>();
+        let log = MemoryLog::new();
+        let pc = PageCache::open(disk, log, LRU::new(K))?;
+
+        let id = pc.new_page()?.id;
+        {
+            let _txn = pc.begin();
+            let page = pc.fetch_page(id)?;
+            page.write().put_range(b"rolled back", 0..11);
+        }
+
+        let page = pc.fetch_page(id)?;
+        assert_eq!(&page.read().data[0..11], &[0; 11]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback_new_pages() -> crate::Result<()> {
+        const MEMORY: usize = PAGE_SIZE * 16;
+        let pc = PageCache::open(Memory::new::<MEMORY>(), MemoryLog::new(), LRU::new(K))?;
+        let undo =
+            || TXN.with_borrow(|active| active.iter().map(|txn| txn.undo.len()).sum::<usize>());
+
+        let id = pc.new_page()?.id;
+        {
+            let _txn = pc.begin();
+            pc.fetch_page(id)?.write().put_range(b"rolled back", 0..11);
+            assert_eq!(1, undo());
+
+            // Nothing can reach the pages the transaction is given once it rolls back, so their
+            // old contents aren't kept, nor are the operations on an index made in it
+            let nested = pc.begin();
+            let page = pc.new_page()?;
+            for i in 0..10 {
+                page.write().put_range(&[i], 0..1);
+            }
+            drop(page);
+            nested.commit()?;
+
+            let schema = schema! { c1 Int };
+            let hash_table = ExtendibleHashTable::<i32>::create(pc.clone(), &schema)?;
+            for i in 0..10 {
+                hash_table.insert(Builder::new().int(i).build(), i)?;
+            }
+            assert_eq!(1, undo());
+        }
+
+        let page = pc.fetch_page(id)?;
+        assert_eq!(&page.read().data[0..11], &[0; 11]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_skips_applied() -> crate::Result<()> {
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let page = pc.new_page()?;
+            id = page.id;
+            drop(page);
+
+            let txn = pc.begin();
+            pc.fetch_page(id)?.write().put_range(b"logged", 0..6);
+            txn.commit()?;
+            pc.flush_page(id)?;
+        }
+
+        // The page on disk is stamped with the record's LSN, so recovery leaves it alone
+        let mut buf = disk.read_page(id).unwrap();
+        assert_ne!(0, page_lsn(&buf));
+        buf[0..6].copy_from_slice(b"stored");
+        disk.write_page(id, &buf).unwrap();
+
+        let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+        let page = pc.fetch_page(id)?;
+        assert_eq!(&page.read().data[0..6], b"stored");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_after_checkpoint() -> crate::Result<()> {
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let page = pc.new_page()?;
+            id = page.id;
+            drop(page);
+
+            for i in 0..100 {
+                let txn = pc.begin();
+                pc.fetch_page(id)?.write().put_range(&[i], 0..1);
+                txn.commit()?;
+            }
+            pc.checkpoint()?;
+            assert!(log.read_all().unwrap().is_empty());
+        }
+
+        // The log was discarded, but its next records still have to come after the LSN that the
+        // page was written with
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let txn = pc.begin();
+            pc.fetch_page(id)?.write().put_range(b"after", 0..5);
+            txn.commit()?;
+        }
+
+        let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+        let page = pc.fetch_page(id)?;
+        assert_eq!(&page.read().data[0..5], b"after");
+
+        Ok(())
+    }
+
+    /// Counts how many times the log is synced, each sync taking as long as a disk's might
+    #[derive(Default)]
+    struct SlowLog {
+        log: MemoryLog,
+        syncs: AtomicUsize,
+    }
+
+    impl LogStorage for SlowLog {
+        fn append(&self, buf: &[u8]) -> io::Result<()> {
+            self.log.append(buf)
+        }
+
+        fn read_all(&self) -> io::Result<Vec<u8>> {
+            self.log.read_all()
+        }
+
+        fn truncate(&self, len: u64) -> io::Result<()> {
+            self.log.truncate(len)
+        }
+
+        fn sync(&self) -> io::Result<()> {
+            std::thread::sleep(Duration::from_millis(1));
+            self.syncs.fetch_add(1, Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_group_commit() -> crate::Result<()> {
+        const THREADS: usize = 8;
+        const COMMITS: usize = 20;
+        let log = Arc::new(SlowLog::default());
+        let pc = PageCache::open(Memory::new::<{ PAGE_SIZE * 16 }>(), log.clone(), LRU::new(K))?;
+
+        let ids = (0..THREADS).map(|_| Ok(pc.new_page()?.id)).collect::<crate::Result<Vec<_>>>()?;
+        let syncs = log.syncs.load(Relaxed);
+
+        std::thread::scope(|s| -> crate::Result<()> {
+            let threads = ids
+                .iter()
+                .map(|id| {
+                    let pc = &pc;
+                    s.spawn(move || -> crate::Result<()> {
+                        for i in 0..COMMITS {
+                            let txn = pc.begin();
+                            pc.fetch_page(*id)?.write().put_range(&[i as u8 + 1], 0..1);
+                            txn.commit()?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for thread in threads {
+                thread.join().unwrap()?;
+            }
+
+            Ok(())
+        })?;
+
+        // Commits that come in whilst the log is being synced share the next sync
+        assert!(log.syncs.load(Relaxed) - syncs < THREADS * COMMITS);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_torn_log() -> crate::Result<()> {
+        let disk = Arc::new(Memory::new::<MEMORY>());
+        let log = Arc::new(MemoryLog::new());
+
+        let id;
+        {
+            let pc = PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+            let txn = pc.begin();
+            let page = pc.new_page()?;
+            id = page.id;
+            page.write().put_range(b"torn", 0..4);
+            drop(page);
+            txn.commit()?;
+        }
+
+        // Lose the end of the commit record, the transaction should be treated as never having
+        // committed
+        log.tear(1);
+
+        PageCache::open(disk.clone(), log.clone(), LRU::new(K))?;
+        assert_eq!(&disk.read_page(id).unwrap()[0..4], &[0; 4]);
+
+        Ok(())
+    }
+}